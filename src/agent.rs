@@ -0,0 +1,82 @@
+//! Log formats of the agent CLIs the daemon can follow. Every format is
+//! translated into Codex-style [`LogEntry`] values, so session rendering does
+//! not depend on which agent wrote the log.
+
+use std::{path::Path, str::FromStr};
+
+use anyhow::{Result, bail};
+use serde::Deserialize;
+
+use crate::discovery::infer_session_id_from_path;
+use crate::render::{LogEntry, RenderOptions};
+
+pub mod aider;
+mod claude;
+pub mod generic;
+
+/// Which agent CLI's session logs to follow.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Agent {
+    /// Codex rollouts, discovered through `history.jsonl`.
+    #[default]
+    Codex,
+    /// Claude Code transcripts under `~/.claude/projects`, discovered by
+    /// modification time.
+    Claude,
+    /// Aider chat histories in project directories below the sessions root.
+    Aider,
+    /// JSON-lines logs matching `log-glob`, read through configured selectors.
+    Generic,
+}
+
+impl FromStr for Agent {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "codex" => Ok(Agent::Codex),
+            "claude" => Ok(Agent::Claude),
+            "aider" => Ok(Agent::Aider),
+            "generic" => Ok(Agent::Generic),
+            other => {
+                bail!("unknown agent `{other}` (expected `codex`, `claude`, `aider`, or `generic`)")
+            }
+        }
+    }
+}
+
+impl Agent {
+    /// The session id a log file belongs to, from its path.
+    pub fn session_id_from_path(self, path: &Path) -> Option<String> {
+        let name = |path: Option<&Path>| {
+            path.and_then(Path::file_stem)
+                .and_then(|stem| stem.to_str())
+                .map(str::to_string)
+        };
+        match self {
+            Agent::Codex => infer_session_id_from_path(path),
+            Agent::Claude | Agent::Generic => name(Some(path)),
+            // Every project has the same file name; the project names it.
+            Agent::Aider => name(path.parent()),
+        }
+    }
+}
+
+/// Per-session state for formats whose lines only make sense in context.
+#[derive(Debug, Clone, Default)]
+pub struct Decoder {
+    aider: aider::Transcript,
+}
+
+impl Decoder {
+    /// Translate one log line into the Codex events it stands for.
+    pub fn entries(&mut self, options: &RenderOptions, line: &str) -> Vec<LogEntry> {
+        match options.agent {
+            Agent::Codex => LogEntry::parse(line).into_iter().collect(),
+            Agent::Claude => claude::entries(line),
+            Agent::Aider => self.aider.entries(line),
+            Agent::Generic => generic::entries(line, &options.generic),
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! Aider chat histories (`.aider.chat.history.md` in each project). The file is
+//! Markdown: `#### ` lines are user prompts, `> ` lines are aider's own output,
+//! and everything else is the assistant's reply.
+
+use serde_json::{Value, json};
+
+use crate::render::LogEntry;
+
+/// File aider appends the conversation to, in the project root.
+pub const CHAT_HISTORY: &str = ".aider.chat.history.md";
+
+/// Where the reader is within the history file.
+#[derive(Debug, Clone, Default)]
+pub(super) struct Transcript {
+    /// Start of the current aider run; lines carry no time of their own.
+    started: Option<String>,
+    /// Inside a fenced code or edit block of a reply.
+    in_fence: bool,
+    /// The current reply already produced its headline.
+    replied: bool,
+    /// Counter for the synthetic call ids of applied edits.
+    edits: u64,
+}
+
+impl Transcript {
+    pub(super) fn entries(&mut self, line: &str) -> Vec<LogEntry> {
+        let line = line.trim_end();
+        if let Some(started) = line.strip_prefix("# aider chat started at ") {
+            *self = Transcript {
+                started: Some(started.trim().replacen(' ', "T", 1)),
+                ..Transcript::default()
+            };
+            return vec![self.entry("session_meta", json!({ "originator": "aider" }))];
+        }
+        if line.starts_with("#### ") {
+            self.in_fence = false;
+            self.replied = false;
+            return vec![self.event(json!({ "type": "task_started" }))];
+        }
+        if let Some(output) = line.strip_prefix("> ") {
+            return self.output(output.trim());
+        }
+        if line.trim_start().starts_with("```") {
+            self.in_fence = !self.in_fence;
+            return Vec::new();
+        }
+        if self.in_fence || self.replied || is_edit_marker(line) || line.trim().is_empty() {
+            return Vec::new();
+        }
+        self.replied = true;
+        vec![self.event(json!({ "type": "agent_message", "message": line }))]
+    }
+
+    fn entry(&self, kind: &str, payload: Value) -> LogEntry {
+        LogEntry::new(self.started.clone(), Some(kind.to_string()), payload)
+    }
+
+    fn event(&self, payload: Value) -> LogEntry {
+        self.entry("event_msg", payload)
+    }
+
+    /// Aider's status lines: token usage and applied edits.
+    fn output(&mut self, output: &str) -> Vec<LogEntry> {
+        if let Some(usage) = output.strip_prefix("Tokens: ") {
+            let count = |suffix: &str| {
+                usage
+                    .split(", ")
+                    .find_map(|part| part.split(". ").next()?.strip_suffix(suffix))
+                    .and_then(parse_count)
+                    .unwrap_or(0)
+            };
+            let (sent, received) = (count(" sent"), count(" received"));
+            return vec![self.event(json!({
+                "type": "token_count",
+                "info": {
+                    "total_token_usage": { "input_tokens": sent, "output_tokens": received },
+                    "last_token_usage": { "total_tokens": sent + received },
+                },
+            }))];
+        }
+        if let Some(path) = output.strip_prefix("Applied edit to ") {
+            self.edits += 1;
+            let call_id = format!("aider-edit-{}", self.edits);
+            return vec![
+                self.event(json!({
+                    "type": "patch_apply_begin",
+                    "call_id": call_id,
+                    "changes": { path: { "update": { "unified_diff": "" } } },
+                })),
+                self.event(json!({ "type": "patch_apply_end", "call_id": call_id })),
+            ];
+        }
+        Vec::new()
+    }
+}
+
+fn is_edit_marker(line: &str) -> bool {
+    ["<<<<<<< SEARCH", "=======", ">>>>>>> REPLACE"].contains(&line.trim())
+}
+
+/// Parse aider's abbreviated counts such as `2.4k` or `1,234`.
+fn parse_count(text: &str) -> Option<u64> {
+    let text = text.trim().replace(',', "");
+    let (number, scale) = match text.strip_suffix('k') {
+        Some(number) => (number, 1_000.0),
+        None => (text.as_str(), 1.0),
+    };
+    number
+        .parse::<f64>()
+        .ok()
+        .map(|value| (value * scale).round() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::EventPayload;
+
+    #[test]
+    fn reply_headline_edits_and_tokens_become_events() {
+        let history = "\
+# aider chat started at 2025-10-29 12:00:00
+
+#### add a greeting
+
+I'll add a greeting to main.
+
+```rust
+fn main() {}
+```
+
+> Applied edit to src/main.rs
+> Tokens: 2.4k sent, 150 received. Cost: $0.01 message, $0.02 session.
+";
+        let mut transcript = Transcript::default();
+        let entries: Vec<LogEntry> = history
+            .lines()
+            .flat_map(|line| transcript.entries(line))
+            .collect();
+        let kinds: Vec<&str> = entries.iter().map(LogEntry::kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                "",
+                "task_started",
+                "agent_message",
+                "patch_apply_begin",
+                "patch_apply_end",
+                "token_count"
+            ]
+        );
+        assert_eq!(
+            entries[2].event,
+            EventPayload::AgentMessage {
+                message: "I'll add a greeting to main.".to_string()
+            }
+        );
+        assert_eq!(entries[2].timestamp.as_deref(), Some("2025-10-29T12:00:00"));
+        let EventPayload::TokenCount { info: Some(info) } = &entries[5].event else {
+            panic!("token_count was not typed");
+        };
+        assert_eq!(
+            info.total_token_usage.map(|usage| usage.input_tokens),
+            Some(2400)
+        );
+    }
+}
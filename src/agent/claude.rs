@@ -0,0 +1,285 @@
+//! Claude Code transcripts (`~/.claude/projects/<project>/<session>.jsonl`).
+//! Each line holds a whole user or assistant message; its content blocks map
+//! onto the Codex events with the same meaning.
+
+use serde_json::{Map, Value, json};
+use tracing::warn;
+
+use crate::metrics::METRICS;
+use crate::render::LogEntry;
+
+/// Context window of current Claude models, which transcripts do not record.
+const CONTEXT_WINDOW: u64 = 200_000;
+
+pub(super) fn entries(line: &str) -> Vec<LogEntry> {
+    if line.trim().is_empty() {
+        return Vec::new();
+    }
+    let record: Value = match serde_json::from_str(line) {
+        Ok(record) => record,
+        Err(err) => {
+            let parse_errors = METRICS.record_parse_error();
+            warn!(error = %err, parse_errors, "Skipping malformed log entry");
+            return Vec::new();
+        }
+    };
+    let field = |key: &str| record.get(key).and_then(Value::as_str);
+    let entry = |kind: &str, payload: Value| {
+        LogEntry::new(
+            field("timestamp").map(str::to_string),
+            Some(kind.to_string()),
+            payload,
+        )
+    };
+    let message = record.get("message");
+    let content = message.and_then(|message| message.get("content"));
+
+    let mut entries = Vec::new();
+    if let Some(version) = field("version") {
+        entries.push(entry(
+            "session_meta",
+            json!({ "cwd": field("cwd"), "originator": "claude-code", "cli_version": version }),
+        ));
+    }
+    match field("type") {
+        Some("assistant") => {
+            if let Some(model) = message
+                .and_then(|message| message.get("model"))
+                .and_then(Value::as_str)
+                .filter(|model| !model.starts_with('<'))
+            {
+                entries.push(entry(
+                    "turn_context",
+                    json!({ "cwd": field("cwd"), "model": model }),
+                ));
+            }
+            for block in blocks(content) {
+                let payload = match block.get("type").and_then(Value::as_str) {
+                    Some("thinking") => json!({
+                        "type": "agent_reasoning",
+                        "text": block.get("thinking"),
+                    }),
+                    Some("text") => json!({
+                        "type": "agent_message",
+                        "message": block.get("text"),
+                    }),
+                    Some("tool_use") => tool_use(block, field("cwd")),
+                    _ => continue,
+                };
+                entries.push(entry("event_msg", payload));
+            }
+            if let Some(usage) = message.and_then(|message| message.get("usage")) {
+                entries.push(entry("event_msg", token_count(usage)));
+            }
+        }
+        Some("user") if record.get("isMeta").and_then(Value::as_bool) != Some(true) => {
+            let results: Vec<&str> = blocks(content)
+                .filter(|block| block.get("type").and_then(Value::as_str) == Some("tool_result"))
+                .filter_map(|block| block.get("tool_use_id").and_then(Value::as_str))
+                .collect();
+            if results.is_empty() {
+                // A prompt typed by the user starts a new task.
+                entries.push(entry("event_msg", json!({ "type": "task_started" })));
+            }
+            for call_id in results {
+                entries.push(entry(
+                    "event_msg",
+                    json!({ "type": "exec_command_end", "call_id": call_id }),
+                ));
+            }
+        }
+        _ => {}
+    }
+    entries
+}
+
+fn blocks(content: Option<&Value>) -> impl Iterator<Item = &Value> {
+    content.and_then(Value::as_array).into_iter().flatten()
+}
+
+/// The begin event matching a tool: shell commands, file edits, and the todo
+/// list have Codex equivalents; everything else is shown as a tool call.
+fn tool_use(block: &Value, cwd: Option<&str>) -> Value {
+    let call_id = block.get("id").cloned().unwrap_or(Value::Null);
+    let name = block
+        .get("name")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let input = block.get("input").cloned().unwrap_or(Value::Null);
+    let text = |key: &str| input.get(key).and_then(Value::as_str).unwrap_or_default();
+
+    match name {
+        "Bash" => json!({
+            "type": "exec_command_begin",
+            "call_id": call_id,
+            "command": text("command"),
+            "cwd": cwd,
+        }),
+        "TodoWrite" => {
+            let steps: Vec<Value> = input
+                .get("todos")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .map(|todo| json!({ "step": todo.get("content"), "status": todo.get("status") }))
+                .collect();
+            json!({ "type": "plan_update", "plan": steps })
+        }
+        "Edit" | "MultiEdit" | "Write" => {
+            let change = match name {
+                "Write" => json!({ "add": { "content": text("content") } }),
+                "Edit" => json!({ "update": { "unified_diff": hunk(&input) } }),
+                _ => {
+                    let diff: String = input
+                        .get("edits")
+                        .and_then(Value::as_array)
+                        .into_iter()
+                        .flatten()
+                        .map(hunk)
+                        .collect();
+                    json!({ "update": { "unified_diff": diff } })
+                }
+            };
+            let mut changes = Map::new();
+            changes.insert(text("file_path").to_string(), change);
+            json!({ "type": "patch_apply_begin", "call_id": call_id, "changes": changes })
+        }
+        _ => {
+            let (server, tool) = match name
+                .strip_prefix("mcp__")
+                .and_then(|rest| rest.split_once("__"))
+            {
+                Some((server, tool)) => (Some(server), tool),
+                None => (None, name),
+            };
+            json!({
+                "type": "mcp_tool_call_begin",
+                "call_id": call_id,
+                "invocation": { "server": server, "tool": tool, "arguments": input },
+            })
+        }
+    }
+}
+
+/// A unified diff hunk replacing `old_string` with `new_string`.
+fn hunk(edit: &Value) -> String {
+    let lines = |key: &str| {
+        edit.get(key)
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .lines()
+            .collect::<Vec<_>>()
+    };
+    let (old, new) = (lines("old_string"), lines("new_string"));
+    let mut diff = format!("@@ -1,{} +1,{} @@\n", old.len(), new.len());
+    for line in old {
+        diff.push_str(&format!("-{line}\n"));
+    }
+    for line in new {
+        diff.push_str(&format!("+{line}\n"));
+    }
+    diff
+}
+
+fn token_count(usage: &Value) -> Value {
+    let count = |key: &str| usage.get(key).and_then(Value::as_u64).unwrap_or(0);
+    let cached = count("cache_read_input_tokens");
+    let input = count("input_tokens") + count("cache_creation_input_tokens") + cached;
+    let output = count("output_tokens");
+    json!({
+        "type": "token_count",
+        "info": {
+            "total_token_usage": {
+                "input_tokens": input,
+                "cached_input_tokens": cached,
+                "output_tokens": output,
+            },
+            "last_token_usage": { "total_tokens": input + output },
+            "model_context_window": CONTEXT_WINDOW,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::EventPayload;
+
+    #[test]
+    fn assistant_message_maps_blocks_to_codex_events() {
+        let line = json!({
+            "type": "assistant",
+            "timestamp": "2025-10-29T12:00:00Z",
+            "cwd": "/srv/project",
+            "version": "2.0.1",
+            "message": {
+                "model": "claude-sonnet-4-5",
+                "content": [
+                    { "type": "thinking", "thinking": "Checking the tests" },
+                    { "type": "tool_use", "id": "toolu_1", "name": "Bash", "input": { "command": "cargo test" } },
+                    { "type": "tool_use", "id": "toolu_2", "name": "mcp__github__search", "input": {} }
+                ],
+                "usage": { "input_tokens": 10, "cache_read_input_tokens": 90, "output_tokens": 5 }
+            }
+        })
+        .to_string();
+
+        let entries = entries(&line);
+        let kinds: Vec<(&str, &str)> = entries
+            .iter()
+            .map(|entry| (entry.record.as_deref().unwrap_or_default(), entry.kind()))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("session_meta", ""),
+                ("turn_context", ""),
+                ("event_msg", "agent_reasoning"),
+                ("event_msg", "exec_command_begin"),
+                ("event_msg", "mcp_tool_call_begin"),
+                ("event_msg", "token_count"),
+            ]
+        );
+        let EventPayload::ExecCommandBegin { command, .. } = &entries[3].event else {
+            panic!("exec_command_begin was not typed");
+        };
+        assert_eq!(command.text(), "cargo test");
+        let EventPayload::McpToolCallBegin {
+            invocation: Some(invocation),
+            ..
+        } = &entries[4].event
+        else {
+            panic!("mcp_tool_call_begin was not typed");
+        };
+        assert_eq!(invocation.server.as_deref(), Some("github"));
+        let EventPayload::TokenCount { info: Some(info) } = &entries[5].event else {
+            panic!("token_count was not typed");
+        };
+        assert_eq!(
+            info.last_token_usage.map(|usage| usage.total_tokens),
+            Some(105)
+        );
+    }
+
+    #[test]
+    fn user_lines_start_tasks_or_end_tool_calls() {
+        let prompt = json!({
+            "type": "user",
+            "message": { "role": "user", "content": "Fix the build" }
+        })
+        .to_string();
+        let result = json!({
+            "type": "user",
+            "message": {
+                "role": "user",
+                "content": [{ "type": "tool_result", "tool_use_id": "toolu_1", "content": "ok" }]
+            }
+        })
+        .to_string();
+
+        assert_eq!(entries(&prompt)[0].kind(), "task_started");
+        let ended = entries(&result);
+        assert_eq!(ended[0].kind(), "exec_command_end");
+        assert_eq!(ended[0].event.call_id(), "toolu_1");
+    }
+}
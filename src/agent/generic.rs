@@ -0,0 +1,187 @@
+//! JSON-lines logs of any agent CLI, read through user-supplied selectors for
+//! the timestamp, event kind, and text of each line.
+
+use std::{fmt, str::FromStr};
+
+use anyhow::{Result, bail};
+use serde_json::{Value, json};
+use tracing::warn;
+
+use crate::metrics::METRICS;
+use crate::render::LogEntry;
+
+/// A JSONPath-like field selector such as `$.event.text` or `items[0].msg`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selector(Vec<Step>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    Key(String),
+    Index(usize),
+}
+
+impl FromStr for Selector {
+    type Err = anyhow::Error;
+
+    fn from_str(path: &str) -> Result<Self> {
+        let body = path.strip_prefix('$').unwrap_or(path);
+        let body = body.strip_prefix('.').unwrap_or(body);
+        let mut steps = Vec::new();
+        for part in body.split('.') {
+            let (key, mut indices) = match part.split_once('[') {
+                Some((key, rest)) => (key, Some(rest)),
+                None => (part, None),
+            };
+            if !key.is_empty() {
+                steps.push(Step::Key(key.to_string()));
+            } else if indices.is_none() {
+                bail!("empty segment in selector `{path}`");
+            }
+            while let Some(rest) = indices {
+                let Some((index, tail)) = rest.split_once(']') else {
+                    bail!("unclosed `[` in selector `{path}`");
+                };
+                match index.trim_matches(|c| c == '\'' || c == '"') {
+                    quoted if quoted.len() != index.len() => {
+                        steps.push(Step::Key(quoted.to_string()));
+                    }
+                    _ => steps.push(Step::Index(index.parse().map_err(|_| {
+                        anyhow::anyhow!("bad index `{index}` in selector `{path}`")
+                    })?)),
+                }
+                indices = tail.strip_prefix('[');
+                if indices.is_none() && !tail.is_empty() {
+                    bail!("unexpected `{tail}` in selector `{path}`");
+                }
+            }
+        }
+        Ok(Selector(steps))
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("$")?;
+        for step in &self.0 {
+            match step {
+                Step::Key(key) => write!(f, ".{key}")?,
+                Step::Index(index) => write!(f, "[{index}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Selector {
+    pub fn select<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        self.0.iter().try_fold(value, |value, step| match step {
+            Step::Key(key) => value.get(key),
+            Step::Index(index) => value.get(index),
+        })
+    }
+
+    /// The selected value as text; numbers and booleans are formatted.
+    fn text(&self, value: &Value) -> Option<String> {
+        match self.select(value)? {
+            Value::String(text) => Some(text.clone()),
+            Value::Null | Value::Array(_) | Value::Object(_) => None,
+            other => Some(other.to_string()),
+        }
+    }
+}
+
+/// How `--agent generic` reads a log line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenericFormat {
+    pub timestamp: Selector,
+    pub kind: Selector,
+    pub text: Selector,
+    /// Event kinds to show; every line with text when empty.
+    pub event_kinds: Vec<String>,
+    /// Event kinds shown as final replies rather than reasoning.
+    pub message_kinds: Vec<String>,
+}
+
+impl Default for GenericFormat {
+    fn default() -> Self {
+        let selector = |key: &str| Selector(vec![Step::Key(key.to_string())]);
+        Self {
+            timestamp: selector("timestamp"),
+            kind: selector("type"),
+            text: selector("text"),
+            event_kinds: Vec::new(),
+            message_kinds: Vec::new(),
+        }
+    }
+}
+
+pub(super) fn entries(line: &str, format: &GenericFormat) -> Vec<LogEntry> {
+    if line.trim().is_empty() {
+        return Vec::new();
+    }
+    let record: Value = match serde_json::from_str(line) {
+        Ok(record) => record,
+        Err(err) => {
+            let parse_errors = METRICS.record_parse_error();
+            warn!(error = %err, parse_errors, "Skipping malformed log entry");
+            return Vec::new();
+        }
+    };
+    let kind = format.kind.text(&record).unwrap_or_default();
+    if !format.event_kinds.is_empty() && !format.event_kinds.contains(&kind) {
+        return Vec::new();
+    }
+    let Some(text) = format.text.text(&record) else {
+        return Vec::new();
+    };
+    let payload = if format.message_kinds.contains(&kind) {
+        json!({ "type": "agent_message", "message": text })
+    } else {
+        json!({ "type": "agent_reasoning", "text": text })
+    };
+    vec![LogEntry::new(
+        format.timestamp.text(&record),
+        Some("event_msg".to_string()),
+        payload,
+    )]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selectors_pick_nested_fields_and_indices() -> Result<()> {
+        let value = json!({ "event": { "parts": [{ "text": "hi" }], "odd key": 3 } });
+        let selector: Selector = "$.event.parts[0].text".parse()?;
+        assert_eq!(selector.select(&value), Some(&json!("hi")));
+        assert_eq!(selector.to_string(), "$.event.parts[0].text");
+        let quoted: Selector = "event['odd key']".parse()?;
+        assert_eq!(quoted.text(&value).as_deref(), Some("3"));
+        assert!("event[".parse::<Selector>().is_err());
+        assert!("a..b".parse::<Selector>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn configured_kinds_become_reasoning_or_messages() -> Result<()> {
+        let format = GenericFormat {
+            timestamp: "time".parse()?,
+            kind: "event.kind".parse()?,
+            text: "event.body".parse()?,
+            event_kinds: vec!["think".to_string(), "reply".to_string()],
+            message_kinds: vec!["reply".to_string()],
+        };
+        let line = |kind: &str| {
+            json!({ "time": "2025-10-29T12:00:00Z", "event": { "kind": kind, "body": "Working" } })
+                .to_string()
+        };
+
+        let think = entries(&line("think"), &format);
+        assert_eq!(think[0].kind(), "agent_reasoning");
+        assert_eq!(think[0].timestamp.as_deref(), Some("2025-10-29T12:00:00Z"));
+        assert_eq!(entries(&line("reply"), &format)[0].kind(), "agent_message");
+        assert!(entries(&line("debug"), &format).is_empty());
+        Ok(())
+    }
+}
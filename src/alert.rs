@@ -0,0 +1,299 @@
+//! Desktop notifications and sounds for the moments a session needs
+//! attention: an approval request, an error, or a finished task.
+
+use std::{collections::HashMap, fmt, path::Path, process::Command, str::FromStr};
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::output::WaybarOutput;
+
+/// An event that can raise an alert.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum AlertKind {
+    /// Codex waits for a command or patch to be approved.
+    Approval,
+    /// A turn failed.
+    Error,
+    /// A task completed.
+    Done,
+}
+
+impl FromStr for AlertKind {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "approval" => Ok(AlertKind::Approval),
+            "error" => Ok(AlertKind::Error),
+            "done" => Ok(AlertKind::Done),
+            other => bail!("unknown alert `{other}` (expected `approval`, `error`, or `done`)"),
+        }
+    }
+}
+
+impl AlertKind {
+    /// The alert a session payload stands for, if any. Retried stream errors
+    /// do not count.
+    pub fn of(payload: &WaybarOutput) -> Option<AlertKind> {
+        let has = |class: &str| payload.class.iter().any(|existing| existing == class);
+        if has("approval-pending") {
+            Some(AlertKind::Approval)
+        } else if has("error") && !has("stream-error") {
+            Some(AlertKind::Error)
+        } else if has("state-done") {
+            Some(AlertKind::Done)
+        } else {
+            None
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            AlertKind::Approval => "Codex needs approval",
+            AlertKind::Error => "Codex error",
+            AlertKind::Done => "Codex finished",
+        }
+    }
+}
+
+/// What each session last alerted about, so an alert fires once when a
+/// session enters that state rather than on every later update.
+#[derive(Debug, Default)]
+pub struct AlertTracker {
+    last: HashMap<String, (AlertKind, String)>,
+}
+
+impl AlertTracker {
+    /// Record the payload a session shows now, returning its alert if the
+    /// session was not already showing it.
+    pub fn observe(&mut self, session_id: &str, payload: &WaybarOutput) -> Option<AlertKind> {
+        let Some(kind) = AlertKind::of(payload) else {
+            self.last.remove(session_id);
+            return None;
+        };
+        let seen = (kind, payload.text.clone());
+        if self.last.get(session_id) == Some(&seen) {
+            return None;
+        }
+        self.last.insert(session_id.to_string(), seen);
+        Some(kind)
+    }
+
+    pub fn forget(&mut self, session_id: &str) {
+        self.last.remove(session_id);
+    }
+}
+
+/// Show a desktop notification for `kind` without waiting for it, over D-Bus
+/// in `dbus` builds and through `notify-send` otherwise. Failures are
+/// reported on stderr; the bar keeps working without a notification daemon.
+pub fn notify(kind: AlertKind, payload: &WaybarOutput) {
+    #[cfg(feature = "dbus")]
+    {
+        let body = payload.text.clone();
+        tokio::spawn(async move {
+            let urgency = match kind {
+                AlertKind::Approval => crate::dbus::Urgency::Critical,
+                AlertKind::Error | AlertKind::Done => crate::dbus::Urgency::Normal,
+            };
+            if let Err(err) = crate::dbus::send_notification(kind.title(), &body, urgency).await {
+                warn!(error = %format!("{err:#}"), "Desktop notification failed");
+            }
+        });
+    }
+    #[cfg(not(feature = "dbus"))]
+    {
+        let mut command = notify_send(kind, &payload.text);
+        tokio::task::spawn_blocking(move || match command.status() {
+            Ok(status) if !status.success() => warn!(%status, "notify-send failed"),
+            Ok(_) => {}
+            Err(err) => warn!(error = %err, "notify-send failed to start"),
+        });
+    }
+}
+
+/// The `notify-send` call for builds without D-Bus, with the same app name
+/// and urgency the D-Bus path sends.
+#[cfg(any(test, not(feature = "dbus")))]
+fn notify_send(kind: AlertKind, body: &str) -> Command {
+    let urgency = match kind {
+        AlertKind::Approval => "critical",
+        AlertKind::Error | AlertKind::Done => "normal",
+    };
+    let mut command = Command::new("notify-send");
+    command
+        .args(["--app-name", "codex-shimmer", "--urgency", urgency, "--"])
+        .args([kind.title(), body]);
+    command
+}
+
+/// Play `sound` with `player`, a command line the file is appended to, in
+/// the background.
+pub fn play_sound(player: &str, sound: &Path) {
+    let mut words = player.split_whitespace();
+    let Some(program) = words.next() else {
+        return;
+    };
+    let mut command = Command::new(program);
+    command.args(words).arg(sound);
+    let player = player.to_string();
+    tokio::task::spawn_blocking(move || match command.status() {
+        Ok(status) if !status.success() => warn!(player, %status, "Sound player failed"),
+        Ok(_) => {}
+        Err(err) => warn!(player, error = %err, "Sound player failed to start"),
+    });
+}
+
+/// A daily span of local time such as `22:00-07:00` in which sounds stay
+/// off. It may run past midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct QuietHours {
+    /// Minutes after midnight.
+    start: u16,
+    end: u16,
+}
+
+impl FromStr for QuietHours {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let minutes = |time: &str| -> Result<u16> {
+            let (hours, minutes) = time.trim().split_once(':').context("expected `HH:MM`")?;
+            let (hours, minutes): (u16, u16) = (hours.parse()?, minutes.parse()?);
+            if hours > 23 || minutes > 59 {
+                bail!("`{time}` is not a time of day");
+            }
+            Ok(hours * 60 + minutes)
+        };
+        let parse = || -> Result<Self> {
+            let (start, end) = value.split_once('-').context("expected `HH:MM-HH:MM`")?;
+            Ok(QuietHours {
+                start: minutes(start)?,
+                end: minutes(end)?,
+            })
+        };
+        parse().with_context(|| format!("Invalid quiet hours `{value}`"))
+    }
+}
+
+impl TryFrom<String> for QuietHours {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl fmt::Display for QuietHours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (start, end) = (self.start, self.end);
+        write!(
+            f,
+            "{:02}:{:02}-{:02}:{:02}",
+            start / 60,
+            start % 60,
+            end / 60,
+            end % 60
+        )
+    }
+}
+
+impl QuietHours {
+    /// Whether the local time of day is inside the span.
+    pub fn active(&self) -> bool {
+        local_minute_of_day().is_some_and(|minute| self.contains(minute))
+    }
+
+    fn contains(&self, minute: u16) -> bool {
+        if self.start <= self.end {
+            (self.start..self.end).contains(&minute)
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+}
+
+/// Minutes since local midnight, from the C library's idea of the time zone.
+fn local_minute_of_day() -> Option<u16> {
+    let now = unsafe { libc::time(std::ptr::null_mut()) };
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&now, &mut tm) }.is_null() {
+        return None;
+    }
+    Some((tm.tm_hour * 60 + tm.tm_min) as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::idle_payload;
+
+    #[test]
+    fn alerts_fire_once_per_state() {
+        let payload = |text: &str, class: &[&str]| WaybarOutput {
+            text: text.to_string(),
+            class: class.iter().map(|class| class.to_string()).collect(),
+            ..idle_payload()
+        };
+        let approval = payload("Approval needed: rm -rf target", &["approval-pending"]);
+        let mut tracker = AlertTracker::default();
+
+        assert_eq!(tracker.observe("a", &approval), Some(AlertKind::Approval));
+        assert_eq!(tracker.observe("a", &approval), None);
+        assert_eq!(tracker.observe("b", &approval), Some(AlertKind::Approval));
+        assert_eq!(
+            tracker.observe("a", &payload("Running", &["state-working"])),
+            None
+        );
+        assert_eq!(tracker.observe("a", &approval), Some(AlertKind::Approval));
+        assert_eq!(
+            tracker.observe(
+                "a",
+                &payload("stream error: retrying", &["error", "stream-error"])
+            ),
+            None
+        );
+        assert_eq!(
+            tracker.observe("a", &payload("All tests pass.", &["state-done"])),
+            Some(AlertKind::Done)
+        );
+    }
+
+    #[test]
+    fn quiet_hours_may_wrap_past_midnight() -> Result<()> {
+        let night: QuietHours = "22:00-07:30".parse()?;
+        assert!(night.contains(23 * 60));
+        assert!(night.contains(7 * 60 + 29));
+        assert!(!night.contains(7 * 60 + 30));
+        assert!(!night.contains(12 * 60));
+        assert_eq!(night.to_string(), "22:00-07:30");
+
+        let lunch: QuietHours = "12:00-13:00".parse()?;
+        assert!(lunch.contains(12 * 60 + 30) && !lunch.contains(13 * 60));
+        assert!("25:00-07:00".parse::<QuietHours>().is_err());
+        assert!("22:00".parse::<QuietHours>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn notify_send_matches_the_dbus_notification() {
+        let command = notify_send(AlertKind::Approval, "Approval needed: rm -rf target");
+        assert_eq!(command.get_program(), "notify-send");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            [
+                "--app-name",
+                "codex-shimmer",
+                "--urgency",
+                "critical",
+                "--",
+                "Codex needs approval",
+                "Approval needed: rm -rf target"
+            ]
+        );
+    }
+}
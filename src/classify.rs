@@ -0,0 +1,95 @@
+//! User-defined rules that add CSS classes to a payload when its label
+//! mentions a keyword or matches a regex, so themes can react to content.
+
+use anyhow::{Context, Result, bail};
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+
+/// One `[[class-rule]]` table of the config file.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClassRule {
+    pub class: String,
+    /// A keyword, matched case-insensitively anywhere in the label.
+    pub contains: Option<String>,
+    pub pattern: Option<String>,
+}
+
+/// Compiled class rules; every rule that matches adds its class.
+#[derive(Debug, Clone, Default)]
+pub struct ClassRules {
+    rules: Vec<(Regex, String)>,
+}
+
+impl PartialEq for ClassRules {
+    fn eq(&self, other: &Self) -> bool {
+        self.rules.len() == other.rules.len()
+            && self
+                .rules
+                .iter()
+                .zip(&other.rules)
+                .all(|((a, a_class), (b, b_class))| a.as_str() == b.as_str() && a_class == b_class)
+    }
+}
+
+impl Eq for ClassRules {}
+
+impl ClassRules {
+    pub fn new(rules: &[ClassRule]) -> Result<Self> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                if rule.class.trim().is_empty() {
+                    bail!("class rule without a `class`");
+                }
+                let pattern = match (&rule.contains, &rule.pattern) {
+                    (Some(keyword), None) => RegexBuilder::new(&regex::escape(keyword))
+                        .case_insensitive(true)
+                        .build()?,
+                    (None, Some(pattern)) => Regex::new(pattern)
+                        .with_context(|| format!("Invalid class rule pattern `{pattern}`"))?,
+                    _ => bail!(
+                        "class rule `{}` needs exactly one of `contains` or `pattern`",
+                        rule.class
+                    ),
+                };
+                Ok((pattern, rule.class.trim().to_string()))
+            })
+            .collect::<Result<_>>()?;
+        Ok(ClassRules { rules })
+    }
+
+    /// The classes whose rules match `text`, in rule order.
+    pub fn classes<'a>(&'a self, text: &'a str) -> impl Iterator<Item = &'a str> {
+        self.rules
+            .iter()
+            .filter(move |(pattern, _)| pattern.is_match(text))
+            .map(|(_, class)| class.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keywords_and_patterns_add_classes() -> Result<()> {
+        let rule = |class: &str, contains: Option<&str>, pattern: Option<&str>| ClassRule {
+            class: class.to_string(),
+            contains: contains.map(str::to_string),
+            pattern: pattern.map(str::to_string),
+        };
+        let rules = ClassRules::new(&[
+            rule("warning", Some("failed"), None),
+            rule("urgent", None, Some(r"\berror\b")),
+        ])?;
+        let classes = |text| rules.classes(text).collect::<Vec<_>>();
+        assert_eq!(classes("2 tests FAILED"), ["warning"]);
+        assert_eq!(classes("error: build failed"), ["warning", "urgent"]);
+        assert!(classes("all green").is_empty());
+
+        assert!(ClassRules::new(&[rule("x", Some("a"), Some("b"))]).is_err());
+        assert!(ClassRules::new(&[rule("", Some("a"), None)]).is_err());
+        Ok(())
+    }
+}
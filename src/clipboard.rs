@@ -0,0 +1,28 @@
+//! The Wayland clipboard, through `wl-copy` from wl-clipboard, which talks the
+//! wlr data-control protocol and stays around to serve the selection.
+
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, Result, bail};
+
+/// Put `text` on the clipboard.
+pub fn copy(text: &str) -> Result<()> {
+    let mut child = Command::new("wl-copy")
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("Failed to run wl-copy; is wl-clipboard installed?")?;
+    child
+        .stdin
+        .take()
+        .context("wl-copy has no stdin")?
+        .write_all(text.as_bytes())
+        .context("Failed to write to wl-copy")?;
+    let status = child.wait()?;
+    if !status.success() {
+        bail!("wl-copy failed ({status})");
+    }
+    Ok(())
+}
@@ -0,0 +1,1129 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::ErrorKind,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result, bail};
+use dirs::{cache_dir, config_dir, home_dir, runtime_dir, state_dir};
+use glob::Pattern;
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::agent::{
+    Agent,
+    generic::{GenericFormat, Selector},
+};
+use crate::alert::{AlertKind, QuietHours};
+use crate::classify::{ClassRule, ClassRules};
+use crate::container::Container;
+use crate::discovery::{CodexHome, Discovery, IgnoreList, PathIndex, SessionSource};
+use crate::focus::Compositor;
+use crate::output::PayloadVersion;
+use crate::redact::Redactor;
+use crate::render::{
+    DisplayMode, ElapsedDisplay, Markup, PercentageSource, PhaseIcons, RenderOptions, RendererKind,
+    TimestampStyle, Truncation,
+};
+use crate::rewrite::{PhaseAliasRule, PhaseAliases, RewriteRule, Rewrites};
+use crate::statsd;
+use crate::sway::BarMode;
+use crate::template::Template;
+
+const DEFAULT_POLL_MS: u64 = 250;
+const DEFAULT_MAX_POLL_MS: u64 = 10_000;
+const DEFAULT_SESSION_REFRESH_SECS: u64 = 5;
+const DEFAULT_SESSION_WINDOW: usize = 4;
+const DEFAULT_MAX_SESSIONS: usize = 16;
+const DEFAULT_MAX_CHARS: usize = 120;
+const DEFAULT_MAX_LINE_BYTES: usize = 256 * 1024;
+const DEFAULT_SWITCH_HYSTERESIS_MS: u64 = 1000;
+const DEFAULT_RECORD_MAX_BYTES: u64 = 10 * 1024 * 1024;
+const DEFAULT_WAYBAR_PROCESS: &str = "waybar";
+const DEFAULT_SOUND_PLAYER: &str = "paplay";
+const DEFAULT_TERMINAL: &str = "xdg-terminal-exec";
+const DEFAULT_MQTT_TOPIC: &str = "codex-shimmer/payload";
+const DEFAULT_LOG_GLOB: &str = "**/*.jsonl";
+/// The merged cache inside `--cache-dir` when `--cache-file` is not given.
+const MERGED_CACHE_NAME: &str = "latest.json";
+
+/// Options that can be set from `config.toml`. Every field mirrors a CLI flag;
+/// command-line values always win over the file.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub session_file: Option<PathBuf>,
+    pub session_id: Option<String>,
+    pub history_path: Option<PathBuf>,
+    pub sessions_root: Option<PathBuf>,
+    /// Codex homes followed together, as `NAME=PATH` or just `PATH`.
+    pub codex_home: Option<Vec<String>>,
+    /// Codex homes inside containers, as `ENGINE:CONTAINER[:PATH]`.
+    pub container: Option<Vec<String>>,
+    pub agent: Option<Agent>,
+    pub discovery: Option<Discovery>,
+    /// Glob patterns of the models whose sessions are shown.
+    pub model_filter: Option<Vec<String>>,
+    /// Session ids and working directory globs never tracked.
+    pub ignore: Option<Vec<String>>,
+    pub ignore_file: Option<PathBuf>,
+    /// Prefer the sessions running where the focused window is.
+    pub follow_focus: Option<Compositor>,
+    /// Sway bar mode while an approval is pending.
+    pub sway_approval_bar: Option<BarMode>,
+    pub log_glob: Option<String>,
+    pub timestamp_path: Option<String>,
+    pub kind_path: Option<String>,
+    pub text_path: Option<String>,
+    pub event_kinds: Option<Vec<String>>,
+    pub message_kinds: Option<Vec<String>>,
+    pub poll_ms: Option<u64>,
+    pub max_poll_ms: Option<u64>,
+    pub session_refresh_secs: Option<u64>,
+    pub session_window: Option<usize>,
+    pub max_sessions: Option<usize>,
+    pub max_line_bytes: Option<usize>,
+    pub idle_after_secs: Option<u64>,
+    pub min_emit_interval_ms: Option<u64>,
+    pub switch_hysteresis_ms: Option<u64>,
+    pub max_chars: Option<usize>,
+    pub ellipsis: Option<String>,
+    /// `[[rewrite]]` rules, in order.
+    pub rewrite: Option<Vec<RewriteRule>>,
+    pub mode: Option<DisplayMode>,
+    pub percentage: Option<PercentageSource>,
+    pub renderer: Option<RendererKind>,
+    pub markup: Option<Markup>,
+    pub timestamps: Option<TimestampStyle>,
+    pub elapsed: Option<ElapsedDisplay>,
+    pub payload: Option<PayloadVersion>,
+    /// Extra patterns whose matches are masked.
+    pub redact: Option<Vec<String>>,
+    pub redact_defaults: Option<bool>,
+    /// `[[class-rule]]` tables.
+    pub class_rule: Option<Vec<ClassRule>>,
+    pub format: Option<Template>,
+    pub tooltip_format: Option<Template>,
+    /// Icons by phase slug, layered over the defaults.
+    pub phase_icons: Option<BTreeMap<String, String>>,
+    /// `[[phase-alias]]` rules, in order.
+    pub phase_alias: Option<Vec<PhaseAliasRule>>,
+    /// Events that raise a desktop notification.
+    pub notify: Option<Vec<AlertKind>>,
+    /// Sound files by event, layered over the fallback's.
+    pub sound: Option<BTreeMap<AlertKind, PathBuf>>,
+    pub sound_player: Option<String>,
+    pub quiet_hours: Option<QuietHours>,
+    /// Command `open` runs for the displayed session.
+    pub open_command: Option<Template>,
+    /// Command line that `open` appends its command to.
+    pub terminal: Option<String>,
+    pub mqtt_broker: Option<String>,
+    pub mqtt_topic: Option<String>,
+    pub mqtt_qos: Option<u8>,
+    pub webhook_url: Option<String>,
+    pub webhook_class: Option<Vec<String>>,
+    pub waybar_signal: Option<u8>,
+    pub waybar_process: Option<Vec<String>>,
+    pub cache_file: Option<PathBuf>,
+    pub cache_dir: Option<PathBuf>,
+    pub cache_reasoning: Option<PathBuf>,
+    pub cache_status: Option<PathBuf>,
+    pub cache_tokens: Option<PathBuf>,
+    pub no_fsync: Option<bool>,
+    pub record: Option<PathBuf>,
+    pub record_max_bytes: Option<u64>,
+    pub event_store: Option<bool>,
+    pub event_store_path: Option<PathBuf>,
+    pub start_at_beginning: Option<bool>,
+    pub stdout: Option<bool>,
+    pub control_socket: Option<PathBuf>,
+    pub metrics_addr: Option<SocketAddr>,
+    pub otel_endpoint: Option<String>,
+    pub statsd_addr: Option<String>,
+    pub statsd_prefix: Option<String>,
+    pub dogstatsd: Option<bool>,
+    pub log_level: Option<String>,
+    pub log_file: Option<PathBuf>,
+}
+
+impl Config {
+    /// Read a config file, returning `None` when it does not exist.
+    pub fn load(path: &Path) -> Result<Option<Config>> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let config: Config = toml::from_str(&content)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        Ok(Some(config))
+    }
+
+    /// Log file path with `~` expanded.
+    pub fn log_file_path(&self) -> Option<PathBuf> {
+        self.log_file.as_deref().map(expand_tilde)
+    }
+
+    /// Control socket path, falling back to the per-user default.
+    pub fn control_socket_path(&self) -> PathBuf {
+        self.control_socket
+            .as_deref()
+            .map(expand_tilde)
+            .unwrap_or_else(default_control_socket)
+    }
+
+    /// Event store path, falling back to the per-user default, whether or not
+    /// the store is enabled.
+    pub fn event_store_path(&self) -> Result<PathBuf> {
+        match &self.event_store_path {
+            Some(path) => Ok(expand_tilde(path)),
+            None => default_event_store(),
+        }
+    }
+
+    /// Fill every unset field of `self` from `fallback`.
+    pub fn merged_over(&self, fallback: &Config) -> Config {
+        Config {
+            session_file: self
+                .session_file
+                .clone()
+                .or_else(|| fallback.session_file.clone()),
+            session_id: self
+                .session_id
+                .clone()
+                .or_else(|| fallback.session_id.clone()),
+            history_path: self
+                .history_path
+                .clone()
+                .or_else(|| fallback.history_path.clone()),
+            sessions_root: self
+                .sessions_root
+                .clone()
+                .or_else(|| fallback.sessions_root.clone()),
+            codex_home: self
+                .codex_home
+                .clone()
+                .or_else(|| fallback.codex_home.clone()),
+            container: self
+                .container
+                .clone()
+                .or_else(|| fallback.container.clone()),
+            agent: self.agent.or(fallback.agent),
+            discovery: self.discovery.or(fallback.discovery),
+            model_filter: self
+                .model_filter
+                .clone()
+                .or_else(|| fallback.model_filter.clone()),
+            ignore: self.ignore.clone().or_else(|| fallback.ignore.clone()),
+            ignore_file: self
+                .ignore_file
+                .clone()
+                .or_else(|| fallback.ignore_file.clone()),
+            follow_focus: self.follow_focus.or(fallback.follow_focus),
+            sway_approval_bar: self.sway_approval_bar.or(fallback.sway_approval_bar),
+            log_glob: self.log_glob.clone().or_else(|| fallback.log_glob.clone()),
+            timestamp_path: self
+                .timestamp_path
+                .clone()
+                .or_else(|| fallback.timestamp_path.clone()),
+            kind_path: self
+                .kind_path
+                .clone()
+                .or_else(|| fallback.kind_path.clone()),
+            text_path: self
+                .text_path
+                .clone()
+                .or_else(|| fallback.text_path.clone()),
+            event_kinds: self
+                .event_kinds
+                .clone()
+                .or_else(|| fallback.event_kinds.clone()),
+            message_kinds: self
+                .message_kinds
+                .clone()
+                .or_else(|| fallback.message_kinds.clone()),
+            poll_ms: self.poll_ms.or(fallback.poll_ms),
+            max_poll_ms: self.max_poll_ms.or(fallback.max_poll_ms),
+            session_refresh_secs: self.session_refresh_secs.or(fallback.session_refresh_secs),
+            session_window: self.session_window.or(fallback.session_window),
+            max_sessions: self.max_sessions.or(fallback.max_sessions),
+            max_line_bytes: self.max_line_bytes.or(fallback.max_line_bytes),
+            idle_after_secs: self.idle_after_secs.or(fallback.idle_after_secs),
+            min_emit_interval_ms: self.min_emit_interval_ms.or(fallback.min_emit_interval_ms),
+            switch_hysteresis_ms: self.switch_hysteresis_ms.or(fallback.switch_hysteresis_ms),
+            max_chars: self.max_chars.or(fallback.max_chars),
+            ellipsis: self.ellipsis.clone().or_else(|| fallback.ellipsis.clone()),
+            rewrite: self.rewrite.clone().or_else(|| fallback.rewrite.clone()),
+            mode: self.mode.or(fallback.mode),
+            percentage: self.percentage.or(fallback.percentage),
+            renderer: self.renderer.or(fallback.renderer),
+            markup: self.markup.or(fallback.markup),
+            timestamps: self.timestamps.or(fallback.timestamps),
+            elapsed: self.elapsed.or(fallback.elapsed),
+            payload: self.payload.or(fallback.payload),
+            redact: self.redact.clone().or_else(|| fallback.redact.clone()),
+            redact_defaults: self.redact_defaults.or(fallback.redact_defaults),
+            class_rule: self
+                .class_rule
+                .clone()
+                .or_else(|| fallback.class_rule.clone()),
+            format: self.format.clone().or_else(|| fallback.format.clone()),
+            tooltip_format: self
+                .tooltip_format
+                .clone()
+                .or_else(|| fallback.tooltip_format.clone()),
+            phase_icons: match (&self.phase_icons, &fallback.phase_icons) {
+                (Some(icons), Some(fallback)) => {
+                    let mut merged = fallback.clone();
+                    merged.extend(icons.clone());
+                    Some(merged)
+                }
+                (icons, fallback) => icons.clone().or_else(|| fallback.clone()),
+            },
+            phase_alias: self
+                .phase_alias
+                .clone()
+                .or_else(|| fallback.phase_alias.clone()),
+            notify: self.notify.clone().or_else(|| fallback.notify.clone()),
+            sound: match (&self.sound, &fallback.sound) {
+                (Some(sounds), Some(fallback)) => {
+                    let mut merged = fallback.clone();
+                    merged.extend(sounds.clone());
+                    Some(merged)
+                }
+                (sounds, fallback) => sounds.clone().or_else(|| fallback.clone()),
+            },
+            sound_player: self
+                .sound_player
+                .clone()
+                .or_else(|| fallback.sound_player.clone()),
+            quiet_hours: self.quiet_hours.or(fallback.quiet_hours),
+            open_command: self
+                .open_command
+                .clone()
+                .or_else(|| fallback.open_command.clone()),
+            terminal: self.terminal.clone().or_else(|| fallback.terminal.clone()),
+            mqtt_broker: self
+                .mqtt_broker
+                .clone()
+                .or_else(|| fallback.mqtt_broker.clone()),
+            mqtt_topic: self
+                .mqtt_topic
+                .clone()
+                .or_else(|| fallback.mqtt_topic.clone()),
+            mqtt_qos: self.mqtt_qos.or(fallback.mqtt_qos),
+            webhook_url: self
+                .webhook_url
+                .clone()
+                .or_else(|| fallback.webhook_url.clone()),
+            webhook_class: self
+                .webhook_class
+                .clone()
+                .or_else(|| fallback.webhook_class.clone()),
+            waybar_signal: self.waybar_signal.or(fallback.waybar_signal),
+            waybar_process: self
+                .waybar_process
+                .clone()
+                .or_else(|| fallback.waybar_process.clone()),
+            cache_file: self
+                .cache_file
+                .clone()
+                .or_else(|| fallback.cache_file.clone()),
+            cache_dir: self
+                .cache_dir
+                .clone()
+                .or_else(|| fallback.cache_dir.clone()),
+            cache_reasoning: self
+                .cache_reasoning
+                .clone()
+                .or_else(|| fallback.cache_reasoning.clone()),
+            cache_status: self
+                .cache_status
+                .clone()
+                .or_else(|| fallback.cache_status.clone()),
+            cache_tokens: self
+                .cache_tokens
+                .clone()
+                .or_else(|| fallback.cache_tokens.clone()),
+            no_fsync: self.no_fsync.or(fallback.no_fsync),
+            record: self.record.clone().or_else(|| fallback.record.clone()),
+            record_max_bytes: self.record_max_bytes.or(fallback.record_max_bytes),
+            event_store: self.event_store.or(fallback.event_store),
+            event_store_path: self
+                .event_store_path
+                .clone()
+                .or_else(|| fallback.event_store_path.clone()),
+            start_at_beginning: self.start_at_beginning.or(fallback.start_at_beginning),
+            stdout: self.stdout.or(fallback.stdout),
+            control_socket: self
+                .control_socket
+                .clone()
+                .or_else(|| fallback.control_socket.clone()),
+            metrics_addr: self.metrics_addr.or(fallback.metrics_addr),
+            otel_endpoint: self
+                .otel_endpoint
+                .clone()
+                .or_else(|| fallback.otel_endpoint.clone()),
+            statsd_addr: self
+                .statsd_addr
+                .clone()
+                .or_else(|| fallback.statsd_addr.clone()),
+            statsd_prefix: self
+                .statsd_prefix
+                .clone()
+                .or_else(|| fallback.statsd_prefix.clone()),
+            dogstatsd: self.dogstatsd.or(fallback.dogstatsd),
+            log_level: self
+                .log_level
+                .clone()
+                .or_else(|| fallback.log_level.clone()),
+            log_file: self.log_file.clone().or_else(|| fallback.log_file.clone()),
+        }
+    }
+}
+
+/// Fully resolved runtime settings after applying CLI overrides, the config
+/// file, and built-in defaults.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    pub session_file: Option<PathBuf>,
+    pub session_id: Option<String>,
+    pub history_path: PathBuf,
+    pub sessions_root: PathBuf,
+    /// Codex homes whose sessions are merged; empty to follow just one.
+    pub codex_homes: Vec<CodexHome>,
+    pub agent: Agent,
+    /// How Codex sessions are found.
+    pub discovery: Discovery,
+    /// Only sessions of a model matching one of these are shown; empty
+    /// shows all.
+    pub model_filter: Vec<Pattern>,
+    /// Discovered sessions never tracked.
+    pub ignore: IgnoreList,
+    /// Whose focused window decides which session is shown, fixed until a
+    /// restart.
+    pub follow_focus: Option<Compositor>,
+    /// Switch swaybar to this mode while an approval is pending, fixed until
+    /// a restart.
+    pub sway_approval_bar: Option<BarMode>,
+    /// Log files of `--agent generic`, relative to `sessions_root`.
+    pub log_glob: String,
+    pub generic: GenericFormat,
+    pub poll_ms: u64,
+    /// Longest poll interval a quiet session backs off to.
+    pub max_poll_ms: u64,
+    pub session_refresh_secs: u64,
+    pub session_window: usize,
+    /// Most sessions tracked at once; 0 for no limit.
+    pub max_sessions: usize,
+    /// Longer log lines are cut short, or skipped when that fails.
+    pub max_line_bytes: usize,
+    /// Show the idle payload after this many seconds without events; 0 never.
+    pub idle_after_secs: u64,
+    /// Shortest time between two writes of the same output; 0 for no limit.
+    pub min_emit_interval_ms: u64,
+    /// Another session's event takes over the display only when it is at
+    /// least this much newer than the shown session's latest; 0 always.
+    pub switch_hysteresis_ms: u64,
+    pub max_chars: usize,
+    /// Marks a shortened label; empty for none.
+    pub ellipsis: String,
+    /// Rules run over label text before truncation.
+    pub rewrites: Rewrites,
+    pub mode: DisplayMode,
+    pub percentage: PercentageSource,
+    pub renderer: RendererKind,
+    pub markup: Markup,
+    pub timestamps: TimestampStyle,
+    pub elapsed: ElapsedDisplay,
+    pub payload: PayloadVersion,
+    pub redactor: Redactor,
+    pub class_rules: ClassRules,
+    /// Label format, replacing the rendered text.
+    pub format: Option<Template>,
+    /// Tooltip format, replacing the rendered tooltip.
+    pub tooltip_format: Option<Template>,
+    pub phase_icons: PhaseIcons,
+    pub phase_aliases: PhaseAliases,
+    pub notify: Vec<AlertKind>,
+    /// Sound files played by `sound_player` when these events arrive.
+    pub sounds: BTreeMap<AlertKind, PathBuf>,
+    pub sound_player: String,
+    /// When sounds stay off.
+    pub quiet_hours: Option<QuietHours>,
+    /// What `open` runs; `None` resumes the session with the agent's CLI.
+    pub open_command: Option<Template>,
+    pub terminal: String,
+    /// Broker that receives every payload, with the `mqtt` feature.
+    pub mqtt_broker: Option<String>,
+    pub mqtt_topic: String,
+    /// 0, 1, or 2.
+    pub mqtt_qos: u8,
+    /// URL every payload is posted to as JSON.
+    pub webhook_url: Option<String>,
+    /// Classes a payload needs one of to be posted; empty posts all.
+    pub webhook_class: Vec<String>,
+    pub waybar_signal: Option<u8>,
+    /// Process names that receive the Waybar signal.
+    pub waybar_process: Vec<String>,
+    pub cache_file: Option<PathBuf>,
+    /// Directory holding one cache file per tracked session.
+    pub cache_dir: Option<PathBuf>,
+    /// Files for the activity label, task state, and context usage of the
+    /// session on display, each for its own module.
+    pub cache_reasoning: Option<PathBuf>,
+    pub cache_status: Option<PathBuf>,
+    pub cache_tokens: Option<PathBuf>,
+    /// Flush cache files to disk before renaming them into place.
+    pub fsync: bool,
+    /// Debug file of every consumed log line and the payload it rendered.
+    pub record: Option<PathBuf>,
+    /// Size at which the debug file is moved aside and started again.
+    pub record_max_bytes: u64,
+    /// SQLite database every rendered event is kept in, with the `store`
+    /// feature; fixed until a restart.
+    pub event_store: Option<PathBuf>,
+    pub start_at_beginning: bool,
+    pub stdout: bool,
+    pub control_socket: PathBuf,
+    /// Where `/metrics` is served, if anywhere.
+    pub metrics_addr: Option<SocketAddr>,
+    /// OTLP/HTTP collector that receives spans and token counts, with the
+    /// `otel` feature.
+    pub otel_endpoint: Option<String>,
+    /// StatsD agent that receives counters and timings, as `host:port`.
+    pub statsd_addr: Option<String>,
+    pub statsd_prefix: String,
+    /// Tag metrics DogStatsD-style instead of naming them after the tag.
+    pub dogstatsd: bool,
+}
+
+impl Settings {
+    pub fn resolve(config: &Config) -> Result<Settings> {
+        let history_path = match &config.history_path {
+            Some(path) => expand_tilde(path),
+            None => default_history_path().context("Unable to determine default history path")?,
+        };
+        let agent = config.agent.unwrap_or_default();
+        let sessions_root = match (&config.sessions_root, agent) {
+            (Some(path), _) => expand_tilde(path),
+            (None, Agent::Codex) => {
+                default_sessions_root().context("Unable to determine default sessions path")?
+            }
+            (None, Agent::Claude) => default_claude_projects_root()
+                .context("Unable to determine default sessions path")?,
+            (None, Agent::Aider) => home_dir().context("Home directory not found")?,
+            (None, Agent::Generic) => bail!("--agent generic requires --sessions-root"),
+        };
+        let mut codex_homes = config
+            .codex_home
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .map(|spec| parse_codex_home(spec))
+            .collect::<Result<Vec<_>>>()?;
+        for spec in config.container.as_deref().unwrap_or_default() {
+            codex_homes.push(Container::parse(spec)?.resolve()?);
+        }
+        if !codex_homes.is_empty() && agent != Agent::Codex {
+            bail!("--codex-home and --container only apply to --agent codex");
+        }
+        let selector = |path: &Option<String>, fallback: Selector| match path {
+            Some(path) => path.parse::<Selector>(),
+            None => Ok(fallback),
+        };
+        let defaults = GenericFormat::default();
+        let generic = GenericFormat {
+            timestamp: selector(&config.timestamp_path, defaults.timestamp)?,
+            kind: selector(&config.kind_path, defaults.kind)?,
+            text: selector(&config.text_path, defaults.text)?,
+            event_kinds: config.event_kinds.clone().unwrap_or_default(),
+            message_kinds: config.message_kinds.clone().unwrap_or_default(),
+        };
+        let mut ignored = config.ignore.clone().unwrap_or_default();
+        if let Some(path) = &config.ignore_file {
+            ignored.extend(IgnoreList::read_file(&expand_tilde(path))?);
+        }
+        // `~/src/scratch/*` reads naturally for a directory.
+        let ignored: Vec<String> = ignored
+            .iter()
+            .map(|entry| match entry.strip_prefix("~/") {
+                Some(_) => expand_tilde(Path::new(entry))
+                    .to_string_lossy()
+                    .into_owned(),
+                None => entry.clone(),
+            })
+            .collect();
+        let stdout = config.stdout.unwrap_or(false);
+        let cache_dir = config.cache_dir.as_deref().map(expand_tilde);
+        let cache_reasoning = config.cache_reasoning.as_deref().map(expand_tilde);
+        let cache_status = config.cache_status.as_deref().map(expand_tilde);
+        let cache_tokens = config.cache_tokens.as_deref().map(expand_tilde);
+        let split = cache_reasoning.is_some() || cache_status.is_some() || cache_tokens.is_some();
+        let cache_file = match (&config.cache_file, &cache_dir) {
+            (Some(path), _) => Some(expand_tilde(path)),
+            (None, Some(dir)) => Some(dir.join(MERGED_CACHE_NAME)),
+            // With nowhere else to publish, write the cache the plugin reads by default.
+            (None, None) if !split && !stdout && config.webhook_url.is_none() => {
+                Some(default_cache_file()?)
+            }
+            (None, None) => None,
+        };
+        let mqtt_qos = config.mqtt_qos.unwrap_or(0);
+        if mqtt_qos > 2 {
+            bail!("--mqtt-qos must be 0, 1, or 2");
+        }
+
+        Ok(Settings {
+            session_file: config.session_file.as_deref().map(expand_tilde),
+            session_id: config.session_id.clone(),
+            history_path,
+            sessions_root,
+            codex_homes,
+            agent,
+            discovery: config.discovery.unwrap_or_default(),
+            model_filter: config
+                .model_filter
+                .as_deref()
+                .unwrap_or_default()
+                .iter()
+                .map(|pattern| {
+                    Pattern::new(pattern)
+                        .with_context(|| format!("Invalid --model-filter `{pattern}`"))
+                })
+                .collect::<Result<_>>()?,
+            ignore: IgnoreList::new(ignored.iter().map(String::as_str))?,
+            follow_focus: config.follow_focus,
+            sway_approval_bar: config.sway_approval_bar,
+            log_glob: config
+                .log_glob
+                .clone()
+                .unwrap_or_else(|| DEFAULT_LOG_GLOB.to_string()),
+            generic,
+            poll_ms: config.poll_ms.unwrap_or(DEFAULT_POLL_MS),
+            max_poll_ms: config.max_poll_ms.unwrap_or(DEFAULT_MAX_POLL_MS),
+            session_refresh_secs: config
+                .session_refresh_secs
+                .unwrap_or(DEFAULT_SESSION_REFRESH_SECS),
+            session_window: config.session_window.unwrap_or(DEFAULT_SESSION_WINDOW),
+            max_sessions: config.max_sessions.unwrap_or(DEFAULT_MAX_SESSIONS),
+            max_line_bytes: config
+                .max_line_bytes
+                .unwrap_or(DEFAULT_MAX_LINE_BYTES)
+                .max(1),
+            idle_after_secs: config.idle_after_secs.unwrap_or(0),
+            min_emit_interval_ms: config.min_emit_interval_ms.unwrap_or(0),
+            switch_hysteresis_ms: config
+                .switch_hysteresis_ms
+                .unwrap_or(DEFAULT_SWITCH_HYSTERESIS_MS),
+            max_chars: config.max_chars.unwrap_or(DEFAULT_MAX_CHARS),
+            ellipsis: config
+                .ellipsis
+                .clone()
+                .unwrap_or_else(|| Truncation::DEFAULT_ELLIPSIS.to_string()),
+            rewrites: Rewrites::new(config.rewrite.as_deref().unwrap_or_default())?,
+            mode: config.mode.unwrap_or_default(),
+            percentage: config.percentage.unwrap_or_default(),
+            renderer: config.renderer.unwrap_or_default(),
+            markup: config.markup.unwrap_or_default(),
+            timestamps: config.timestamps.unwrap_or_default(),
+            elapsed: config.elapsed.unwrap_or_default(),
+            payload: config.payload.unwrap_or_default(),
+            redactor: Redactor::new(
+                config.redact_defaults.unwrap_or(true),
+                config.redact.as_deref().unwrap_or_default(),
+            )?,
+            class_rules: ClassRules::new(config.class_rule.as_deref().unwrap_or_default())?,
+            format: config.format.clone(),
+            tooltip_format: config.tooltip_format.clone(),
+            phase_icons: PhaseIcons::with_overrides(
+                config.phase_icons.as_ref().unwrap_or(&BTreeMap::new()),
+            ),
+            phase_aliases: PhaseAliases::new(config.phase_alias.as_deref().unwrap_or_default())?,
+            notify: config.notify.clone().unwrap_or_default(),
+            sounds: config
+                .sound
+                .iter()
+                .flatten()
+                .map(|(kind, path)| (*kind, expand_tilde(path)))
+                .collect(),
+            sound_player: config
+                .sound_player
+                .clone()
+                .unwrap_or_else(|| DEFAULT_SOUND_PLAYER.to_string()),
+            quiet_hours: config.quiet_hours,
+            open_command: config.open_command.clone(),
+            terminal: config
+                .terminal
+                .clone()
+                .unwrap_or_else(|| DEFAULT_TERMINAL.to_string()),
+            mqtt_broker: config.mqtt_broker.clone(),
+            mqtt_topic: config
+                .mqtt_topic
+                .clone()
+                .unwrap_or_else(|| DEFAULT_MQTT_TOPIC.to_string()),
+            mqtt_qos,
+            webhook_url: config.webhook_url.clone(),
+            webhook_class: config.webhook_class.clone().unwrap_or_default(),
+            waybar_signal: config.waybar_signal,
+            waybar_process: config
+                .waybar_process
+                .clone()
+                .unwrap_or_else(|| vec![DEFAULT_WAYBAR_PROCESS.to_string()]),
+            cache_file,
+            cache_dir,
+            cache_reasoning,
+            cache_status,
+            cache_tokens,
+            fsync: !config.no_fsync.unwrap_or(false),
+            record: config.record.as_deref().map(expand_tilde),
+            record_max_bytes: config.record_max_bytes.unwrap_or(DEFAULT_RECORD_MAX_BYTES),
+            event_store: match config.event_store {
+                Some(true) => Some(config.event_store_path()?),
+                _ => None,
+            },
+            start_at_beginning: config.start_at_beginning.unwrap_or(false),
+            stdout,
+            control_socket: config.control_socket_path(),
+            metrics_addr: config.metrics_addr,
+            otel_endpoint: config.otel_endpoint.clone(),
+            statsd_addr: config.statsd_addr.clone(),
+            statsd_prefix: config
+                .statsd_prefix
+                .clone()
+                .unwrap_or_else(|| statsd::DEFAULT_PREFIX.to_string()),
+            dogstatsd: config.dogstatsd.unwrap_or(false),
+        })
+    }
+
+    pub fn render_options(&self) -> RenderOptions {
+        RenderOptions {
+            truncation: Truncation {
+                max_width: self.max_chars,
+                ellipsis: self.ellipsis.clone(),
+                rewrites: self.rewrites.clone(),
+            },
+            mode: self.mode,
+            percentage: self.percentage,
+            renderer: self.renderer,
+            markup: self.markup,
+            timestamps: self.timestamps,
+            elapsed: self.elapsed,
+            payload: self.payload,
+            redactor: self.redactor.clone(),
+            class_rules: self.class_rules.clone(),
+            agent: self.agent,
+            generic: self.generic.clone(),
+            format: self.format.clone(),
+            phase_icons: self.phase_icons.clone(),
+            phase_aliases: self.phase_aliases.clone(),
+            tooltip_format: self.tooltip_format.clone(),
+        }
+    }
+
+    pub fn session_source(&self) -> SessionSource {
+        SessionSource {
+            agent: self.agent,
+            history_path: self.history_path.clone(),
+            sessions_root: self.sessions_root.clone(),
+            log_glob: self.log_glob.clone(),
+            homes: self.codex_homes.clone(),
+            discovery: self.discovery,
+            paths: PathIndex::default(),
+        }
+    }
+
+    /// Whether sessions of `model` pass `--model-filter`. Sessions whose model
+    /// is not known yet do not when a filter is set.
+    pub fn shows_model(&self, model: Option<&str>) -> bool {
+        self.model_filter.is_empty()
+            || model.is_some_and(|model| {
+                self.model_filter
+                    .iter()
+                    .any(|pattern| pattern.matches(model))
+            })
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_ms.max(10))
+    }
+
+    /// The poll interval for a session whose log has not grown for
+    /// `quiet_for`: `--poll-ms` while it is active, then 2 s after half a
+    /// minute and `--max-poll-ms` after five.
+    pub fn backoff_interval(&self, quiet_for: Duration) -> Duration {
+        let poll = self.poll_interval();
+        let stretched = if quiet_for < Duration::from_secs(30) {
+            poll
+        } else if quiet_for < Duration::from_secs(300) {
+            Duration::from_secs(2)
+        } else {
+            Duration::from_millis(self.max_poll_ms)
+        };
+        stretched
+            .min(Duration::from_millis(self.max_poll_ms))
+            .max(poll)
+    }
+
+    /// The wait before reading a log again after `failures` reads in a row
+    /// failed: `--poll-ms` doubled for each, up to `--max-poll-ms`.
+    pub fn retry_interval(&self, failures: u32) -> Duration {
+        let poll = self.poll_interval();
+        poll.saturating_mul(1 << failures.min(16))
+            .min(Duration::from_millis(self.max_poll_ms))
+            .max(poll)
+    }
+
+    pub fn session_refresh_interval(&self) -> Duration {
+        Duration::from_secs(self.session_refresh_secs)
+    }
+
+    pub fn idle_after(&self) -> Option<Duration> {
+        (self.idle_after_secs > 0).then(|| Duration::from_secs(self.idle_after_secs))
+    }
+
+    pub fn min_emit_interval(&self) -> Duration {
+        Duration::from_millis(self.min_emit_interval_ms)
+    }
+
+    pub fn switch_hysteresis(&self) -> Duration {
+        Duration::from_millis(self.switch_hysteresis_ms)
+    }
+}
+
+/// Tracks the config file on disk so the daemon can pick up edits without a
+/// restart.
+#[derive(Debug)]
+pub struct ConfigWatcher {
+    path: PathBuf,
+    overrides: Config,
+    last_modified: Option<SystemTime>,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: PathBuf, overrides: Config) -> Self {
+        let last_modified = modified_time(&path);
+        Self {
+            path,
+            overrides,
+            last_modified,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Load the current settings from the config file and CLI overrides.
+    pub fn load(&self) -> Result<Settings> {
+        let file = Config::load(&self.path)?.unwrap_or_default();
+        Settings::resolve(&self.overrides.merged_over(&file))
+    }
+
+    /// Return freshly resolved settings when the config file changed since the
+    /// last call. Invalid edits are reported and the previous settings kept.
+    pub fn poll_reload(&mut self) -> Option<Settings> {
+        let modified = modified_time(&self.path);
+        if modified == self.last_modified {
+            return None;
+        }
+        self.last_modified = modified;
+        match self.load() {
+            Ok(settings) => Some(settings),
+            Err(err) => {
+                warn!(error = %format!("{err:#}"), "Ignoring config reload");
+                None
+            }
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Default config location, preferring `codex-shimmer` but honouring a legacy
+/// `codex-waybar` directory when only that one exists.
+pub fn default_config_path() -> Option<PathBuf> {
+    let base = config_dir()?;
+    let preferred = base.join("codex-shimmer").join("config.toml");
+    let legacy = base.join("codex-waybar").join("config.toml");
+    if !preferred.exists() && legacy.exists() {
+        Some(legacy)
+    } else {
+        Some(preferred)
+    }
+}
+
+/// `$XDG_RUNTIME_DIR/codex-shimmer.sock`, or a per-user socket in the temp
+/// directory when no runtime dir is set.
+pub fn default_control_socket() -> PathBuf {
+    match runtime_dir() {
+        Some(dir) => dir.join("codex-shimmer.sock"),
+        None => {
+            let uid = unsafe { libc::getuid() };
+            std::env::temp_dir().join(format!("codex-shimmer-{uid}.sock"))
+        }
+    }
+}
+
+/// `$CODEX_HOME`, the way Codex itself resolves it, or `~/.codex`.
+pub fn codex_home() -> Result<PathBuf> {
+    match std::env::var_os("CODEX_HOME") {
+        Some(home) if !home.is_empty() => Ok(expand_tilde(Path::new(&home))),
+        _ => Ok(home_dir()
+            .context("Home directory not found")?
+            .join(".codex")),
+    }
+}
+
+pub fn default_history_path() -> Result<PathBuf> {
+    Ok(codex_home()?.join("history.jsonl"))
+}
+
+pub fn default_sessions_root() -> Result<PathBuf> {
+    Ok(codex_home()?.join("sessions"))
+}
+
+/// `NAME=PATH`, or a bare `PATH` named after its directory without the
+/// leading dot (`~/.codex-work` is `codex-work`).
+fn parse_codex_home(spec: &str) -> Result<CodexHome> {
+    let (name, path) = match spec.split_once('=') {
+        Some((name, path)) => (name.to_string(), expand_tilde(Path::new(path))),
+        None => {
+            let path = expand_tilde(Path::new(spec));
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().trim_start_matches('.').to_string())
+                .unwrap_or_default();
+            (name, path)
+        }
+    };
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        bail!("Invalid --codex-home `{spec}`: the name must be letters, digits, `-`, or `_`");
+    }
+    Ok(CodexHome::new(&name, &path))
+}
+
+/// `$XDG_STATE_HOME/codex-shimmer/events.sqlite`, where the recorder keeps
+/// its event history.
+pub fn default_event_store() -> Result<PathBuf> {
+    let dir = state_dir().context("Unable to determine the state directory")?;
+    Ok(dir.join("codex-shimmer").join("events.sqlite"))
+}
+
+/// `$XDG_CACHE_HOME/codex-shimmer/latest.json`, where the bundled unit and
+/// the Waybar module snippet expect the cache.
+pub fn default_cache_file() -> Result<PathBuf> {
+    let dir = cache_dir().context("Unable to determine the cache directory")?;
+    Ok(dir.join("codex-shimmer").join(MERGED_CACHE_NAME))
+}
+
+pub fn default_claude_projects_root() -> Result<PathBuf> {
+    let mut path = home_dir().context("Home directory not found")?;
+    path.push(".claude");
+    path.push("projects");
+    Ok(path)
+}
+
+fn expand_tilde(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use tempfile::tempdir;
+
+    #[test]
+    fn cli_overrides_take_precedence_over_file() {
+        let file = Config {
+            poll_ms: Some(1000),
+            max_chars: Some(80),
+            cache_file: Some(PathBuf::from("/tmp/file.json")),
+            ..Config::default()
+        };
+        let cli = Config {
+            poll_ms: Some(100),
+            ..Config::default()
+        };
+
+        let merged = cli.merged_over(&file);
+        assert_eq!(merged.poll_ms, Some(100));
+        assert_eq!(merged.max_chars, Some(80));
+        assert_eq!(merged.cache_file, Some(PathBuf::from("/tmp/file.json")));
+    }
+
+    #[test]
+    fn stdout_mode_does_not_need_cache_file() {
+        let settings = Settings::resolve(&Config::default()).expect("default settings");
+        assert_eq!(
+            settings.cache_file,
+            Some(default_cache_file().expect("cache dir"))
+        );
+
+        let config = Config {
+            stdout: Some(true),
+            ..Config::default()
+        };
+        let settings = Settings::resolve(&config).expect("stdout-only settings");
+        assert!(settings.stdout);
+        assert_eq!(settings.cache_file, None);
+    }
+
+    #[test]
+    fn quiet_sessions_back_off_to_max_poll() {
+        let settings = Settings::resolve(&Config::default()).expect("default settings");
+        let secs = Duration::from_secs;
+        assert_eq!(
+            settings.backoff_interval(secs(5)),
+            Duration::from_millis(250)
+        );
+        assert_eq!(settings.backoff_interval(secs(60)), secs(2));
+        assert_eq!(settings.backoff_interval(secs(3600)), secs(10));
+
+        assert_eq!(settings.retry_interval(1), Duration::from_millis(500));
+        assert_eq!(settings.retry_interval(3), secs(2));
+        assert_eq!(settings.retry_interval(40), secs(10));
+
+        let config = Config {
+            poll_ms: Some(500),
+            max_poll_ms: Some(500),
+            ..Config::default()
+        };
+        let settings = Settings::resolve(&config).expect("fixed-rate settings");
+        assert_eq!(
+            settings.backoff_interval(secs(3600)),
+            Duration::from_millis(500)
+        );
+    }
+
+    #[test]
+    fn watcher_reloads_after_config_edit() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "cache-file = \"/tmp/a.json\"\nmax-chars = 50\n")?;
+
+        let mut watcher = ConfigWatcher::new(path.clone(), Config::default());
+        assert_eq!(watcher.load()?.max_chars, 50);
+        assert!(watcher.poll_reload().is_none());
+
+        fs::write(&path, "cache-file = \"/tmp/a.json\"\nmax-chars = 70\n")?;
+        let modified = SystemTime::now() + Duration::from_secs(5);
+        File::options()
+            .write(true)
+            .open(&path)?
+            .set_modified(modified)?;
+
+        let reloaded = watcher.poll_reload().expect("config change detected");
+        assert_eq!(reloaded.max_chars, 70);
+        Ok(())
+    }
+
+    #[test]
+    fn cache_dir_holds_the_merged_cache_by_default() -> Result<()> {
+        let config = Config {
+            cache_dir: Some(PathBuf::from("/tmp/codex-shimmer")),
+            ..Config::default()
+        };
+        let settings = Settings::resolve(&config)?;
+        assert_eq!(
+            settings.cache_file,
+            Some(PathBuf::from("/tmp/codex-shimmer/latest.json"))
+        );
+
+        let config = Config {
+            cache_file: Some(PathBuf::from("/tmp/merged.json")),
+            ..config
+        };
+        let settings = Settings::resolve(&config)?;
+        assert_eq!(settings.cache_file, Some(PathBuf::from("/tmp/merged.json")));
+        assert_eq!(
+            settings.cache_dir,
+            Some(PathBuf::from("/tmp/codex-shimmer"))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn sounds_and_quiet_hours_parse_from_toml() -> Result<()> {
+        let config: Config =
+            toml::from_str("quiet-hours = \"22:00-07:00\"\n[sound]\napproval = \"~/ding.oga\"\n")?;
+        let settings = Settings::resolve(&Config {
+            stdout: Some(true),
+            ..config
+        })?;
+        let home = home_dir().expect("home directory");
+        assert_eq!(settings.sounds[&AlertKind::Approval], home.join("ding.oga"));
+        assert_eq!(settings.sound_player, "paplay");
+        assert_eq!(settings.quiet_hours, Some("22:00-07:00".parse()?));
+        assert!(toml::from_str::<Config>("[sound]\nbeep = \"x.oga\"\n").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn codex_homes_are_named_after_their_directory() -> Result<()> {
+        let home = parse_codex_home("/srv/codex/.codex-work")?;
+        assert_eq!(home.name, "codex-work");
+        assert_eq!(
+            home.sessions_root,
+            PathBuf::from("/srv/codex/.codex-work/sessions")
+        );
+        let named = parse_codex_home("personal=/home/me/.codex")?;
+        assert_eq!(named.name, "personal");
+        assert_eq!(
+            named.history_path,
+            PathBuf::from("/home/me/.codex/history.jsonl")
+        );
+        assert!(parse_codex_home("two words=/tmp").is_err());
+
+        let config = Config {
+            codex_home: Some(vec!["/tmp/a".to_string()]),
+            agent: Some(Agent::Claude),
+            stdout: Some(true),
+            ..Config::default()
+        };
+        assert!(Settings::resolve(&config).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn model_filter_matches_globs() -> Result<()> {
+        let open = Settings::resolve(&Config {
+            stdout: Some(true),
+            ..Config::default()
+        })?;
+        assert!(open.shows_model(None));
+
+        let filtered = Settings::resolve(&Config {
+            stdout: Some(true),
+            model_filter: Some(vec!["gpt-5*".to_string(), "o3".to_string()]),
+            ..Config::default()
+        })?;
+        assert!(filtered.shows_model(Some("gpt-5-codex")));
+        assert!(filtered.shows_model(Some("o3")));
+        assert!(!filtered.shows_model(Some("o3-mini")));
+        assert!(!filtered.shows_model(None));
+
+        assert!(
+            Settings::resolve(&Config {
+                model_filter: Some(vec!["gpt-[5".to_string()]),
+                ..Config::default()
+            })
+            .is_err()
+        );
+        Ok(())
+    }
+}
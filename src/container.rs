@@ -0,0 +1,162 @@
+//! Codex homes inside docker or podman containers, such as a devcontainer.
+//!
+//! The session logs are read from the host side of the container's mounts
+//! when the Codex home is bind-mounted or on a volume, and through
+//! `/proc/<pid>/root` otherwise, so they can be tailed like any local file.
+
+use std::{
+    path::{Component, Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Result, bail};
+use serde_json::Value;
+
+use crate::discovery::CodexHome;
+
+/// Where Codex keeps its files in a container started as root.
+const DEFAULT_HOME: &str = "/root/.codex";
+const ENGINES: [&str; 2] = ["docker", "podman"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Container {
+    pub engine: String,
+    pub name: String,
+    /// The Codex home as seen inside the container.
+    pub home: PathBuf,
+}
+
+impl Container {
+    /// `ENGINE:CONTAINER` or `ENGINE:CONTAINER:PATH`, such as `podman:devbox`
+    /// or `docker:web:/home/node/.codex`.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut parts = spec.splitn(3, ':');
+        let engine = parts.next().unwrap_or_default();
+        let name = parts.next().unwrap_or_default();
+        if !ENGINES.contains(&engine) || name.is_empty() {
+            bail!("Invalid --container `{spec}`: expected docker:NAME or podman:NAME, then :PATH");
+        }
+        let home = PathBuf::from(parts.next().unwrap_or(DEFAULT_HOME));
+        if !home.is_absolute() {
+            bail!(
+                "Invalid --container `{spec}`: {} is not an absolute path",
+                home.display()
+            );
+        }
+        Ok(Container {
+            engine: engine.to_string(),
+            name: name.to_string(),
+            home,
+        })
+    }
+
+    /// Ask the engine where the Codex home is on the host. The container has
+    /// to be running unless its home is mounted from the host.
+    pub fn resolve(&self) -> Result<CodexHome> {
+        let output = Command::new(&self.engine)
+            .args(["inspect", "--type", "container", &self.name])
+            .output()
+            .with_context(|| format!("Failed to run {}", self.engine))?;
+        if !output.status.success() {
+            bail!(
+                "{} inspect {} failed: {}",
+                self.engine,
+                self.name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let inspected: Value = serde_json::from_slice(&output.stdout)
+            .with_context(|| format!("Unexpected output from {} inspect", self.engine))?;
+        let host = host_path(&inspected, &self.home).with_context(|| {
+            format!(
+                "Container {} is not running and {} is not mounted from the host",
+                self.name,
+                self.home.display()
+            )
+        })?;
+        Ok(CodexHome::new(&profile_name(&self.name), &host))
+    }
+}
+
+/// The host path of `home` in a container described by `inspect` output:
+/// below the source of the deepest mount holding it, or below the root
+/// filesystem of the running container's process.
+fn host_path(inspect: &Value, home: &Path) -> Option<PathBuf> {
+    let container = inspect.get(0).unwrap_or(inspect);
+    let mounted = container["Mounts"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|mount| {
+            let source = mount["Source"].as_str()?;
+            let destination = Path::new(mount["Destination"].as_str()?);
+            let rest = home.strip_prefix(destination).ok()?;
+            Some((
+                destination.components().count(),
+                Path::new(source).join(rest),
+            ))
+        })
+        .max_by_key(|(depth, _)| *depth)
+        .map(|(_, path)| path);
+    mounted.or_else(|| {
+        let pid = container["State"]["Pid"].as_u64().filter(|pid| *pid > 0)?;
+        let inside: PathBuf = home
+            .components()
+            .filter(|part| matches!(part, Component::Normal(_)))
+            .collect();
+        Some(PathBuf::from(format!("/proc/{pid}/root")).join(inside))
+    })
+}
+
+/// Container names may hold dots, which class names should not.
+fn profile_name(container: &str) -> String {
+    container
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn codex_home_maps_through_mounts_or_the_process_root() -> Result<()> {
+        let container = Container::parse("podman:dev.box")?;
+        assert_eq!(container.home, Path::new(DEFAULT_HOME));
+        assert_eq!(profile_name(&container.name), "dev-box");
+        assert!(Container::parse("lxc:dev").is_err());
+        assert!(Container::parse("docker:dev:relative").is_err());
+
+        let home = Path::new("/home/node/.codex");
+        let inspect = json!([{
+            "State": {"Pid": 4242},
+            "Mounts": [
+                {"Source": "/home/me/src", "Destination": "/workspace"},
+                {"Source": "/var/lib/docker/volumes/home/_data", "Destination": "/home/node"},
+                {"Source": "/home/me/.codex-dev", "Destination": "/home/node/.codex"},
+            ],
+        }]);
+        assert_eq!(
+            host_path(&inspect, home),
+            Some(PathBuf::from("/home/me/.codex-dev"))
+        );
+
+        let unmounted = json!([{"State": {"Pid": 4242}, "Mounts": []}]);
+        assert_eq!(
+            host_path(&unmounted, home),
+            Some(PathBuf::from("/proc/4242/root/home/node/.codex"))
+        );
+
+        let stopped = json!([{"State": {"Pid": 0}, "Mounts": []}]);
+        assert_eq!(host_path(&stopped, home), None);
+        Ok(())
+    }
+}
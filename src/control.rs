@@ -0,0 +1,215 @@
+use std::{fmt, io::ErrorKind, os::unix::fs::FileTypeExt, path::Path, str::FromStr};
+
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    sync::{mpsc, oneshot},
+};
+use tracing::warn;
+
+use crate::daemon::DaemonMessage;
+
+/// A runtime command accepted on the control socket, one per line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlCommand {
+    /// Stop publishing updates; sessions keep being tailed.
+    Pause,
+    /// Publish again, starting with the newest event seen while paused.
+    Resume,
+    /// Only display events from this session, or the one whose id starts
+    /// with this prefix.
+    Pin(String),
+    /// Display the newest event across sessions again.
+    Unpin,
+    /// Re-scan history and session files and republish the current payload.
+    Refresh,
+    /// Report the daemon state as JSON.
+    Status,
+}
+
+impl FromStr for ControlCommand {
+    type Err = anyhow::Error;
+
+    fn from_str(line: &str) -> Result<Self> {
+        let mut words = line.split_whitespace();
+        let command = match (words.next(), words.next()) {
+            (Some("pause"), None) => ControlCommand::Pause,
+            (Some("resume"), None) => ControlCommand::Resume,
+            (Some("pin"), Some(session_id)) => ControlCommand::Pin(session_id.to_string()),
+            (Some("pin"), None) => bail!("pin requires a session id"),
+            (Some("unpin"), None) => ControlCommand::Unpin,
+            (Some("refresh"), None) => ControlCommand::Refresh,
+            (Some("status"), None) => ControlCommand::Status,
+            (Some(other), _) => bail!("unknown command: {other}"),
+            (None, _) => bail!("empty command"),
+        };
+        if words.next().is_some() {
+            bail!("too many arguments");
+        }
+        Ok(command)
+    }
+}
+
+impl fmt::Display for ControlCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ControlCommand::Pause => f.write_str("pause"),
+            ControlCommand::Resume => f.write_str("resume"),
+            ControlCommand::Pin(session_id) => write!(f, "pin {session_id}"),
+            ControlCommand::Unpin => f.write_str("unpin"),
+            ControlCommand::Refresh => f.write_str("refresh"),
+            ControlCommand::Status => f.write_str("status"),
+        }
+    }
+}
+
+/// Reply to `status`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DaemonStatus {
+    pub paused: bool,
+    pub pinned: Option<String>,
+    /// Session whose event is currently published.
+    pub displayed: Option<String>,
+    pub sessions: Vec<SessionStatus>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionStatus {
+    pub session_id: String,
+    pub path: Option<String>,
+    pub last_event: Option<String>,
+}
+
+/// Bind the control socket, replacing a stale socket file left behind by a
+/// previous run. Fails if another daemon is still listening on it.
+pub async fn bind(path: &Path) -> Result<UnixListener> {
+    if UnixStream::connect(path).await.is_ok() {
+        bail!("another daemon is already listening on {}", path.display());
+    }
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => std::fs::remove_file(path)?,
+        Ok(_) => bail!("{} exists and is not a socket", path.display()),
+        Err(err) if err.kind() != ErrorKind::NotFound => return Err(err.into()),
+        Err(_) => {}
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    UnixListener::bind(path).with_context(|| format!("Failed to bind {}", path.display()))
+}
+
+/// Accept control connections and forward each command to the coordinator.
+pub async fn serve(listener: UnixListener, tx: mpsc::Sender<DaemonMessage>) {
+    loop {
+        let Ok((stream, _)) = listener.accept().await else {
+            continue;
+        };
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            if let Err(err) = handle_connection(stream, tx).await {
+                warn!(error = %format!("{err:#}"), "Control connection failed");
+            }
+        });
+    }
+}
+
+async fn handle_connection(stream: UnixStream, tx: mpsc::Sender<DaemonMessage>) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut line = String::new();
+    BufReader::new(reader).read_line(&mut line).await?;
+
+    let response = match line.parse::<ControlCommand>() {
+        Ok(command) => dispatch(&tx, command).await,
+        Err(err) => Err(err.to_string()),
+    };
+    let response = match response {
+        Ok(body) => body,
+        Err(message) => format!("error: {message}"),
+    };
+    writer.write_all(response.as_bytes()).await?;
+    writer.write_all(b"\n").await?;
+    writer.shutdown().await?;
+    Ok(())
+}
+
+/// Hand a command to the coordinator and wait for its reply.
+pub async fn dispatch(
+    tx: &mpsc::Sender<DaemonMessage>,
+    command: ControlCommand,
+) -> Result<String, String> {
+    let (reply, response) = oneshot::channel();
+    tx.send(DaemonMessage::Control { command, reply })
+        .await
+        .map_err(|_| "daemon is shutting down".to_string())?;
+    response
+        .await
+        .map_err(|_| "daemon dropped the request".to_string())?
+}
+
+/// Send one command to a running daemon and return its reply.
+pub async fn send_command(path: &Path, command: &ControlCommand) -> Result<String> {
+    let mut stream = UnixStream::connect(path)
+        .await
+        .with_context(|| format!("No daemon listening on {}", path.display()))?;
+    stream.write_all(format!("{command}\n").as_bytes()).await?;
+    stream.shutdown().await?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    let response = response.trim_end();
+    match response.strip_prefix("error: ") {
+        Some(message) => bail!("{message}"),
+        None => Ok(response.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn commands_round_trip_through_wire_format() {
+        for command in [
+            ControlCommand::Pause,
+            ControlCommand::Resume,
+            ControlCommand::Pin("alpha".to_string()),
+            ControlCommand::Unpin,
+            ControlCommand::Refresh,
+            ControlCommand::Status,
+        ] {
+            assert_eq!(
+                command.to_string().parse::<ControlCommand>().unwrap(),
+                command
+            );
+        }
+        assert!("pin".parse::<ControlCommand>().is_err());
+        assert!("pause now".parse::<ControlCommand>().is_err());
+        assert!("reboot".parse::<ControlCommand>().is_err());
+    }
+
+    #[tokio::test]
+    async fn socket_forwards_commands_and_replies() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("control.sock");
+        let (tx, mut rx) = mpsc::channel(4);
+        tokio::spawn(serve(bind(&path).await?, tx));
+        tokio::spawn(async move {
+            while let Some(DaemonMessage::Control { command, reply }) = rx.recv().await {
+                let _ = reply.send(match command {
+                    ControlCommand::Pin(id) => Err(format!("session {id} is not tracked")),
+                    _ => Ok("ok".to_string()),
+                });
+            }
+        });
+
+        assert_eq!(send_command(&path, &ControlCommand::Pause).await?, "ok");
+        let err = send_command(&path, &ControlCommand::Pin("beta".to_string()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "session beta is not tracked");
+        Ok(())
+    }
+}
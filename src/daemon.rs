@@ -0,0 +1,1703 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+};
+
+use anyhow::{Context, Result};
+use tokio::{
+    signal::unix::{SignalKind, signal},
+    sync::{Notify, mpsc, oneshot, watch},
+    task::JoinHandle,
+    time::{Duration, Instant, MissedTickBehavior, interval, interval_at, sleep_until},
+};
+use tracing::{debug, info, warn};
+
+use crate::alert::{self, AlertTracker};
+use crate::config::{ConfigWatcher, Settings};
+use crate::control::{self, ControlCommand, DaemonStatus, SessionStatus};
+use crate::discovery::{
+    CodexHome, SessionMeta, SessionSource, discover_sessions, merge_session_targets,
+};
+use crate::focus;
+use crate::metrics::{self, METRICS};
+use crate::output::{
+    Emission, WaybarOutput, idle_payload, load_emitted, mark_degraded, placeholder_payload,
+    run_emitter, session_cache_path, stopped_payload, summary_payload, unconfigured_payload,
+};
+use crate::record::Recorder;
+use crate::render::{
+    DisplayMode, ElapsedDisplay, RenderedEvent, TimestampStyle, compare_timestamps, parse_timestamp,
+};
+use crate::session::{SessionRenderer, View};
+use crate::statsd::Statsd;
+use crate::sway;
+use crate::tailer::{initialize_session_state, tail_session};
+use crate::watch::{FsChange, FsWatcher};
+
+/// A rendered event tagged with the session it came from.
+#[derive(Debug, Clone)]
+pub struct SessionEvent {
+    pub session_id: String,
+    pub event: RenderedEvent,
+    /// When the daemon rendered the event, counting up across sessions, so
+    /// within a session it follows the log order. Orders events that share
+    /// a timestamp, or have none.
+    pub sequence: u64,
+}
+
+/// Messages sent from the worker tasks to the coordinator.
+#[derive(Debug)]
+pub enum DaemonMessage {
+    /// Session ids found in history, oldest first.
+    Discovered(Vec<String>),
+    /// A tailer located its session file and read it from the start.
+    Primed {
+        session_id: String,
+        path: PathBuf,
+        lines: Vec<String>,
+    },
+    /// Complete lines appended to a session file since the last read.
+    Lines {
+        session_id: String,
+        lines: Vec<String>,
+    },
+    /// A resumed session is now written to a new log, whose lines follow.
+    Resumed { session_id: String, path: PathBuf },
+    /// The session file could not be located or disappeared.
+    Missing { session_id: String },
+    /// Reading a session log kept failing with `error`, or works again
+    /// (`None`).
+    Degraded {
+        session_id: String,
+        error: Option<String>,
+    },
+    /// The working directory of the newly focused window, with
+    /// `--follow-focus`.
+    Focus(Option<PathBuf>),
+    /// A command from the control socket, answered with a reply body or an
+    /// error message.
+    Control {
+        command: ControlCommand,
+        reply: oneshot::Sender<Result<String, String>>,
+    },
+}
+
+/// Handle to a running per-session tail task.
+struct Tailer {
+    path: Option<PathBuf>,
+    wake: Arc<Notify>,
+    task: JoinHandle<()>,
+}
+
+impl Drop for Tailer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Run the tailing daemon until an unrecoverable error occurs, reloading
+/// settings from `config_watcher` as the config file changes.
+///
+/// Each tracked session is tailed by its own task, history is polled by a
+/// discovery task, and cache writes happen on an emitter task. This function
+/// owns all session state and coordinates them over channels.
+pub async fn run(config_watcher: ConfigWatcher) -> Result<()> {
+    let settings = config_watcher.load()?;
+    let (settings_tx, _) = watch::channel(settings.clone());
+    let (tx, mut rx) = mpsc::channel(256);
+    let (fs_tx, mut fs_rx) = mpsc::unbounded_channel();
+    let (emit_tx, emit_rx) = mpsc::channel(16);
+    if cfg!(not(feature = "otel")) && settings.otel_endpoint.is_some() {
+        warn!(
+            "OpenTelemetry export needs a build with the `otel` feature; --otel-endpoint is ignored"
+        );
+    }
+    if cfg!(not(feature = "store")) && settings.event_store.is_some() {
+        warn!("The event store needs a build with the `store` feature; --event-store is ignored");
+    }
+    if cfg!(not(feature = "mqtt")) && settings.mqtt_broker.is_some() {
+        warn!("MQTT publishing needs a build with the `mqtt` feature; --mqtt-broker is ignored");
+    }
+
+    let mut emitter = tokio::spawn(run_emitter(emit_rx, settings_tx.subscribe()));
+    let _control = match control::bind(&settings.control_socket).await {
+        Ok(listener) => Some(tokio::spawn(control::serve(listener, tx.clone()))),
+        Err(err) => {
+            warn!(error = %format!("{err:#}"), "Control socket unavailable");
+            None
+        }
+    };
+    let _metrics = match settings.metrics_addr {
+        Some(addr) => match metrics::bind(addr).await {
+            Ok(listener) => Some(tokio::spawn(metrics::serve(listener))),
+            Err(err) => {
+                warn!(error = %format!("{err:#}"), "Metrics endpoint unavailable");
+                None
+            }
+        },
+        None => None,
+    };
+    if let Some(addr) = &settings.statsd_addr {
+        match Statsd::connect(addr, &settings.statsd_prefix, settings.dogstatsd) {
+            Ok(statsd) => METRICS.set_statsd(Some(statsd)),
+            Err(err) => warn!(error = %format!("{err:#}"), "StatsD unavailable"),
+        }
+    }
+    let (published_tx, _) = watch::channel(None);
+    #[cfg(feature = "dbus")]
+    let _dbus = {
+        let (tx, published) = (tx.clone(), published_tx.subscribe());
+        tokio::spawn(async move {
+            if let Err(err) = crate::dbus::serve(tx, published).await {
+                warn!(error = %format!("{err:#}"), "D-Bus service unavailable");
+            }
+        })
+    };
+    #[cfg(feature = "mqtt")]
+    let _mqtt = settings.mqtt_broker.is_some().then(|| {
+        let (settings, published) = (settings.clone(), published_tx.subscribe());
+        tokio::spawn(async move {
+            if let Err(err) = crate::mqtt::serve(&settings, published).await {
+                warn!(error = %format!("{err:#}"), "MQTT publishing stopped");
+            }
+        })
+    });
+
+    let _focus = settings
+        .follow_focus
+        .map(|compositor| tokio::spawn(focus::follow(compositor, tx.clone())));
+
+    let _sway_bar = settings.sway_approval_bar.map(|mode| {
+        let published = published_tx.subscribe();
+        tokio::spawn(async move {
+            if let Err(err) = sway::show_bar_on_approval(mode, published).await {
+                warn!(error = %format!("{err:#}"), "Stopped switching the sway bar");
+            }
+        })
+    });
+
+    let fs_watcher = match FsWatcher::new(move |change| {
+        let _ = fs_tx.send(change);
+    }) {
+        Ok(watcher) => Some(watcher),
+        Err(err) => {
+            warn!(error = %err, "File watching unavailable, falling back to polling");
+            None
+        }
+    };
+
+    let mut daemon = Daemon::new(
+        config_watcher,
+        settings_tx,
+        tx,
+        emit_tx,
+        published_tx,
+        fs_watcher,
+    )?;
+    let mut tick = interval(daemon.tick_period());
+    tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut clock = interval(Duration::from_secs(1));
+    clock.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut terminate = signal(SignalKind::terminate()).context("Failed to listen for SIGTERM")?;
+    let mut interrupt = signal(SignalKind::interrupt()).context("Failed to listen for SIGINT")?;
+    let mut user1 = signal(SignalKind::user_defined1()).context("Failed to listen for SIGUSR1")?;
+
+    loop {
+        let idle_at = daemon.idle_deadline();
+        tokio::select! {
+            Some(message) = rx.recv() => daemon.handle_message(message).await,
+            _ = sleep_until(idle_at.unwrap_or_else(Instant::now)), if idle_at.is_some() => {
+                daemon.go_idle().await;
+            }
+            Some(change) = fs_rx.recv() => daemon.handle_fs_change(change),
+            _ = tick.tick() => daemon.handle_tick().await,
+            _ = clock.tick(), if daemon.clock_driven() => daemon.refresh_display().await,
+            _ = user1.recv() => {
+                info!("Refreshing on SIGUSR1");
+                daemon.force_refresh().await;
+            }
+            _ = terminate.recv() => break,
+            _ = interrupt.recv() => break,
+            result = &mut emitter => {
+                return result.context("Emitter task failed")?;
+            }
+        }
+        // A reloaded config can change the poll or refresh interval.
+        let period = daemon.tick_period();
+        if period != tick.period() {
+            debug!(?period, "Tick interval changed");
+            tick = interval_at(Instant::now() + period, period);
+            tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        }
+    }
+
+    info!("Stopping");
+    daemon.stop().await;
+    // Closing the emitter's channel lets it write what it holds and return.
+    drop(daemon);
+    emitter.await.context("Emitter task failed")?
+}
+
+struct Daemon {
+    config_watcher: ConfigWatcher,
+    settings_tx: watch::Sender<Settings>,
+    tx: mpsc::Sender<DaemonMessage>,
+    emit_tx: mpsc::Sender<Emission>,
+    /// The payload currently on display, for consumers other than the emitter.
+    published_tx: watch::Sender<Option<WaybarOutput>>,
+    fs_watcher: Option<FsWatcher>,
+    /// Where sessions are discovered, fixed until a restart.
+    source: SessionSource,
+    /// Whether a directory discovery reads does not exist yet.
+    waiting_for_dirs: bool,
+    /// Sessions dropped for running in an ignored directory.
+    ignored: HashSet<String>,
+    discovery_wake: Arc<Notify>,
+    explicit_paths: HashMap<String, PathBuf>,
+    tracked_sessions: Vec<String>,
+    session_meta: HashMap<String, SessionMeta>,
+    renderers: HashMap<String, SessionRenderer>,
+    alerts: AlertTracker,
+    /// Spans and token counts for the OpenTelemetry exporter.
+    #[cfg(feature = "otel")]
+    telemetry: Option<mpsc::UnboundedSender<crate::otel::Record>>,
+    /// Rendered events on their way to the event store.
+    #[cfg(feature = "store")]
+    store: Option<mpsc::UnboundedSender<crate::store::StoredEvent>>,
+    tailers: HashMap<String, Tailer>,
+    /// Newest rendered event per tracked session.
+    latest: HashMap<String, SessionEvent>,
+    last_emitted: Option<SessionEvent>,
+    /// Sessions whose log keeps failing to read, with the last error.
+    degraded: BTreeMap<String, String>,
+    /// The errors the payload on display was marked with.
+    shown_degraded: BTreeMap<String, String>,
+    /// When a tracked session last produced an event.
+    last_activity: Instant,
+    /// Whether the idle payload replaced the display after inactivity.
+    idle: bool,
+    paused: bool,
+    pinned: Option<String>,
+    /// The sequence number of the last rendered event.
+    sequence: u64,
+    /// Working directory of the focused window, with `--follow-focus`.
+    focused: Option<PathBuf>,
+    /// The `--record` file, if one is set and could be opened.
+    recorder: Option<Recorder>,
+    _discovery: Option<JoinHandle<()>>,
+}
+
+impl Daemon {
+    fn new(
+        config_watcher: ConfigWatcher,
+        settings_tx: watch::Sender<Settings>,
+        tx: mpsc::Sender<DaemonMessage>,
+        emit_tx: mpsc::Sender<Emission>,
+        published_tx: watch::Sender<Option<WaybarOutput>>,
+        mut fs_watcher: Option<FsWatcher>,
+    ) -> Result<Self> {
+        let settings = settings_tx.borrow().clone();
+        let source = settings.session_source();
+        let auto_discover = settings.session_file.is_none() && settings.session_id.is_none();
+
+        let tracked_sessions: Vec<String> = match (&settings.session_id, &settings.session_file) {
+            (Some(id), _) => vec![id.clone()],
+            (None, Some(path)) => vec![
+                settings
+                    .agent
+                    .session_id_from_path(path)
+                    .context("Failed to infer session id from --session-file")?,
+            ],
+            (None, None) => Vec::new(),
+        };
+
+        let mut explicit_paths: HashMap<String, PathBuf> = HashMap::new();
+        if let Some(path) = &settings.session_file
+            && let Some(session_id) = tracked_sessions.first()
+        {
+            explicit_paths.insert(session_id.clone(), path.clone());
+        }
+
+        if let Some(watcher) = fs_watcher.as_mut()
+            && let Some(dir) = config_watcher.path().parent()
+        {
+            watcher.watch_dir(dir);
+        }
+
+        let discovery_wake = Arc::new(Notify::new());
+        let discovery = auto_discover.then(|| {
+            tokio::spawn(discover_sessions(
+                source.clone(),
+                settings_tx.subscribe(),
+                discovery_wake.clone(),
+                tx.clone(),
+            ))
+        });
+
+        // Pick up where the last run left the display.
+        let restored = settings.cache_file.as_deref().and_then(load_emitted);
+        if let Some((payload, _)) = &restored {
+            published_tx.send_replace(Some(payload.clone()));
+        }
+        let last_emitted = restored.map(|(payload, state)| SessionEvent {
+            session_id: state.session_id,
+            event: RenderedEvent {
+                payload,
+                timestamp: state.timestamp,
+            },
+            sequence: 0,
+        });
+
+        let mut daemon = Self {
+            config_watcher,
+            settings_tx,
+            tx,
+            emit_tx,
+            published_tx,
+            fs_watcher,
+            source,
+            waiting_for_dirs: false,
+            ignored: HashSet::new(),
+            discovery_wake,
+            explicit_paths,
+            tracked_sessions: Vec::new(),
+            session_meta: HashMap::new(),
+            renderers: HashMap::new(),
+            alerts: AlertTracker::default(),
+            #[cfg(feature = "otel")]
+            telemetry: settings.otel_endpoint.clone().map(|endpoint| {
+                let (telemetry, records) = mpsc::unbounded_channel();
+                tokio::spawn(crate::otel::export(endpoint, records));
+                telemetry
+            }),
+            #[cfg(feature = "store")]
+            store: settings.event_store.clone().map(|path| {
+                let (store, events) = mpsc::unbounded_channel();
+                tokio::task::spawn_blocking(move || crate::store::write(path, events));
+                store
+            }),
+            tailers: HashMap::new(),
+            latest: HashMap::new(),
+            last_emitted,
+            degraded: BTreeMap::new(),
+            shown_degraded: BTreeMap::new(),
+            last_activity: Instant::now(),
+            idle: false,
+            paused: false,
+            pinned: None,
+            sequence: 0,
+            focused: None,
+            recorder: None,
+            _discovery: discovery,
+        };
+        daemon.sync_recorder(&settings);
+        daemon.arm_watches();
+        daemon.retarget(&tracked_sessions);
+        Ok(daemon)
+    }
+
+    fn settings(&self) -> Settings {
+        self.settings_tx.borrow().clone()
+    }
+
+    /// Watch the directories holding the history files and, with
+    /// `--discovery sessions`, the session trees. One not created yet, as on
+    /// a fresh install, is waited for through its nearest existing parent.
+    /// Returns whether one of them appeared.
+    fn arm_watches(&mut self) -> bool {
+        let Some(watcher) = self.fs_watcher.as_mut() else {
+            return false;
+        };
+        let dirs = self
+            .source
+            .history_paths()
+            .into_iter()
+            .filter_map(Path::parent)
+            .map(|dir| (dir, false));
+        let trees = self
+            .source
+            .session_roots()
+            .into_iter()
+            .map(|root| (root, true));
+        let mut appeared = false;
+        self.waiting_for_dirs = false;
+        for (dir, tree) in dirs.chain(trees) {
+            if dir.is_dir() {
+                appeared |= if tree {
+                    watcher.watch_tree(dir)
+                } else {
+                    watcher.watch_dir(dir)
+                };
+            } else if let Some(parent) = dir.ancestors().skip(1).find(|parent| parent.is_dir()) {
+                self.waiting_for_dirs = true;
+                watcher.watch_dir(parent);
+            }
+        }
+        appeared
+    }
+
+    async fn handle_message(&mut self, message: DaemonMessage) {
+        match message {
+            DaemonMessage::Discovered(ids) => {
+                self.retarget(&ids);
+                self.settle_display().await;
+                if ids.is_empty()
+                    && self.last_emitted.is_none()
+                    && let Some(root) = self.source.missing_root()
+                {
+                    let payload = unconfigured_payload(root);
+                    self.emit(SessionEvent {
+                        session_id: String::new(),
+                        event: RenderedEvent {
+                            payload,
+                            timestamp: None,
+                        },
+                        sequence: 0,
+                    })
+                    .await;
+                }
+            }
+            DaemonMessage::Primed {
+                session_id,
+                path,
+                lines,
+            } => {
+                let Some(tailer) = self.tailers.get_mut(&session_id) else {
+                    return;
+                };
+                tailer.path = Some(path.clone());
+                if let Some(watcher) = self.fs_watcher.as_mut() {
+                    watcher.watch_file(&path);
+                }
+
+                let meta = self.session_meta.entry(session_id.clone()).or_default();
+                meta.missing_count = 0;
+                // The tailer re-read the file from the start.
+                self.renderers.remove(&session_id);
+                let event = self.render_lines(&session_id, &lines);
+                // Turns replayed from the log were exported when they happened.
+                #[cfg(feature = "otel")]
+                if let Some(renderer) = self.renderers.get_mut(&session_id) {
+                    renderer.take_telemetry();
+                }
+                let Some(event) = event else {
+                    return;
+                };
+                // Whatever the log already showed is old news.
+                self.alerts.observe(&session_id, &event.event.payload);
+                self.write_session_cache(&event).await;
+                if self.last_emitted.as_ref().is_none_or(|last| {
+                    select_newer_event(Some(last.clone()), event.clone())
+                        .is_some_and(|picked| picked.session_id == event.session_id)
+                }) {
+                    self.emit(event).await;
+                }
+            }
+            DaemonMessage::Lines { session_id, lines } => {
+                let event = self.render_lines(&session_id, &lines);
+                #[cfg(feature = "otel")]
+                self.export_telemetry(&session_id);
+                if let Some(event) = event {
+                    self.raise_alert(&event);
+                    self.write_session_cache(&event).await;
+                    self.last_activity = Instant::now();
+                    self.idle = false;
+                    if self.has_ended(&session_id) {
+                        // Fall back to a session that is still running.
+                        self.emit_current().await;
+                    } else if !self.holds_display(&event) {
+                        self.emit(event).await;
+                    }
+                }
+            }
+            DaemonMessage::Resumed { session_id, path } => {
+                let Some(tailer) = self.tailers.get_mut(&session_id) else {
+                    return;
+                };
+                if let Some(watcher) = self.fs_watcher.as_mut() {
+                    watcher.watch_file(&path);
+                }
+                tailer.path = Some(path);
+                self.sync_watches();
+            }
+            DaemonMessage::Missing { session_id } => {
+                debug!(session_id = %session_id, "Session log is missing");
+                if self.degraded.remove(&session_id).is_some() {
+                    self.show_health().await;
+                }
+                self.tailers.remove(&session_id);
+                self.alerts.forget(&session_id);
+                self.session_meta.entry(session_id).or_default().mark_miss();
+                self.sync_watches();
+            }
+            DaemonMessage::Degraded { session_id, error } => {
+                match error {
+                    Some(error) => self.degraded.insert(session_id, error),
+                    None => self.degraded.remove(&session_id),
+                };
+                self.show_health().await;
+            }
+            DaemonMessage::Focus(cwd) => {
+                self.focused = cwd;
+                self.emit_current().await;
+            }
+            DaemonMessage::Control { command, reply } => {
+                let response = self.handle_control(command).await;
+                let _ = reply.send(response);
+            }
+        }
+    }
+
+    async fn handle_control(&mut self, command: ControlCommand) -> Result<String, String> {
+        match command {
+            ControlCommand::Pause => self.paused = true,
+            ControlCommand::Resume => {
+                self.paused = false;
+                self.emit_current().await;
+            }
+            ControlCommand::Pin(session_id) => {
+                self.pinned = Some(resolve_pin(&self.tracked_sessions, &session_id)?);
+                self.emit_current().await;
+            }
+            ControlCommand::Unpin => {
+                if self.pinned.take().is_some() {
+                    self.emit_current().await;
+                }
+            }
+            ControlCommand::Refresh => self.force_refresh().await,
+            ControlCommand::Status => {
+                return serde_json::to_string(&self.status()).map_err(|err| err.to_string());
+            }
+        }
+        Ok("ok".to_string())
+    }
+
+    /// Re-read the config and history, restart the tailers of sessions whose
+    /// log went missing, and write the display again even if it is
+    /// unchanged. Run for `refresh` on the control socket and on SIGUSR1.
+    async fn force_refresh(&mut self) {
+        self.reload_config();
+        self.discovery_wake.notify_one();
+        for meta in self.session_meta.values_mut() {
+            meta.missing_count = 0;
+        }
+        let tracked = self.tracked_sessions.clone();
+        self.retarget(&tracked);
+        for tailer in self.tailers.values() {
+            tailer.wake.notify_one();
+        }
+        let shown = self.last_emitted.take();
+        self.emit_current().await;
+        if self.last_emitted.is_none()
+            && let Some(shown) = shown
+        {
+            self.emit(shown).await;
+        }
+    }
+
+    fn status(&self) -> DaemonStatus {
+        DaemonStatus {
+            paused: self.paused,
+            pinned: self.pinned.clone(),
+            displayed: self
+                .last_emitted
+                .as_ref()
+                .map(|event| event.session_id.clone()),
+            sessions: self
+                .tracked_sessions
+                .iter()
+                .map(|session_id| SessionStatus {
+                    session_id: session_id.clone(),
+                    path: self
+                        .tailers
+                        .get(session_id)
+                        .and_then(|tailer| tailer.path.as_ref())
+                        .map(|path| path.display().to_string()),
+                    last_event: self
+                        .session_meta
+                        .get(session_id)
+                        .and_then(|meta| meta.last_event_timestamp.clone()),
+                })
+                .collect(),
+        }
+    }
+
+    fn handle_fs_change(&mut self, change: FsChange) {
+        if self.waiting_for_dirs && self.arm_watches() {
+            debug!("A directory sessions are discovered from was created");
+            self.discovery_wake.notify_one();
+        }
+        for path in &change.paths {
+            if change.replaced
+                && let Some(watcher) = self.fs_watcher.as_mut()
+            {
+                // The inode behind the watch is gone; re-arm on the new file.
+                watcher.forget_file(path);
+                watcher.watch_file(path);
+            }
+            if self.source.history_paths().contains(&path.as_path()) || self.is_new_log(path) {
+                self.discovery_wake.notify_one();
+            }
+            if path == self.config_watcher.path() {
+                self.reload_config();
+            }
+            for tailer in self.tailers.values() {
+                if tailer.path.as_ref() == Some(path) {
+                    tailer.wake.notify_one();
+                }
+            }
+        }
+    }
+
+    /// A session log under a watched tree that no tailer follows yet.
+    fn is_new_log(&self, path: &Path) -> bool {
+        path.extension().is_some_and(|ext| ext == "jsonl")
+            && self
+                .source
+                .session_roots()
+                .iter()
+                .any(|root| path.starts_with(root))
+            && !self
+                .tailers
+                .values()
+                .any(|tailer| tailer.path.as_deref() == Some(path))
+    }
+
+    async fn handle_tick(&mut self) {
+        self.reload_config();
+        if self.waiting_for_dirs && self.arm_watches() {
+            self.discovery_wake.notify_one();
+        }
+        let snapshot = self.tracked_sessions.clone();
+        self.retarget(&snapshot);
+        self.settle_display().await;
+    }
+
+    fn reload_config(&mut self) {
+        let Some(mut reloaded) = self.config_watcher.poll_reload() else {
+            return;
+        };
+        let current = self.settings();
+        if reloaded.session_source() != self.source
+            || reloaded.session_id != current.session_id
+            || reloaded.session_file != current.session_file
+        {
+            warn!("Session source changes in the config take effect after a restart");
+        }
+        // Lines keep being parsed in the format of the sessions being tailed.
+        reloaded.agent = current.agent;
+        self.sync_recorder(&reloaded);
+        self.settings_tx.send_replace(reloaded);
+    }
+
+    /// Open, switch, or close the `--record` file to match `settings`.
+    fn sync_recorder(&mut self, settings: &Settings) {
+        let Some(path) = &settings.record else {
+            self.recorder = None;
+            return;
+        };
+        if self
+            .recorder
+            .as_ref()
+            .is_some_and(|recorder| recorder.matches(path, settings.record_max_bytes))
+        {
+            return;
+        }
+        self.recorder = match Recorder::open(path, settings.record_max_bytes) {
+            Ok(recorder) => Some(recorder),
+            Err(err) => {
+                warn!(path = %path.display(), error = %err, "Failed to open the record file");
+                None
+            }
+        };
+    }
+
+    /// Recompute the tracked set from `discovered` and start or stop tailers to
+    /// match it.
+    fn retarget(&mut self, discovered: &[String]) {
+        let settings = self.settings();
+        // Sessions asked for by id or file are followed whatever the list says.
+        if settings.session_id.is_none() && settings.session_file.is_none() {
+            let ignored = |id: &String| settings.ignore.session(id) || self.ignored.contains(id);
+            self.session_meta.retain(|id, _| !ignored(id));
+            let kept: Vec<String> = discovered
+                .iter()
+                .filter(|id| !ignored(id))
+                .cloned()
+                .collect();
+            return self.track(&kept, &settings);
+        }
+        self.track(discovered, &settings);
+    }
+
+    fn track(&mut self, discovered: &[String], settings: &Settings) {
+        for session_id in discovered {
+            self.session_meta.entry(session_id.clone()).or_default();
+        }
+        // The newest sessions from discovery always fit.
+        let cap = match settings.max_sessions {
+            0 => 0,
+            cap => cap.max(settings.session_window),
+        };
+        self.tracked_sessions = merge_session_targets(discovered, &mut self.session_meta, cap);
+        METRICS.set_tracked_sessions(self.tracked_sessions.len());
+
+        let tracked: HashSet<&String> = self.tracked_sessions.iter().collect();
+        for session_id in self.tailers.keys().filter(|id| !tracked.contains(id)) {
+            info!(session_id = %session_id, "Stopped tracking session");
+        }
+        self.tailers.retain(|id, _| tracked.contains(id));
+        self.session_meta.retain(|id, _| tracked.contains(id));
+        self.renderers.retain(|id, _| tracked.contains(id));
+        self.latest.retain(|id, _| tracked.contains(id));
+        self.degraded.retain(|id, _| tracked.contains(id));
+        if self.pinned.as_ref().is_some_and(|id| !tracked.contains(id)) {
+            info!("Pinned session is no longer tracked; showing the newest session");
+            self.pinned = None;
+        }
+
+        for session_id in &self.tracked_sessions {
+            if self.tailers.contains_key(session_id) {
+                continue;
+            }
+            info!(session_id = %session_id, "Tracking session");
+            let wake = Arc::new(Notify::new());
+            let task = tokio::spawn(tail_session(
+                session_id.clone(),
+                self.explicit_paths.get(session_id).cloned(),
+                self.source.clone(),
+                wake.clone(),
+                self.settings_tx.subscribe(),
+                self.fs_watcher.is_some(),
+                self.tx.clone(),
+            ));
+            self.tailers.insert(
+                session_id.clone(),
+                Tailer {
+                    path: None,
+                    wake,
+                    task,
+                },
+            );
+        }
+        self.sync_watches();
+    }
+
+    fn sync_watches(&mut self) {
+        if let Some(watcher) = self.fs_watcher.as_mut() {
+            let live: HashSet<&PathBuf> = self
+                .tailers
+                .values()
+                .filter_map(|tailer| tailer.path.as_ref())
+                .collect();
+            watcher.retain_files(&live);
+        }
+    }
+
+    /// Render a batch of lines from one session, recording activity and
+    /// returning what the session displays after the last of them.
+    fn render_lines(&mut self, session_id: &str, lines: &[String]) -> Option<SessionEvent> {
+        let settings = self.settings();
+        let options = settings.render_options();
+        let profile = self
+            .tailers
+            .get(session_id)
+            .and_then(|tailer| tailer.path.as_deref())
+            .and_then(|path| self.source.home_of(path))
+            .map(CodexHome::class);
+        let renderer = self
+            .renderers
+            .entry(session_id.to_string())
+            .or_insert_with(|| SessionRenderer::new(session_id).with_profile(profile));
+        let mut newest_event: Option<SessionEvent> = None;
+        for line in lines {
+            let rendered = renderer.render_line(line, &options);
+            if let Some(recorder) = self.recorder.as_mut() {
+                let payload = rendered.as_ref().ok().and_then(Option::as_ref);
+                let payload = payload.map(|event| &event.payload);
+                if let Err(err) = recorder.record(session_id, line, payload, &settings.redactor) {
+                    warn!(error = %err, "Failed to write the record file; recording stopped");
+                    self.recorder = None;
+                }
+            }
+            match rendered {
+                Ok(Some(event)) => {
+                    #[cfg(feature = "store")]
+                    if let Some(store) = &self.store {
+                        let _ = store
+                            .send(crate::store::StoredEvent::new(session_id, renderer, &event));
+                    }
+                    self.session_meta
+                        .entry(session_id.to_string())
+                        .or_default()
+                        .record_event(event.timestamp.clone());
+                    // Later lines supersede earlier ones: the renderer
+                    // already folded them into the session's current state.
+                    self.sequence += 1;
+                    newest_event = Some(SessionEvent {
+                        session_id: session_id.to_string(),
+                        event,
+                        sequence: self.sequence,
+                    });
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    warn!(session_id = %session_id, error = %format!("{err:#}"), "Failed to process log entry");
+                }
+            }
+        }
+        let shows_model = settings.shows_model(renderer.model());
+        if let Some(meta) = self.session_meta.get_mut(session_id) {
+            meta.ended = renderer.ended();
+        }
+        let auto_discover = settings.session_id.is_none() && settings.session_file.is_none();
+        if auto_discover && settings.ignore.cwd(renderer.cwd()) {
+            info!(session_id = %session_id, cwd = renderer.cwd(), "Ignoring session");
+            self.ignored.insert(session_id.to_string());
+            let tracked = self.tracked_sessions.clone();
+            self.retarget(&tracked);
+            return None;
+        }
+        if !shows_model {
+            self.latest.remove(session_id);
+            return None;
+        }
+        if let Some(event) = &newest_event {
+            self.latest.insert(session_id.to_string(), event.clone());
+        }
+        newest_event
+    }
+
+    /// An approval request in another session stays on screen until answered.
+    fn approval_elsewhere(&self, session_id: &str) -> bool {
+        self.renderers
+            .iter()
+            .any(|(id, renderer)| id != session_id && renderer.awaiting_approval())
+    }
+
+    /// Whether the focused window is in the project of another session, which
+    /// is shown instead unless this one waits for an approval.
+    fn focused_elsewhere(&self, session_id: &str) -> bool {
+        let Some(focused) = &self.focused else {
+            return false;
+        };
+        let in_focus = |id: &str| {
+            self.renderers
+                .get(id)
+                .and_then(SessionRenderer::cwd)
+                .is_some_and(|cwd| focus::in_project(focused, cwd))
+        };
+        !in_focus(session_id)
+            && self.latest.keys().any(|id| in_focus(id))
+            && !self
+                .renderers
+                .get(session_id)
+                .is_some_and(SessionRenderer::awaiting_approval)
+    }
+
+    /// Whether the live session on display keeps the bar over `event` from
+    /// another session, under `--switch-hysteresis-ms`. An approval request
+    /// always takes over.
+    fn holds_display(&self, event: &SessionEvent) -> bool {
+        let settings = self.settings_tx.borrow();
+        let Some(shown) = &self.last_emitted else {
+            return false;
+        };
+        if settings.mode == DisplayMode::Summary
+            || shown.session_id.is_empty()
+            || self.has_ended(&shown.session_id)
+            || self
+                .renderers
+                .get(&event.session_id)
+                .is_some_and(SessionRenderer::awaiting_approval)
+        {
+            return false;
+        }
+        self.latest
+            .get(&shown.session_id)
+            .is_some_and(|current| within_hysteresis(current, event, settings.switch_hysteresis()))
+    }
+
+    fn has_ended(&self, session_id: &str) -> bool {
+        self.renderers
+            .get(session_id)
+            .is_some_and(SessionRenderer::ended)
+    }
+
+    /// Publish whatever should be on display now: the pinned session's latest
+    /// event, or the newest event across all sessions, preferring sessions in
+    /// the focused window's project and then those that have not shut down.
+    async fn emit_current(&mut self) {
+        let current = match &self.pinned {
+            Some(session_id) => self.latest.get(session_id).cloned(),
+            None => {
+                let candidates: Vec<&SessionEvent> = self
+                    .latest
+                    .values()
+                    .filter(|event| {
+                        !self.approval_elsewhere(&event.session_id)
+                            && !self.focused_elsewhere(&event.session_id)
+                    })
+                    .collect();
+                let live: Vec<&SessionEvent> = candidates
+                    .iter()
+                    .copied()
+                    .filter(|event| !self.has_ended(&event.session_id))
+                    .collect();
+                let pool = if live.is_empty() { candidates } else { live };
+                pool.into_iter().cloned().fold(None, select_newer_event)
+            }
+        };
+        if let Some(event) = current {
+            self.emit(event).await;
+        }
+    }
+
+    /// How often sessions are re-checked and the config reloaded.
+    fn tick_period(&self) -> Duration {
+        let settings = self.settings_tx.borrow();
+        settings
+            .session_refresh_interval()
+            .max(settings.poll_interval())
+    }
+
+    fn idle_deadline(&self) -> Option<Instant> {
+        let idle_after = self.settings_tx.borrow().idle_after()?;
+        (!self.idle).then(|| self.last_activity + idle_after)
+    }
+
+    /// Swap the display for the idle payload after `--idle-after-secs`
+    /// without events; the next event brings the session back.
+    async fn go_idle(&mut self) {
+        self.idle = true;
+        // Pending approvals stay up however long they wait.
+        if self
+            .renderers
+            .values()
+            .any(SessionRenderer::awaiting_approval)
+        {
+            return;
+        }
+        let Some(shown) = &self.last_emitted else {
+            return;
+        };
+        let (session_id, sequence) = (shown.session_id.clone(), shown.sequence);
+        self.emit(SessionEvent {
+            session_id,
+            event: RenderedEvent {
+                payload: idle_payload(),
+                timestamp: None,
+            },
+            sequence,
+        })
+        .await;
+    }
+
+    /// Replace the display once the session on it has left the history
+    /// window, so a finished session does not stay frozen on the bar.
+    async fn settle_display(&mut self) {
+        let Some(shown) = self.last_emitted.as_ref() else {
+            return;
+        };
+        // No session is behind the unconfigured payload; it stays until one
+        // shows up.
+        if shown.session_id.is_empty() || self.tracked_sessions.contains(&shown.session_id) {
+            return;
+        }
+        let session_id = shown.session_id.clone();
+        self.emit_current().await;
+        if self
+            .last_emitted
+            .as_ref()
+            .is_some_and(|last| last.session_id == session_id)
+        {
+            self.emit(SessionEvent {
+                session_id,
+                event: RenderedEvent {
+                    payload: idle_payload(),
+                    timestamp: None,
+                },
+                sequence: 0,
+            })
+            .await;
+        }
+    }
+
+    /// Re-emit the payload on display with the `degraded` mark of the
+    /// sessions failing to read now, or the placeholder when nothing is
+    /// shown yet.
+    async fn show_health(&mut self) {
+        let shown = self.last_emitted.clone().unwrap_or_else(|| SessionEvent {
+            session_id: String::new(),
+            event: RenderedEvent {
+                payload: placeholder_payload(),
+                timestamp: None,
+            },
+            sequence: 0,
+        });
+        self.emit(shown).await;
+    }
+
+    /// Replace the display, and each view, with the stopped payload before
+    /// the daemon exits.
+    async fn stop(&mut self) {
+        let payload = stopped_payload();
+        let settings = self.settings();
+        let views = [
+            &settings.cache_reasoning,
+            &settings.cache_status,
+            &settings.cache_tokens,
+        ];
+        for path in views.into_iter().flatten() {
+            let _ = self
+                .emit_tx
+                .send(Emission::File {
+                    path: path.clone(),
+                    payload: payload.clone(),
+                })
+                .await;
+        }
+        let _ = self
+            .emit_tx
+            .send(Emission::Display {
+                session_id: String::new(),
+                event: RenderedEvent {
+                    payload,
+                    timestamp: None,
+                },
+            })
+            .await;
+    }
+
+    /// Whether the payload on display changes with the clock.
+    fn clock_driven(&self) -> bool {
+        let settings = self.settings_tx.borrow();
+        settings.timestamps != TimestampStyle::Absolute || settings.elapsed != ElapsedDisplay::Off
+    }
+
+    /// Re-render the payload on display so relative times and the elapsed
+    /// counter keep up between log lines.
+    async fn refresh_display(&mut self) {
+        if !self.clock_driven() || self.idle {
+            return;
+        }
+        let options = self.settings_tx.borrow().render_options();
+        let Some(shown) = &self.last_emitted else {
+            return;
+        };
+        let (session_id, sequence) = (shown.session_id.clone(), shown.sequence);
+        if let Some(event) = self
+            .renderers
+            .get(&session_id)
+            .and_then(|renderer| renderer.refresh(&options))
+        {
+            self.emit(SessionEvent {
+                session_id,
+                event,
+                sequence,
+            })
+            .await;
+        }
+    }
+
+    /// Notify the desktop, and play a sound, when a session newly needs
+    /// attention.
+    fn raise_alert(&mut self, event: &SessionEvent) {
+        let payload = &event.event.payload;
+        let Some(kind) = self.alerts.observe(&event.session_id, payload) else {
+            return;
+        };
+        if self.paused {
+            return;
+        }
+        let settings = self.settings_tx.borrow();
+        if settings.notify.contains(&kind) {
+            alert::notify(kind, payload);
+        }
+        if let Some(sound) = settings.sounds.get(&kind)
+            && !settings.quiet_hours.is_some_and(|quiet| quiet.active())
+        {
+            alert::play_sound(&settings.sound_player, sound);
+        }
+    }
+
+    /// Pass the spans and token counts the session's new lines finished on to
+    /// the OpenTelemetry exporter.
+    #[cfg(feature = "otel")]
+    fn export_telemetry(&mut self, session_id: &str) {
+        let (Some(telemetry), Some(renderer)) =
+            (&self.telemetry, self.renderers.get_mut(session_id))
+        else {
+            return;
+        };
+        for record in renderer.take_telemetry() {
+            let _ = telemetry.send(record);
+        }
+    }
+
+    /// Hand a session's latest payload to the emitter for its own cache file,
+    /// whether or not it is the one on display.
+    async fn write_session_cache(&self, event: &SessionEvent) {
+        let Some(cache_dir) = self.settings_tx.borrow().cache_dir.clone() else {
+            return;
+        };
+        if self.paused {
+            return;
+        }
+        let _ = self
+            .emit_tx
+            .send(Emission::File {
+                path: session_cache_path(&cache_dir, &event.session_id),
+                payload: event.event.payload.clone(),
+            })
+            .await;
+    }
+
+    /// Split the session on display into the reasoning, status, and token
+    /// views that have a cache file configured.
+    async fn write_views(&self, shown: &SessionEvent) {
+        let settings = self.settings();
+        let views = [
+            (&settings.cache_reasoning, View::Reasoning),
+            (&settings.cache_status, View::Status),
+            (&settings.cache_tokens, View::Tokens),
+        ];
+        if views.iter().all(|(path, _)| path.is_none()) {
+            return;
+        }
+        let options = settings.render_options();
+        let renderer = self.renderers.get(&shown.session_id);
+        for (path, view) in views {
+            let Some(path) = path else {
+                continue;
+            };
+            // The idle and placeholder payloads stand in for every view.
+            let payload = match renderer {
+                Some(renderer) if shown.event.payload != idle_payload() => {
+                    renderer.view(view, &options)
+                }
+                _ => None,
+            };
+            let payload = payload.unwrap_or_else(|| shown.event.payload.clone());
+            let _ = self
+                .emit_tx
+                .send(Emission::File {
+                    path: path.clone(),
+                    payload,
+                })
+                .await;
+        }
+    }
+
+    /// With `--mode summary`, the count of live sessions that replaces any
+    /// one session's event, stamped as the newest of them.
+    fn summarize(&self) -> Option<SessionEvent> {
+        let mut live: Vec<&SessionEvent> = self
+            .latest
+            .values()
+            .filter(|event| !self.has_ended(&event.session_id))
+            .collect();
+        live.sort_by(|a, b| {
+            timestamp_order(b.event.timestamp.as_ref(), a.event.timestamp.as_ref())
+        });
+        let newest = live.first()?;
+        let settings = self.settings_tx.borrow();
+        let sessions: Vec<(String, &WaybarOutput)> = live
+            .iter()
+            .map(|event| {
+                let name = self
+                    .renderers
+                    .get(&event.session_id)
+                    .and_then(SessionRenderer::cwd)
+                    .and_then(|cwd| Path::new(cwd).file_name())
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| event.session_id.chars().take(8).collect());
+                (settings.markup.apply(&name), &event.event.payload)
+            })
+            .collect();
+        let mut payload = summary_payload(&sessions);
+        settings.payload.stamp(&mut payload);
+        Some(SessionEvent {
+            session_id: newest.session_id.clone(),
+            event: RenderedEvent {
+                payload,
+                timestamp: newest.event.timestamp.clone(),
+            },
+            sequence: newest.sequence,
+        })
+    }
+
+    async fn emit(&mut self, mut event: SessionEvent) {
+        // The summary covers every session, so pins and focus do not apply.
+        let summary = self.settings_tx.borrow().mode == DisplayMode::Summary
+            && !event.session_id.is_empty()
+            && event.event.payload != idle_payload();
+        if summary && let Some(summarized) = self.summarize() {
+            event = summarized;
+        }
+        if self.paused
+            || (!summary
+                && (self
+                    .pinned
+                    .as_ref()
+                    .is_some_and(|session_id| session_id != &event.session_id)
+                    || (self.pinned.is_none()
+                        && (self.approval_elsewhere(&event.session_id)
+                            || self.focused_elsewhere(&event.session_id)))))
+            || (!should_emit(&self.last_emitted, &event) && self.shown_degraded == self.degraded)
+        {
+            return;
+        }
+        let mut shown = event.event.clone();
+        mark_degraded(&mut shown.payload, &self.degraded);
+        if self
+            .emit_tx
+            .send(Emission::Display {
+                session_id: event.session_id.clone(),
+                event: shown.clone(),
+            })
+            .await
+            .is_ok()
+        {
+            self.published_tx.send_replace(Some(shown.payload));
+            self.write_views(&event).await;
+            self.last_emitted = Some(event);
+            self.shown_degraded = self.degraded.clone();
+        }
+    }
+}
+
+/// Render the newest event across the sessions the daemon would track, without
+/// starting any tasks. Used by `--once`.
+pub fn render_latest(settings: &Settings) -> Result<Option<SessionEvent>> {
+    let targets: Vec<(String, Option<PathBuf>)> =
+        match (&settings.session_id, &settings.session_file) {
+            (Some(id), path) => vec![(id.clone(), path.clone())],
+            (None, Some(path)) => vec![(
+                settings
+                    .agent
+                    .session_id_from_path(path)
+                    .context("Failed to infer session id from --session-file")?,
+                Some(path.clone()),
+            )],
+            (None, None) => settings
+                .session_source()
+                .recent(settings.session_window)?
+                .into_iter()
+                .filter(|id| !settings.ignore.session(id))
+                .map(|id| (id, None))
+                .collect(),
+        };
+
+    let source = settings.session_source();
+    let options = settings.render_options();
+    // Each session is read on its own thread, so a large log on slow storage
+    // does not hold up the others.
+    let primed: Vec<_> = thread::scope(|scope| {
+        let readers: Vec<_> = targets
+            .iter()
+            .map(|(session_id, explicit)| {
+                let (source, options) = (&source, &options);
+                scope.spawn(move || {
+                    initialize_session_state(
+                        session_id,
+                        explicit.as_ref(),
+                        source,
+                        options,
+                        true,
+                        settings.max_line_bytes,
+                    )
+                })
+            })
+            .collect();
+        readers
+            .into_iter()
+            .map(|reader| {
+                reader
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    });
+
+    let mut newest_event: Option<SessionEvent> = None;
+    for ((session_id, explicit), primed) in targets.into_iter().zip(primed) {
+        if let Some((state, Some(mut event))) = primed? {
+            let info = event.payload.session.as_ref();
+            if !settings.shows_model(info.and_then(|info| info.model.as_deref()))
+                || (explicit.is_none()
+                    && settings.session_id.is_none()
+                    && settings
+                        .ignore
+                        .cwd(info.and_then(|info| info.cwd.as_deref())))
+            {
+                continue;
+            }
+            event
+                .payload
+                .class
+                .extend(source.home_of(&state.path).map(CodexHome::class));
+            newest_event = select_newer_event(
+                newest_event,
+                SessionEvent {
+                    session_id,
+                    event,
+                    sequence: 0,
+                },
+            );
+        }
+    }
+    Ok(newest_event)
+}
+
+pub fn select_newer_event(
+    current: Option<SessionEvent>,
+    candidate: SessionEvent,
+) -> Option<SessionEvent> {
+    match current {
+        None => Some(candidate),
+        Some(existing) => {
+            // Without a timestamp on both, the one rendered later wins.
+            let (new, old) = (&candidate.event.timestamp, &existing.event.timestamp);
+            let order = match (new, old) {
+                (Some(new), Some(old)) => compare_timestamps(new, old),
+                _ => Ordering::Equal,
+            }
+            .then(candidate.sequence.cmp(&existing.sequence))
+            .then(new.is_some().cmp(&old.is_some()));
+            if order == Ordering::Greater
+                || (order == Ordering::Equal && candidate.event.payload != existing.event.payload)
+            {
+                Some(candidate)
+            } else {
+                Some(existing)
+            }
+        }
+    }
+}
+
+/// The tracked session `pin` names, by its full id or a prefix only one
+/// tracked id starts with.
+fn resolve_pin(tracked: &[String], session_id: &str) -> Result<String, String> {
+    if tracked.iter().any(|id| id == session_id) {
+        return Ok(session_id.to_string());
+    }
+    let mut matches = tracked.iter().filter(|id| id.starts_with(session_id));
+    match (matches.next(), matches.next()) {
+        (Some(id), None) => Ok(id.clone()),
+        (Some(_), Some(_)) => Err(format!("{session_id} matches more than one session")),
+        (None, _) => Err(format!("session {session_id} is not tracked")),
+    }
+}
+
+/// Timestamped events sort after ones without a timestamp.
+fn timestamp_order(a: Option<&String>, b: Option<&String>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => compare_timestamps(a, b),
+        (a, b) => a.is_some().cmp(&b.is_some()),
+    }
+}
+
+/// Whether `candidate`, from another session than `current`, is too close
+/// behind `current` to take the display from it: less than `hysteresis`
+/// newer, or older. Events without a timestamp are never held back.
+pub fn within_hysteresis(
+    current: &SessionEvent,
+    candidate: &SessionEvent,
+    hysteresis: Duration,
+) -> bool {
+    if hysteresis.is_zero() || current.session_id == candidate.session_id {
+        return false;
+    }
+    let parse = |event: &SessionEvent| event.event.timestamp.as_deref().and_then(parse_timestamp);
+    match (parse(current), parse(candidate)) {
+        (Some(current), Some(candidate)) => {
+            candidate
+                .duration_since(current)
+                .is_ok_and(|gap| gap < hysteresis)
+                || candidate < current
+        }
+        _ => false,
+    }
+}
+
+pub fn should_emit(last_emitted: &Option<SessionEvent>, candidate: &SessionEvent) -> bool {
+    match last_emitted {
+        None => true,
+        Some(previous) => {
+            previous.session_id != candidate.session_id
+                || previous.event.timestamp != candidate.event.timestamp
+                || previous.event.payload != candidate.event.payload
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn select_newer_event_prefers_newer_timestamp() {
+        let older = SessionEvent {
+            session_id: "alpha".to_string(),
+            event: RenderedEvent {
+                payload: WaybarOutput {
+                    text: "Old".to_string(),
+                    tooltip: None,
+                    class: vec![],
+                    ..Default::default()
+                },
+                timestamp: Some("2025-10-29T10:00:00Z".to_string()),
+            },
+            sequence: 2,
+        };
+        let newer = SessionEvent {
+            session_id: "beta".to_string(),
+            event: RenderedEvent {
+                payload: WaybarOutput {
+                    text: "New".to_string(),
+                    tooltip: None,
+                    class: vec![],
+                    ..Default::default()
+                },
+                timestamp: Some("2025-10-29T11:00:00Z".to_string()),
+            },
+            sequence: 1,
+        };
+
+        let picked = select_newer_event(Some(older.clone()), newer.clone()).unwrap();
+        assert_eq!(picked.session_id, "beta");
+
+        let unchanged = select_newer_event(Some(newer.clone()), older.clone()).unwrap();
+        assert_eq!(unchanged.session_id, "beta");
+
+        // Without a timestamp, the event rendered later wins.
+        let untimed = |mut event: SessionEvent| {
+            event.event.timestamp = None;
+            event
+        };
+        let picked = select_newer_event(Some(newer.clone()), untimed(older.clone()));
+        assert_eq!(picked.unwrap().session_id, "alpha");
+        let picked = select_newer_event(Some(untimed(older.clone())), newer.clone());
+        assert_eq!(picked.unwrap().session_id, "alpha");
+    }
+
+    #[test]
+    fn another_session_takes_over_only_when_meaningfully_newer() {
+        let event = |session_id: &str, timestamp: Option<&str>| SessionEvent {
+            session_id: session_id.to_string(),
+            event: RenderedEvent {
+                payload: idle_payload(),
+                timestamp: timestamp.map(str::to_string),
+            },
+            sequence: 0,
+        };
+        let shown = event("alpha", Some("2025-10-29T12:00:00.200Z"));
+        let hysteresis = Duration::from_secs(1);
+
+        let close = event("beta", Some("2025-10-29T12:00:00.900Z"));
+        assert!(within_hysteresis(&shown, &close, hysteresis));
+        let older = event("beta", Some("2025-10-29T11:59:59Z"));
+        assert!(within_hysteresis(&shown, &older, hysteresis));
+        let later = event("beta", Some("2025-10-29T12:00:01.200Z"));
+        assert!(!within_hysteresis(&shown, &later, hysteresis));
+        assert!(!within_hysteresis(&shown, &event("beta", None), hysteresis));
+        assert!(!within_hysteresis(&shown, &close, Duration::ZERO));
+        let same = event("alpha", Some("2025-10-29T11:59:59Z"));
+        assert!(!within_hysteresis(&shown, &same, hysteresis));
+    }
+
+    #[test]
+    fn render_latest_picks_newest_event_across_sessions() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let history_path = dir.path().join("history.jsonl");
+        let sessions_root = dir.path().join("sessions");
+        std::fs::create_dir_all(&sessions_root)?;
+        std::fs::write(
+            &history_path,
+            "{\"session_id\":\"alpha\"}\n{\"session_id\":\"beta\"}\n",
+        )?;
+        for (id, timestamp, text) in [
+            ("alpha", "2025-10-29T12:05:00Z", "Alpha step"),
+            ("beta", "2025-10-29T12:00:00Z", "Beta step"),
+        ] {
+            let line = serde_json::json!({
+                "timestamp": timestamp,
+                "type": "event_msg",
+                "payload": { "type": "agent_reasoning", "text": text }
+            });
+            std::fs::write(
+                sessions_root.join(format!("rollout-{id}.jsonl")),
+                format!("{line}\n"),
+            )?;
+        }
+
+        let settings = Settings::resolve(&crate::config::Config {
+            history_path: Some(history_path),
+            sessions_root: Some(sessions_root),
+            stdout: Some(true),
+            ..Default::default()
+        })?;
+        let latest = render_latest(&settings)?.expect("an event");
+        assert_eq!(latest.session_id, "alpha");
+        assert_eq!(latest.event.payload.text, "Alpha step");
+        Ok(())
+    }
+
+    #[test]
+    fn pins_resolve_unique_prefixes() {
+        let tracked = ["0199a213-81c0".to_string(), "0199b5e0-7d21".to_string()];
+        assert_eq!(
+            resolve_pin(&tracked, "0199b"),
+            Ok("0199b5e0-7d21".to_string())
+        );
+        assert_eq!(
+            resolve_pin(&tracked, "0199a213-81c0"),
+            Ok(tracked[0].clone())
+        );
+        assert!(
+            resolve_pin(&tracked, "0199")
+                .unwrap_err()
+                .contains("more than one")
+        );
+        assert!(
+            resolve_pin(&tracked, "ffff")
+                .unwrap_err()
+                .contains("not tracked")
+        );
+    }
+
+    #[tokio::test]
+    async fn reloaded_intervals_change_the_tick_period() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(&config_path, "session-refresh-secs = 2\n")?;
+        let overrides = crate::config::Config {
+            history_path: Some(dir.path().join("history.jsonl")),
+            sessions_root: Some(dir.path().join("sessions")),
+            cache_file: Some(dir.path().join("latest.json")),
+            ..Default::default()
+        };
+        let watcher = ConfigWatcher::new(config_path.clone(), overrides);
+        let (settings_tx, _) = watch::channel(watcher.load()?);
+        let mut daemon = Daemon::new(
+            watcher,
+            settings_tx,
+            mpsc::channel(16).0,
+            mpsc::channel(16).0,
+            watch::channel(None).0,
+            None,
+        )?;
+        daemon.reload_config();
+        assert_eq!(daemon.tick_period(), Duration::from_secs(2));
+
+        std::fs::write(&config_path, "session-refresh-secs = 7\n")?;
+        std::fs::File::options()
+            .write(true)
+            .open(&config_path)?
+            .set_modified(std::time::SystemTime::now() + Duration::from_secs(5))?;
+        daemon.reload_config();
+        assert_eq!(daemon.tick_period(), Duration::from_secs(7));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn restart_after_stop_does_not_go_back_to_older_events() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let history_path = dir.path().join("history.jsonl");
+        let sessions_root = dir.path().join("sessions");
+        let cache_file = dir.path().join("latest.json");
+        std::fs::create_dir_all(&sessions_root)?;
+        std::fs::write(
+            &history_path,
+            "{\"session_id\":\"alpha\"}\n{\"session_id\":\"beta\"}\n",
+        )?;
+        let mut logs = Vec::new();
+        for (id, timestamp, text) in [
+            ("alpha", "2025-10-29T12:00:00Z", "**Alpha step**"),
+            ("beta", "2025-10-29T12:05:00Z", "**Beta step**"),
+        ] {
+            let line = serde_json::json!({
+                "timestamp": timestamp,
+                "type": "event_msg",
+                "payload": { "type": "agent_reasoning", "text": text }
+            })
+            .to_string();
+            let path = sessions_root.join(format!("rollout-{id}.jsonl"));
+            std::fs::write(&path, format!("{line}\n"))?;
+            logs.push((id.to_string(), path, vec![line]));
+        }
+        let config = crate::config::Config {
+            history_path: Some(history_path),
+            sessions_root: Some(sessions_root),
+            cache_file: Some(cache_file.clone()),
+            ..Default::default()
+        };
+        let start = |emit_tx| -> Result<(Daemon, mpsc::Receiver<DaemonMessage>)> {
+            let watcher = ConfigWatcher::new(dir.path().join("config.toml"), config.clone());
+            let (settings_tx, _) = watch::channel(watcher.load()?);
+            let (tx, rx) = mpsc::channel(256);
+            let daemon = Daemon::new(
+                watcher,
+                settings_tx,
+                tx,
+                emit_tx,
+                watch::channel(None).0,
+                None,
+            )?;
+            Ok((daemon, rx))
+        };
+        let prime = || {
+            let messages = logs
+                .iter()
+                .map(|(session_id, path, lines)| DaemonMessage::Primed {
+                    session_id: session_id.clone(),
+                    path: path.clone(),
+                    lines: lines.clone(),
+                });
+            std::iter::once(DaemonMessage::Discovered(vec![
+                "alpha".to_string(),
+                "beta".to_string(),
+            ]))
+            .chain(messages)
+            .collect::<Vec<_>>()
+        };
+
+        // First run: show the newest session, then stop.
+        let (emit_tx, emit_rx) = mpsc::channel(16);
+        let (mut daemon, _rx) = start(emit_tx)?;
+        let emitter = tokio::spawn(run_emitter(emit_rx, daemon.settings_tx.subscribe()));
+        for message in prime() {
+            daemon.handle_message(message).await;
+        }
+        daemon.stop().await;
+        drop(daemon);
+        emitter.await??;
+        let (shown, state) = load_emitted(&cache_file).expect("saved display");
+        assert_eq!(shown.text, stopped_payload().text);
+        assert_eq!(state.session_id, "beta");
+        assert_eq!(state.timestamp.as_deref(), Some("2025-10-29T12:05:00Z"));
+
+        // Second run: the older session is not shown on the way, and the
+        // newest one replaces the stopped payload once.
+        let (emit_tx, mut emit_rx) = mpsc::channel(16);
+        let (mut daemon, _rx) = start(emit_tx)?;
+        for message in prime() {
+            daemon.handle_message(message).await;
+        }
+        let mut shown = Vec::new();
+        while let Ok(Emission::Display { session_id, event }) = emit_rx.try_recv() {
+            shown.push((session_id, event.payload.text));
+        }
+        assert_eq!(shown, [("beta".to_string(), "Beta step".to_string())]);
+
+        for message in prime() {
+            daemon.handle_message(message).await;
+        }
+        assert!(emit_rx.try_recv().is_err());
+        Ok(())
+    }
+}
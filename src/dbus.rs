@@ -0,0 +1,135 @@
+//! Session D-Bus service mirroring the control socket and broadcasting every
+//! published payload, and the desktop notification client. Compiled only with
+//! the `dbus` feature.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use tokio::sync::{mpsc, watch};
+use zbus::{Connection, connection, fdo, interface, object_server::SignalEmitter, zvariant::Value};
+
+use crate::control::{ControlCommand, dispatch};
+use crate::daemon::DaemonMessage;
+use crate::output::WaybarOutput;
+
+pub const BUS_NAME: &str = "org.codexwaybar";
+pub const OBJECT_PATH: &str = "/org/codexwaybar";
+
+struct Service {
+    tx: mpsc::Sender<DaemonMessage>,
+    published: watch::Receiver<Option<WaybarOutput>>,
+}
+
+impl Service {
+    async fn call(&self, command: ControlCommand) -> fdo::Result<String> {
+        dispatch(&self.tx, command)
+            .await
+            .map_err(fdo::Error::Failed)
+    }
+}
+
+#[interface(name = "org.codexwaybar.Daemon")]
+impl Service {
+    /// The payload currently on display as Waybar JSON, or an empty string
+    /// before the first event.
+    async fn latest(&self) -> String {
+        self.published
+            .borrow()
+            .as_ref()
+            .and_then(|payload| serde_json::to_string(payload).ok())
+            .unwrap_or_default()
+    }
+
+    /// Daemon state as JSON, same as `codex-shimmer ctl status`.
+    async fn status(&self) -> fdo::Result<String> {
+        self.call(ControlCommand::Status).await
+    }
+
+    async fn pause(&self) -> fdo::Result<()> {
+        self.call(ControlCommand::Pause).await.map(drop)
+    }
+
+    async fn resume(&self) -> fdo::Result<()> {
+        self.call(ControlCommand::Resume).await.map(drop)
+    }
+
+    async fn pin(&self, session_id: String) -> fdo::Result<()> {
+        self.call(ControlCommand::Pin(session_id)).await.map(drop)
+    }
+
+    async fn unpin(&self) -> fdo::Result<()> {
+        self.call(ControlCommand::Unpin).await.map(drop)
+    }
+
+    async fn refresh(&self) -> fdo::Result<()> {
+        self.call(ControlCommand::Refresh).await.map(drop)
+    }
+
+    /// Emitted with the Waybar JSON of every newly published payload.
+    #[zbus(signal)]
+    async fn updated(emitter: &SignalEmitter<'_>, payload: &str) -> zbus::Result<()>;
+}
+
+/// Claim [`BUS_NAME`] on the session bus and emit `Updated` for every payload
+/// the coordinator publishes. Returns once the coordinator goes away.
+pub async fn serve(
+    tx: mpsc::Sender<DaemonMessage>,
+    mut published: watch::Receiver<Option<WaybarOutput>>,
+) -> Result<()> {
+    let service = Service {
+        tx,
+        published: published.clone(),
+    };
+    let connection = connection::Builder::session()?
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, service)?
+        .build()
+        .await?;
+    let service = connection
+        .object_server()
+        .interface::<_, Service>(OBJECT_PATH)
+        .await?;
+
+    while published.changed().await.is_ok() {
+        let payload = published
+            .borrow_and_update()
+            .as_ref()
+            .and_then(|payload| serde_json::to_string(payload).ok());
+        if let Some(payload) = payload {
+            Service::updated(service.signal_emitter(), &payload).await?;
+        }
+    }
+    Ok(())
+}
+
+/// How insistently the notification server should present a notification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Urgency {
+    Normal = 1,
+    Critical = 2,
+}
+
+/// Show a desktop notification through `org.freedesktop.Notifications`.
+pub async fn send_notification(summary: &str, body: &str, urgency: Urgency) -> Result<()> {
+    let connection = Connection::session().await?;
+    let hints = HashMap::from([("urgency", Value::U8(urgency as u8))]);
+    connection
+        .call_method(
+            Some("org.freedesktop.Notifications"),
+            "/org/freedesktop/Notifications",
+            Some("org.freedesktop.Notifications"),
+            "Notify",
+            &(
+                "codex-shimmer",
+                0u32,
+                "",
+                summary,
+                body,
+                Vec::<&str>::new(),
+                hints,
+                -1i32,
+            ),
+        )
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,212 @@
+//! `codex-shimmer demo`: a made-up Codex session for styling the bar. The
+//! script is written to a rollout log one event at a time, the way Codex
+//! writes it, and the daemon tails that log like any other, so each payload
+//! gets the same rendering, classes, and outputs as a real session.
+
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result};
+use serde_json::{Value, json};
+
+use crate::render::format_timestamp;
+
+/// The id at the end of the demo rollout's file name.
+pub const SESSION_ID: &str = "0199a213-81c0-7800-8aa1-00000000de70";
+
+/// A couple of Codex turns: each event with the pause before it.
+pub fn script() -> Vec<(Duration, Value)> {
+    let secs = Duration::from_secs_f32;
+    let changes = json!({
+        "src/tailer.rs": {
+            "update": {
+                "unified_diff": "@@ -1 +1 @@\n-let line = String::from_utf8(bytes)?;\n+let line = String::from_utf8_lossy(&bytes);\n",
+            },
+        },
+    });
+    vec![
+        (secs(4.0), json!({ "type": "task_started" })),
+        (
+            secs(0.5),
+            json!({
+                "type": "agent_reasoning",
+                "text": "**Exploring the tailer**\n\nReading how rollout lines are split and handed to the renderer.",
+            }),
+        ),
+        (
+            secs(2.5),
+            json!({
+                "type": "exec_command_begin",
+                "call_id": "demo-search",
+                "command": ["rg", "-n", "read_lines", "src"],
+                "cwd": "~/src/codex-shimmer",
+            }),
+        ),
+        (
+            secs(2.0),
+            json!({ "type": "exec_command_end", "call_id": "demo-search", "exit_code": 0 }),
+        ),
+        (secs(0.5), token_count(18_400, 2_100)),
+        (
+            secs(1.0),
+            json!({
+                "type": "agent_reasoning",
+                "text": "**Planning the fix**\n\nA line longer than the read buffer is cut mid-character; decode it lossily instead.",
+            }),
+        ),
+        (
+            secs(3.0),
+            json!({
+                "type": "apply_patch_approval_request",
+                "call_id": "demo-patch",
+                "changes": changes,
+                "reason": "Edit the tailer",
+            }),
+        ),
+        (
+            secs(4.0),
+            json!({
+                "type": "patch_apply_begin",
+                "call_id": "demo-patch",
+                "auto_approved": false,
+                "changes": changes,
+            }),
+        ),
+        (
+            secs(1.0),
+            json!({ "type": "patch_apply_end", "call_id": "demo-patch", "success": true }),
+        ),
+        (
+            secs(1.0),
+            json!({
+                "type": "exec_approval_request",
+                "call_id": "demo-test",
+                "command": ["cargo", "test"],
+                "cwd": "~/src/codex-shimmer",
+                "reason": "Run the test suite",
+            }),
+        ),
+        (
+            secs(4.0),
+            json!({
+                "type": "exec_command_begin",
+                "call_id": "demo-test",
+                "command": ["cargo", "test"],
+                "cwd": "~/src/codex-shimmer",
+            }),
+        ),
+        (
+            secs(5.0),
+            json!({ "type": "exec_command_end", "call_id": "demo-test", "exit_code": 0 }),
+        ),
+        (secs(0.5), token_count(41_700, 5_300)),
+        (
+            secs(0.5),
+            json!({
+                "type": "agent_reasoning",
+                "text": "**Verifying the results**\n\nAll tests pass; summarizing the change.",
+            }),
+        ),
+        (
+            secs(3.0),
+            json!({
+                "type": "agent_message",
+                "message": "Long lines are now decoded lossily, so one bad byte no longer drops the event.\n\nTests pass.",
+            }),
+        ),
+        (secs(1.0), json!({ "type": "task_complete" })),
+        (
+            secs(6.0),
+            json!({ "type": "stream_error", "message": "stream disconnected before completion; retrying 1/5" }),
+        ),
+        (secs(4.0), json!({ "type": "task_started" })),
+        (
+            secs(0.5),
+            json!({ "type": "turn_aborted", "reason": "interrupted" }),
+        ),
+    ]
+}
+
+fn token_count(in_context: u64, output: u64) -> Value {
+    json!({
+        "type": "token_count",
+        "info": {
+            "total_token_usage": {
+                "input_tokens": in_context,
+                "cached_input_tokens": in_context / 2,
+                "output_tokens": output,
+            },
+            "last_token_usage": { "total_tokens": in_context + output },
+            "model_context_window": 272_000,
+        },
+    })
+}
+
+fn line(kind: &str, payload: Value) -> String {
+    json!({
+        "timestamp": format_timestamp(SystemTime::now()),
+        "type": kind,
+        "payload": payload,
+    })
+    .to_string()
+}
+
+/// Create the demo rollout in `dir`, starting with its `session_meta` line.
+pub fn create_rollout(dir: &Path) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let path = dir.join(format!("rollout-demo-{SESSION_ID}.jsonl"));
+    let meta = json!({
+        "id": SESSION_ID,
+        "cwd": "~/src/codex-shimmer",
+        "originator": "codex-shimmer demo",
+    });
+    std::fs::write(&path, line("session_meta", meta) + "\n")
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Append the script to the rollout at `path`, over and over.
+pub async fn play(path: PathBuf) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    loop {
+        for (pause, payload) in script() {
+            tokio::time::sleep(pause).await;
+            writeln!(file, "{}", line("event_msg", payload))?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, Settings};
+    use crate::session::SessionRenderer;
+
+    #[test]
+    fn script_shows_reasoning_commands_and_approvals() -> Result<()> {
+        let options = Settings::resolve(&Config::default())?.render_options();
+        let mut renderer = SessionRenderer::default();
+        let mut classes = Vec::new();
+        for (_, payload) in script() {
+            if let Some(event) = renderer.render_line(&line("event_msg", payload), &options)? {
+                classes.extend(event.payload.class);
+            }
+        }
+        for class in [
+            "phase-planning-the-fix",
+            "exec-running",
+            "approval-pending",
+            "agent-message",
+        ] {
+            assert!(classes.iter().any(|c| c == class), "no `{class}` payload");
+        }
+        Ok(())
+    }
+}
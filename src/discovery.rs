@@ -0,0 +1,1009 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Instant, SystemTime},
+};
+
+use anyhow::{Context, Result, bail};
+use glob::glob;
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::{
+    sync::{Notify, mpsc, watch},
+    task::spawn_blocking,
+    time::sleep,
+};
+use tracing::{debug, trace, warn};
+
+use crate::agent::{Agent, aider::CHAT_HISTORY};
+use crate::config::Settings;
+use crate::daemon::DaemonMessage;
+
+/// With `--discovery sessions`, the day directories of session logs read on
+/// each refresh, going further back only to fill the session window.
+const RECENT_DAYS: usize = 3;
+
+/// How Codex sessions are found.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Discovery {
+    /// The sessions named last in `history.jsonl`.
+    #[default]
+    History,
+    /// The most recently modified logs in the `sessions/YYYY/MM/DD` tree,
+    /// which include sessions not yet written to history.
+    Sessions,
+}
+
+impl FromStr for Discovery {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "history" => Ok(Discovery::History),
+            "sessions" => Ok(Discovery::Sessions),
+            other => bail!("unknown discovery `{other}` (expected `history` or `sessions`)"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SessionMeta {
+    pub last_event_timestamp: Option<String>,
+    pub last_event_instant: Instant,
+    pub missing_count: u32,
+    /// Whether the session has shut down, making it the first to stop being
+    /// tracked.
+    pub ended: bool,
+}
+
+impl SessionMeta {
+    pub fn new() -> Self {
+        Self {
+            last_event_timestamp: None,
+            last_event_instant: Instant::now(),
+            missing_count: 0,
+            ended: false,
+        }
+    }
+
+    pub fn record_event(&mut self, timestamp: Option<String>) {
+        self.last_event_timestamp = timestamp;
+        self.last_event_instant = Instant::now();
+        self.missing_count = 0;
+    }
+
+    pub fn mark_miss(&mut self) {
+        self.missing_count = self.missing_count.saturating_add(1);
+    }
+}
+
+impl Default for SessionMeta {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn infer_session_id_from_path(path: &Path) -> Option<String> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.split('-').next_back())
+        .and_then(|segment| segment.strip_suffix(".jsonl"))
+        .map(|s| s.to_string())
+}
+
+pub fn locate_session_file(root: &Path, session_id: &str) -> Result<Option<PathBuf>> {
+    let pattern = format!("{}/**/*{}*.jsonl", root.display(), session_id);
+    let mut newest_path: Option<PathBuf> = None;
+    let mut newest_mtime: Option<std::time::SystemTime> = None;
+
+    for path in glob(&pattern)?.flatten() {
+        if let Ok(metadata) = fs::metadata(&path) {
+            let mtime = metadata
+                .modified()
+                .unwrap_or(std::time::SystemTime::UNIX_EPOCH);
+            if newest_mtime.is_none_or(|current| mtime > current) {
+                newest_mtime = Some(mtime);
+                newest_path = Some(path);
+            }
+        }
+    }
+
+    Ok(newest_path)
+}
+
+pub fn recent_session_ids(history_path: &Path, limit: usize) -> Result<Vec<String>> {
+    let mut index = HistoryIndex::default();
+    index.update(history_path)?;
+    Ok(index.recent(limit).into_iter().map(|(id, _)| id).collect())
+}
+
+/// History files read so far, by path, kept across refreshes.
+pub type HistoryIndexes = HashMap<PathBuf, HistoryIndex>;
+
+/// What has been read of a history file, so a refresh only parses the lines
+/// appended since the last one instead of the whole (often large) file.
+#[derive(Debug, Default)]
+pub struct HistoryIndex {
+    /// Byte offset just past the last line read.
+    offset: u64,
+    /// Tells a replaced file from one that was appended to.
+    inode: Option<u64>,
+    lines: u64,
+    /// The line of each session's latest entry and that entry's `ts`.
+    latest: HashMap<String, (u64, u64)>,
+}
+
+impl HistoryIndex {
+    /// Read what was appended to `path` since the last update, starting over
+    /// when the file was replaced or truncated.
+    pub fn update(&mut self, path: &Path) -> Result<()> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                *self = HistoryIndex::default();
+                return Ok(());
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let meta = file.metadata()?;
+        let inode = Some(meta.ino());
+        if inode != self.inode || meta.len() < self.offset {
+            *self = HistoryIndex {
+                inode,
+                ..HistoryIndex::default()
+            };
+        }
+        if meta.len() == self.offset {
+            return Ok(());
+        }
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut appended = Vec::new();
+        file.read_to_end(&mut appended)?;
+
+        let complete = appended
+            .iter()
+            .rposition(|byte| *byte == b'\n')
+            .map_or(0, |end| end + 1);
+        for line in appended[..complete].split(|byte| *byte == b'\n') {
+            self.record(line);
+        }
+        let mut consumed = complete;
+        // An unterminated last line is taken once it is a whole entry; while
+        // Codex is still writing it, it is read again on the next update.
+        let rest = &appended[complete..];
+        if !rest.is_empty() && serde_json::from_str::<Value>(&String::from_utf8_lossy(rest)).is_ok()
+        {
+            self.record(rest);
+            consumed = appended.len();
+        }
+        self.offset += consumed as u64;
+        trace!(path = %path.display(), offset = self.offset, "Read history");
+        Ok(())
+    }
+
+    fn record(&mut self, line: &[u8]) {
+        let line = String::from_utf8_lossy(line);
+        if line.trim().is_empty() {
+            return;
+        }
+        self.lines += 1;
+        if let Ok(val) = serde_json::from_str::<Value>(&line)
+            && let Some(id) = val.get("session_id").and_then(|v| v.as_str())
+        {
+            let ts = val.get("ts").and_then(Value::as_u64).unwrap_or(0);
+            self.latest.insert(id.to_string(), (self.lines, ts));
+        }
+    }
+
+    /// The last `limit` sessions with the time (`ts`, in seconds) of their
+    /// latest entry, oldest first.
+    pub fn recent(&self, limit: usize) -> Vec<(String, u64)> {
+        let mut sessions: Vec<(u64, &String, u64)> = self
+            .latest
+            .iter()
+            .map(|(id, (line, ts))| (*line, id, *ts))
+            .collect();
+        sessions.sort_unstable();
+        let skip = sessions.len().saturating_sub(limit);
+        sessions
+            .into_iter()
+            .skip(skip)
+            .map(|(_, id, ts)| (id.clone(), ts))
+            .collect()
+    }
+}
+
+/// Ids of Claude Code transcripts under `root`, the most recently modified
+/// last.
+pub fn recent_transcript_ids(root: &Path, limit: usize) -> Result<Vec<String>> {
+    let pattern = format!("{}/*/*.jsonl", root.display());
+    let paths: Vec<PathBuf> = glob(&pattern)?.flatten().collect();
+    Ok(recently_modified(Agent::Claude, paths, limit))
+}
+
+/// The rollout files of `session_id` among Codex's session trees, oldest
+/// first: a resumed session continues in a new file under the same id. With
+/// the files found before as `known`, only the newest day directories are
+/// searched, for a file started since, instead of whole trees.
+fn locate_codex_logs(
+    roots: &[&Path],
+    session_id: &str,
+    known: Vec<PathBuf>,
+) -> Result<Vec<PathBuf>> {
+    let named = |path: &PathBuf| {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.contains(session_id))
+    };
+    let mut found = Vec::new();
+    for root in roots {
+        if known.is_empty() {
+            let pattern = format!("{}/**/*{}*.jsonl*", root.display(), session_id);
+            found.extend(glob(&pattern)?.flatten().filter(|path| {
+                path.extension().is_some_and(|ext| ext == "jsonl") || is_compressed(path)
+            }));
+        } else {
+            found.extend(recent_day_logs(root, 0)?.into_iter().filter(named));
+        }
+    }
+    let mut logs = known;
+    for path in found {
+        if !logs.contains(&path) {
+            logs.push(path);
+        }
+    }
+    // Rollout names start with the time the file was started; rotated copies
+    // of a file come before it, oldest first.
+    logs.sort_by_cached_key(|path| {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let rollout = name
+            .find(".jsonl")
+            .map_or(&*name, |end| &name[..end])
+            .to_string();
+        let modified = fs::metadata(path).and_then(|meta| meta.modified()).ok();
+        (rollout, !is_compressed(path), modified)
+    });
+    // Only a live log can be tailed.
+    while logs.last().is_some_and(|path| is_compressed(path)) {
+        logs.pop();
+    }
+    Ok(logs)
+}
+
+/// A log rotated and compressed by Codex or logrotate, read only for priming.
+pub fn is_compressed(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext == "gz" || ext == "zst")
+}
+
+/// Codex logs in the newest `YYYY/MM/DD` directories below `root`: those of
+/// the last `RECENT_DAYS` days with sessions, and older ones until there are
+/// `limit` logs.
+fn recent_day_logs(root: &Path, limit: usize) -> Result<Vec<PathBuf>> {
+    let pattern = format!("{}/[0-9]*/[0-9]*/[0-9]*", root.display());
+    let mut days: Vec<PathBuf> = glob(&pattern)?.flatten().filter(|p| p.is_dir()).collect();
+    // Codex zero-pads the components, so the newest day sorts last.
+    days.sort_unstable_by(|a, b| b.cmp(a));
+    let mut logs = Vec::new();
+    for (scanned, day) in days.iter().enumerate() {
+        if scanned >= RECENT_DAYS && logs.len() >= limit {
+            break;
+        }
+        logs.extend(glob(&format!("{}/*.jsonl", day.display()))?.flatten());
+    }
+    Ok(logs)
+}
+
+/// Aider chat histories of the projects named `project` (a glob) up to three
+/// directories below `root`.
+fn aider_histories(root: &Path, project: &str) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for parents in ["", "*/", "*/*/"] {
+        let pattern = format!("{}/{parents}{project}/{CHAT_HISTORY}", root.display());
+        paths.extend(glob(&pattern)?.flatten());
+    }
+    Ok(paths)
+}
+
+/// Session ids of the `limit` most recently modified `paths`, oldest first.
+fn recently_modified(agent: Agent, paths: Vec<PathBuf>, limit: usize) -> Vec<String> {
+    let mut sessions: Vec<(SystemTime, String)> = paths
+        .into_iter()
+        .filter_map(|path| {
+            let mtime = fs::metadata(&path).and_then(|meta| meta.modified()).ok()?;
+            Some((mtime, agent.session_id_from_path(&path)?))
+        })
+        .collect();
+    sessions.sort();
+    let skip = sessions.len().saturating_sub(limit);
+    sessions.into_iter().skip(skip).map(|(_, id)| id).collect()
+}
+
+/// Sessions that are never tracked, by id or by the directory they run in,
+/// such as scripted `codex exec` batch jobs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IgnoreList {
+    sessions: HashSet<String>,
+    cwds: Vec<glob::Pattern>,
+}
+
+impl IgnoreList {
+    /// Each entry is a session id, or a glob of working directories when it
+    /// contains a `/`.
+    pub fn new<'a>(entries: impl IntoIterator<Item = &'a str>) -> Result<Self> {
+        let mut ignore = IgnoreList::default();
+        for entry in entries {
+            if entry.contains('/') {
+                let pattern = glob::Pattern::new(entry)
+                    .with_context(|| format!("Invalid ignored directory `{entry}`"))?;
+                ignore.cwds.push(pattern);
+            } else {
+                ignore.sessions.insert(entry.to_string());
+            }
+        }
+        Ok(ignore)
+    }
+
+    /// The entries of an ignore file: one per line, with blank lines and
+    /// `#` comments skipped.
+    pub fn read_file(path: &Path) -> Result<Vec<String>> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read ignore file {}", path.display()))?;
+        Ok(text
+            .lines()
+            .map(|line| line.split_once('#').map_or(line, |(entry, _)| entry).trim())
+            .filter(|entry| !entry.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    pub fn session(&self, session_id: &str) -> bool {
+        self.sessions.contains(session_id)
+    }
+
+    pub fn cwd(&self, cwd: Option<&str>) -> bool {
+        cwd.is_some_and(|cwd| self.cwds.iter().any(|pattern| pattern.matches(cwd)))
+    }
+}
+
+/// One of several Codex homes followed together, such as separate work and
+/// personal profiles.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodexHome {
+    /// Shown as a `profile-<name>` class on the payloads of its sessions.
+    pub name: String,
+    pub history_path: PathBuf,
+    pub sessions_root: PathBuf,
+}
+
+impl CodexHome {
+    pub fn new(name: &str, home: &Path) -> Self {
+        CodexHome {
+            name: name.to_string(),
+            history_path: home.join("history.jsonl"),
+            sessions_root: home.join("sessions"),
+        }
+    }
+
+    pub fn class(&self) -> String {
+        format!("profile-{}", self.name)
+    }
+}
+
+/// Session logs already located, by session id. The clones of a
+/// [`SessionSource`] share it, so a tailer restarted after its log rotated
+/// or the daemon lost track of it finds the log without walking the tree.
+#[derive(Debug, Clone, Default)]
+pub struct PathIndex(Arc<Mutex<HashMap<String, Vec<PathBuf>>>>);
+
+impl PathIndex {
+    fn get(&self, session_id: &str) -> Vec<PathBuf> {
+        let paths = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        paths.get(session_id).cloned().unwrap_or_default()
+    }
+
+    fn set(&self, session_id: &str, parts: &[PathBuf]) {
+        let mut paths = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        if parts.is_empty() {
+            paths.remove(session_id);
+        } else {
+            paths.insert(session_id.to_string(), parts.to_vec());
+        }
+    }
+}
+
+/// Only a cache: two sources reading the same logs are equal whatever they
+/// have looked up.
+impl PartialEq for PathIndex {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for PathIndex {}
+
+/// Where the followed agent keeps its session logs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionSource {
+    pub agent: Agent,
+    pub history_path: PathBuf,
+    pub sessions_root: PathBuf,
+    /// Pattern below `sessions_root` matching the logs of `--agent generic`.
+    pub log_glob: String,
+    /// Codex homes merged into one set of sessions; when empty only
+    /// `history_path` and `sessions_root` are read.
+    pub homes: Vec<CodexHome>,
+    pub discovery: Discovery,
+    pub paths: PathIndex,
+}
+
+impl SessionSource {
+    /// The history files to watch for new sessions.
+    pub fn history_paths(&self) -> Vec<&Path> {
+        if self.homes.is_empty() {
+            vec![self.history_path.as_path()]
+        } else {
+            self.homes
+                .iter()
+                .map(|home| home.history_path.as_path())
+                .collect()
+        }
+    }
+
+    /// The session log trees to watch for new logs, which only
+    /// `--discovery sessions` reads.
+    pub fn session_roots(&self) -> Vec<&Path> {
+        if self.agent != Agent::Codex || self.discovery != Discovery::Sessions {
+            Vec::new()
+        } else {
+            self.codex_roots()
+        }
+    }
+
+    /// The session log tree when it does not exist yet (with several Codex
+    /// homes, when none of theirs does), as before the agent first ran.
+    pub fn missing_root(&self) -> Option<&Path> {
+        let roots = match self.agent {
+            Agent::Codex => self.codex_roots(),
+            _ => vec![self.sessions_root.as_path()],
+        };
+        let first = roots.first().copied()?;
+        roots.iter().all(|root| !root.is_dir()).then_some(first)
+    }
+
+    fn codex_roots(&self) -> Vec<&Path> {
+        if self.homes.is_empty() {
+            vec![self.sessions_root.as_path()]
+        } else {
+            self.homes
+                .iter()
+                .map(|home| home.sessions_root.as_path())
+                .collect()
+        }
+    }
+
+    /// The Codex home a session log belongs to, when several are followed.
+    pub fn home_of(&self, path: &Path) -> Option<&CodexHome> {
+        self.homes
+            .iter()
+            .find(|home| path.starts_with(&home.sessions_root))
+    }
+
+    /// The most recent sessions, oldest first.
+    pub fn recent(&self, limit: usize) -> Result<Vec<String>> {
+        self.recent_indexed(limit, &mut HistoryIndexes::new())
+    }
+
+    /// Like [`recent`](Self::recent), reading only what was appended to the
+    /// history files indexed in `histories` by earlier calls.
+    pub fn recent_indexed(
+        &self,
+        limit: usize,
+        histories: &mut HistoryIndexes,
+    ) -> Result<Vec<String>> {
+        let mut history = |path: &Path| -> Result<Vec<(String, u64)>> {
+            let index = histories.entry(path.to_path_buf()).or_default();
+            index.update(path)?;
+            Ok(index.recent(limit))
+        };
+        match self.agent {
+            Agent::Codex if self.discovery == Discovery::Sessions => {
+                let mut logs = Vec::new();
+                for root in self.session_roots() {
+                    logs.extend(recent_day_logs(root, limit)?);
+                }
+                Ok(recently_modified(self.agent, logs, limit))
+            }
+            Agent::Codex if !self.homes.is_empty() => {
+                let mut sessions = Vec::new();
+                for home in &self.homes {
+                    sessions.extend(history(&home.history_path)?);
+                }
+                // Entries without a time sort first, as the oldest.
+                sessions.sort_by_key(|(_, ts)| *ts);
+                let mut seen = HashSet::new();
+                let mut ids: Vec<String> = sessions
+                    .into_iter()
+                    .rev()
+                    .filter(|(id, _)| seen.insert(id.clone()))
+                    .take(limit)
+                    .map(|(id, _)| id)
+                    .collect();
+                ids.reverse();
+                Ok(ids)
+            }
+            Agent::Codex => Ok(history(&self.history_path)?
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect()),
+            Agent::Claude => recent_transcript_ids(&self.sessions_root, limit),
+            Agent::Aider => Ok(recently_modified(
+                self.agent,
+                aider_histories(&self.sessions_root, "*")?,
+                limit,
+            )),
+            Agent::Generic => Ok(recently_modified(self.agent, self.generic_logs()?, limit)),
+        }
+    }
+
+    /// The log file of one session, the one it is written to now.
+    pub fn find(&self, session_id: &str) -> Result<Option<PathBuf>> {
+        Ok(self.find_parts(session_id)?.pop())
+    }
+
+    /// Every log file of one session, oldest first. Only resumed Codex
+    /// sessions have more than one.
+    pub fn find_parts(&self, session_id: &str) -> Result<Vec<PathBuf>> {
+        let mut known = self.paths.get(session_id);
+        if !known.iter().all(|path| path.is_file()) {
+            known.clear();
+        }
+        let found = match self.agent {
+            Agent::Codex => locate_codex_logs(&self.codex_roots(), session_id, known.clone())?,
+            _ if !known.is_empty() => known.clone(),
+            _ => self.find_newest(session_id)?.into_iter().collect(),
+        };
+        if found != known {
+            match found.last() {
+                Some(path) => debug!(
+                    session_id,
+                    path = %path.display(),
+                    parts = found.len(),
+                    "Found session log"
+                ),
+                None => debug!(
+                    session_id,
+                    root = %self.sessions_root.display(),
+                    "No log found for session"
+                ),
+            }
+            self.paths.set(session_id, &found);
+        }
+        Ok(found)
+    }
+
+    fn find_newest(&self, session_id: &str) -> Result<Option<PathBuf>> {
+        let newest = |paths: Vec<PathBuf>| {
+            paths
+                .into_iter()
+                .max_by_key(|path| fs::metadata(path).and_then(|meta| meta.modified()).ok())
+        };
+        match self.agent {
+            Agent::Codex | Agent::Claude => locate_session_file(&self.sessions_root, session_id),
+            Agent::Aider => Ok(newest(aider_histories(
+                &self.sessions_root,
+                &glob::Pattern::escape(session_id),
+            )?)),
+            Agent::Generic => Ok(newest(
+                self.generic_logs()?
+                    .into_iter()
+                    .filter(|path| {
+                        self.agent.session_id_from_path(path).as_deref() == Some(session_id)
+                    })
+                    .collect(),
+            )),
+        }
+    }
+
+    fn generic_logs(&self) -> Result<Vec<PathBuf>> {
+        let pattern = format!("{}/{}", self.sessions_root.display(), self.log_glob);
+        trace!(pattern, "Listing generic logs");
+        Ok(glob(&pattern)?.flatten().collect())
+    }
+}
+
+/// Re-read history on every refresh interval (or when `wake` fires) and send
+/// the most recent session ids to the coordinator. Agents other than Codex have
+/// no history file, so their logs are ranked by modification time instead, as
+/// are Codex logs with `--discovery sessions`.
+pub async fn discover_sessions(
+    source: SessionSource,
+    settings: watch::Receiver<Settings>,
+    wake: Arc<Notify>,
+    tx: mpsc::Sender<DaemonMessage>,
+) {
+    let mut histories = HistoryIndexes::new();
+    loop {
+        let (limit, refresh) = {
+            let settings = settings.borrow();
+            (settings.session_window, settings.session_refresh_interval())
+        };
+        let lookup = source.clone();
+        let mut indexed = std::mem::take(&mut histories);
+        let found = spawn_blocking(move || {
+            let ids = lookup.recent_indexed(limit, &mut indexed);
+            (ids, indexed)
+        })
+        .await
+        .map(|(ids, indexed)| {
+            histories = indexed;
+            ids
+        });
+        match found {
+            Ok(Ok(ids)) => {
+                debug!(agent = ?source.agent, ?ids, "Discovered sessions");
+                if tx.send(DaemonMessage::Discovered(ids)).await.is_err() {
+                    return;
+                }
+            }
+            Ok(Err(err)) => {
+                warn!(error = %format!("{err:#}"), "Failed to discover sessions");
+            }
+            Err(_) => return,
+        }
+
+        tokio::select! {
+            _ = wake.notified() => {}
+            _ = sleep(refresh) => {}
+        }
+    }
+}
+
+/// The sessions to track: the ones just discovered, then the ones tracked
+/// before. Beyond `limit` (0 for no limit), sessions that have shut down are
+/// dropped first, then the least recently active, so a burst of short runs
+/// does not push out a long-lived session.
+pub fn merge_session_targets(
+    discovered: &[String],
+    session_meta: &mut HashMap<String, SessionMeta>,
+    limit: usize,
+) -> Vec<String> {
+    let discovered_set: HashSet<&String> = discovered.iter().collect();
+    session_meta.retain(|id, meta| meta.missing_count < 5 || discovered_set.contains(id));
+
+    let mut combined: Vec<String> = Vec::new();
+
+    for id in discovered {
+        if !combined.contains(id) {
+            combined.push(id.clone());
+        }
+    }
+
+    for id in session_meta.keys() {
+        if !combined.contains(id) {
+            combined.push(id.clone());
+        }
+    }
+
+    if limit > 0 && combined.len() > limit {
+        let mut sorted = combined.clone();
+        sorted.sort_by_key(|id| {
+            session_meta.get(id).map_or((true, Instant::now()), |meta| {
+                (!meta.ended, meta.last_event_instant)
+            })
+        });
+
+        let mut retained: Vec<String> = sorted.into_iter().rev().take(limit).collect();
+        retained.reverse();
+
+        combined.retain(|id| retained.contains(id));
+    }
+
+    combined
+}
+
+/// The logs of the one session asked for by id or file, or of the `limit`
+/// most recent sessions, oldest first; each with its parts oldest first.
+pub fn session_logs(settings: &Settings, limit: usize) -> Result<Vec<(String, Vec<PathBuf>)>> {
+    let source = settings.session_source();
+    match (&settings.session_id, &settings.session_file) {
+        (Some(id), Some(path)) => Ok(vec![(id.clone(), vec![path.clone()])]),
+        (Some(id), None) => Ok(vec![(id.clone(), source.find_parts(id)?)]),
+        (None, Some(path)) => Ok(vec![(
+            settings
+                .agent
+                .session_id_from_path(path)
+                .unwrap_or_default(),
+            vec![path.clone()],
+        )]),
+        (None, None) => source
+            .recent(limit)?
+            .into_iter()
+            .map(|id| {
+                let parts = source.find_parts(&id)?;
+                Ok((id, parts))
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, io::Write, time::Duration};
+    use tempfile::tempdir;
+    #[test]
+    fn recent_session_ids_returns_unique_sessions_in_order() -> Result<()> {
+        let dir = tempdir()?;
+        let history_path = dir.path().join("history.jsonl");
+        fs::write(
+            &history_path,
+            r#"
+{"session_id":"alpha"}
+{"session_id":"beta"}
+{"session_id":"alpha"}
+{"session_id":"gamma"}
+"#
+            .trim_start(),
+        )?;
+
+        let ids = recent_session_ids(&history_path, 2)?;
+        assert_eq!(ids, vec!["alpha".to_string(), "gamma".to_string()]);
+
+        let ids_three = recent_session_ids(&history_path, 3)?;
+        assert_eq!(
+            ids_three,
+            vec!["beta".to_string(), "alpha".to_string(), "gamma".to_string()]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn merge_session_targets_keeps_recently_active_sessions() {
+        let mut meta = HashMap::new();
+        let mut active_meta = SessionMeta::new();
+        active_meta.record_event(Some("2025-10-29T12:00:00Z".to_string()));
+        meta.insert("old-session".to_string(), active_meta);
+
+        let discovered = vec!["new-session".to_string()];
+        let merged = merge_session_targets(&discovered, &mut meta, 2);
+
+        assert!(merged.contains(&"new-session".to_string()));
+        assert!(merged.contains(&"old-session".to_string()));
+    }
+
+    #[test]
+    fn recent_transcript_ids_orders_by_modification_time() -> Result<()> {
+        let dir = tempdir()?;
+        let project = dir.path().join("-srv-project");
+        fs::create_dir(&project)?;
+        for (id, age) in [("older", 60), ("newest", 0), ("oldest", 120)] {
+            let path = project.join(format!("{id}.jsonl"));
+            fs::write(&path, "{}\n")?;
+            let mtime = SystemTime::now() - std::time::Duration::from_secs(age);
+            File::options()
+                .write(true)
+                .open(&path)?
+                .set_modified(mtime)?;
+        }
+
+        let ids = recent_transcript_ids(dir.path(), 2)?;
+        assert_eq!(ids, vec!["older".to_string(), "newest".to_string()]);
+        Ok(())
+    }
+
+    #[test]
+    fn codex_homes_merge_by_history_time() -> Result<()> {
+        let dir = tempdir()?;
+        let work = CodexHome::new("work", &dir.path().join(".codex-work"));
+        let personal = CodexHome::new("personal", &dir.path().join(".codex"));
+        for (home, history, id) in [
+            (
+                &work,
+                "{\"session_id\":\"w1\",\"ts\":100}\n{\"session_id\":\"w2\",\"ts\":300}\n",
+                "w2",
+            ),
+            (&personal, "{\"session_id\":\"p1\",\"ts\":200}\n", "p1"),
+        ] {
+            fs::create_dir_all(home.sessions_root.join("2025/10/06"))?;
+            fs::write(&home.history_path, history)?;
+            fs::write(
+                home.sessions_root
+                    .join(format!("2025/10/06/rollout-{id}.jsonl")),
+                "",
+            )?;
+        }
+        let source = SessionSource {
+            agent: Agent::Codex,
+            history_path: work.history_path.clone(),
+            sessions_root: work.sessions_root.clone(),
+            log_glob: String::new(),
+            homes: vec![work, personal],
+            discovery: Discovery::History,
+            paths: PathIndex::default(),
+        };
+
+        assert_eq!(source.recent(2)?, ["p1", "w2"]);
+        assert_eq!(source.recent(5)?, ["w1", "p1", "w2"]);
+        let path = source.find("p1")?.expect("p1 log");
+        assert_eq!(
+            source.home_of(&path).map(CodexHome::class).as_deref(),
+            Some("profile-personal")
+        );
+        assert_eq!(source.history_paths().len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn sessions_discovery_ranks_recent_logs_by_mtime() -> Result<()> {
+        let dir = tempdir()?;
+        let root = dir.path().join("sessions");
+        let log = |day: &str, id: &str, age: u64| -> Result<()> {
+            let day = root.join(day);
+            fs::create_dir_all(&day)?;
+            let path = day.join(format!("rollout-2025-10-06T10-00-00-{id}.jsonl"));
+            File::create(&path)?.set_modified(SystemTime::now() - Duration::from_secs(age))?;
+            Ok(())
+        };
+        log("2025/09/30", "old", 900)?;
+        log("2025/10/02", "mid", 800)?;
+        log("2025/10/05", "resumed", 10)?;
+        log("2025/10/06", "busy", 60)?;
+        log("2025/10/06", "quiet", 600)?;
+        fs::write(root.join("2025/10/06/notes.txt"), "")?;
+
+        let source = SessionSource {
+            agent: Agent::Codex,
+            history_path: dir.path().join("history.jsonl"),
+            sessions_root: root.clone(),
+            log_glob: String::new(),
+            homes: Vec::new(),
+            discovery: Discovery::Sessions,
+            paths: PathIndex::default(),
+        };
+        assert_eq!(source.recent(2)?, ["busy", "resumed"]);
+        assert_eq!(
+            source.recent(10)?,
+            ["old", "mid", "quiet", "busy", "resumed"]
+        );
+        assert_eq!(source.session_roots(), [root.as_path()]);
+        assert_eq!(
+            recent_day_logs(&root, 1)?.len(),
+            4,
+            "only the last days are read"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn history_index_reads_only_appended_lines() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("history.jsonl");
+        fs::write(
+            &path,
+            "{\"session_id\":\"a\",\"ts\":1}\n{\"session_id\":\"b\",\"ts\":2}\n",
+        )?;
+        let mut index = HistoryIndex::default();
+        index.update(&path)?;
+        assert_eq!(
+            index.recent(5),
+            [("a".to_string(), 1), ("b".to_string(), 2)]
+        );
+
+        let mut file = fs::OpenOptions::new().append(true).open(&path)?;
+        file.write_all(b"{\"session_id\":\"a\",\"ts\":3}\n{\"session_id\":\"c\"")?;
+        index.update(&path)?;
+        assert_eq!(
+            index.recent(5),
+            [("b".to_string(), 2), ("a".to_string(), 3)]
+        );
+
+        file.write_all(b",\"ts\":4}\n")?;
+        index.update(&path)?;
+        assert_eq!(
+            index.recent(2),
+            [("a".to_string(), 3), ("c".to_string(), 4)]
+        );
+
+        // Rewritten shorter, as when the history is pruned.
+        fs::write(&path, "{\"session_id\":\"d\",\"ts\":5}")?;
+        index.update(&path)?;
+        assert_eq!(index.recent(5), [("d".to_string(), 5)]);
+        Ok(())
+    }
+
+    #[test]
+    fn located_logs_are_remembered_until_they_go() -> Result<()> {
+        let dir = tempdir()?;
+        let root = dir.path().join("sessions");
+        let old = root.join("2024/01/02/rollout-2024-01-02T10-00-00-abc.jsonl");
+        fs::create_dir_all(old.parent().expect("parent"))?;
+        fs::write(&old, "")?;
+        let source = SessionSource {
+            agent: Agent::Codex,
+            history_path: dir.path().join("history.jsonl"),
+            sessions_root: root.clone(),
+            log_glob: String::new(),
+            homes: Vec::new(),
+            discovery: Discovery::History,
+            paths: PathIndex::default(),
+        };
+        assert_eq!(source.find("abc")?, Some(old.clone()));
+        assert_eq!(source.clone().paths.get("abc"), std::slice::from_ref(&old));
+
+        let moved = root.join("2024/01/03/rollout-2024-01-02T10-00-00-abc.jsonl");
+        fs::create_dir_all(moved.parent().expect("parent"))?;
+        fs::rename(&old, &moved)?;
+        assert_eq!(source.find("abc")?, Some(moved));
+        assert_eq!(source.find("missing")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn missing_root_is_reported_until_created() -> Result<()> {
+        let dir = tempdir()?;
+        let home = CodexHome::new("codex", &dir.path().join(".codex"));
+        let source = SessionSource {
+            agent: Agent::Codex,
+            history_path: home.history_path.clone(),
+            sessions_root: home.sessions_root.clone(),
+            log_glob: String::new(),
+            homes: Vec::new(),
+            discovery: Discovery::History,
+            paths: PathIndex::default(),
+        };
+        assert_eq!(source.missing_root(), Some(home.sessions_root.as_path()));
+        assert!(source.recent(5)?.is_empty());
+
+        fs::create_dir_all(&home.sessions_root)?;
+        assert_eq!(source.missing_root(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn ignore_list_matches_ids_and_directories() -> Result<()> {
+        let dir = tempdir()?;
+        let file = dir.path().join("ignore");
+        fs::write(&file, "# batch jobs\n/srv/batch/*  # nightly\n\nabc-123\n")?;
+        let entries = IgnoreList::read_file(&file)?;
+        assert_eq!(entries, ["/srv/batch/*", "abc-123"]);
+
+        let ignore = IgnoreList::new(entries.iter().map(String::as_str))?;
+        assert!(ignore.session("abc-123"));
+        assert!(!ignore.session("/srv/batch/*"));
+        assert!(ignore.cwd(Some("/srv/batch/report")));
+        assert!(!ignore.cwd(Some("/home/me/project")));
+        assert!(!ignore.cwd(None));
+        assert!(IgnoreList::new(["/srv/[batch"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn merge_session_targets_drops_ended_sessions_first() {
+        let mut meta = HashMap::new();
+        let mut interactive = SessionMeta::new();
+        interactive.record_event(Some("2025-10-29T09:00:00Z".to_string()));
+        meta.insert("interactive".to_string(), interactive);
+        for run in ["exec-1", "exec-2", "exec-3"] {
+            let mut finished = SessionMeta::new();
+            finished.record_event(Some("2025-10-29T12:00:00Z".to_string()));
+            finished.ended = true;
+            meta.insert(run.to_string(), finished);
+        }
+
+        let discovered = vec!["exec-3".to_string(), "exec-4".to_string()];
+        let merged = merge_session_targets(&discovered, &mut meta, 3);
+        assert_eq!(merged.len(), 3);
+        assert!(merged.contains(&"interactive".to_string()));
+        assert!(merged.contains(&"exec-4".to_string()));
+
+        let unlimited = merge_session_targets(&discovered, &mut meta, 0);
+        assert_eq!(unlimited.len(), 5);
+    }
+}
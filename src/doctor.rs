@@ -0,0 +1,332 @@
+//! `codex-shimmer doctor`: checks the usual reasons the widget stays empty and
+//! says how to fix each one.
+
+use std::{
+    fs::{self, OpenOptions},
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, SystemTime},
+};
+
+use dirs::config_dir;
+
+use crate::agent::Agent;
+use crate::config::{Settings, default_cache_file};
+use crate::discovery::Discovery;
+use crate::service::UNIT;
+use crate::waybar;
+
+/// A session log older than this suggests the daemon is looking in the wrong
+/// place.
+const STALE_AFTER: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug)]
+pub struct Check {
+    pub status: Status,
+    pub summary: String,
+    /// What to do about a warning or failure.
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(summary: impl Into<String>) -> Self {
+        Check {
+            status: Status::Ok,
+            summary: summary.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(summary: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check {
+            status: Status::Warn,
+            summary: summary.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(summary: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check {
+            status: Status::Fail,
+            summary: summary.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+/// Run every check against the resolved settings.
+pub fn run(settings: &Settings) -> Vec<Check> {
+    let mut checks = Vec::new();
+    if settings.agent == Agent::Codex
+        && settings.discovery == Discovery::History
+        && settings.session_file.is_none()
+    {
+        for path in settings.session_source().history_paths() {
+            checks.push(codex_home(path));
+            checks.push(history(path));
+        }
+    }
+    checks.push(recent_session(settings));
+    checks.extend(
+        cache_paths(settings)
+            .iter()
+            .map(|path| cache_writable(path)),
+    );
+    checks.push(waybar_module(
+        config_dir().map(|dir| dir.join("waybar")).as_deref(),
+    ));
+    checks.push(service());
+    checks
+}
+
+/// Print the checks, returning whether none failed.
+pub fn report(checks: &[Check]) -> bool {
+    for check in checks {
+        let label = match check.status {
+            Status::Ok => "ok  ",
+            Status::Warn => "warn",
+            Status::Fail => "FAIL",
+        };
+        println!("{label}  {}", check.summary);
+        if let Some(fix) = &check.fix {
+            for line in fix.lines() {
+                println!("      {line}");
+            }
+        }
+    }
+    checks.iter().all(|check| check.status != Status::Fail)
+}
+
+fn codex_home(history_path: &Path) -> Check {
+    let Some(home) = history_path.parent() else {
+        return Check::fail(
+            format!("{} has no parent directory", history_path.display()),
+            "Pass --history-path with the full path to Codex's history.jsonl.",
+        );
+    };
+    if home.is_dir() {
+        Check::ok(format!("{} exists", home.display()))
+    } else {
+        Check::fail(
+            format!("{} does not exist", home.display()),
+            "Run `codex` once so it creates its home directory. If Codex keeps its files \
+             elsewhere, export CODEX_HOME for the daemon (in its unit's environment) too.",
+        )
+    }
+}
+
+fn history(path: &Path) -> Check {
+    match fs::File::open(path) {
+        Ok(_) => Check::ok(format!("{} is readable", path.display())),
+        Err(err) => Check::fail(
+            format!("{} is not readable: {err}", path.display()),
+            "Start a Codex session so the history file is written, and check its permissions.",
+        ),
+    }
+}
+
+/// Find the newest session the way the daemon does and check its log is
+/// there and recent.
+fn recent_session(settings: &Settings) -> Check {
+    let log = match &settings.session_file {
+        Some(path) => path.clone(),
+        None => {
+            let source = settings.session_source();
+            let root = source.sessions_root.display();
+            let newest = match source.recent(1) {
+                Ok(ids) => ids.into_iter().last(),
+                Err(err) => {
+                    return Check::fail(
+                        format!("Sessions could not be listed: {err:#}"),
+                        format!(
+                            "Check that {root} exists and is readable, or pass --sessions-root."
+                        ),
+                    );
+                }
+            };
+            let Some(session_id) = newest else {
+                return Check::warn(
+                    format!("No sessions found for {:?}", settings.agent),
+                    "Start a session; the widget shows a placeholder until one exists.",
+                );
+            };
+            match source.find(&session_id) {
+                Ok(Some(path)) => path,
+                Ok(None) | Err(_) => {
+                    return Check::fail(
+                        format!("Session {session_id} has no log under {root}"),
+                        "Point --sessions-root at the directory holding the session logs.",
+                    );
+                }
+            }
+        }
+    };
+    let modified = fs::metadata(&log).and_then(|meta| meta.modified());
+    match modified {
+        Ok(modified) if is_recent(modified, SystemTime::now()) => {
+            Check::ok(format!("Newest session log is {}", log.display()))
+        }
+        Ok(_) => Check::warn(
+            format!("Newest session log {} is over a week old", log.display()),
+            "If you used the agent since, --sessions-root or --history-path points at an old \
+             location.",
+        ),
+        Err(err) => Check::fail(
+            format!("{} is not readable: {err}", log.display()),
+            "Check the path given with --session-file and its permissions.",
+        ),
+    }
+}
+
+fn is_recent(modified: SystemTime, now: SystemTime) -> bool {
+    now.duration_since(modified)
+        .is_ok_and(|age| age <= STALE_AFTER)
+}
+
+/// The configured cache files, or the default one.
+fn cache_paths(settings: &Settings) -> Vec<PathBuf> {
+    let configured: Vec<PathBuf> = [
+        &settings.cache_file,
+        &settings.cache_reasoning,
+        &settings.cache_status,
+        &settings.cache_tokens,
+    ]
+    .into_iter()
+    .flatten()
+    .cloned()
+    .collect();
+    if configured.is_empty() {
+        default_cache_file().into_iter().collect()
+    } else {
+        configured
+    }
+}
+
+/// Write and remove a scratch file next to `path`, leaving the cache alone.
+fn cache_writable(path: &Path) -> Check {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let probe = dir.join(format!(".codex-shimmer-doctor-{}", std::process::id()));
+    let written = fs::create_dir_all(dir).and_then(|()| {
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&probe)
+            .map(drop)
+    });
+    let _ = fs::remove_file(&probe);
+    match written {
+        Ok(()) => Check::ok(format!("{} is writable", dir.display())),
+        Err(err) => Check::fail(
+            format!("Cannot write the cache file {}: {err}", path.display()),
+            format!(
+                "Create {} with write permission for your user, or choose another --cache-file.",
+                dir.display()
+            ),
+        ),
+    }
+}
+
+fn waybar_module(waybar_dir: Option<&Path>) -> Check {
+    let config = waybar_dir.and_then(waybar::find_config);
+    let Some(config) = config else {
+        return Check::warn(
+            "No Waybar config found",
+            "Add the module from examples/waybar-config-snippet.jsonc to your Waybar config.",
+        );
+    };
+    match fs::read_to_string(&config) {
+        Ok(text) if references_module(&text) => {
+            Check::ok(format!("{} references the module", config.display()))
+        }
+        Ok(_) => Check::fail(
+            format!("{} does not reference the module", config.display()),
+            "Add \"cffi/codex_shimmer\" to a modules list and its settings block from \
+             examples/waybar-config-snippet.jsonc, then restart Waybar.",
+        ),
+        Err(err) => Check::fail(
+            format!("{} is not readable: {err}", config.display()),
+            "Check the permissions of the Waybar config.",
+        ),
+    }
+}
+
+/// Either the CFFI module or a `custom` module running the binary.
+fn references_module(config: &str) -> bool {
+    config.contains("codex_shimmer") || config.contains("codex-shimmer")
+}
+
+fn service() -> Check {
+    let output = Command::new("systemctl")
+        .args(["--user", "is-active", UNIT])
+        .output();
+    match output {
+        Ok(output) => match String::from_utf8_lossy(&output.stdout).trim() {
+            "active" => Check::ok(format!("{UNIT} is active")),
+            // No user manager to ask, e.g. over SSH or in a container.
+            "" => Check::warn(
+                format!(
+                    "Could not ask systemd about {UNIT}: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+                "Run doctor from your desktop session, or start `codex-shimmer` from your \
+                 compositor's autostart.",
+            ),
+            state => Check::fail(
+                format!("{UNIT} is {state}"),
+                format!(
+                    "systemctl --user enable --now {UNIT}\n\
+                     journalctl --user -u codex-shimmer -p warning   # if it keeps stopping"
+                ),
+            ),
+        },
+        Err(err) => Check::warn(
+            format!("Could not ask systemd about {UNIT}: {err}"),
+            "Without systemd, start `codex-shimmer` from your compositor's autostart.",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use tempfile::tempdir;
+
+    #[test]
+    fn waybar_config_must_name_the_module() -> Result<()> {
+        let dir = tempdir()?;
+        assert_eq!(waybar_module(Some(dir.path())).status, Status::Warn);
+
+        fs::write(dir.path().join("config"), r#"{"modules-left": ["clock"]}"#)?;
+        assert_eq!(waybar_module(Some(dir.path())).status, Status::Fail);
+
+        fs::write(
+            dir.path().join("config.jsonc"),
+            r#"{"modules-left": ["cffi/codex_shimmer"]}"#,
+        )?;
+        let check = waybar_module(Some(dir.path()));
+        assert_eq!(check.status, Status::Ok);
+        assert!(check.summary.contains("config.jsonc"));
+        Ok(())
+    }
+
+    #[test]
+    fn cache_check_leaves_no_files_behind() -> Result<()> {
+        let dir = tempdir()?;
+        let cache = dir.path().join("nested").join("latest.json");
+        assert_eq!(cache_writable(&cache).status, Status::Ok);
+        assert_eq!(fs::read_dir(dir.path().join("nested"))?.count(), 0);
+
+        let now = SystemTime::now();
+        assert!(is_recent(now - Duration::from_secs(60), now));
+        assert!(!is_recent(now - STALE_AFTER * 2, now));
+        Ok(())
+    }
+}
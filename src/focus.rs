@@ -0,0 +1,154 @@
+//! Following the focused window, so the bar shows the session of the project
+//! being looked at. The compositor reports the focused window's process, and
+//! the working directory comes from the newest process below it: in a
+//! terminal, the shell or the agent running in it.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{Result, bail};
+use serde::Deserialize;
+use tokio::{sync::mpsc, task};
+use tracing::{debug, warn};
+
+use crate::daemon::DaemonMessage;
+use crate::sway;
+
+mod hyprland;
+
+/// The compositor asked about the focused window.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Compositor {
+    Hyprland,
+    Sway,
+}
+
+impl FromStr for Compositor {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "hyprland" => Ok(Compositor::Hyprland),
+            "sway" => Ok(Compositor::Sway),
+            other => bail!("unknown compositor `{other}` (expected `hyprland` or `sway`)"),
+        }
+    }
+}
+
+/// Tell the daemon the working directory of every newly focused window until
+/// the compositor's IPC goes away.
+pub async fn follow(compositor: Compositor, tx: mpsc::Sender<DaemonMessage>) {
+    let (pid_tx, mut pids) = mpsc::channel(8);
+    let watcher = match compositor {
+        Compositor::Hyprland => tokio::spawn(hyprland::watch(pid_tx)),
+        Compositor::Sway => tokio::spawn(sway::watch_focus(pid_tx)),
+    };
+    let mut focused = None;
+    while let Some(pid) = pids.recv().await {
+        let cwd = match pid {
+            Some(pid) => task::spawn_blocking(move || window_cwd(pid))
+                .await
+                .ok()
+                .flatten(),
+            None => None,
+        };
+        if cwd == focused {
+            continue;
+        }
+        debug!(cwd = ?cwd, "Focused window changed directory");
+        focused = cwd.clone();
+        if tx.send(DaemonMessage::Focus(cwd)).await.is_err() {
+            return;
+        }
+    }
+    if let Ok(Err(err)) = watcher.await {
+        warn!(error = %format!("{err:#}"), "Stopped following the focused window");
+    }
+}
+
+/// Whether a window focused in `focused` shows the project of a session
+/// started in `session_cwd`.
+pub fn in_project(focused: &Path, session_cwd: &str) -> bool {
+    focused.starts_with(session_cwd)
+}
+
+/// The working directory of the newest process below `pid`, or of `pid`
+/// itself when it has no children.
+fn window_cwd(pid: u32) -> Option<PathBuf> {
+    let children = child_processes(Path::new("/proc"));
+    let pid = newest_descendant(pid, &children);
+    fs::read_link(format!("/proc/{pid}/cwd")).ok()
+}
+
+/// Child pids of every process, with their start times.
+fn child_processes(proc: &Path) -> HashMap<u32, Vec<(u64, u32)>> {
+    let mut children: HashMap<u32, Vec<(u64, u32)>> = HashMap::new();
+    let Ok(entries) = fs::read_dir(proc) else {
+        return children;
+    };
+    for entry in entries.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse().ok())
+        else {
+            continue;
+        };
+        let Some((parent, started)) = fs::read_to_string(entry.path().join("stat"))
+            .ok()
+            .and_then(|stat| parse_stat(&stat))
+        else {
+            continue;
+        };
+        children.entry(parent).or_default().push((started, pid));
+    }
+    children
+}
+
+/// The parent pid and start time from `/proc/<pid>/stat`. The command name
+/// in parentheses may hold spaces and parentheses itself.
+fn parse_stat(stat: &str) -> Option<(u32, u64)> {
+    let (_, fields) = stat.rsplit_once(')')?;
+    let fields: Vec<&str> = fields.split_whitespace().collect();
+    Some((fields.get(1)?.parse().ok()?, fields.get(19)?.parse().ok()?))
+}
+
+/// Follow the most recently started child down from `pid`.
+fn newest_descendant(mut pid: u32, children: &HashMap<u32, Vec<(u64, u32)>>) -> u32 {
+    // Guards against a cycle from pids reused while /proc was read.
+    for _ in 0..children.len() {
+        match children.get(&pid).and_then(|kids| kids.iter().max()) {
+            Some((_, child)) => pid = *child,
+            None => break,
+        }
+    }
+    pid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn focused_window_resolves_to_its_newest_descendant() {
+        let stat = "4242 (tmux: client (1)) S 4100 4242 4100 0 -1 4194560 1 0 0 0 0 0 0 0 \
+                    20 0 1 0 98765 1000 100";
+        assert_eq!(parse_stat(stat), Some((4100, 98765)));
+        assert_eq!(parse_stat("garbage"), None);
+
+        // terminal 10 -> shells 11 and 12 (newer) -> codex 13 under shell 12
+        let children = HashMap::from([(10, vec![(500, 11), (700, 12)]), (12, vec![(900, 13)])]);
+        assert_eq!(newest_descendant(10, &children), 13);
+        assert_eq!(newest_descendant(11, &children), 11);
+
+        let focused = Path::new("/home/me/src/shimmer/src");
+        assert!(in_project(focused, "/home/me/src/shimmer"));
+        assert!(!in_project(focused, "/home/me/src/shim"));
+        assert!(!in_project(Path::new("/home/me"), "/home/me/src/shimmer"));
+    }
+}
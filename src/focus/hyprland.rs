@@ -0,0 +1,82 @@
+//! Hyprland's IPC: `.socket.sock` answers one request per connection and
+//! `.socket2.sock` streams events, both under
+//! `$XDG_RUNTIME_DIR/hypr/$HYPRLAND_INSTANCE_SIGNATURE`.
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use serde_json::Value;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::UnixStream,
+    sync::mpsc,
+};
+
+/// Send the pid of the focused window, or `None` without one, now and on
+/// every focus change.
+pub async fn watch(pids: mpsc::Sender<Option<u32>>) -> Result<()> {
+    let dir = socket_dir()?;
+    let events = UnixStream::connect(dir.join(".socket2.sock"))
+        .await
+        .with_context(|| format!("Failed to connect to Hyprland in {}", dir.display()))?;
+    let mut lines = BufReader::new(events).lines();
+    loop {
+        if pids.send(active_window_pid(&dir).await?).await.is_err() {
+            return Ok(());
+        }
+        // Also sent when switching to a workspace, or an empty one.
+        loop {
+            let Some(line) = lines.next_line().await? else {
+                bail!("Hyprland closed its event socket");
+            };
+            if line.starts_with("activewindowv2>>") {
+                break;
+            }
+        }
+    }
+}
+
+/// Newer Hyprland keeps its sockets in the runtime dir, older in `/tmp`.
+fn socket_dir() -> Result<PathBuf> {
+    let instance = env::var_os("HYPRLAND_INSTANCE_SIGNATURE")
+        .context("HYPRLAND_INSTANCE_SIGNATURE is not set; is Hyprland running?")?;
+    let runtime = env::var_os("XDG_RUNTIME_DIR")
+        .map(|dir| PathBuf::from(dir).join("hypr").join(&instance))
+        .filter(|dir| dir.is_dir());
+    Ok(runtime.unwrap_or_else(|| Path::new("/tmp/hypr").join(instance)))
+}
+
+async fn active_window_pid(dir: &Path) -> Result<Option<u32>> {
+    let mut stream = UnixStream::connect(dir.join(".socket.sock"))
+        .await
+        .context("Failed to query Hyprland")?;
+    stream.write_all(b"j/activewindow").await?;
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply).await?;
+    Ok(window_pid(&reply))
+}
+
+/// Hyprland answers `{}` when no window has focus.
+fn window_pid(reply: &[u8]) -> Option<u32> {
+    let window: Value = serde_json::from_slice(reply).ok()?;
+    window["pid"]
+        .as_u64()
+        .and_then(|pid| u32::try_from(pid).ok())
+        .filter(|pid| *pid > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn active_window_reply_gives_its_pid() {
+        let reply = br#"{"address": "0x55d1", "class": "foot", "title": "codex", "pid": 4242}"#;
+        assert_eq!(window_pid(reply), Some(4242));
+        assert_eq!(window_pid(b"{}"), None);
+        assert_eq!(window_pid(b"Invalid"), None);
+    }
+}
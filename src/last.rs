@@ -0,0 +1,226 @@
+//! `codex-shimmer last`: the most recently rendered events, read back from the
+//! session logs, for scripts and `on-click` handlers.
+
+use std::fmt::Write;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::config::Settings;
+use crate::discovery::session_logs;
+use crate::output::WaybarOutput;
+use crate::render::{RenderedEvent, parse_timestamp};
+use crate::session::SessionRenderer;
+use crate::tailer::read_log;
+
+/// One rendered event.
+#[derive(Debug, Clone, Serialize)]
+pub struct Recent {
+    pub session_id: String,
+    pub timestamp: Option<String>,
+    /// Kind of the log entry, such as `agent_reasoning` or `exec_command_begin`.
+    pub kind: String,
+    /// The full reasoning or message, redacted, or the label for other events.
+    pub text: String,
+    pub payload: WaybarOutput,
+}
+
+impl Recent {
+    /// The entry for `event`, just rendered by `renderer`.
+    pub fn new(session_id: &str, renderer: &SessionRenderer, event: RenderedEvent) -> Self {
+        Recent {
+            session_id: session_id.to_string(),
+            timestamp: event.timestamp,
+            kind: renderer.event_type().unwrap_or_default().to_string(),
+            text: renderer
+                .text()
+                .map_or_else(|| event.payload.text.clone(), str::to_string),
+            payload: event.payload,
+        }
+    }
+}
+
+/// The last `count` events rendered from the tracked sessions, or from the
+/// one asked for by id or file, oldest first.
+pub fn recent(settings: &Settings, count: usize) -> Result<Vec<Recent>> {
+    let options = settings.render_options();
+    let mut events = Vec::new();
+    for (session_id, parts) in session_logs(settings, settings.session_window)? {
+        if settings.session_id.is_none() && settings.ignore.session(&session_id) {
+            continue;
+        }
+        let mut renderer = SessionRenderer::new(&session_id);
+        let mut rendered = Vec::new();
+        for part in parts {
+            for line in read_log(&part, settings.max_line_bytes)? {
+                let Some(event) = renderer.render_line(&line, &options)? else {
+                    continue;
+                };
+                rendered.push(Recent::new(&session_id, &renderer, event));
+            }
+        }
+        // Only the last `count` of each session can make the cut.
+        let skip = rendered.len().saturating_sub(count);
+        events.extend(rendered.into_iter().skip(skip));
+    }
+    // Events from before a session's first timestamp come first, then the
+    // rest by instant, with unreadable timestamps last. Stable, so ties keep
+    // their log order.
+    events.sort_by_cached_key(|event| {
+        event
+            .timestamp
+            .as_deref()
+            .map(|timestamp| parse_timestamp(timestamp).ok_or_else(|| timestamp.to_string()))
+    });
+    let skip = events.len().saturating_sub(count);
+    Ok(events.split_off(skip))
+}
+
+/// The newest reasoning across the tracked sessions, or in the one asked for,
+/// in full.
+pub fn latest_reasoning(settings: &Settings) -> Result<Option<Recent>> {
+    Ok(recent(settings, usize::MAX)?
+        .into_iter()
+        .rev()
+        .find(|event| event.kind == "agent_reasoning"))
+}
+
+/// An event as `last` prints it: the time, the session, and the first line
+/// of the text.
+pub fn format_recent(event: &Recent) -> String {
+    let mut out = String::new();
+    if let Some(timestamp) = &event.timestamp {
+        let _ = write!(out, "{timestamp}  ");
+    }
+    let _ = write!(
+        out,
+        "{}  {}",
+        event.session_id,
+        event.text.lines().next().unwrap_or_default()
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn recent_events_interleave_sessions_by_time() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let history_path = dir.path().join("history.jsonl");
+        let sessions_root = dir.path().join("sessions");
+        std::fs::create_dir_all(&sessions_root)?;
+        std::fs::write(
+            &history_path,
+            "{\"session_id\":\"alpha\"}\n{\"session_id\":\"beta\"}\n",
+        )?;
+        for (id, steps) in [
+            (
+                "alpha",
+                [("12:00:00", "Alpha one"), ("12:02:00", "Alpha two")],
+            ),
+            ("beta", [("12:01:00", "Beta one"), ("12:03:00", "Beta two")]),
+        ] {
+            let lines: String = steps
+                .iter()
+                .map(|(time, text)| {
+                    let line = serde_json::json!({
+                        "timestamp": format!("2025-10-29T{time}Z"),
+                        "type": "event_msg",
+                        "payload": { "type": "agent_reasoning", "text": format!("**{text}**\n\nDetails.") }
+                    });
+                    format!("{line}\n")
+                })
+                .collect();
+            std::fs::write(sessions_root.join(format!("rollout-{id}.jsonl")), lines)?;
+        }
+
+        let settings = Settings::resolve(&Config {
+            history_path: Some(history_path),
+            sessions_root: Some(sessions_root),
+            stdout: Some(true),
+            ..Default::default()
+        })?;
+        let events = recent(&settings, 3)?;
+        let printed: Vec<String> = events.iter().map(format_recent).collect();
+        assert_eq!(
+            printed,
+            [
+                "2025-10-29T12:01:00Z  beta  **Beta one**",
+                "2025-10-29T12:02:00Z  alpha  **Alpha two**",
+                "2025-10-29T12:03:00Z  beta  **Beta two**",
+            ]
+        );
+        assert_eq!(events[2].kind, "agent_reasoning");
+        assert_eq!(events[2].text, "**Beta two**\n\nDetails.");
+        assert_eq!(
+            latest_reasoning(&settings)?.map(|event| event.session_id),
+            Some("beta".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn events_without_readable_timestamps_still_sort() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let history_path = dir.path().join("history.jsonl");
+        let sessions_root = dir.path().join("sessions");
+        std::fs::create_dir_all(&sessions_root)?;
+        std::fs::write(
+            &history_path,
+            "{\"session_id\":\"alpha\"}\n{\"session_id\":\"beta\"}\n",
+        )?;
+        let line = |timestamp: Option<&str>, text: &str| {
+            let mut line = serde_json::json!({
+                "type": "event_msg",
+                "payload": { "type": "agent_reasoning", "text": format!("**{text}**") }
+            });
+            if let Some(timestamp) = timestamp {
+                line["timestamp"] = timestamp.into();
+            }
+            format!("{line}\n")
+        };
+        std::fs::write(
+            sessions_root.join("rollout-alpha.jsonl"),
+            [
+                line(None, "Alpha untimed"),
+                line(Some("2025-10-29T12:02:00Z"), "Alpha timed"),
+            ]
+            .concat(),
+        )?;
+        std::fs::write(
+            sessions_root.join("rollout-beta.jsonl"),
+            [
+                line(Some("yesterday"), "Beta unreadable"),
+                line(Some("2025-10-29T12:01:00Z"), "Beta timed"),
+                line(None, "Beta untimed"),
+            ]
+            .concat(),
+        )?;
+
+        let settings = Settings::resolve(&Config {
+            history_path: Some(history_path),
+            sessions_root: Some(sessions_root),
+            stdout: Some(true),
+            ..Default::default()
+        })?;
+        let texts: Vec<String> = recent(&settings, usize::MAX)?
+            .into_iter()
+            .map(|event| event.text)
+            .collect();
+        assert_eq!(
+            texts,
+            [
+                "**Alpha untimed**",
+                // Untimed lines take the timestamp before them.
+                "**Beta timed**",
+                "**Beta untimed**",
+                "**Alpha timed**",
+                "**Beta unreadable**",
+            ]
+        );
+        Ok(())
+    }
+}
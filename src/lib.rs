@@ -0,0 +1,51 @@
+//! Tail Codex CLI rollout logs (or Claude Code transcripts) and render the latest activity as Waybar JSON.
+//!
+//! The `codex-shimmer` binary is a thin CLI over these modules; other bar tools
+//! can embed the tailer, session discovery, and rendering pieces directly.
+
+pub mod agent;
+pub mod alert;
+pub mod classify;
+pub mod clipboard;
+pub mod config;
+pub mod container;
+pub mod control;
+pub mod daemon;
+#[cfg(feature = "dbus")]
+pub mod dbus;
+pub mod demo;
+pub mod discovery;
+pub mod doctor;
+pub mod focus;
+pub mod last;
+pub mod logging;
+pub mod metrics;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+pub mod open;
+#[cfg(feature = "otel")]
+pub mod otel;
+pub mod output;
+pub mod record;
+pub mod redact;
+pub mod render;
+pub mod replay;
+pub mod rewrite;
+pub mod search;
+pub mod service;
+pub mod session;
+pub mod sink;
+#[cfg(feature = "store")]
+pub mod stats;
+pub mod statsd;
+#[cfg(feature = "store")]
+pub mod store;
+pub mod sway;
+pub mod tailer;
+pub mod template;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod uninstall;
+pub mod watch;
+pub mod waybar;
+pub mod webhook;
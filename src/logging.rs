@@ -0,0 +1,59 @@
+//! Where operational messages go: the systemd journal, with each field as a
+//! journal field, when the daemon runs as a service, and stderr otherwise;
+//! optionally also a log file rotated daily.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{EnvFilter, fmt, prelude::*};
+
+/// Rotated log files kept next to the current one.
+const MAX_LOG_FILES: usize = 7;
+const DEFAULT_LEVEL: &str = "info";
+
+/// Install the global subscriber. `level` is a level such as `debug` or a
+/// full filter such as `codex_shimmer::discovery=trace`; without it `RUST_LOG`
+/// applies, and without that `info`.
+///
+/// The journal is used when systemd connected stderr to it (`JOURNAL_STREAM`
+/// is set), so `journalctl --user -u codex-shimmer SESSION_ID=…` can filter on
+/// fields.
+pub fn init(level: Option<&str>, file: Option<&Path>) -> Result<()> {
+    let filter = match level {
+        Some(level) => {
+            EnvFilter::try_new(level).with_context(|| format!("Invalid log level `{level}`"))?
+        }
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LEVEL)),
+    };
+    let journal = std::env::var_os("JOURNAL_STREAM")
+        .and_then(|_| tracing_journald::layer().ok())
+        .map(|layer| layer.with_field_prefix(None));
+    let stderr = journal.is_none().then(|| {
+        fmt::layer()
+            .with_writer(std::io::stderr)
+            .without_time()
+            .with_target(false)
+    });
+    let file = file
+        .map(|path| -> Result<_> {
+            let name = path
+                .file_name()
+                .with_context(|| format!("Log file {} has no file name", path.display()))?;
+            let appender = RollingFileAppender::builder()
+                .rotation(Rotation::DAILY)
+                .filename_prefix(name.to_string_lossy())
+                .max_log_files(MAX_LOG_FILES)
+                .build(path.parent().unwrap_or(Path::new(".")))
+                .with_context(|| format!("Failed to open log file {}", path.display()))?;
+            Ok(fmt::layer().with_writer(appender).with_ansi(false))
+        })
+        .transpose()?;
+    let _ = tracing_subscriber::registry()
+        .with(filter)
+        .with(journal)
+        .with(stderr)
+        .with(file)
+        .try_init();
+    Ok(())
+}
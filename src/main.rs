@@ -1,28 +1,66 @@
 use std::{
-    collections::{HashMap, HashSet, hash_map::Entry},
-    fs::{self, File},
-    io::{self, BufRead, BufReader, ErrorKind, Seek, SeekFrom, Write},
+    ffi::OsString,
+    net::SocketAddr,
     path::{Path, PathBuf},
-    process::Command,
-    thread,
-    time::{Duration, Instant},
+    time::Duration,
 };
 
-use anyhow::{Context, Result};
-use clap::Parser;
-use dirs::home_dir;
-use glob::glob;
-use libc;
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use anyhow::{Context, Result, bail};
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, parser::ValueSource};
+
+use codex_shimmer::agent::Agent;
+use codex_shimmer::alert::{AlertKind, QuietHours};
+use codex_shimmer::classify::ClassRule;
+use codex_shimmer::clipboard;
+use codex_shimmer::config::{Config, ConfigWatcher, default_config_path};
+use codex_shimmer::control::{ControlCommand, send_command};
+use codex_shimmer::daemon;
+use codex_shimmer::demo;
+use codex_shimmer::discovery::Discovery;
+use codex_shimmer::doctor;
+use codex_shimmer::focus::Compositor;
+use codex_shimmer::last;
+use codex_shimmer::logging;
+use codex_shimmer::open;
+use codex_shimmer::output::{
+    PayloadVersion, placeholder_payload, print_cache, write_payload_to_stdout,
+};
+use codex_shimmer::render::{
+    DisplayMode, ElapsedDisplay, Markup, PercentageSource, RendererKind, TimestampStyle,
+};
+use codex_shimmer::replay::{self, Pacing};
+use codex_shimmer::rewrite::{PhaseAliasRule, RewriteRule};
+use codex_shimmer::search;
+use codex_shimmer::service::{self, Action};
+use codex_shimmer::sway::BarMode;
+use codex_shimmer::template::Template;
+use codex_shimmer::uninstall::{self, Layout};
+use codex_shimmer::waybar;
+use regex::{Regex, RegexBuilder};
+use tokio::task::JoinHandle;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Publish Codex reasoning updates for Waybar")]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Print the contents of a cache file once and exit
     #[arg(long)]
     print_cache: Option<PathBuf>,
 
+    /// With --print-cache, mark the payload stale when the daemon has not touched the cache for N seconds (it does every 30 while running)
+    #[arg(long, requires = "print_cache")]
+    stale_after: Option<u64>,
+
+    /// Render the latest event of the newest session, print it, and exit
+    #[arg(long)]
+    once: bool,
+
+    /// Config file to load (defaults to ~/.config/codex-shimmer/config.toml)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
     /// Explicit session log file to read (skip auto-discovery)
     #[arg(long)]
     session_file: Option<PathBuf>,
@@ -31,1051 +69,1001 @@ struct Args {
     #[arg(long)]
     session_id: Option<String>,
 
-    /// Path to Codex history.jsonl (defaults to ~/.codex/history.jsonl)
+    /// Path to Codex history.jsonl (defaults to $CODEX_HOME/history.jsonl, or ~/.codex/history.jsonl)
     #[arg(long)]
     history_path: Option<PathBuf>,
 
-    /// Root of Codex sessions directory (defaults to ~/.codex/sessions)
+    /// Root of Codex sessions directory (defaults to $CODEX_HOME/sessions, or ~/.codex/sessions)
     #[arg(long)]
     sessions_root: Option<PathBuf>,
 
-    /// Poll interval in milliseconds while tailing
-    #[arg(long, default_value_t = 250)]
-    poll_ms: u64,
-
-    /// Re-check history for a fresher session every N seconds
-    #[arg(long, default_value_t = 5)]
-    session_refresh_secs: u64,
-
-    /// Track up to N recent Codex sessions concurrently
-    #[arg(long, default_value_t = 4)]
-    session_window: usize,
+    /// Codex home to follow instead of the default, as `NAME=PATH` or `PATH`; repeat to merge several, each payload getting a `profile-NAME` class
+    #[arg(long)]
+    codex_home: Vec<String>,
 
-    /// Maximum characters to emit for the Waybar label
-    #[arg(long, default_value_t = 120)]
-    max_chars: usize,
+    /// Codex home inside a docker or podman container, as `ENGINE:CONTAINER[:PATH]` with PATH defaulting to /root/.codex; repeat for several, each payload getting a `profile-CONTAINER` class
+    #[arg(long)]
+    container: Vec<String>,
 
-    /// Custom Waybar signal number to emit after each cache refresh (enable with module `signal:`)
+    /// Agent whose logs to follow: `codex`, `claude` (Claude Code), or `aider` [default: codex]
     #[arg(long)]
-    waybar_signal: Option<u8>,
+    agent: Option<Agent>,
 
-    /// Write the most recent payload to the specified cache file
+    /// How Codex sessions are found: `history` (history.jsonl) or `sessions` (the newest logs under --sessions-root, including sessions not in history yet) [default: history]
     #[arg(long)]
-    cache_file: Option<PathBuf>,
+    discovery: Option<Discovery>,
 
-    /// Replay the entire log from the beginning instead of tailing new entries
+    /// Only show sessions whose model matches this glob, such as `gpt-5*`; repeatable
     #[arg(long)]
-    start_at_beginning: bool,
-}
+    model_filter: Vec<String>,
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-struct WaybarOutput {
-    text: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    tooltip: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    alt: Option<String>,
-    #[serde(skip_serializing_if = "Vec::is_empty")]
-    class: Vec<String>,
-}
+    /// Never track this session id, or sessions running in directories matching this glob when it contains a `/` (e.g. `~/batch/*`); repeatable
+    #[arg(long)]
+    ignore: Vec<String>,
 
-#[derive(Debug, Clone)]
-struct RenderedEvent {
-    payload: WaybarOutput,
-    timestamp: Option<String>,
-}
+    /// File of entries like --ignore, one per line; `#` starts a comment
+    #[arg(long)]
+    ignore_file: Option<PathBuf>,
 
-#[derive(Debug, Clone)]
-struct SessionEvent {
-    session_id: String,
-    event: RenderedEvent,
-}
+    /// Prefer the sessions started in the focused window's working directory, as reported by `hyprland` or `sway`
+    #[arg(long)]
+    follow_focus: Option<Compositor>,
 
-#[derive(Debug)]
-struct SessionState {
-    path: PathBuf,
-    offset: u64,
-}
+    /// Switch swaybar to this mode (`dock`, `hide`, `invisible`, or `overlay`) while an approval is pending, then back
+    #[arg(long)]
+    sway_approval_bar: Option<BarMode>,
 
-#[derive(Debug, Clone)]
-struct SessionMeta {
-    last_event_timestamp: Option<String>,
-    last_event_instant: Instant,
-    missing_count: u32,
-}
+    /// Log files for `--agent generic`, relative to --sessions-root [default: **/*.jsonl]
+    #[arg(long)]
+    log_glob: Option<String>,
 
-impl SessionMeta {
-    fn new() -> Self {
-        Self {
-            last_event_timestamp: None,
-            last_event_instant: Instant::now(),
-            missing_count: 0,
-        }
-    }
+    /// JSONPath-like selector of the timestamp for `--agent generic` [default: timestamp]
+    #[arg(long)]
+    timestamp_path: Option<String>,
 
-    fn record_event(&mut self, timestamp: Option<String>) {
-        self.last_event_timestamp = timestamp;
-        self.last_event_instant = Instant::now();
-        self.missing_count = 0;
-    }
+    /// Selector of the event kind for `--agent generic` [default: type]
+    #[arg(long)]
+    kind_path: Option<String>,
 
-    fn mark_miss(&mut self) {
-        self.missing_count = self.missing_count.saturating_add(1);
-    }
-}
+    /// Selector of the displayed text for `--agent generic` [default: text]
+    #[arg(long)]
+    text_path: Option<String>,
 
-#[cfg(any(target_os = "linux", target_os = "android"))]
-fn ignore_realtime_signals() {
-    unsafe {
-        let base = libc::SIGRTMIN();
-        if base <= 0 {
-            return;
-        }
-        for offset in 0..16 {
-            let sig = base + offset;
-            libc::signal(sig, libc::SIG_IGN);
-        }
-    }
-}
+    /// Event kind to show with `--agent generic`; repeatable [default: all]
+    #[arg(long)]
+    event_kinds: Vec<String>,
 
-#[cfg(not(any(target_os = "linux", target_os = "android")))]
-fn ignore_realtime_signals() {}
+    /// Event kind shown as a final reply rather than reasoning; repeatable
+    #[arg(long)]
+    message_kinds: Vec<String>,
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+    /// Poll interval in milliseconds while tailing [default: 250]
+    #[arg(long)]
+    poll_ms: Option<u64>,
 
-    ignore_realtime_signals();
+    /// Longest poll interval a session backs off to after a quiet spell [default: 10000]
+    #[arg(long)]
+    max_poll_ms: Option<u64>,
 
-    if let Some(cache_path) = &args.print_cache {
-        return print_cache(cache_path);
-    }
+    /// Re-check history for a fresher session every N seconds [default: 5]
+    #[arg(long)]
+    session_refresh_secs: Option<u64>,
 
-    let waybar_signal = args.waybar_signal;
+    /// Start tracking the N newest sessions on each refresh [default: 4]
+    #[arg(long)]
+    session_window: Option<usize>,
 
-    let cache_path = args
-        .cache_file
-        .as_deref()
-        .context("--cache-file is required unless --print-cache is used")?;
+    /// Track at most N sessions, dropping ended and then least recently active ones (0 for no limit) [default: 16]
+    #[arg(long)]
+    max_sessions: Option<usize>,
 
-    let history_path = args
-        .history_path
-        .unwrap_or(default_history_path().context("Unable to determine default history path")?);
-    let sessions_root = args
-        .sessions_root
-        .unwrap_or(default_sessions_root().context("Unable to determine default sessions path")?);
+    /// Keep at most N bytes of a log line, cutting long entries short [default: 262144]
+    #[arg(long)]
+    max_line_bytes: Option<usize>,
 
-    let poll_interval = Duration::from_millis(args.poll_ms.max(10));
-    let session_refresh_interval = Duration::from_secs(args.session_refresh_secs);
-    let mut last_session_refresh = Instant::now() - session_refresh_interval;
+    /// Show an idle payload after N seconds without Codex events (0 disables) [default: 0]
+    #[arg(long)]
+    idle_after_secs: Option<u64>,
 
-    let auto_discover = args.session_file.is_none() && args.session_id.is_none();
-    let mut tracked_sessions: Vec<String> = if auto_discover {
-        recent_session_ids(&history_path, args.session_window)?
-    } else {
-        match (&args.session_id, &args.session_file) {
-            (Some(id), _) => vec![id.clone()],
-            (None, Some(path)) => vec![
-                infer_session_id_from_path(path)
-                    .context("Failed to infer session id from --session-file")?,
-            ],
-            (None, None) => Vec::new(),
-        }
-    };
+    /// Write the payload at most once every N milliseconds, keeping the latest (0 disables) [default: 0]
+    #[arg(long)]
+    min_emit_interval_ms: Option<u64>,
 
-    let mut explicit_paths: HashMap<String, PathBuf> = HashMap::new();
-    if let Some(path) = &args.session_file {
-        if let Some(session_id) = tracked_sessions.get(0) {
-            explicit_paths.insert(session_id.clone(), path.clone());
-        }
-    }
+    /// Keep showing the current session unless another's event is N milliseconds newer (0 disables) [default: 1000]
+    #[arg(long)]
+    switch_hysteresis_ms: Option<u64>,
 
-    let mut session_states: HashMap<String, SessionState> = HashMap::new();
-    let mut session_meta: HashMap<String, SessionMeta> = HashMap::new();
-    let mut last_emitted: Option<SessionEvent> = None;
+    /// Maximum display columns of the Waybar label, ellipsis included [default: 120]
+    #[arg(long)]
+    max_chars: Option<usize>,
 
-    for session_id in &tracked_sessions {
-        session_meta
-            .entry(session_id.clone())
-            .or_insert_with(SessionMeta::new);
-    }
+    /// Appended to shortened labels; pass an empty string for none [default: …]
+    #[arg(long)]
+    ellipsis: Option<String>,
 
-    let snapshot = tracked_sessions.clone();
-    tracked_sessions = merge_session_targets(&snapshot, &mut session_meta, args.session_window);
-
-    bootstrap_sessions(
-        &mut session_states,
-        &mut session_meta,
-        &mut last_emitted,
-        &tracked_sessions,
-        &explicit_paths,
-        &sessions_root,
-        args.max_chars,
-        args.start_at_beginning,
-        cache_path,
-        waybar_signal,
-    )?;
+    /// Rewrite label text matching a regex before truncation, as `PATTERN=>REPLACEMENT`; repeatable, applied in order
+    #[arg(long, value_parser = parse_rewrite)]
+    rewrite: Vec<RewriteRule>,
 
-    loop {
-        if auto_discover && last_session_refresh.elapsed() >= session_refresh_interval {
-            let discovered = recent_session_ids(&history_path, args.session_window)?;
-            tracked_sessions =
-                merge_session_targets(&discovered, &mut session_meta, args.session_window);
-            last_session_refresh = Instant::now();
-        } else {
-            let snapshot = tracked_sessions.clone();
-            tracked_sessions =
-                merge_session_targets(&snapshot, &mut session_meta, args.session_window);
-        }
+    /// What the label shows: `activity`, `tokens` (context usage), or `summary` (a count of sessions) [default: activity]
+    #[arg(long)]
+    mode: Option<DisplayMode>,
 
-        if tracked_sessions.is_empty() {
-            thread::sleep(poll_interval);
-            continue;
-        }
+    /// What the payload `percentage` tracks: `auto` (plan progress, or context usage in tokens mode), `context`, `plan`, or `off` [default: auto]
+    #[arg(long)]
+    percentage: Option<PercentageSource>,
 
-        prune_untracked_sessions(&tracked_sessions, &mut session_states, &mut session_meta);
-
-        let mut newest_event: Option<SessionEvent> = None;
-
-        for session_id in &tracked_sessions {
-            match session_states.entry(session_id.clone()) {
-                Entry::Vacant(entry) => {
-                    let explicit = explicit_paths.get(session_id);
-                    if let Some((state, initial_event)) = initialize_session_state(
-                        session_id,
-                        explicit,
-                        &sessions_root,
-                        args.max_chars,
-                        args.start_at_beginning,
-                    )? {
-                        let meta_entry = session_meta
-                            .entry(session_id.clone())
-                            .or_insert_with(SessionMeta::new);
-                        if let Some(event) = initial_event {
-                            meta_entry.record_event(event.timestamp.clone());
-                            newest_event = select_newer_event(
-                                newest_event,
-                                SessionEvent {
-                                    session_id: session_id.clone(),
-                                    event,
-                                },
-                            );
-                        } else {
-                            meta_entry.missing_count = 0;
-                        }
-                        entry.insert(state);
-                    } else {
-                        session_meta
-                            .entry(session_id.clone())
-                            .or_insert_with(SessionMeta::new)
-                            .mark_miss();
-                    }
-                }
-                Entry::Occupied(mut entry) => {
-                    let mut reinitialize = false;
-                    {
-                        let state = entry.get_mut();
-                        match read_new_lines(&state.path, &mut state.offset) {
-                            Ok(lines) => {
-                                for line in lines {
-                                    match process_log_line(&line, args.max_chars) {
-                                        Ok(Some(event)) => {
-                                            let timestamp = event.timestamp.clone();
-                                            newest_event = select_newer_event(
-                                                newest_event,
-                                                SessionEvent {
-                                                    session_id: session_id.clone(),
-                                                    event,
-                                                },
-                                            );
-                                            session_meta
-                                                .entry(session_id.clone())
-                                                .or_insert_with(SessionMeta::new)
-                                                .record_event(timestamp);
-                                        }
-                                        Ok(None) => {}
-                                        Err(err) => {
-                                            eprintln!("Failed to process log entry: {err:?}");
-                                        }
-                                    }
-                                }
-                            }
-                            Err(err) if err.kind() == io::ErrorKind::NotFound => {
-                                reinitialize = true;
-                                session_meta
-                                    .entry(session_id.clone())
-                                    .or_insert_with(SessionMeta::new)
-                                    .mark_miss();
-                            }
-                            Err(err) => {
-                                eprintln!("Error reading {}: {err}", state.path.display());
-                            }
-                        }
-                    }
-
-                    if reinitialize {
-                        let explicit = explicit_paths.get(session_id);
-                        match initialize_session_state(
-                            session_id,
-                            explicit,
-                            &sessions_root,
-                            args.max_chars,
-                            args.start_at_beginning,
-                        )? {
-                            Some((state, initial_event)) => {
-                                let meta_entry = session_meta
-                                    .entry(session_id.clone())
-                                    .or_insert_with(SessionMeta::new);
-                                if let Some(event) = initial_event {
-                                    meta_entry.record_event(event.timestamp.clone());
-                                    newest_event = select_newer_event(
-                                        newest_event,
-                                        SessionEvent {
-                                            session_id: session_id.clone(),
-                                            event,
-                                        },
-                                    );
-                                } else {
-                                    meta_entry.missing_count = 0;
-                                }
-                                entry.insert(state);
-                            }
-                            None => {
-                                entry.remove();
-                                session_meta.remove(session_id);
-                            }
-                        }
-                    }
-                }
-            }
-        }
+    /// How reasoning reads: `compact` (phase heading), `verbose` (full text), or
+    /// `phase-only` (skip reasoning without a heading) [default: compact]
+    #[arg(long)]
+    renderer: Option<RendererKind>,
 
-        if let Some(event) = newest_event {
-            if should_emit(&last_emitted, &event) {
-                emit_payload(&event.event, cache_path, waybar_signal)?;
-                last_emitted = Some(event);
-            }
-        }
+    /// `escape` log text for Pango markup (Waybar `custom` modules) or `pass` it through unchanged [default: pass]
+    #[arg(long)]
+    markup: Option<Markup>,
 
-        thread::sleep(poll_interval);
-    }
-}
+    /// Tooltip times: `absolute`, `relative` (`2m ago`), or `both` [default: absolute]
+    #[arg(long)]
+    timestamps: Option<TimestampStyle>,
 
-fn default_history_path() -> Result<PathBuf> {
-    let mut path = home_dir().context("Home directory not found")?;
-    path.push(".codex");
-    path.push("history.jsonl");
-    Ok(path)
-}
+    /// Count the time since the last event while a turn runs: `off`, `field` (payload `elapsed`), or `text` (also after the label) [default: off]
+    #[arg(long)]
+    elapsed: Option<ElapsedDisplay>,
 
-fn default_sessions_root() -> Result<PathBuf> {
-    let mut path = home_dir().context("Home directory not found")?;
-    path.push(".codex");
-    path.push("sessions");
-    Ok(path)
-}
+    /// Payload schema: `v1`, or `v2` with `version` and a `meta` object (session id, event type, phase, model) [default: v1]
+    #[arg(long)]
+    payload: Option<PayloadVersion>,
 
-fn infer_session_id_from_path(path: &Path) -> Option<String> {
-    path.file_name()
-        .and_then(|name| name.to_str())
-        .and_then(|name| name.split('-').last())
-        .and_then(|segment| segment.strip_suffix(".jsonl"))
-        .map(|s| s.to_string())
-}
+    /// Mask matches of this regex in labels and tooltips; repeatable, in addition to the built-in key, token, and email patterns
+    #[arg(long)]
+    redact: Vec<String>,
 
-fn locate_session_file(root: &Path, session_id: &str) -> Result<Option<PathBuf>> {
-    let pattern = format!("{}/**/*{}*.jsonl", root.display(), session_id);
-    let mut newest_path: Option<PathBuf> = None;
-    let mut newest_mtime: Option<std::time::SystemTime> = None;
-
-    for entry in glob(&pattern)? {
-        if let Ok(path) = entry {
-            if let Ok(metadata) = fs::metadata(&path) {
-                let mtime = metadata
-                    .modified()
-                    .unwrap_or(std::time::SystemTime::UNIX_EPOCH);
-                if newest_mtime.map_or(true, |current| mtime > current) {
-                    newest_mtime = Some(mtime);
-                    newest_path = Some(path);
-                }
-            }
-        }
-    }
+    /// Skip the built-in redaction patterns
+    #[arg(long)]
+    no_default_redactions: bool,
 
-    Ok(newest_path)
-}
+    /// Add a CSS class when the label matches a regex, as `CLASS=PATTERN` (e.g. `urgent=(?i)error`); repeatable
+    #[arg(long, value_parser = parse_class_rule)]
+    class_rule: Vec<ClassRule>,
 
-fn recent_session_ids(history_path: &Path, limit: usize) -> Result<Vec<String>> {
-    if limit == 0 {
-        return Ok(Vec::new());
-    }
+    /// Label format such as `{phase}: {text} ({session_short})`; see the README for variables
+    #[arg(long)]
+    format: Option<Template>,
 
-    let file = match File::open(history_path) {
-        Ok(f) => f,
-        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
-        Err(err) => return Err(err.into()),
-    };
+    /// Tooltip format, e.g. `{tooltip}\n\n{model} in {cwd}`
+    #[arg(long)]
+    tooltip_format: Option<Template>,
 
-    let reader = BufReader::new(file);
-    let mut lines = Vec::new();
-    for line in reader.lines() {
-        match line {
-            Ok(line) => lines.push(line),
-            Err(err) => {
-                eprintln!("Skipping malformed history entry: {err}");
-            }
-        }
-    }
+    /// Icon shown before a phase heading, as `slug=icon`; repeatable, an empty icon hides the phase's default
+    #[arg(long, value_parser = parse_phase_icon)]
+    phase_icon: Vec<(String, String)>,
 
-    let mut seen = HashSet::new();
-    let mut ordered = Vec::new();
+    /// Show a phase heading under another name, as `PHASE=ALIAS`; repeatable (regex rules go in the config file)
+    #[arg(long, value_parser = parse_phase_alias)]
+    phase_alias: Vec<PhaseAliasRule>,
 
-    for line in lines.iter().rev() {
-        if line.trim().is_empty() {
-            continue;
-        }
-        if let Ok(val) = serde_json::from_str::<Value>(line) {
-            if let Some(id) = val.get("session_id").and_then(|v| v.as_str()) {
-                if seen.insert(id.to_string()) {
-                    ordered.push(id.to_string());
-                    if ordered.len() == limit {
-                        break;
-                    }
-                }
-            }
-        }
-    }
+    /// Show a desktop notification for `approval`, `error`, or `done` events; repeatable (over D-Bus with the `dbus` feature, else through `notify-send`)
+    #[arg(long)]
+    notify: Vec<AlertKind>,
 
-    ordered.reverse();
-    Ok(ordered)
-}
+    /// Play a sound file for an event, as `EVENT=PATH` with `approval`, `error`, or `done`; repeatable
+    #[arg(long, value_parser = parse_sound)]
+    sound: Vec<(AlertKind, PathBuf)>,
 
-fn merge_session_targets(
-    discovered: &[String],
-    session_meta: &mut HashMap<String, SessionMeta>,
-    limit: usize,
-) -> Vec<String> {
-    let discovered_set: HashSet<&String> = discovered.iter().collect();
-    session_meta.retain(|id, meta| meta.missing_count < 5 || discovered_set.contains(id));
+    /// Command that plays sound files; the file is appended [default: paplay]
+    #[arg(long)]
+    sound_player: Option<String>,
 
-    let mut combined: Vec<String> = Vec::new();
+    /// Local times without sounds, such as `22:00-07:00`
+    #[arg(long)]
+    quiet_hours: Option<QuietHours>,
 
-    for id in discovered {
-        if !combined.contains(id) {
-            combined.push(id.clone());
-        }
-    }
+    /// Command `open` runs for the displayed session, e.g. `codex resume {session}`; see the README for variables
+    #[arg(long)]
+    open_command: Option<Template>,
 
-    for id in session_meta.keys() {
-        if !combined.contains(id) {
-            combined.push(id.clone());
-        }
-    }
+    /// Command line `open` appends its command to [default: xdg-terminal-exec]
+    #[arg(long)]
+    terminal: Option<String>,
 
-    if limit > 0 && combined.len() > limit {
-        let mut sorted = combined.clone();
-        sorted.sort_by(|a, b| {
-            let instant_a = session_meta
-                .get(a)
-                .map(|meta| meta.last_event_instant)
-                .unwrap_or_else(Instant::now);
-            let instant_b = session_meta
-                .get(b)
-                .map(|meta| meta.last_event_instant)
-                .unwrap_or_else(Instant::now);
-            instant_a.cmp(&instant_b)
-        });
-
-        let mut retained: Vec<String> = sorted.into_iter().rev().take(limit).collect();
-        retained.reverse();
-
-        combined.retain(|id| retained.contains(id));
-    }
+    /// MQTT broker that receives every payload, as `host[:port]` (needs the `mqtt` feature)
+    #[arg(long)]
+    mqtt_broker: Option<String>,
 
-    combined
-}
+    /// MQTT topic payloads are published to [default: codex-shimmer/payload]
+    #[arg(long)]
+    mqtt_topic: Option<String>,
 
-fn prune_untracked_sessions(
-    tracked: &[String],
-    session_states: &mut HashMap<String, SessionState>,
-    session_meta: &mut HashMap<String, SessionMeta>,
-) {
-    let tracked_set: HashSet<&String> = tracked.iter().collect();
-    session_states.retain(|id, _| tracked_set.contains(id));
-    session_meta.retain(|id, _| tracked_set.contains(id));
-}
+    /// MQTT quality of service: 0, 1, or 2 [default: 0]
+    #[arg(long)]
+    mqtt_qos: Option<u8>,
 
-fn initialize_session_state(
-    session_id: &str,
-    explicit_path: Option<&PathBuf>,
-    sessions_root: &Path,
-    max_chars: usize,
-    start_at_beginning: bool,
-) -> Result<Option<(SessionState, Option<RenderedEvent>)>> {
-    let path = match explicit_path {
-        Some(path) => path.clone(),
-        None => match locate_session_file(sessions_root, session_id)? {
-            Some(path) => path,
-            None => return Ok(None),
-        },
-    };
+    /// POST every payload as JSON to this URL, retrying server errors with backoff
+    #[arg(long)]
+    webhook_url: Option<String>,
 
-    let mut offset = 0;
-    let event = prime_session(&path, &mut offset, max_chars, start_at_beginning)?;
-    Ok(Some((SessionState { path, offset }, event)))
-}
+    /// Only post payloads carrying this CSS class (e.g. `approval-pending`); repeatable
+    #[arg(long)]
+    webhook_class: Vec<String>,
 
-fn bootstrap_sessions(
-    session_states: &mut HashMap<String, SessionState>,
-    session_meta: &mut HashMap<String, SessionMeta>,
-    last_emitted: &mut Option<SessionEvent>,
-    tracked_sessions: &[String],
-    explicit_paths: &HashMap<String, PathBuf>,
-    sessions_root: &Path,
-    max_chars: usize,
-    start_at_beginning: bool,
-    cache_path: &Path,
+    /// Custom Waybar signal number to emit after each cache refresh (enable with module `signal:`)
+    #[arg(long)]
     waybar_signal: Option<u8>,
-) -> Result<()> {
-    let mut newest_event: Option<SessionEvent> = None;
-
-    for session_id in tracked_sessions {
-        if session_states.contains_key(session_id) {
-            continue;
-        }
-        let explicit = explicit_paths.get(session_id);
-        if let Some((state, initial_event)) = initialize_session_state(
-            session_id,
-            explicit,
-            sessions_root,
-            max_chars,
-            start_at_beginning,
-        )? {
-            let meta_entry = session_meta
-                .entry(session_id.clone())
-                .or_insert_with(SessionMeta::new);
-            if let Some(event) = initial_event {
-                meta_entry.record_event(event.timestamp.clone());
-                newest_event = select_newer_event(
-                    newest_event,
-                    SessionEvent {
-                        session_id: session_id.clone(),
-                        event,
-                    },
-                );
-            } else {
-                meta_entry.missing_count = 0;
-            }
-            session_states.insert(session_id.clone(), state);
-        } else {
-            session_meta
-                .entry(session_id.clone())
-                .or_insert_with(SessionMeta::new)
-                .mark_miss();
-        }
-    }
 
-    if let Some(event) = newest_event {
-        emit_payload(&event.event, cache_path, waybar_signal)?;
-        *last_emitted = Some(event);
-    }
+    /// Process name to signal; repeat for several bars [default: waybar]
+    #[arg(long)]
+    waybar_process: Vec<String>,
 
-    Ok(())
-}
+    /// Write the most recent payload to the specified cache file [default: $XDG_CACHE_HOME/codex-shimmer/latest.json unless another output is set]
+    #[arg(long)]
+    cache_file: Option<PathBuf>,
 
-fn select_newer_event(
-    current: Option<SessionEvent>,
-    candidate: SessionEvent,
-) -> Option<SessionEvent> {
-    match current {
-        None => Some(candidate),
-        Some(existing) => {
-            if is_newer_timestamp(
-                candidate.event.timestamp.as_ref(),
-                existing.event.timestamp.as_ref(),
-            ) || (candidate.event.timestamp == existing.event.timestamp
-                && candidate.event.payload != existing.event.payload)
-            {
-                Some(candidate)
-            } else {
-                Some(existing)
-            }
-        }
-    }
-}
+    /// Also write each tracked session's payload to `<dir>/<session-id>.json`; the merged payload goes to `<dir>/latest.json` unless --cache-file is set
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
 
-fn is_newer_timestamp(candidate: Option<&String>, current: Option<&String>) -> bool {
-    match (candidate, current) {
-        (Some(candidate), Some(current)) => candidate > current,
-        (Some(_), None) => true,
-        _ => false,
-    }
-}
+    /// Write the activity label of the session on display to this file, whatever --mode shows
+    #[arg(long)]
+    cache_reasoning: Option<PathBuf>,
 
-fn should_emit(last_emitted: &Option<SessionEvent>, candidate: &SessionEvent) -> bool {
-    match last_emitted {
-        None => true,
-        Some(previous) => {
-            previous.session_id != candidate.session_id
-                || previous.event.timestamp != candidate.event.timestamp
-                || previous.event.payload != candidate.event.payload
-        }
-    }
-}
+    /// Write the task state of the session on display (`Working`, `Done`, `Approval needed`, …) to this file
+    #[arg(long)]
+    cache_status: Option<PathBuf>,
 
-fn read_new_lines(path: &Path, offset: &mut u64) -> io::Result<Vec<String>> {
-    let file = File::open(path)?;
-    let file_len = file.metadata()?.len();
-    if *offset > file_len {
-        *offset = 0;
-    }
+    /// Write the context usage of the session on display (`25% ctx`) to this file
+    #[arg(long)]
+    cache_tokens: Option<PathBuf>,
 
-    let mut reader = BufReader::new(file);
-    reader.seek(SeekFrom::Start(*offset))?;
+    /// Skip flushing cache files to disk before the atomic rename, e.g. for a cache on tmpfs
+    #[arg(long)]
+    no_fsync: bool,
 
-    let mut lines = Vec::new();
-    loop {
-        let mut buffer = String::new();
-        let bytes = reader.read_line(&mut buffer)?;
-        if bytes == 0 {
-            break;
-        }
-        *offset += bytes as u64;
-        if let Some(stripped) = buffer.strip_suffix('\n') {
-            lines.push(stripped.to_string());
-        } else {
-            lines.push(buffer);
-        }
-    }
-    Ok(lines)
-}
+    /// Append every log line read and the payload it rendered, redacted, to this file for bug reports
+    #[arg(long)]
+    record: Option<PathBuf>,
 
-fn prime_session(
-    path: &Path,
-    offset: &mut u64,
-    max_chars: usize,
-    start_at_beginning: bool,
-) -> Result<Option<RenderedEvent>> {
-    let metadata = match fs::metadata(path) {
-        Ok(meta) => meta,
-        Err(err) if err.kind() == ErrorKind::NotFound => {
-            *offset = 0;
-            return Ok(None);
-        }
-        Err(err) => return Err(err.into()),
-    };
+    /// Move the --record file to `<path>.1` and start a new one past this size [default: 10485760]
+    #[arg(long)]
+    record_max_bytes: Option<u64>,
 
-    if start_at_beginning {
-        *offset = 0;
-    } else {
-        *offset = metadata.len();
-    }
+    /// Keep every rendered event in a SQLite database, for `stats` and `search` (needs the `store` feature)
+    #[arg(long)]
+    event_store: bool,
 
-    let file = match File::open(path) {
-        Ok(f) => f,
-        Err(err) if err.kind() == ErrorKind::NotFound => {
-            *offset = 0;
-            return Ok(None);
-        }
-        Err(err) => return Err(err.into()),
-    };
-    let reader = BufReader::new(file);
-    let mut last_event: Option<RenderedEvent> = None;
-    for line in reader.lines() {
-        let line = match line {
-            Ok(line) => line,
-            Err(err) if err.kind() == ErrorKind::NotFound => {
-                *offset = 0;
-                return Ok(None);
-            }
-            Err(err) => return Err(err.into()),
-        };
-        if line.trim().is_empty() {
-            continue;
-        }
-        if let Some(event) = process_log_line(&line, max_chars)? {
-            last_event = Some(event);
-        }
-    }
+    /// Event store database [default: $XDG_STATE_HOME/codex-shimmer/events.sqlite]
+    #[arg(long)]
+    event_store_path: Option<PathBuf>,
 
-    *offset = metadata.len();
+    /// Replay the entire log from the beginning instead of tailing new entries
+    #[arg(long)]
+    start_at_beginning: bool,
 
-    Ok(last_event)
-}
+    /// Print a JSON line to stdout on every update (for Waybar `exec` modules)
+    #[arg(long)]
+    stdout: bool,
 
-fn process_log_line(line: &str, max_chars: usize) -> Result<Option<RenderedEvent>> {
-    if line.trim().is_empty() {
-        return Ok(None);
-    }
+    /// Control socket path [default: $XDG_RUNTIME_DIR/codex-shimmer.sock]
+    #[arg(long, global = true)]
+    control_socket: Option<PathBuf>,
 
-    let value: Value = match serde_json::from_str(line) {
-        Ok(val) => val,
-        Err(err) => {
-            eprintln!("Skipping malformed log entry: {err}");
-            return Ok(None);
-        }
-    };
+    /// Serve Prometheus metrics at `http://ADDR/metrics`, e.g. `127.0.0.1:9464`
+    #[arg(long)]
+    metrics_addr: Option<SocketAddr>,
 
-    let payload = match value.get("payload") {
-        Some(payload) => payload,
-        None => return Ok(None),
-    };
+    /// Export turn and tool-call spans and token counts to an OTLP/HTTP collector such as `http://localhost:4318` (needs the `otel` feature)
+    #[arg(long)]
+    otel_endpoint: Option<String>,
 
-    if payload
-        .get("type")
-        .and_then(Value::as_str)
-        .map(|t| t != "agent_reasoning")
-        .unwrap_or(true)
-    {
-        return Ok(None);
-    }
+    /// Send event counts and cache write timings to a StatsD agent at HOST:PORT
+    #[arg(long)]
+    statsd_addr: Option<String>,
 
-    let raw_text = payload
-        .get("text")
-        .and_then(Value::as_str)
-        .unwrap_or_default();
+    /// Prefix of StatsD metric names [default: codex_shimmer]
+    #[arg(long)]
+    statsd_prefix: Option<String>,
 
-    if raw_text.is_empty() {
-        return Ok(None);
-    }
+    /// Tag StatsD metrics DogStatsD-style (`|#type:…`) instead of naming them after the tag
+    #[arg(long)]
+    dogstatsd: bool,
 
-    let sanitized = sanitize_text(raw_text);
-    let truncated = truncate_text(&sanitized, max_chars);
-    let timestamp = value
-        .get("timestamp")
-        .and_then(Value::as_str)
-        .map(|s| s.to_string());
+    /// Log level (`error`, `warn`, `info`, `debug`, `trace`) or filter such as `codex_shimmer::discovery=debug` [default: $RUST_LOG or info]
+    #[arg(long, global = true)]
+    log_level: Option<String>,
 
-    let phase = extract_phase(raw_text);
+    /// Also log to this file, rotated daily with the last 7 days kept
+    #[arg(long, global = true)]
+    log_file: Option<PathBuf>,
+}
 
-    let mut classes = vec!["codex".to_string(), "agent-reasoning".to_string()];
-    if let Some(ref label) = phase {
-        if let Some(slug) = slugify(label) {
-            classes.push(format!("phase-{}", slug));
-        }
-    }
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Send a command to the running daemon over its control socket
+    Ctl {
+        #[command(subcommand)]
+        command: CtlCommand,
+    },
+    /// Put the newest reasoning, in full, on the Wayland clipboard with wl-copy
+    CopyLast,
+    /// Play a made-up Codex session through the outputs, for styling the bar without Codex
+    Demo,
+    /// Check the Codex logs, cache file, Waybar config, and service, and suggest fixes
+    Doctor,
+    /// Print the most recently rendered events, oldest first
+    Last {
+        /// How many events to print
+        #[arg(short = 'n', long, default_value_t = 20)]
+        count: usize,
+        /// Only events from this session, like --session-id
+        #[arg(long)]
+        session: Option<String>,
+        /// Print one JSON object per event, with the full text and payload
+        #[arg(long)]
+        json: bool,
+    },
+    /// Resume the session the bar shows in a terminal, for a Waybar on-click
+    Open,
+    /// Manage the systemd user unit; `enable` bakes the flags given before `service` into it
+    Service {
+        #[command(subcommand)]
+        command: ServiceCommand,
+    },
+    /// Play a recorded session log back through the outputs, to reproduce what it showed
+    Replay {
+        /// The rollout, transcript, or chat history to play
+        path: PathBuf,
+        /// Play this many times faster than the log's timestamps [default: 1]
+        #[arg(long, conflicts_with = "interval_ms")]
+        speed: Option<f64>,
+        /// Ignore the timestamps and wait this long before each line
+        #[arg(long)]
+        interval_ms: Option<u64>,
+    },
+    /// Print the reasoning and replies matching a regex, with the session and time of each
+    Search {
+        /// Regular expression matched against each line of the text
+        pattern: String,
+        /// Match without regard to case
+        #[arg(short, long)]
+        ignore_case: bool,
+        /// Lines shown around each match [default: 1]
+        #[arg(short = 'C', long)]
+        context: Option<usize>,
+        /// Search this many of the most recent sessions [default: 100]
+        #[arg(long, conflicts_with = "store")]
+        limit: Option<usize>,
+        /// Search the event store instead of the session logs
+        #[arg(long)]
+        store: bool,
+    },
+    /// Add the custom/codex module and its style to the Waybar config, backing it up first
+    SetupWaybar {
+        /// Take the module and style back out instead
+        #[arg(long)]
+        remove: bool,
+        /// Waybar config directory [default: $WAYBAR_CONFIG_DIR or ~/.config/waybar]
+        #[arg(long)]
+        waybar_dir: Option<PathBuf>,
+    },
+    /// Summarize sessions, turns, commands, approvals, tokens, and cost from the event store
+    Stats {
+        /// Since local midnight (the default)
+        #[arg(long, conflicts_with = "week")]
+        today: bool,
+        /// Over today and the six days before
+        #[arg(long)]
+        week: bool,
+        /// Print the totals as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Browse the tracked sessions and follow the reasoning and commands of one in the terminal
+    Tui,
+    /// Stop the service and remove what install.sh put in place
+    Uninstall {
+        /// Print what would be removed without touching anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Leave ~/.config/codex-shimmer in place
+        #[arg(long)]
+        keep_config: bool,
+        /// Installation prefix given to install.sh [default: $PREFIX or ~/.local]
+        #[arg(long)]
+        prefix: Option<PathBuf>,
+    },
+}
 
-    let tooltip = build_tooltip(timestamp.as_deref(), raw_text, &sanitized, &truncated);
-    let display_text = phase.clone().unwrap_or_else(|| truncated.clone());
-
-    Ok(Some(RenderedEvent {
-        payload: WaybarOutput {
-            text: display_text,
-            tooltip,
-            alt: phase,
-            class: classes,
-        },
-        timestamp,
-    }))
+#[derive(Subcommand, Debug)]
+enum CtlCommand {
+    /// Stop publishing updates until `resume`
+    Pause,
+    /// Publish updates again
+    Resume,
+    /// Only display events from the given session (a unique id prefix is enough)
+    Pin { session_id: String },
+    /// Display the newest event across sessions again
+    Unpin,
+    /// Re-scan sessions and republish the current payload
+    Refresh,
+    /// Print the daemon state as JSON
+    Status,
 }
 
-fn sanitize_text(input: &str) -> String {
-    let mut text = input.replace('\n', " ").replace('\r', " ");
-    text = text.replace("**", "");
-    collapse_whitespace(&text)
+#[derive(Subcommand, Debug)]
+enum ServiceCommand {
+    /// Write the unit, then enable and (re)start it
+    Enable,
+    /// Stop and disable the unit
+    Disable,
+    /// Report whether the service is active and the daemon answers
+    Status,
+    /// Restart the running service
+    Restart,
 }
 
-fn collapse_whitespace(input: &str) -> String {
-    let mut out = String::with_capacity(input.len());
-    let mut last_space = false;
-    for ch in input.chars() {
-        if ch.is_whitespace() {
-            if !last_space {
-                out.push(' ');
-                last_space = true;
-            }
-        } else {
-            out.push(ch);
-            last_space = false;
+impl From<ServiceCommand> for Action {
+    fn from(command: ServiceCommand) -> Self {
+        match command {
+            ServiceCommand::Enable => Action::Enable,
+            ServiceCommand::Disable => Action::Disable,
+            ServiceCommand::Status => Action::Status,
+            ServiceCommand::Restart => Action::Restart,
         }
     }
-    out.trim().to_string()
 }
 
-fn truncate_text(text: &str, max_len: usize) -> String {
-    if text.len() <= max_len {
-        return text.to_string();
-    }
-    let mut truncated = String::new();
-    for ch in text.chars() {
-        let next_len = truncated.len() + ch.len_utf8();
-        if next_len > max_len {
-            truncated = truncated.trim_end().to_owned();
-            truncated.push('…');
-            return truncated;
+impl From<CtlCommand> for ControlCommand {
+    fn from(command: CtlCommand) -> Self {
+        match command {
+            CtlCommand::Pause => ControlCommand::Pause,
+            CtlCommand::Resume => ControlCommand::Resume,
+            CtlCommand::Pin { session_id } => ControlCommand::Pin(session_id),
+            CtlCommand::Unpin => ControlCommand::Unpin,
+            CtlCommand::Refresh => ControlCommand::Refresh,
+            CtlCommand::Status => ControlCommand::Status,
         }
-        truncated.push(ch);
     }
-    truncated
 }
 
-fn extract_phase(raw: &str) -> Option<String> {
-    if let Some(stripped) = raw.strip_prefix("**") {
-        if let Some(end) = stripped.find("**") {
-            return Some(stripped[..end].trim().to_string());
+impl Args {
+    /// Options given on the command line, layered over the config file.
+    fn overrides(&self) -> Config {
+        Config {
+            session_file: self.session_file.clone(),
+            session_id: self.session_id.clone(),
+            history_path: self.history_path.clone(),
+            sessions_root: self.sessions_root.clone(),
+            codex_home: (!self.codex_home.is_empty()).then(|| self.codex_home.clone()),
+            container: (!self.container.is_empty()).then(|| self.container.clone()),
+            agent: self.agent,
+            discovery: self.discovery,
+            model_filter: (!self.model_filter.is_empty()).then(|| self.model_filter.clone()),
+            ignore: (!self.ignore.is_empty()).then(|| self.ignore.clone()),
+            ignore_file: self.ignore_file.clone(),
+            follow_focus: self.follow_focus,
+            sway_approval_bar: self.sway_approval_bar,
+            log_glob: self.log_glob.clone(),
+            timestamp_path: self.timestamp_path.clone(),
+            kind_path: self.kind_path.clone(),
+            text_path: self.text_path.clone(),
+            event_kinds: (!self.event_kinds.is_empty()).then(|| self.event_kinds.clone()),
+            message_kinds: (!self.message_kinds.is_empty()).then(|| self.message_kinds.clone()),
+            poll_ms: self.poll_ms,
+            max_poll_ms: self.max_poll_ms,
+            session_refresh_secs: self.session_refresh_secs,
+            session_window: self.session_window,
+            max_sessions: self.max_sessions,
+            max_line_bytes: self.max_line_bytes,
+            idle_after_secs: self.idle_after_secs,
+            min_emit_interval_ms: self.min_emit_interval_ms,
+            switch_hysteresis_ms: self.switch_hysteresis_ms,
+            max_chars: self.max_chars,
+            ellipsis: self.ellipsis.clone(),
+            rewrite: (!self.rewrite.is_empty()).then(|| self.rewrite.clone()),
+            mode: self.mode,
+            percentage: self.percentage,
+            renderer: self.renderer,
+            markup: self.markup,
+            timestamps: self.timestamps,
+            elapsed: self.elapsed,
+            payload: self.payload,
+            redact: (!self.redact.is_empty()).then(|| self.redact.clone()),
+            redact_defaults: self.no_default_redactions.then_some(false),
+            class_rule: (!self.class_rule.is_empty()).then(|| self.class_rule.clone()),
+            format: self.format.clone(),
+            tooltip_format: self.tooltip_format.clone(),
+            phase_icons: (!self.phase_icon.is_empty())
+                .then(|| self.phase_icon.iter().cloned().collect()),
+            phase_alias: (!self.phase_alias.is_empty()).then(|| self.phase_alias.clone()),
+            notify: (!self.notify.is_empty()).then(|| self.notify.clone()),
+            sound: (!self.sound.is_empty()).then(|| self.sound.iter().cloned().collect()),
+            sound_player: self.sound_player.clone(),
+            quiet_hours: self.quiet_hours,
+            open_command: self.open_command.clone(),
+            terminal: self.terminal.clone(),
+            mqtt_broker: self.mqtt_broker.clone(),
+            mqtt_topic: self.mqtt_topic.clone(),
+            mqtt_qos: self.mqtt_qos,
+            webhook_url: self.webhook_url.clone(),
+            webhook_class: (!self.webhook_class.is_empty()).then(|| self.webhook_class.clone()),
+            waybar_signal: self.waybar_signal,
+            waybar_process: (!self.waybar_process.is_empty()).then(|| self.waybar_process.clone()),
+            cache_file: self.cache_file.clone(),
+            cache_dir: self.cache_dir.clone(),
+            cache_reasoning: self.cache_reasoning.clone(),
+            cache_status: self.cache_status.clone(),
+            cache_tokens: self.cache_tokens.clone(),
+            no_fsync: self.no_fsync.then_some(true),
+            record: self.record.clone(),
+            record_max_bytes: self.record_max_bytes,
+            event_store: self.event_store.then_some(true),
+            event_store_path: self.event_store_path.clone(),
+            start_at_beginning: self.start_at_beginning.then_some(true),
+            stdout: self.stdout.then_some(true),
+            control_socket: self.control_socket.clone(),
+            metrics_addr: self.metrics_addr,
+            otel_endpoint: self.otel_endpoint.clone(),
+            statsd_addr: self.statsd_addr.clone(),
+            statsd_prefix: self.statsd_prefix.clone(),
+            dogstatsd: self.dogstatsd.then_some(true),
+            log_level: self.log_level.clone(),
+            log_file: self.log_file.clone(),
         }
     }
-    None
 }
 
-fn slugify(input: &str) -> Option<String> {
-    let mut slug = String::new();
-    let mut last_dash = false;
-    for ch in input.chars() {
-        if ch.is_ascii_alphanumeric() {
-            slug.push(ch.to_ascii_lowercase());
-            last_dash = false;
-        } else if ch.is_whitespace() || ch == '-' || ch == '_' {
-            if !slug.is_empty() && !last_dash {
-                slug.push('-');
-                last_dash = true;
-            }
-        } else if !slug.is_empty() && !last_dash {
-            slug.push('-');
-            last_dash = true;
-        }
-    }
-    while slug.ends_with('-') {
-        slug.pop();
-    }
-    if slug.is_empty() { None } else { Some(slug) }
+fn parse_phase_icon(value: &str) -> Result<(String, String), String> {
+    value
+        .split_once('=')
+        .map(|(slug, icon)| (slug.trim().to_string(), icon.trim().to_string()))
+        .ok_or_else(|| format!("expected `slug=icon`, got `{value}`"))
 }
 
-fn build_tooltip(
-    timestamp: Option<&str>,
-    raw_text: &str,
-    sanitized: &str,
-    truncated: &str,
-) -> Option<String> {
-    let mut parts = Vec::new();
-    if let Some(ts) = timestamp {
-        parts.push(ts.to_string());
-    }
-    let raw_trimmed = raw_text.trim();
-    if !raw_trimmed.is_empty() && raw_trimmed != sanitized {
-        parts.push(raw_trimmed.to_string());
-    } else if sanitized != truncated {
-        parts.push(sanitized.to_string());
-    }
-    if parts.is_empty() {
-        None
-    } else {
-        Some(parts.join("\n"))
-    }
+fn parse_sound(value: &str) -> Result<(AlertKind, PathBuf), String> {
+    let (kind, path) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected `EVENT=PATH`, got `{value}`"))?;
+    let kind = kind.trim().parse().map_err(|err| format!("{err}"))?;
+    Ok((kind, PathBuf::from(path)))
 }
 
-fn emit_payload(event: &RenderedEvent, cache_path: &Path, waybar_signal: Option<u8>) -> Result<()> {
-    write_payload_to_cache(&event.payload, cache_path)?;
-    if let Some(sig) = waybar_signal {
-        notify_waybar(sig);
-    }
-    Ok(())
+fn parse_class_rule(value: &str) -> Result<ClassRule, String> {
+    value
+        .split_once('=')
+        .map(|(class, pattern)| ClassRule {
+            class: class.trim().to_string(),
+            contains: None,
+            pattern: Some(pattern.to_string()),
+        })
+        .ok_or_else(|| format!("expected `CLASS=PATTERN`, got `{value}`"))
 }
 
-#[cfg(any(target_os = "linux", target_os = "android"))]
-fn notify_waybar(signal: u8) {
-    let arg = format!("-RTMIN+{}", signal);
-    let _ = Command::new("pkill").arg(&arg).arg("waybar").status();
+fn parse_phase_alias(value: &str) -> Result<PhaseAliasRule, String> {
+    value
+        .split_once('=')
+        .map(|(phase, alias)| PhaseAliasRule {
+            phase: Some(phase.trim().to_string()),
+            pattern: None,
+            alias: alias.trim().to_string(),
+        })
+        .ok_or_else(|| format!("expected `PHASE=ALIAS`, got `{value}`"))
 }
 
-#[cfg(not(any(target_os = "linux", target_os = "android")))]
-fn notify_waybar(_signal: u8) {}
+fn parse_rewrite(value: &str) -> Result<RewriteRule, String> {
+    value
+        .split_once("=>")
+        .map(|(pattern, replacement)| RewriteRule {
+            pattern: pattern.to_string(),
+            replacement: replacement.to_string(),
+        })
+        .ok_or_else(|| format!("expected `PATTERN=>REPLACEMENT`, got `{value}`"))
+}
 
-fn write_payload_to_cache(payload: &WaybarOutput, cache_path: &Path) -> Result<()> {
-    if let Some(parent) = cache_path.parent() {
-        fs::create_dir_all(parent)?;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn ignore_realtime_signals() {
+    unsafe {
+        let base = libc::SIGRTMIN();
+        if base <= 0 {
+            return;
+        }
+        for offset in 0..16 {
+            let sig = base + offset;
+            libc::signal(sig, libc::SIG_IGN);
+        }
     }
+}
 
-    let temp_path = cache_path.with_extension("tmp");
-    {
-        let mut file = File::create(&temp_path)?;
-        serde_json::to_writer(&mut file, payload)?;
-        file.write_all(b"\n")?;
-        file.sync_all()?;
-    }
-    fs::rename(&temp_path, cache_path)?;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn ignore_realtime_signals() {}
 
-    Ok(())
+/// Prefix of the environment variable behind each flag, such as
+/// `CODEX_SHIMMER_CACHE_FILE` for `--cache-file`.
+const ENV_PREFIX: &str = "CODEX_SHIMMER_";
+/// Repeatable flags whose environment variable holds a comma-separated list.
+/// The others take patterns or templates that may contain commas themselves.
+const ENV_LISTS: [&str; 9] = [
+    "codex_home",
+    "container",
+    "event_kinds",
+    "ignore",
+    "message_kinds",
+    "model_filter",
+    "notify",
+    "webhook_class",
+    "waybar_process",
+];
+
+/// Parse the command line, falling back to `CODEX_SHIMMER_*` variables for
+/// flags that are not given, so a unit can use an `EnvironmentFile`. The
+/// matches are returned too, for [`global_args`].
+fn parse_args() -> (Args, ArgMatches) {
+    let command = Args::command().mut_args(|arg| {
+        let id = arg.get_id().to_string();
+        if arg.is_positional() || matches!(id.as_str(), "help" | "version") {
+            return arg;
+        }
+        let env = format!("{ENV_PREFIX}{}", id.to_uppercase());
+        let arg = arg.env(env);
+        if ENV_LISTS.contains(&id.as_str()) {
+            arg.value_delimiter(',')
+        } else {
+            arg
+        }
+    });
+    let matches = command.get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    (args, matches)
 }
 
-fn print_cache(path: &Path) -> Result<()> {
-    match fs::read_to_string(path) {
-        Ok(content) => {
-            print!("{}", content);
+/// The flags of `matches` given on the command line outside any subcommand,
+/// in the order given, as `--flag=value` words the daemon can be started with
+/// again. Ones taken from `CODEX_SHIMMER_*` variables are left out.
+fn global_args(matches: &ArgMatches) -> Vec<OsString> {
+    let mut words = Vec::new();
+    for arg in Args::command().get_arguments() {
+        let id = arg.get_id().as_str();
+        let Some(long) = arg.get_long() else {
+            continue;
+        };
+        if matches.value_source(id) != Some(ValueSource::CommandLine) {
+            continue;
         }
-        Err(err) if err.kind() == ErrorKind::NotFound => {
-            let payload = WaybarOutput {
-                text: "Waiting for Codex…".to_string(),
-                tooltip: None,
-                alt: Some("initializing".to_string()),
-                class: vec!["codex".to_owned(), "agent-reasoning".to_owned()],
-            };
-            println!("{}", serde_json::to_string(&payload)?);
-            return Ok(());
+        let indices = matches.indices_of(id).into_iter().flatten();
+        if !arg.get_action().takes_values() {
+            words.extend(indices.map(|index| (index, OsString::from(format!("--{long}")))));
+            continue;
+        }
+        let values = matches.get_raw(id).into_iter().flatten();
+        for (index, value) in indices.zip(values) {
+            let mut word = OsString::from(format!("--{long}="));
+            word.push(value);
+            words.push((index, word));
         }
-        Err(err) => return Err(err.into()),
     }
+    words.sort_by_key(|(index, _)| *index);
+    words.into_iter().map(|(_, word)| word).collect()
+}
+
+/// Print usage totals from the event store at `path`.
+#[cfg(feature = "store")]
+fn print_stats(path: &Path, week: bool, json: bool) -> Result<()> {
+    use codex_shimmer::{stats, store};
 
-    io::stdout().flush()?;
+    if !path.exists() {
+        bail!(
+            "No event store at {}; run the daemon with --event-store first",
+            path.display()
+        );
+    }
+    let period = if week {
+        stats::Period::Week
+    } else {
+        stats::Period::Today
+    };
+    let now = std::time::SystemTime::now();
+    let totals = stats::collect(&store::open(path)?, period.start(now))?;
+    if json {
+        println!("{}", serde_json::to_string(&totals)?);
+    } else {
+        print!("{}", stats::report(&totals, period));
+    }
     Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use serde_json::json;
-    use std::fs;
-    use std::io::Write;
-    use tempfile::{NamedTempFile, tempdir};
+#[cfg(not(feature = "store"))]
+fn print_stats(_path: &Path, _week: bool, _json: bool) -> Result<()> {
+    bail!("`stats` reads the event store, which needs a build with the `store` feature")
+}
 
-    #[test]
-    fn prime_session_returns_none_when_file_missing() -> Result<()> {
-        let dir = tempdir()?;
-        let session_path = dir.path().join("missing-session.jsonl");
-        let mut offset = 42;
+/// Search the reasoning and replies in the event store at `path`.
+#[cfg(feature = "store")]
+fn search_event_store(path: &Path, pattern: &Regex, context: usize) -> Result<Vec<search::Hit>> {
+    if !path.exists() {
+        bail!(
+            "No event store at {}; run the daemon with --event-store first",
+            path.display()
+        );
+    }
+    search::search_store(&codex_shimmer::store::open(path)?, pattern, context)
+}
 
-        let result = prime_session(&session_path, &mut offset, 120, false)?;
+#[cfg(not(feature = "store"))]
+fn search_event_store(_path: &Path, _pattern: &Regex, _context: usize) -> Result<Vec<search::Hit>> {
+    bail!("`search --store` reads the event store, which needs a build with the `store` feature")
+}
 
-        assert!(result.is_none());
-        assert_eq!(offset, 0);
-        Ok(())
-    }
+#[cfg(feature = "tui")]
+fn run_tui(settings: codex_shimmer::config::Settings) -> Result<()> {
+    codex_shimmer::tui::run(settings)
+}
 
-    #[test]
-    fn prime_session_reads_last_payload() -> Result<()> {
-        let dir = tempdir()?;
-        let session_path = dir.path().join("session.jsonl");
-        let mut file = File::create(&session_path)?;
-        let payload_one = json!({
-            "timestamp": "2025-10-29T12:00:00Z",
-            "type": "event_msg",
-            "payload": { "type": "agent_reasoning", "text": "**First step** details" }
-        });
-        let payload_two = json!({
-            "timestamp": "2025-10-29T12:01:00Z",
-            "type": "event_msg",
-            "payload": { "type": "agent_reasoning", "text": "Second step" }
-        });
-        writeln!(file, "{payload_one}")?;
-        writeln!(file, "{payload_two}")?;
-
-        let mut offset = 0;
-        let result = prime_session(&session_path, &mut offset, 120, false)?;
-
-        assert!(result.is_some());
-        let event = result.unwrap();
-        assert_eq!(event.payload.text, "Second step");
-        assert_eq!(event.timestamp.as_deref(), Some("2025-10-29T12:01:00Z"));
-        Ok(())
-    }
+#[cfg(not(feature = "tui"))]
+fn run_tui(_settings: codex_shimmer::config::Settings) -> Result<()> {
+    bail!("`tui` needs a build with the `tui` feature")
+}
 
-    #[test]
-    fn read_new_lines_resets_offset_when_file_shrinks() -> Result<()> {
-        let temp = NamedTempFile::new()?;
-        fs::write(temp.path(), "line1\nline2\n")?;
-        let mut offset = fs::metadata(temp.path())?.len();
+/// A per-process directory under the temp directory for a scratch log.
+fn scratch_dir(purpose: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("codex-shimmer-{purpose}-{}", std::process::id()))
+}
+
+/// Run the daemon on the scratch log at `path` while `player` writes it, and
+/// remove `dir` afterwards. A player that fails stops the daemon.
+async fn follow_scratch(
+    config_path: PathBuf,
+    mut overrides: Config,
+    dir: &Path,
+    path: PathBuf,
+    mut player: JoinHandle<Result<()>>,
+) -> Result<()> {
+    overrides.session_file = Some(path);
+    let result = tokio::select! {
+        result = daemon::run(ConfigWatcher::new(config_path, overrides)) => result,
+        Ok(Err(err)) = &mut player => Err(err),
+    };
+    player.abort();
+    let _ = std::fs::remove_dir_all(dir);
+    result
+}
 
-        fs::write(temp.path(), "line3\n")?;
+#[tokio::main]
+async fn main() -> Result<()> {
+    let (mut args, matches) = parse_args();
 
-        let lines = read_new_lines(temp.path(), &mut offset)?;
-        assert_eq!(lines, vec!["line3".to_string()]);
-        assert_eq!(offset, fs::metadata(temp.path())?.len());
-        Ok(())
-    }
+    ignore_realtime_signals();
 
-    #[test]
-    fn recent_session_ids_returns_unique_sessions_in_order() -> Result<()> {
-        let dir = tempdir()?;
-        let history_path = dir.path().join("history.jsonl");
-        fs::write(
-            &history_path,
-            r#"
-{"session_id":"alpha"}
-{"session_id":"beta"}
-{"session_id":"alpha"}
-{"session_id":"gamma"}
-"#
-            .trim_start(),
-        )?;
-
-        let ids = recent_session_ids(&history_path, 2)?;
-        assert_eq!(ids, vec!["alpha".to_string(), "gamma".to_string()]);
-
-        let ids_three = recent_session_ids(&history_path, 3)?;
-        assert_eq!(
-            ids_three,
-            vec!["beta".to_string(), "alpha".to_string(), "gamma".to_string()]
+    let config_path = args
+        .config
+        .clone()
+        .or_else(default_config_path)
+        .context("Unable to determine default config path")?;
+    let file = Config::load(&config_path)?.unwrap_or_default();
+    let merged = args.overrides().merged_over(&file);
+    logging::init(
+        merged.log_level.as_deref(),
+        merged.log_file_path().as_deref(),
+    )?;
+
+    if let Some(cache_path) = &args.print_cache {
+        let markup = merged.markup;
+        return print_cache(
+            cache_path,
+            args.stale_after.map(Duration::from_secs),
+            markup.unwrap_or_default(),
         );
-        Ok(())
+    }
+    match args.command.take() {
+        Some(Command::Ctl { command }) => {
+            let socket = merged.control_socket_path();
+            let response = send_command(&socket, &command.into()).await?;
+            if response != "ok" {
+                println!("{response}");
+            }
+            return Ok(());
+        }
+        Some(Command::CopyLast) => {
+            let mut overrides = args.overrides();
+            overrides.stdout = Some(true);
+            let settings = ConfigWatcher::new(config_path, overrides).load()?;
+            let Some(event) = last::latest_reasoning(&settings)? else {
+                bail!("No reasoning to copy");
+            };
+            return clipboard::copy(&event.text);
+        }
+        Some(Command::Demo) => {
+            let dir = scratch_dir("demo");
+            let rollout = demo::create_rollout(&dir)?;
+            let player = tokio::spawn(demo::play(rollout.clone()));
+            return follow_scratch(config_path, args.overrides(), &dir, rollout, player).await;
+        }
+        Some(Command::Doctor) => {
+            // The cache file is checked on its own, so it is not required here.
+            let mut overrides = args.overrides();
+            overrides.stdout = Some(true);
+            let settings = ConfigWatcher::new(config_path, overrides).load()?;
+            if !doctor::report(&doctor::run(&settings)) {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Open) => {
+            let mut overrides = args.overrides();
+            overrides.stdout = Some(true);
+            let mut settings = ConfigWatcher::new(config_path, overrides).load()?;
+            // The daemon knows which session the bar shows; without one, the
+            // session with the newest event stands in.
+            if settings.session_id.is_none()
+                && settings.session_file.is_none()
+                && let Ok(status) =
+                    send_command(&merged.control_socket_path(), &ControlCommand::Status).await
+            {
+                let status: serde_json::Value = serde_json::from_str(&status)?;
+                settings.session_id = status["displayed"].as_str().map(str::to_string);
+            }
+            let event = daemon::render_latest(&settings)?.context("No session to open")?;
+            return open::launch(&settings, &event);
+        }
+        Some(Command::Service { command }) => {
+            let daemon_args = global_args(&matches);
+            let unit_dir = Layout::from_env(None)?.systemd_user_dir;
+            let socket = merged.control_socket_path();
+            if !service::run(command.into(), &unit_dir, &daemon_args, &socket).await? {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Replay {
+            path,
+            speed,
+            interval_ms,
+        }) => {
+            let pacing = match (speed, interval_ms) {
+                (_, Some(interval)) => Pacing::Fixed(Duration::from_millis(interval)),
+                (Some(speed), None) if !(speed.is_finite() && speed > 0.0) => {
+                    bail!("--speed must be a positive number")
+                }
+                (speed, None) => Pacing::Timed(speed.unwrap_or(1.0)),
+            };
+            let options = ConfigWatcher::new(config_path.clone(), args.overrides())
+                .load()?
+                .render_options();
+            let dir = scratch_dir("replay");
+            let copy = replay::prepare(&path, &dir)?;
+            let player = tokio::spawn(replay::play(path, copy.clone(), pacing, options));
+            return follow_scratch(config_path, args.overrides(), &dir, copy, player).await;
+        }
+        Some(Command::Last {
+            count,
+            session,
+            json,
+        }) => {
+            let mut overrides = args.overrides();
+            overrides.stdout = Some(true);
+            overrides.session_id = session.or(overrides.session_id);
+            let settings = ConfigWatcher::new(config_path, overrides).load()?;
+            for event in last::recent(&settings, count)? {
+                if json {
+                    println!("{}", serde_json::to_string(&event)?);
+                } else {
+                    println!("{}", last::format_recent(&event));
+                }
+            }
+            return Ok(());
+        }
+        Some(Command::Search {
+            pattern,
+            ignore_case,
+            context,
+            limit,
+            store,
+        }) => {
+            let pattern = RegexBuilder::new(&pattern)
+                .case_insensitive(ignore_case)
+                .build()
+                .with_context(|| format!("Invalid pattern `{pattern}`"))?;
+            let context = context.unwrap_or(1);
+            let hits = if store {
+                search_event_store(&merged.event_store_path()?, &pattern, context)?
+            } else {
+                let mut overrides = args.overrides();
+                overrides.stdout = Some(true);
+                let settings = ConfigWatcher::new(config_path, overrides).load()?;
+                search::search_logs(&settings, &pattern, context, limit.unwrap_or(100))?
+            };
+            let printed: Vec<String> = hits.iter().map(search::format_hit).collect();
+            print!("{}", printed.join("\n"));
+            if hits.is_empty() {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::SetupWaybar { remove, waybar_dir }) => {
+            let env_dir = |name: &str| std::env::var_os(name).map(PathBuf::from);
+            let dir = waybar_dir
+                .or_else(|| env_dir("WAYBAR_CONFIG_DIR"))
+                .or_else(|| dirs::config_dir().map(|dir| dir.join("waybar")))
+                .context("Unable to determine the Waybar config directory")?;
+            let backups = env_dir("WAYBAR_BACKUP_ROOT")
+                .or_else(|| dirs::data_dir().map(|dir| dir.join("codex-shimmer/backups")))
+                .context("Unable to determine the backup directory")?;
+            let changed = if remove {
+                waybar::remove(&dir, &backups)?
+            } else {
+                waybar::install(&dir, &backups)?
+            };
+            if changed {
+                println!("Restart Waybar to apply the change.");
+            }
+            return Ok(());
+        }
+        Some(Command::Stats { week, json, .. }) => {
+            return print_stats(&merged.event_store_path()?, week, json);
+        }
+        Some(Command::Tui) => {
+            let mut overrides = args.overrides();
+            overrides.stdout = Some(true);
+            return run_tui(ConfigWatcher::new(config_path, overrides).load()?);
+        }
+        Some(Command::Uninstall {
+            dry_run,
+            keep_config,
+            prefix,
+        }) => {
+            let layout = Layout::from_env(prefix)?;
+            uninstall::execute(&uninstall::plan(&layout, keep_config), dry_run)?;
+            if !dry_run {
+                println!(
+                    "codex-shimmer has been uninstalled; restart Waybar to unload the module."
+                );
+            }
+            return Ok(());
+        }
+        None => {}
     }
 
-    #[test]
-    fn select_newer_event_prefers_newer_timestamp() {
-        let older = SessionEvent {
-            session_id: "alpha".to_string(),
-            event: RenderedEvent {
-                payload: WaybarOutput {
-                    text: "Old".to_string(),
-                    tooltip: None,
-                    alt: None,
-                    class: vec![],
-                },
-                timestamp: Some("2025-10-29T10:00:00Z".to_string()),
-            },
+    if args.once {
+        let mut overrides = args.overrides();
+        overrides.stdout = Some(true);
+        let settings = ConfigWatcher::new(config_path, overrides).load()?;
+        let mut payload = match daemon::render_latest(&settings)? {
+            Some(event) => event.event.payload,
+            None => placeholder_payload(),
         };
-        let newer = SessionEvent {
-            session_id: "beta".to_string(),
-            event: RenderedEvent {
-                payload: WaybarOutput {
-                    text: "New".to_string(),
-                    tooltip: None,
-                    alt: None,
-                    class: vec![],
-                },
-                timestamp: Some("2025-10-29T11:00:00Z".to_string()),
-            },
-        };
-
-        let picked = select_newer_event(Some(older.clone()), newer.clone()).unwrap();
-        assert_eq!(picked.session_id, "beta");
-
-        let unchanged = select_newer_event(Some(newer.clone()), older.clone()).unwrap();
-        assert_eq!(unchanged.session_id, "beta");
+        settings.payload.stamp(&mut payload);
+        return write_payload_to_stdout(&payload);
     }
 
-    #[test]
-    fn merge_session_targets_keeps_recently_active_sessions() {
-        let mut meta = HashMap::new();
-        let mut active_meta = SessionMeta::new();
-        active_meta.record_event(Some("2025-10-29T12:00:00Z".to_string()));
-        meta.insert("old-session".to_string(), active_meta);
-
-        let discovered = vec!["new-session".to_string()];
-        let merged = merge_session_targets(&discovered, &mut meta, 2);
+    daemon::run(ConfigWatcher::new(config_path, args.overrides())).await
+}
 
-        assert!(merged.contains(&"new-session".to_string()));
-        assert!(merged.contains(&"old-session".to_string()));
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
     #[test]
-    fn emit_payload_writes_payload() -> Result<()> {
-        let dir = tempdir()?;
-        let cache_path = dir.path().join("cache.json");
-        let event = RenderedEvent {
-            payload: WaybarOutput {
-                text: "Hello".to_string(),
-                tooltip: Some("Tooltip".to_string()),
-                alt: Some("phase".to_string()),
-                class: vec!["codex".to_string()],
-            },
-            timestamp: None,
-        };
-
-        emit_payload(&event, &cache_path, None)?;
-
-        let written = fs::read_to_string(&cache_path)?;
-        let parsed: Value = serde_json::from_str(written.trim())?;
-        assert_eq!(parsed["text"].as_str(), Some("Hello"));
-        assert_eq!(parsed["tooltip"].as_str(), Some("Tooltip"));
-        Ok(())
+    fn service_unit_keeps_flag_values_named_service() {
+        let matches = Args::command().get_matches_from([
+            "codex-shimmer",
+            "--cache-file",
+            "service",
+            "--session-id=service",
+            "--no-default-redactions",
+            "--redact",
+            "a",
+            "--redact",
+            "b",
+            "service",
+            "enable",
+        ]);
+        assert_eq!(
+            global_args(&matches),
+            [
+                "--cache-file=service",
+                "--session-id=service",
+                "--no-default-redactions",
+                "--redact=a",
+                "--redact=b",
+            ]
+            .map(OsString::from)
+        );
     }
 }
@@ -0,0 +1,297 @@
+//! Counters and gauges about the daemon, served over HTTP in the Prometheus
+//! text format with `--metrics-addr` and forwarded to StatsD with
+//! `--statsd-addr`.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    net::SocketAddr,
+    sync::{
+        Mutex, RwLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+use tracing::warn;
+
+use crate::statsd::Statsd;
+
+/// Upper bounds of the cache write latency buckets, in seconds.
+const WRITE_BUCKETS: [f64; 8] = [0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.1, 1.0];
+
+/// The process-wide metrics, updated wherever the measured work happens.
+pub static METRICS: Metrics = Metrics::new();
+
+#[derive(Debug)]
+pub struct Metrics {
+    /// Log entries decoded, by entry type.
+    events: Mutex<BTreeMap<String, u64>>,
+    parse_errors: AtomicU64,
+    tracked_sessions: AtomicU64,
+    cache_writes: Mutex<Histogram>,
+    last_event: Mutex<Option<Instant>>,
+    /// Where each update is also sent, if anywhere.
+    statsd: RwLock<Option<Statsd>>,
+}
+
+#[derive(Debug)]
+struct Histogram {
+    buckets: [u64; WRITE_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Metrics {
+    pub const fn new() -> Self {
+        Metrics {
+            events: Mutex::new(BTreeMap::new()),
+            parse_errors: AtomicU64::new(0),
+            tracked_sessions: AtomicU64::new(0),
+            cache_writes: Mutex::new(Histogram {
+                buckets: [0; WRITE_BUCKETS.len()],
+                count: 0,
+                sum: 0.0,
+            }),
+            last_event: Mutex::new(None),
+            statsd: RwLock::new(None),
+        }
+    }
+
+    pub fn set_statsd(&self, statsd: Option<Statsd>) {
+        *self.statsd.write().expect("metrics lock poisoned") = statsd;
+    }
+
+    fn statsd(&self, send: impl FnOnce(&Statsd)) {
+        if let Some(statsd) = &*self.statsd.read().expect("metrics lock poisoned") {
+            send(statsd);
+        }
+    }
+
+    pub fn record_event(&self, kind: &str) {
+        let mut events = self.events.lock().expect("metrics lock poisoned");
+        *events.entry(kind.to_string()).or_default() += 1;
+        *self.last_event.lock().expect("metrics lock poisoned") = Some(Instant::now());
+        self.statsd(|statsd| statsd.count("events", Some(("type", kind))));
+    }
+
+    /// Count a malformed log line, returning the count so far.
+    pub fn record_parse_error(&self) -> u64 {
+        self.statsd(|statsd| statsd.count("parse_errors", None));
+        self.parse_errors.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    pub fn set_tracked_sessions(&self, count: usize) {
+        self.tracked_sessions.store(count as u64, Ordering::Relaxed);
+        self.statsd(|statsd| statsd.gauge("tracked_sessions", count as u64));
+    }
+
+    pub fn record_cache_write(&self, took: Duration) {
+        self.statsd(|statsd| statsd.timing("cache_write", took));
+        let seconds = took.as_secs_f64();
+        let mut writes = self.cache_writes.lock().expect("metrics lock poisoned");
+        for (bucket, bound) in writes.buckets.iter_mut().zip(WRITE_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        writes.count += 1;
+        writes.sum += seconds;
+    }
+
+    /// Every metric in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        out.push_str("# HELP codex_shimmer_events_total Log entries processed, by type.\n");
+        out.push_str("# TYPE codex_shimmer_events_total counter\n");
+        for (kind, count) in self.events.lock().expect("metrics lock poisoned").iter() {
+            let kind = if kind.is_empty() { "unknown" } else { kind };
+            let _ = writeln!(
+                out,
+                "codex_shimmer_events_total{{type=\"{}\"}} {count}",
+                escape_label(kind)
+            );
+        }
+
+        out.push_str(
+            "# HELP codex_shimmer_parse_errors_total Log lines that were not valid JSON or had unexpected fields.\n",
+        );
+        out.push_str("# TYPE codex_shimmer_parse_errors_total counter\n");
+        let _ = writeln!(
+            out,
+            "codex_shimmer_parse_errors_total {}",
+            self.parse_errors.load(Ordering::Relaxed)
+        );
+
+        out.push_str("# HELP codex_shimmer_tracked_sessions Sessions being tailed.\n");
+        out.push_str("# TYPE codex_shimmer_tracked_sessions gauge\n");
+        let _ = writeln!(
+            out,
+            "codex_shimmer_tracked_sessions {}",
+            self.tracked_sessions.load(Ordering::Relaxed)
+        );
+
+        out.push_str("# HELP codex_shimmer_cache_write_seconds Time to write a cache file.\n");
+        out.push_str("# TYPE codex_shimmer_cache_write_seconds histogram\n");
+        {
+            let writes = self.cache_writes.lock().expect("metrics lock poisoned");
+            for (count, bound) in writes.buckets.iter().zip(WRITE_BUCKETS) {
+                let _ = writeln!(
+                    out,
+                    "codex_shimmer_cache_write_seconds_bucket{{le=\"{bound}\"}} {count}"
+                );
+            }
+            let _ = writeln!(
+                out,
+                "codex_shimmer_cache_write_seconds_bucket{{le=\"+Inf\"}} {}",
+                writes.count
+            );
+            let _ = writeln!(out, "codex_shimmer_cache_write_seconds_sum {}", writes.sum);
+            let _ = writeln!(
+                out,
+                "codex_shimmer_cache_write_seconds_count {}",
+                writes.count
+            );
+        }
+
+        // Without an event yet there is no age to report.
+        if let Some(last) = *self.last_event.lock().expect("metrics lock poisoned") {
+            out.push_str(
+                "# HELP codex_shimmer_last_event_age_seconds Seconds since the last log entry.\n",
+            );
+            out.push_str("# TYPE codex_shimmer_last_event_age_seconds gauge\n");
+            let _ = writeln!(
+                out,
+                "codex_shimmer_last_event_age_seconds {}",
+                last.elapsed().as_secs_f64()
+            );
+        }
+        out
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n")
+}
+
+pub async fn bind(addr: SocketAddr) -> Result<TcpListener> {
+    TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind {addr}"))
+}
+
+/// Answer `GET /metrics` with the process-wide metrics.
+pub async fn serve(listener: TcpListener) {
+    loop {
+        let Ok((stream, _)) = listener.accept().await else {
+            continue;
+        };
+        tokio::spawn(async move {
+            if let Err(err) = handle_connection(stream, &METRICS).await {
+                warn!(error = %format!("{err:#}"), "Metrics connection failed");
+            }
+        });
+    }
+}
+
+async fn handle_connection(stream: TcpStream, metrics: &Metrics) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    let mut request = String::new();
+    reader.read_line(&mut request).await?;
+    // Drain the headers so the client sees its request was read.
+    let mut header = String::new();
+    while reader.read_line(&mut header).await? > 2 {
+        header.clear();
+    }
+
+    let mut words = request.split_whitespace();
+    let (status, content_type, body) = match (words.next(), words.next()) {
+        (Some("GET"), Some("/metrics")) => {
+            ("200 OK", "text/plain; version=0.0.4", metrics.render())
+        }
+        _ => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+    };
+    let head = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n",
+        body.len()
+    );
+    writer.write_all(head.as_bytes()).await?;
+    writer.write_all(body.as_bytes()).await?;
+    writer.shutdown().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    #[test]
+    fn metrics_render_in_text_format() {
+        let metrics = Metrics::new();
+        assert!(!metrics.render().contains("last_event_age"));
+
+        metrics.record_event("agent_reasoning");
+        metrics.record_event("agent_reasoning");
+        metrics.record_event("say \"hi\"");
+        metrics.record_parse_error();
+        metrics.set_tracked_sessions(3);
+        metrics.record_cache_write(Duration::from_millis(2));
+
+        let text = metrics.render();
+        for line in [
+            "codex_shimmer_events_total{type=\"agent_reasoning\"} 2",
+            "codex_shimmer_events_total{type=\"say \\\"hi\\\"\"} 1",
+            "codex_shimmer_parse_errors_total 1",
+            "codex_shimmer_tracked_sessions 3",
+            "codex_shimmer_cache_write_seconds_bucket{le=\"0.001\"} 0",
+            "codex_shimmer_cache_write_seconds_bucket{le=\"0.0025\"} 1",
+            "codex_shimmer_cache_write_seconds_bucket{le=\"+Inf\"} 1",
+            "codex_shimmer_cache_write_seconds_count 1",
+        ] {
+            assert!(
+                text.lines().any(|existing| existing == line),
+                "{line}\n{text}"
+            );
+        }
+        assert!(text.contains("codex_shimmer_last_event_age_seconds "));
+    }
+
+    #[tokio::test]
+    async fn only_metrics_path_is_served() -> Result<()> {
+        let listener = bind("127.0.0.1:0".parse()?).await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(serve(listener));
+
+        let get = |path: &'static str| async move {
+            let mut stream = TcpStream::connect(addr).await?;
+            stream
+                .write_all(format!("GET {path} HTTP/1.1\r\nHost: x\r\n\r\n").as_bytes())
+                .await?;
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await?;
+            anyhow::Ok(response)
+        };
+        let metrics = get("/metrics").await?;
+        assert!(metrics.starts_with("HTTP/1.1 200 OK"));
+        assert!(metrics.contains("# TYPE codex_shimmer_events_total counter"));
+        assert!(get("/").await?.starts_with("HTTP/1.1 404"));
+        Ok(())
+    }
+}
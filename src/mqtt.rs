@@ -0,0 +1,91 @@
+//! Publishes every payload the daemon shows to an MQTT topic, for home
+//! automation dashboards. Compiled only with the `mqtt` feature.
+
+use anyhow::{Context, Result};
+use rumqttc::{AsyncClient, MqttOptions};
+use tokio::{
+    sync::watch,
+    time::{Duration, sleep},
+};
+use tracing::warn;
+
+use crate::config::Settings;
+use crate::output::WaybarOutput;
+
+const DEFAULT_PORT: u16 = 1883;
+
+/// Publish each payload the coordinator publishes to `mqtt_topic`, retained
+/// so new subscribers see the current one. Returns once the coordinator goes
+/// away.
+pub async fn serve(
+    settings: &Settings,
+    mut published: watch::Receiver<Option<WaybarOutput>>,
+) -> Result<()> {
+    let Some(broker) = &settings.mqtt_broker else {
+        return Ok(());
+    };
+    let (host, port) = broker_address(broker)?;
+    let mut options = MqttOptions::new("codex-shimmer", host, port);
+    options.set_keep_alive(Duration::from_secs(30));
+    let (client, mut events) = AsyncClient::new(options, 16);
+    // The event loop drives the connection and reconnects on the next poll.
+    tokio::spawn(async move {
+        loop {
+            if let Err(err) = events.poll().await {
+                warn!(error = %err, "MQTT connection failed");
+                sleep(Duration::from_secs(5)).await;
+            }
+        }
+    });
+
+    let qos = rumqttc::qos(settings.mqtt_qos).context("Invalid MQTT QoS")?;
+    while published.changed().await.is_ok() {
+        let payload = published
+            .borrow_and_update()
+            .as_ref()
+            .and_then(|payload| serde_json::to_vec(payload).ok());
+        if let Some(payload) = payload {
+            client
+                .publish(settings.mqtt_topic.as_str(), qos, true, payload)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Host and port from `mqtt://host:port`, `host:port`, or `host`.
+fn broker_address(broker: &str) -> Result<(String, u16)> {
+    let address = broker
+        .strip_prefix("mqtt://")
+        .or_else(|| broker.strip_prefix("tcp://"))
+        .unwrap_or(broker)
+        .trim_end_matches('/');
+    match address.rsplit_once(':') {
+        Some((host, port)) => {
+            let port = port
+                .parse()
+                .with_context(|| format!("Invalid MQTT broker port in `{broker}`"))?;
+            Ok((host.to_string(), port))
+        }
+        None => Ok((address.to_string(), DEFAULT_PORT)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn broker_addresses_default_the_port() -> Result<()> {
+        assert_eq!(
+            broker_address("mqtt://hub.lan:8883")?,
+            ("hub.lan".to_string(), 8883)
+        );
+        assert_eq!(
+            broker_address("localhost")?,
+            ("localhost".to_string(), 1883)
+        );
+        assert!(broker_address("hub.lan:mqtt").is_err());
+        Ok(())
+    }
+}
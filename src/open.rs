@@ -0,0 +1,139 @@
+//! `codex-shimmer open`: resume the session the bar shows in a terminal, for
+//! a Waybar `on-click`.
+
+use std::{
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::agent::Agent;
+use crate::config::Settings;
+use crate::daemon::SessionEvent;
+use crate::template::Template;
+
+/// What `open` runs for Codex sessions without an `open-command`.
+const CODEX_RESUME: &str = "codex resume {session}";
+/// The same for Claude Code.
+const CLAUDE_RESUME: &str = "claude --resume {session}";
+
+/// The terminal followed by the `open-command` words for `event`'s session.
+pub fn command_line(settings: &Settings, event: &SessionEvent) -> Result<Vec<String>> {
+    let template: Template = match (&settings.open_command, settings.agent) {
+        (Some(template), _) => template.clone(),
+        (None, Agent::Codex) => CODEX_RESUME.parse()?,
+        (None, Agent::Claude) => CLAUDE_RESUME.parse()?,
+        (None, _) => bail!("This agent has no resume command; set `open-command`"),
+    };
+    let info = event.event.payload.session.as_ref();
+    let value = |name: &str| match name {
+        "session" => Some(event.session_id.clone()),
+        "session_short" => Some(event.session_id.chars().take(8).collect()),
+        "cwd" => info.and_then(|info| info.cwd.clone()),
+        "model" => info.and_then(|info| info.model.clone()),
+        _ => None,
+    };
+    let mut words: Vec<String> = settings
+        .terminal
+        .split_whitespace()
+        .map(str::to_string)
+        .collect();
+    // Split before expanding, so a value with spaces stays one argument.
+    for word in template.to_string().split_whitespace() {
+        let word = word.parse::<Template>()?.render(value);
+        if !word.is_empty() {
+            words.push(word);
+        }
+    }
+    Ok(words)
+}
+
+/// Start the command for `event`'s session in its working directory, and
+/// leave it running.
+pub fn launch(settings: &Settings, event: &SessionEvent) -> Result<()> {
+    let words = command_line(settings, event)?;
+    let Some((program, args)) = words.split_first() else {
+        bail!("`terminal` and `open-command` are both empty");
+    };
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    if let Some(cwd) = event
+        .event
+        .payload
+        .session
+        .as_ref()
+        .and_then(|info| info.cwd.as_deref())
+        .filter(|cwd| Path::new(cwd).is_dir())
+    {
+        command.current_dir(cwd);
+    }
+    command
+        .spawn()
+        .with_context(|| format!("Failed to run {program}"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::output::{SessionInfo, WaybarOutput};
+    use crate::render::RenderedEvent;
+
+    #[test]
+    fn commands_resume_the_session_in_the_terminal() -> Result<()> {
+        let event = SessionEvent {
+            session_id: "0199a213-81c0-7800".to_string(),
+            event: RenderedEvent {
+                payload: WaybarOutput {
+                    text: "Writing tests".to_string(),
+                    tooltip: None,
+                    class: Vec::new(),
+                    session: Some(SessionInfo {
+                        cwd: Some("/home/me/proj".to_string()),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                timestamp: None,
+            },
+            sequence: 0,
+        };
+
+        let settings = Settings::resolve(&Config {
+            stdout: Some(true),
+            ..Default::default()
+        })?;
+        assert_eq!(
+            command_line(&settings, &event)?,
+            ["xdg-terminal-exec", "codex", "resume", "0199a213-81c0-7800"]
+        );
+
+        let settings = Settings::resolve(&Config {
+            stdout: Some(true),
+            terminal: Some("alacritty -e".to_string()),
+            open_command: Some("nvim {cwd}/NOTES.md".parse()?),
+            ..Default::default()
+        })?;
+        assert_eq!(
+            command_line(&settings, &event)?,
+            ["alacritty", "-e", "nvim", "/home/me/proj/NOTES.md"]
+        );
+
+        let mut spaced = event.clone();
+        spaced.event.payload.session = Some(SessionInfo {
+            cwd: Some("/home/me/My Project".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(
+            command_line(&settings, &spaced)?,
+            ["alacritty", "-e", "nvim", "/home/me/My Project/NOTES.md"]
+        );
+        Ok(())
+    }
+}
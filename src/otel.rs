@@ -0,0 +1,447 @@
+//! Exports session activity to an OpenTelemetry collector over OTLP/HTTP with
+//! JSON encoding: a span per turn with a child span per tool call, and token
+//! counts as metrics. Compiled only with the `otel` feature.
+
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{Value, json};
+use tokio::{sync::mpsc, task::spawn_blocking, time::interval};
+use tracing::warn;
+
+use crate::render::{EventPayload, LogEntry, ToolResult, parse_timestamp};
+
+/// How often buffered records are sent to the collector.
+const FLUSH_EVERY: Duration = Duration::from_secs(5);
+const TIMEOUT: Duration = Duration::from_secs(10);
+const SERVICE_NAME: &str = "codex-shimmer";
+
+/// Something to export, produced as log entries are rendered.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Record {
+    Span(Span),
+    Tokens(TokenSample),
+}
+
+/// A finished turn or tool call.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub trace_id: String,
+    pub span_id: String,
+    pub parent_span_id: Option<String>,
+    pub name: String,
+    /// Unix time in nanoseconds.
+    pub start: u64,
+    pub end: u64,
+    pub attributes: Vec<(&'static str, String)>,
+    pub failed: bool,
+}
+
+/// A session's token totals as of one `token_count` entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenSample {
+    pub session_id: String,
+    /// When counting started, in Unix nanoseconds.
+    pub start: u64,
+    pub time: u64,
+    pub counts: Vec<(&'static str, u64)>,
+}
+
+#[derive(Debug, Clone)]
+struct OpenSpan {
+    trace_id: String,
+    span_id: String,
+    name: String,
+    start: u64,
+    attributes: Vec<(&'static str, String)>,
+}
+
+/// Turns one session's log entries into spans and token samples.
+#[derive(Debug, Clone, Default)]
+pub struct SpanRecorder {
+    model: Option<String>,
+    first_seen: Option<u64>,
+    turn: Option<OpenSpan>,
+    calls: HashMap<String, OpenSpan>,
+    records: Vec<Record>,
+}
+
+impl SpanRecorder {
+    pub fn observe(&mut self, session_id: &str, entry: &LogEntry) {
+        let time = unix_nanos(entry.timestamp.as_deref());
+        let started = *self.first_seen.get_or_insert(time);
+        let call_id = entry.event.call_id();
+        match &entry.event {
+            EventPayload::TurnContext(header) => self.model = header.model.clone(),
+            EventPayload::TaskStarted => {
+                self.finish_turn(time, false);
+                let mut attributes = vec![("session.id", session_id.to_string())];
+                attributes.extend(
+                    self.model
+                        .clone()
+                        .map(|model| ("gen_ai.request.model", model)),
+                );
+                let key = [session_id, &time.to_string()];
+                self.turn = Some(OpenSpan {
+                    trace_id: hex_id(16, &key),
+                    span_id: hex_id(8, &key),
+                    name: "turn".to_string(),
+                    start: time,
+                    attributes,
+                });
+            }
+            EventPayload::TaskComplete => self.finish_turn(time, false),
+            EventPayload::TurnAborted => self.finish_turn(time, true),
+            EventPayload::ExecCommandBegin { command, .. } => self.begin_call(
+                session_id,
+                call_id,
+                "exec",
+                time,
+                ("process.command_line", command.text()),
+            ),
+            EventPayload::McpToolCallBegin { invocation, .. } => self.begin_call(
+                session_id,
+                call_id,
+                "tool",
+                time,
+                (
+                    "gen_ai.tool.name",
+                    invocation.clone().unwrap_or_default().name(),
+                ),
+            ),
+            EventPayload::PatchApplyBegin { changes, .. } => {
+                let files = changes.keys().cloned().collect::<Vec<_>>().join(", ");
+                self.begin_call(
+                    session_id,
+                    call_id,
+                    "apply_patch",
+                    time,
+                    ("codex.files", files),
+                );
+            }
+            EventPayload::ExecCommandEnd { exit_code, .. } => {
+                self.finish_call(call_id, time, exit_code.is_some_and(|code| code != 0));
+            }
+            EventPayload::PatchApplyEnd { success, .. } => {
+                self.finish_call(call_id, time, *success == Some(false));
+            }
+            EventPayload::McpToolCallEnd { result, .. } => {
+                let failed = result.as_ref().is_some_and(ToolResult::failed);
+                self.finish_call(call_id, time, failed);
+            }
+            EventPayload::TokenCount { info } => {
+                let Some(usage) = info.as_ref().and_then(|info| info.total_token_usage) else {
+                    return;
+                };
+                self.records.push(Record::Tokens(TokenSample {
+                    session_id: session_id.to_string(),
+                    start: started,
+                    time,
+                    counts: vec![
+                        ("input", usage.input_tokens),
+                        ("cached_input", usage.cached_input_tokens),
+                        ("output", usage.output_tokens),
+                        ("reasoning_output", usage.reasoning_output_tokens),
+                    ],
+                }));
+            }
+            _ => {}
+        }
+    }
+
+    /// The records finished since the last call.
+    pub fn take(&mut self) -> Vec<Record> {
+        std::mem::take(&mut self.records)
+    }
+
+    fn begin_call(
+        &mut self,
+        session_id: &str,
+        call_id: &str,
+        name: &str,
+        time: u64,
+        attribute: (&'static str, String),
+    ) {
+        let trace_id = match &self.turn {
+            Some(turn) => turn.trace_id.clone(),
+            // Older rollouts have no task events to hang calls from.
+            None => hex_id(16, &[session_id, call_id]),
+        };
+        self.calls.insert(
+            call_id.to_string(),
+            OpenSpan {
+                trace_id,
+                span_id: hex_id(8, &[session_id, call_id]),
+                name: name.to_string(),
+                start: time,
+                attributes: vec![("session.id", session_id.to_string()), attribute],
+            },
+        );
+    }
+
+    fn finish_call(&mut self, call_id: &str, time: u64, failed: bool) {
+        let Some(call) = self.calls.remove(call_id) else {
+            return;
+        };
+        let parent_span_id = self
+            .turn
+            .as_ref()
+            .filter(|turn| turn.trace_id == call.trace_id)
+            .map(|turn| turn.span_id.clone());
+        self.records
+            .push(Record::Span(call.finish(parent_span_id, time, failed)));
+    }
+
+    fn finish_turn(&mut self, time: u64, aborted: bool) {
+        if let Some(turn) = self.turn.take() {
+            self.records
+                .push(Record::Span(turn.finish(None, time, aborted)));
+        }
+    }
+}
+
+impl OpenSpan {
+    fn finish(self, parent_span_id: Option<String>, end: u64, failed: bool) -> Span {
+        Span {
+            trace_id: self.trace_id,
+            span_id: self.span_id,
+            parent_span_id,
+            name: self.name,
+            start: self.start,
+            end: end.max(self.start),
+            attributes: self.attributes,
+            failed,
+        }
+    }
+}
+
+/// A stable id of `bytes` bytes, hex-encoded, so a turn replayed after a
+/// restart keeps its ids.
+fn hex_id(bytes: usize, parts: &[&str]) -> String {
+    (0..bytes.div_ceil(8))
+        .map(|block| {
+            let mut hasher = DefaultHasher::new();
+            (block, parts).hash(&mut hasher);
+            format!("{:016x}", hasher.finish())
+        })
+        .collect::<String>()[..bytes * 2]
+        .to_string()
+}
+
+fn unix_nanos(timestamp: Option<&str>) -> u64 {
+    timestamp
+        .and_then(parse_timestamp)
+        .unwrap_or_else(SystemTime::now)
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_nanos() as u64)
+}
+
+/// Send records to the collector at `endpoint` (such as
+/// `http://localhost:4318`) in batches until every sender is dropped.
+/// Failed batches are reported on stderr and dropped.
+pub async fn export(endpoint: String, mut records: mpsc::UnboundedReceiver<Record>) {
+    let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+    let endpoint = endpoint.trim_end_matches('/').to_string();
+    let mut batch = Vec::new();
+    let mut ticks = interval(FLUSH_EVERY);
+    loop {
+        tokio::select! {
+            record = records.recv() => match record {
+                Some(record) => batch.push(record),
+                None => break,
+            },
+            _ = ticks.tick() => flush(&agent, &endpoint, std::mem::take(&mut batch)).await,
+        }
+    }
+    flush(&agent, &endpoint, batch).await;
+}
+
+async fn flush(agent: &ureq::Agent, endpoint: &str, batch: Vec<Record>) {
+    let (mut spans, mut tokens) = (Vec::new(), Vec::new());
+    for record in batch {
+        match record {
+            Record::Span(span) => spans.push(span),
+            Record::Tokens(sample) => tokens.push(sample),
+        }
+    }
+    for (path, body) in [
+        (
+            "v1/traces",
+            (!spans.is_empty()).then(|| traces_body(&spans)),
+        ),
+        (
+            "v1/metrics",
+            (!tokens.is_empty()).then(|| metrics_body(&tokens)),
+        ),
+    ] {
+        let Some(body) = body else {
+            continue;
+        };
+        let (agent, url) = (agent.clone(), format!("{endpoint}/{path}"));
+        let sent = spawn_blocking(move || {
+            agent
+                .post(&url)
+                .set("Content-Type", "application/json")
+                .send_string(&body.to_string())
+                .map_err(|err| format!("{url}: {err}"))
+        })
+        .await;
+        match sent {
+            Ok(Ok(_)) => {}
+            Ok(Err(err)) => warn!(error = %err, "OpenTelemetry export failed"),
+            Err(err) => warn!(error = %err, "OpenTelemetry export failed"),
+        }
+    }
+}
+
+fn resource() -> Value {
+    json!({ "attributes": attributes(&[("service.name", SERVICE_NAME.to_string())]) })
+}
+
+fn scope() -> Value {
+    json!({ "name": SERVICE_NAME, "version": env!("CARGO_PKG_VERSION") })
+}
+
+fn attributes(pairs: &[(&str, String)]) -> Value {
+    pairs
+        .iter()
+        .map(|(key, value)| json!({ "key": key, "value": { "stringValue": value } }))
+        .collect()
+}
+
+fn traces_body(spans: &[Span]) -> Value {
+    let spans: Vec<Value> = spans
+        .iter()
+        .map(|span| {
+            let mut encoded = json!({
+                "traceId": span.trace_id,
+                "spanId": span.span_id,
+                "name": span.name,
+                // SPAN_KIND_INTERNAL
+                "kind": 1,
+                "startTimeUnixNano": span.start.to_string(),
+                "endTimeUnixNano": span.end.to_string(),
+                "attributes": attributes(&span.attributes),
+                // STATUS_CODE_ERROR or STATUS_CODE_UNSET
+                "status": { "code": if span.failed { 2 } else { 0 } },
+            });
+            if let Some(parent) = &span.parent_span_id {
+                encoded["parentSpanId"] = json!(parent);
+            }
+            encoded
+        })
+        .collect();
+    json!({
+        "resourceSpans": [{
+            "resource": resource(),
+            "scopeSpans": [{ "scope": scope(), "spans": spans }],
+        }]
+    })
+}
+
+fn metrics_body(samples: &[TokenSample]) -> Value {
+    let points: Vec<Value> = samples
+        .iter()
+        .flat_map(|sample| {
+            sample.counts.iter().map(|(kind, count)| {
+                json!({
+                    "attributes": attributes(&[
+                        ("session.id", sample.session_id.clone()),
+                        ("gen_ai.token.type", kind.to_string()),
+                    ]),
+                    "startTimeUnixNano": sample.start.to_string(),
+                    "timeUnixNano": sample.time.to_string(),
+                    "asInt": count.to_string(),
+                })
+            })
+        })
+        .collect();
+    json!({
+        "resourceMetrics": [{
+            "resource": resource(),
+            "scopeMetrics": [{
+                "scope": scope(),
+                "metrics": [{
+                    "name": "codex.tokens",
+                    "description": "Tokens used by a session so far.",
+                    "unit": "{token}",
+                    // AGGREGATION_TEMPORALITY_CUMULATIVE
+                    "sum": { "aggregationTemporality": 2, "isMonotonic": true, "dataPoints": points },
+                }],
+            }],
+        }]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(timestamp: &str, payload: Value) -> LogEntry {
+        LogEntry::parse(
+            &json!({ "timestamp": timestamp, "type": "event_msg", "payload": payload }).to_string(),
+        )
+        .expect("log entry")
+    }
+
+    #[test]
+    fn turns_and_calls_become_nested_spans() {
+        let mut recorder = SpanRecorder::default();
+        for (timestamp, payload) in [
+            ("2025-10-29T12:00:00Z", json!({ "type": "task_started" })),
+            (
+                "2025-10-29T12:00:01Z",
+                json!({ "type": "exec_command_begin", "call_id": "c1", "command": ["cargo", "test"] }),
+            ),
+            (
+                "2025-10-29T12:00:04Z",
+                json!({ "type": "exec_command_end", "call_id": "c1", "exit_code": 101 }),
+            ),
+            (
+                "2025-10-29T12:00:05Z",
+                json!({
+                    "type": "token_count",
+                    "info": { "total_token_usage": { "input_tokens": 900, "output_tokens": 40 } }
+                }),
+            ),
+            ("2025-10-29T12:00:09Z", json!({ "type": "task_complete" })),
+        ] {
+            recorder.observe("s1", &entry(timestamp, payload));
+        }
+
+        let records = recorder.take();
+        let [
+            Record::Span(call),
+            Record::Tokens(tokens),
+            Record::Span(turn),
+        ] = &records[..]
+        else {
+            panic!("unexpected records: {records:?}");
+        };
+        assert_eq!(turn.name, "turn");
+        assert_eq!(turn.end - turn.start, 9_000_000_000);
+        assert!(!turn.failed && turn.parent_span_id.is_none());
+        assert_eq!(call.name, "exec");
+        assert!(call.failed);
+        assert_eq!(call.trace_id, turn.trace_id);
+        assert_eq!(call.parent_span_id.as_ref(), Some(&turn.span_id));
+        assert_eq!(call.trace_id.len(), 32);
+        assert_eq!(call.span_id.len(), 16);
+        assert!(
+            call.attributes
+                .contains(&("process.command_line", "cargo test".to_string()))
+        );
+        assert_eq!(tokens.counts[0], ("input", 900));
+        assert_eq!(tokens.counts[2], ("output", 40));
+        assert!(recorder.take().is_empty());
+
+        let body = traces_body(std::slice::from_ref(call));
+        let span = &body["resourceSpans"][0]["scopeSpans"][0]["spans"][0];
+        assert_eq!(span["status"]["code"], 2);
+        assert_eq!(span["parentSpanId"], json!(turn.span_id));
+        assert_eq!(span["startTimeUnixNano"], json!(call.start.to_string()));
+    }
+}
@@ -0,0 +1,815 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File},
+    io::{self, ErrorKind, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{mpsc, watch},
+    task::spawn_blocking,
+    time::{MissedTickBehavior, interval, sleep_until},
+};
+use tracing::warn;
+
+use crate::config::Settings;
+use crate::metrics::METRICS;
+use crate::render::{Markup, RenderedEvent, escape_markup};
+use crate::sink::{CacheSink, Pipeline, Sink};
+
+/// JSON object understood by Waybar `return-type: json` modules.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WaybarOutput {
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tooltip: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alt: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub class: Vec<String>,
+    /// Drives Waybar's `format-icons` and progress styling.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub percentage: Option<u8>,
+    /// Where the session runs; ignored by Waybar, kept for scripts reading
+    /// the cache.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<SessionInfo>,
+    /// Seconds since the session's last event while a turn is in progress,
+    /// with `--elapsed`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elapsed: Option<u64>,
+    /// Schema version, from `--payload v2` on; absent in the original schema.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u8>,
+    /// What the payload describes, with `--payload v2`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<PayloadMeta>,
+}
+
+/// The `meta` object of a v2 payload.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PayloadMeta {
+    pub session_id: String,
+    /// Kind of the log entry that last changed the display, such as
+    /// `agent_reasoning` or `exec_command_begin`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_type: Option<String>,
+    /// Slug of the current phase heading, as in the `phase-*` class.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phase: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+}
+
+/// Which payload schema to write.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PayloadVersion {
+    /// The fields Waybar reads, plus `session` and `elapsed`.
+    #[default]
+    V1,
+    /// Adds `version` and `meta`.
+    V2,
+}
+
+impl FromStr for PayloadVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "v1" | "1" => Ok(PayloadVersion::V1),
+            "v2" | "2" => Ok(PayloadVersion::V2),
+            other => bail!("unknown payload version `{other}` (expected `v1` or `v2`)"),
+        }
+    }
+}
+
+impl PayloadVersion {
+    /// Mark `payload` with this schema's version number.
+    pub fn stamp(self, payload: &mut WaybarOutput) {
+        if self == PayloadVersion::V2 {
+            payload.version = Some(2);
+        }
+    }
+}
+
+/// Session details from the rollout's `session_meta` and `turn_context` lines.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SessionInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub originator: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cli_version: Option<String>,
+}
+
+impl SessionInfo {
+    pub fn is_empty(&self) -> bool {
+        *self == SessionInfo::default()
+    }
+}
+
+/// A payload for the emitter to write.
+#[derive(Debug, Clone)]
+pub enum Emission {
+    /// The payload on display, for the cache file and stdout, and the
+    /// session it came from.
+    Display {
+        session_id: String,
+        event: RenderedEvent,
+    },
+    /// A payload for one of the extra cache files: a session's own under
+    /// `--cache-dir`, or a single view such as `--cache-status`.
+    File {
+        path: PathBuf,
+        payload: WaybarOutput,
+    },
+}
+
+/// How often the daemon marks the cache file as alive while nothing new is
+/// written to it; `--stale-after` must be longer.
+pub const HEARTBEAT: Duration = Duration::from_secs(30);
+
+/// Hand each rendered event to the sinks (cache file, stdout, webhook) in
+/// order, at most once per `--min-emit-interval-ms` for each output. Returns
+/// on the first failure of a sink.
+pub async fn run_emitter(
+    mut events: mpsc::Receiver<Emission>,
+    settings: watch::Receiver<Settings>,
+) -> Result<()> {
+    if settings.borrow().stdout {
+        let mut placeholder = placeholder_payload();
+        settings.borrow().payload.stamp(&mut placeholder);
+        write_payload_to_stdout(&placeholder)?;
+    }
+
+    let mut pipeline = Pipeline::new(&settings.borrow());
+    let mut throttle = Throttle::default();
+    let mut heartbeat = interval(HEARTBEAT);
+    heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        let interval = settings.borrow().min_emit_interval();
+        let due = throttle.next_due(interval);
+        let emission = tokio::select! {
+            emission = events.recv() => emission,
+            _ = heartbeat.tick() => {
+                // A quiet session is not a dead daemon: keep `--stale-after`
+                // from dimming a cache that is simply up to date.
+                let cache_file = settings.borrow().cache_file.clone();
+                if let Some(path) = cache_file {
+                    let touched = spawn_blocking(move || touch_cache(&path)).await?;
+                    if let Err(err) = touched {
+                        warn!(error = %format!("{err:#}"), "Failed to mark the cache file alive");
+                    }
+                }
+                continue;
+            }
+            _ = sleep_until(due.unwrap_or_else(Instant::now).into()), if due.is_some() => {
+                let current = settings.borrow().clone();
+                for (target, (payload, state)) in throttle.take_due(interval, Instant::now()) {
+                    deliver(&mut pipeline, &current, target, &payload, state).await?;
+                }
+                continue;
+            }
+        };
+        let Some(emission) = emission else {
+            break;
+        };
+        let current = settings.borrow().clone();
+        pipeline.update(&current);
+        let (target, mut payload, state) = match emission {
+            Emission::Display { session_id, event } => {
+                // Payloads from no session, such as the stopped one, keep the
+                // saved state of the last session event, which a restart
+                // compares its sessions against.
+                let state = (!session_id.is_empty()).then_some(EmitState {
+                    session_id,
+                    timestamp: event.timestamp,
+                });
+                (None, event.payload, state)
+            }
+            Emission::File { path, payload } => (Some(path), payload, None),
+        };
+        // Idle payloads do not come from a session, so stamp them here.
+        current.payload.stamp(&mut payload);
+        let now = Instant::now();
+        if let Some((payload, state)) =
+            throttle.admit(target.clone(), (payload, state), interval, now)
+        {
+            deliver(&mut pipeline, &current, target, &payload, state).await?;
+        }
+    }
+    let current = settings.borrow().clone();
+    for (target, (payload, state)) in throttle.drain() {
+        deliver(&mut pipeline, &current, target, &payload, state).await?;
+    }
+    Ok(())
+}
+
+/// Send `payload` to the sinks of the display, or to the cache file `target`.
+/// The display's `state` is saved beside the cache file.
+async fn deliver(
+    pipeline: &mut Pipeline,
+    settings: &Settings,
+    target: Option<PathBuf>,
+    payload: &WaybarOutput,
+    state: Option<EmitState>,
+) -> Result<()> {
+    let Some(path) = target else {
+        pipeline.deliver(payload).await?;
+        if let (Some(cache_file), Some(state)) = (settings.cache_file.clone(), state) {
+            let fsync = settings.fsync;
+            let saved =
+                spawn_blocking(move || write_json(&state, &emit_state_path(&cache_file), fsync))
+                    .await?;
+            if let Err(err) = saved {
+                warn!(error = %format!("{err:#}"), "Failed to save the emit state");
+            }
+        }
+        return Ok(());
+    };
+    CacheSink::new(path, settings).deliver(payload).await
+}
+
+/// The last session event the cache file showed, saved beside it so that a
+/// restarted daemon neither rewrites the same payload, restarting CSS
+/// animations, nor goes back to an older event. It outlives the stopped and
+/// other session-less payloads written after it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EmitState {
+    pub session_id: String,
+    pub timestamp: Option<String>,
+}
+
+/// The file the [`EmitState`] of `cache_file` is kept in.
+pub fn emit_state_path(cache_file: &Path) -> PathBuf {
+    cache_file.with_extension("state")
+}
+
+/// The payload in `cache_file` and the state saved with it, if both are
+/// there and readable.
+pub fn load_emitted(cache_file: &Path) -> Option<(WaybarOutput, EmitState)> {
+    let read = |path: &Path| fs::read_to_string(path).ok();
+    let payload = serde_json::from_str(&read(cache_file)?).ok()?;
+    let state = serde_json::from_str(&read(&emit_state_path(cache_file))?).ok()?;
+    Some((payload, state))
+}
+
+/// Holds back payloads that arrive within `--min-emit-interval-ms` of the
+/// last write to the same output, keeping only the latest. `None` stands for
+/// the display, `Some` for a cache file of its own.
+#[derive(Debug)]
+struct Throttle<T> {
+    written: HashMap<Option<PathBuf>, Instant>,
+    held: HashMap<Option<PathBuf>, T>,
+}
+
+impl<T> Default for Throttle<T> {
+    fn default() -> Self {
+        Throttle {
+            written: HashMap::new(),
+            held: HashMap::new(),
+        }
+    }
+}
+
+impl<T> Throttle<T> {
+    /// `payload` if it may be written to `target` now; otherwise it replaces
+    /// the one held for it.
+    fn admit(
+        &mut self,
+        target: Option<PathBuf>,
+        payload: T,
+        interval: Duration,
+        now: Instant,
+    ) -> Option<T> {
+        let ready = self
+            .written
+            .get(&target)
+            .is_none_or(|written| now >= *written + interval);
+        if !ready {
+            self.held.insert(target, payload);
+            return None;
+        }
+        self.held.remove(&target);
+        self.written.insert(target, now);
+        Some(payload)
+    }
+
+    /// When the first held payload may be written.
+    fn next_due(&self, interval: Duration) -> Option<Instant> {
+        self.held
+            .keys()
+            .filter_map(|target| self.written.get(target))
+            .map(|written| *written + interval)
+            .min()
+    }
+
+    /// The held payloads whose interval is up, marked as written at `now`.
+    fn take_due(&mut self, interval: Duration, now: Instant) -> Vec<(Option<PathBuf>, T)> {
+        let due: Vec<Option<PathBuf>> = self
+            .held
+            .keys()
+            .filter(|target| {
+                self.written
+                    .get(*target)
+                    .is_none_or(|written| now >= *written + interval)
+            })
+            .cloned()
+            .collect();
+        due.into_iter()
+            .filter_map(|target| {
+                let payload = self.held.remove(&target)?;
+                self.written.insert(target.clone(), now);
+                Some((target, payload))
+            })
+            .collect()
+    }
+
+    /// Every held payload, for a last write before shutting down.
+    fn drain(&mut self) -> Vec<(Option<PathBuf>, T)> {
+        self.held.drain().collect()
+    }
+}
+
+/// The file `--cache-dir` keeps a session's payload in.
+pub fn session_cache_path(cache_dir: &Path, session_id: &str) -> PathBuf {
+    cache_dir.join(format!("{session_id}.json"))
+}
+
+/// Print one payload per line for Waybar's `exec` + `return-type: json` mode.
+pub fn write_payload_to_stdout(payload: &WaybarOutput) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    serde_json::to_writer(&mut stdout, payload)?;
+    stdout.write_all(b"\n")?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Shown until the first Codex event has been rendered.
+pub fn placeholder_payload() -> WaybarOutput {
+    WaybarOutput {
+        text: "Waiting for Codex…".to_string(),
+        tooltip: None,
+        alt: Some("initializing".to_string()),
+        class: vec!["codex".to_owned(), "agent-reasoning".to_owned()],
+        ..Default::default()
+    }
+}
+
+/// Shown while the session logs under `sessions_root` do not exist yet, as
+/// before Codex first ran.
+pub fn unconfigured_payload(sessions_root: &Path) -> WaybarOutput {
+    WaybarOutput {
+        text: "No Codex sessions yet".to_string(),
+        tooltip: Some(format!(
+            "Waiting for {} to be created",
+            sessions_root.display()
+        )),
+        alt: Some("unconfigured".to_string()),
+        class: vec!["codex".to_owned(), "unconfigured".to_owned()],
+        ..Default::default()
+    }
+}
+
+/// `--mode summary`: how many sessions are live and how many wait for an
+/// approval, with each session's label after its name in the tooltip.
+/// `sessions` are newest first, their names already escaped for `markup`.
+pub fn summary_payload(sessions: &[(String, &WaybarOutput)]) -> WaybarOutput {
+    let has = |payload: &WaybarOutput, class: &str| payload.class.iter().any(|c| c == class);
+    let waiting = sessions
+        .iter()
+        .filter(|(_, payload)| has(payload, "approval-pending"))
+        .count();
+    let noun = if sessions.len() == 1 {
+        "session"
+    } else {
+        "sessions"
+    };
+    let mut text = format!("{} Codex {noun}", sessions.len());
+    let mut class = vec!["codex".to_owned(), "summary".to_owned()];
+    if waiting > 0 {
+        text.push_str(&format!(" · {waiting} waiting for approval"));
+        class.push("approval-pending".to_owned());
+    }
+    if sessions
+        .iter()
+        .any(|(_, payload)| has(payload, "state-working"))
+    {
+        class.push("state-working".to_owned());
+    }
+    let tooltip = sessions
+        .iter()
+        .map(|(name, payload)| format!("{name}: {}", payload.text))
+        .collect::<Vec<_>>()
+        .join("\n");
+    WaybarOutput {
+        text,
+        tooltip: Some(tooltip),
+        alt: Some("summary".to_string()),
+        class,
+        ..Default::default()
+    }
+}
+
+/// Left on display when the daemon is stopped, so the bar does not keep
+/// showing the last event as if it were current.
+pub fn stopped_payload() -> WaybarOutput {
+    WaybarOutput {
+        text: "codex-shimmer stopped".to_string(),
+        tooltip: None,
+        alt: Some("stopped".to_string()),
+        class: vec!["codex".to_owned(), "stopped".to_owned()],
+        ..Default::default()
+    }
+}
+
+/// Shown once the Codex session on display has exited.
+pub fn idle_payload() -> WaybarOutput {
+    WaybarOutput {
+        text: "Codex idle".to_string(),
+        tooltip: None,
+        alt: Some("idle".to_string()),
+        class: vec!["codex".to_owned(), "idle".to_owned()],
+        ..Default::default()
+    }
+}
+
+pub fn emit_payload(
+    payload: &WaybarOutput,
+    cache_path: &Path,
+    waybar_signal: Option<u8>,
+    waybar_process: &[String],
+    fsync: bool,
+) -> Result<()> {
+    let started = Instant::now();
+    write_payload_to_cache(payload, cache_path, fsync)?;
+    METRICS.record_cache_write(started.elapsed());
+    if let Some(sig) = waybar_signal {
+        notify_waybar(sig, waybar_process);
+    }
+    Ok(())
+}
+
+/// Send `SIGRTMIN+signal` to every running bar whose process name matches one
+/// of `process_names`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn notify_waybar(signal: u8, process_names: &[String]) {
+    let signal = libc::SIGRTMIN() + i32::from(signal);
+    if signal > libc::SIGRTMAX() {
+        return;
+    }
+    for pid in matching_pids(Path::new("/proc"), process_names) {
+        unsafe {
+            libc::kill(pid, signal);
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn notify_waybar(_signal: u8, _process_names: &[String]) {}
+
+/// Pids under `proc_root` whose `comm` or `argv[0]` file name equals one of
+/// `names` exactly, so `waybar` does not also match e.g. `codex-waybar`.
+#[cfg_attr(not(any(target_os = "linux", target_os = "android")), allow(dead_code))]
+fn matching_pids(proc_root: &Path, names: &[String]) -> Vec<i32> {
+    let Ok(entries) = fs::read_dir(proc_root) else {
+        return Vec::new();
+    };
+    let own_pid = std::process::id() as i32;
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let pid: i32 = entry.file_name().to_str()?.parse().ok()?;
+            (pid != own_pid).then_some((pid, entry.path()))
+        })
+        .filter(|(_, dir)| {
+            let comm = fs::read_to_string(dir.join("comm")).unwrap_or_default();
+            let cmdline = fs::read(dir.join("cmdline")).unwrap_or_default();
+            let argv0 = cmdline.split(|byte| *byte == 0).next().unwrap_or_default();
+            let exe = Path::new(std::str::from_utf8(argv0).unwrap_or_default())
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or_default();
+            names
+                .iter()
+                .any(|name| name == comm.trim_end() || name == exe)
+        })
+        .map(|(pid, _)| pid)
+        .collect()
+}
+
+/// Replace `cache_path` atomically through a temporary file, flushed to disk
+/// first when `fsync` is set.
+pub fn write_payload_to_cache(
+    payload: &WaybarOutput,
+    cache_path: &Path,
+    fsync: bool,
+) -> Result<()> {
+    write_json(payload, cache_path, fsync)
+}
+
+fn write_json(value: &impl Serialize, cache_path: &Path, fsync: bool) -> Result<()> {
+    if let Some(parent) = cache_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let temp_path = cache_path.with_extension("tmp");
+    {
+        let mut file = File::create(&temp_path)?;
+        serde_json::to_writer(&mut file, value)?;
+        file.write_all(b"\n")?;
+        if fsync {
+            file.sync_all()?;
+        }
+    }
+    fs::rename(&temp_path, cache_path)?;
+
+    Ok(())
+}
+
+/// Bump the modification time of an existing cache file without rewriting
+/// it, so readers can tell a live daemon from a dead one while its payload
+/// stays the same.
+pub fn touch_cache(path: &Path) -> Result<()> {
+    match File::options().write(true).open(path) {
+        Ok(file) => Ok(file.set_modified(std::time::SystemTime::now())?),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// How long ago the daemon last wrote the cache file or marked it alive.
+fn cache_age(path: &Path) -> Result<Option<Duration>> {
+    Ok(fs::metadata(path)?
+        .modified()
+        .ok()
+        .and_then(|modified| modified.elapsed().ok()))
+}
+
+/// Print the cache file, or the placeholder if it does not exist yet. With
+/// `stale_after`, a cache the daemon has neither rewritten nor marked alive
+/// for longer than that is printed dimmed and with a `stale` class, since the
+/// daemon behind it has likely died.
+pub fn print_cache(path: &Path, stale_after: Option<Duration>, markup: Markup) -> Result<()> {
+    match fs::read_to_string(path) {
+        Ok(content) => {
+            let age = cache_age(path)?;
+            let stale = match (stale_after, age) {
+                (Some(limit), Some(age)) if age > limit => serde_json::from_str(&content)
+                    .ok()
+                    .map(|payload| mark_stale(payload, age, markup)),
+                _ => None,
+            };
+            match stale {
+                Some(payload) => println!("{}", serde_json::to_string(&payload)?),
+                None => print!("{}", content),
+            }
+        }
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            println!("{}", serde_json::to_string(&placeholder_payload())?);
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    }
+
+    io::stdout().flush()?;
+    Ok(())
+}
+
+/// Add the `degraded` class to `payload` and list the sessions whose logs
+/// keep failing to read, with their errors, above its tooltip.
+pub fn mark_degraded(payload: &mut WaybarOutput, errors: &BTreeMap<String, String>) {
+    if errors.is_empty() {
+        return;
+    }
+    let noun = if errors.len() == 1 { "log" } else { "logs" };
+    let mut note = format!("Failed to read {} session {noun}:", errors.len());
+    for (session_id, error) in errors {
+        let short: String = session_id.chars().take(8).collect();
+        note.push_str(&format!("\n{short}: {error}"));
+    }
+    payload.tooltip = Some(match payload.tooltip.take() {
+        Some(tooltip) => format!("{note}\n\n{tooltip}"),
+        None => note,
+    });
+    payload.class.push("degraded".to_string());
+}
+
+/// Dim a stale payload. Text the daemon already escaped is not escaped again.
+fn mark_stale(mut payload: WaybarOutput, age: Duration, markup: Markup) -> WaybarOutput {
+    let note = format!("Not updated for {}s; is the daemon running?", age.as_secs());
+    payload.tooltip = Some(match payload.tooltip.take() {
+        Some(tooltip) => format!("{note}\n\n{tooltip}"),
+        None => note,
+    });
+    payload.text = format!(
+        "<span alpha=\"50%\">{}</span>",
+        match markup {
+            Markup::Pass => escape_markup(&payload.text),
+            Markup::Escape => payload.text,
+        }
+    );
+    payload.class.push("stale".to_string());
+    payload
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use tempfile::tempdir;
+    #[test]
+    fn emit_payload_writes_payload() -> Result<()> {
+        let dir = tempdir()?;
+        let cache_path = dir.path().join("cache.json");
+        let event = RenderedEvent {
+            payload: WaybarOutput {
+                text: "Hello".to_string(),
+                tooltip: Some("Tooltip".to_string()),
+                alt: Some("phase".to_string()),
+                class: vec!["codex".to_string()],
+                ..Default::default()
+            },
+            timestamp: None,
+        };
+
+        emit_payload(&event.payload, &cache_path, None, &[], true)?;
+
+        let written = fs::read_to_string(&cache_path)?;
+        let parsed: Value = serde_json::from_str(written.trim())?;
+        assert_eq!(parsed["text"].as_str(), Some("Hello"));
+        assert_eq!(parsed["tooltip"].as_str(), Some("Tooltip"));
+        Ok(())
+    }
+
+    #[test]
+    fn emitted_payload_is_loaded_with_its_state() -> Result<()> {
+        let dir = tempdir()?;
+        let cache_path = dir.path().join("latest.json");
+        let state = EmitState {
+            session_id: "alpha".to_string(),
+            timestamp: Some("2025-10-29T12:00:00Z".to_string()),
+        };
+        write_payload_to_cache(&idle_payload(), &cache_path, false)?;
+        assert!(load_emitted(&cache_path).is_none());
+
+        write_json(&state, &emit_state_path(&cache_path), false)?;
+        assert_eq!(load_emitted(&cache_path), Some((idle_payload(), state)));
+        Ok(())
+    }
+
+    #[test]
+    fn degraded_payloads_list_failing_sessions() {
+        let mut payload = idle_payload();
+        mark_degraded(&mut payload, &BTreeMap::new());
+        assert_eq!(payload, idle_payload());
+
+        let errors = BTreeMap::from([(
+            "0199a0b1-session".to_string(),
+            "Permission denied (os error 13)".to_string(),
+        )]);
+        mark_degraded(&mut payload, &errors);
+        assert_eq!(payload.class, ["codex", "idle", "degraded"]);
+        assert_eq!(
+            payload.tooltip.as_deref(),
+            Some("Failed to read 1 session log:\n0199a0b1: Permission denied (os error 13)")
+        );
+    }
+
+    #[test]
+    fn throttle_keeps_the_latest_payload_until_the_interval_is_up() {
+        let interval = Duration::from_millis(500);
+        let start = Instant::now();
+        let payload = |text: &str| WaybarOutput {
+            text: text.to_string(),
+            ..idle_payload()
+        };
+        let mut throttle = Throttle::default();
+
+        assert_eq!(
+            throttle.admit(None, payload("one"), interval, start),
+            Some(payload("one"))
+        );
+        let soon = start + Duration::from_millis(100);
+        assert_eq!(throttle.admit(None, payload("two"), interval, soon), None);
+        assert_eq!(throttle.admit(None, payload("three"), interval, soon), None);
+        let file = Some(PathBuf::from("status.json"));
+        assert!(
+            throttle
+                .admit(file, payload("file"), interval, soon)
+                .is_some()
+        );
+
+        assert_eq!(throttle.next_due(interval), Some(start + interval));
+        assert!(throttle.take_due(interval, soon).is_empty());
+        assert_eq!(
+            throttle.take_due(interval, start + interval),
+            [(None, payload("three"))]
+        );
+        assert_eq!(throttle.next_due(interval), None);
+
+        let mut unlimited = Throttle::default();
+        for text in ["a", "b"] {
+            assert!(
+                unlimited
+                    .admit(None, payload(text), Duration::ZERO, start)
+                    .is_some()
+            );
+        }
+    }
+
+    #[test]
+    fn matching_pids_compares_whole_process_names() -> Result<()> {
+        let proc_root = tempdir()?;
+        for (pid, comm, cmdline) in [
+            ("101", "waybar\n", "/usr/bin/waybar\0-c\0top.jsonc\0"),
+            ("102", "codex-waybar\n", "codex-waybar\0"),
+            ("103", "waybar-bottom-b\n", "/opt/bin/waybar-bottom-bar\0"),
+            ("self", "waybar\n", ""),
+        ] {
+            let dir = proc_root.path().join(pid);
+            fs::create_dir(&dir)?;
+            fs::write(dir.join("comm"), comm)?;
+            fs::write(dir.join("cmdline"), cmdline)?;
+        }
+
+        let names = ["waybar".to_string(), "waybar-bottom-bar".to_string()];
+        let mut pids = matching_pids(proc_root.path(), &names);
+        pids.sort();
+        assert_eq!(pids, vec![101, 103]);
+        Ok(())
+    }
+
+    #[test]
+    fn stale_cache_is_dimmed_with_stale_class() -> Result<()> {
+        let dir = tempdir()?;
+        let cache_path = dir.path().join("cache.json");
+        fs::write(&cache_path, r#"{"text":"a < b","class":["codex"]}"#)?;
+        print_cache(&cache_path, Some(Duration::from_secs(60)), Markup::Pass)?;
+
+        let payload: WaybarOutput = serde_json::from_str(&fs::read_to_string(&cache_path)?)?;
+        let stale = mark_stale(payload, Duration::from_secs(120), Markup::Pass);
+        assert_eq!(stale.text, r#"<span alpha="50%">a &lt; b</span>"#);
+        assert_eq!(stale.class, vec!["codex", "stale"]);
+        assert!(stale.tooltip.unwrap().starts_with("Not updated for 120s"));
+        Ok(())
+    }
+
+    #[test]
+    fn heartbeat_keeps_a_quiet_cache_fresh() -> Result<()> {
+        let dir = tempdir()?;
+        let cache_path = dir.path().join("cache.json");
+        let content = r#"{"text":"Waiting for input","class":["codex"]}"#;
+        fs::write(&cache_path, content)?;
+        // Nothing new to show for ten minutes.
+        File::options()
+            .write(true)
+            .open(&cache_path)?
+            .set_modified(std::time::SystemTime::now() - Duration::from_secs(600))?;
+        assert!(cache_age(&cache_path)?.is_some_and(|age| age > HEARTBEAT * 2));
+
+        touch_cache(&cache_path)?;
+        assert!(cache_age(&cache_path)?.is_some_and(|age| age < HEARTBEAT));
+        assert_eq!(fs::read_to_string(&cache_path)?, content);
+
+        touch_cache(&dir.path().join("missing.json"))?;
+        assert!(!dir.path().join("missing.json").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn summary_counts_sessions_and_approvals() {
+        let payload = |text: &str, classes: &[&str]| WaybarOutput {
+            text: text.to_string(),
+            class: classes.iter().map(|class| class.to_string()).collect(),
+            ..idle_payload()
+        };
+        let approval = payload("Approval needed: cargo publish", &["approval-pending"]);
+        let working = payload("Running tests", &["state-working"]);
+        let summary = summary_payload(&[
+            ("shimmer".to_string(), &approval),
+            ("dotfiles".to_string(), &working),
+        ]);
+        assert_eq!(summary.text, "2 Codex sessions · 1 waiting for approval");
+        assert_eq!(
+            summary.tooltip.as_deref(),
+            Some("shimmer: Approval needed: cargo publish\ndotfiles: Running tests")
+        );
+        assert_eq!(
+            summary.class,
+            ["codex", "summary", "approval-pending", "state-working"]
+        );
+        assert_eq!(
+            summary_payload(&[("dotfiles".to_string(), &working)]).text,
+            "1 Codex session"
+        );
+    }
+}
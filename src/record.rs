@@ -0,0 +1,118 @@
+//! `--record`: a debug file of every log line the daemon read and the payload
+//! it rendered, for attaching to a bug report. Lines are redacted like
+//! payloads. Past `--record-max-bytes` the file is moved to `<path>.1` and a
+//! new one started, so the newest records stay within twice the cap.
+
+use std::{
+    ffi::OsString,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use serde_json::json;
+
+use crate::output::WaybarOutput;
+use crate::redact::Redactor;
+use crate::render::format_timestamp;
+
+/// An open `--record` file.
+#[derive(Debug)]
+pub struct Recorder {
+    path: PathBuf,
+    max_bytes: u64,
+    file: File,
+    written: u64,
+}
+
+impl Recorder {
+    /// Open `path` for appending, creating it and its directory as needed.
+    pub fn open(path: &Path, max_bytes: u64) -> io::Result<Recorder> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let written = file.metadata()?.len();
+        Ok(Recorder {
+            path: path.to_path_buf(),
+            max_bytes,
+            file,
+            written,
+        })
+    }
+
+    /// Whether this records to `path` with the cap `max_bytes`.
+    pub fn matches(&self, path: &Path, max_bytes: u64) -> bool {
+        self.path == path && self.max_bytes == max_bytes
+    }
+
+    /// Append one JSON line: the session, the log line with secrets masked,
+    /// and what it rendered, if anything.
+    pub fn record(
+        &mut self,
+        session_id: &str,
+        line: &str,
+        payload: Option<&WaybarOutput>,
+        redactor: &Redactor,
+    ) -> io::Result<()> {
+        let record = json!({
+            "time": format_timestamp(SystemTime::now()),
+            "session": session_id,
+            "line": redactor.redact(line),
+            "payload": payload,
+        });
+        let mut bytes = serde_json::to_vec(&record)?;
+        bytes.push(b'\n');
+        if self.written > 0 && self.written + bytes.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(&bytes)?;
+        self.written += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let mut previous = OsString::from(&self.path);
+        previous.push(".1");
+        std::fs::rename(&self.path, previous)?;
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use serde_json::Value;
+
+    #[test]
+    fn records_redacted_lines_and_rotates_past_the_cap() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("debug/record.jsonl");
+        let redactor = Redactor::new(true, &[])?;
+        let mut recorder = Recorder::open(&path, 300)?;
+
+        let line = r#"{"payload":{"type":"agent_reasoning","text":"export API_KEY=hunter22"}}"#;
+        recorder.record("abc", line, None, &redactor)?;
+        let first: Value = serde_json::from_str(std::fs::read_to_string(&path)?.trim_end())?;
+        assert_eq!(first["session"], "abc");
+        assert_eq!(
+            first["line"],
+            r#"{"payload":{"type":"agent_reasoning","text":"export API_KEY=[redacted]"}}"#
+        );
+        assert!(first["payload"].is_null());
+
+        recorder.record("abc", line, None, &redactor)?;
+        recorder.record("abc", line, None, &redactor)?;
+        let rotated = std::fs::read_to_string(dir.path().join("debug/record.jsonl.1"))?;
+        assert_eq!(rotated.lines().count(), 1);
+        assert_eq!(std::fs::read_to_string(&path)?.lines().count(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,1547 @@
+use std::{cmp::Ordering, collections::BTreeMap, str::FromStr, time::SystemTime};
+
+use anyhow::{Result, bail};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::warn;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::agent::{Agent, generic::GenericFormat};
+use crate::classify::ClassRules;
+use crate::metrics::METRICS;
+use crate::output::{PayloadVersion, WaybarOutput};
+use crate::redact::Redactor;
+use crate::rewrite::{PhaseAliases, Rewrites};
+use crate::template::Template;
+
+/// A log entry rendered into a Waybar payload.
+#[derive(Debug, Clone)]
+pub struct RenderedEvent {
+    pub payload: WaybarOutput,
+    pub timestamp: Option<String>,
+}
+
+/// What the label shows.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DisplayMode {
+    /// The latest reasoning, message, command, or approval.
+    #[default]
+    Activity,
+    /// Context-window usage such as `73% ctx`; activity moves to the tooltip.
+    Tokens,
+    /// A count of the live sessions, such as `3 Codex sessions · 1 waiting
+    /// for approval`, listing each one's activity in the tooltip.
+    Summary,
+}
+
+impl FromStr for DisplayMode {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "activity" => Ok(DisplayMode::Activity),
+            "tokens" => Ok(DisplayMode::Tokens),
+            "summary" => Ok(DisplayMode::Summary),
+            other => bail!("unknown mode `{other}` (expected `activity`, `tokens`, or `summary`)"),
+        }
+    }
+}
+
+/// Whether `text` and `tooltip` are Pango markup.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Markup {
+    /// Leave log text as it is: for the GTK module, which shows plain text, or
+    /// to let markup written by the agent through.
+    #[default]
+    Pass,
+    /// Escape `&`, `<`, and `>` in log text so Waybar's markup parser accepts
+    /// it; markup written into `format` and `tooltip-format` is kept.
+    Escape,
+}
+
+impl FromStr for Markup {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "pass" => Ok(Markup::Pass),
+            "escape" => Ok(Markup::Escape),
+            other => bail!("unknown markup mode `{other}` (expected `pass` or `escape`)"),
+        }
+    }
+}
+
+impl Markup {
+    /// Prepare rendered log text for the payload.
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            Markup::Pass => text.to_string(),
+            Markup::Escape => escape_markup(text),
+        }
+    }
+}
+
+/// Escape text for Waybar's Pango markup.
+pub fn escape_markup(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// How event times appear in tooltips.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TimestampStyle {
+    /// The timestamp as logged.
+    #[default]
+    Absolute,
+    /// How long ago, such as `2m ago`; refreshed while the payload is shown.
+    Relative,
+    /// The logged timestamp followed by the relative time.
+    Both,
+}
+
+impl FromStr for TimestampStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "absolute" => Ok(TimestampStyle::Absolute),
+            "relative" => Ok(TimestampStyle::Relative),
+            "both" => Ok(TimestampStyle::Both),
+            other => bail!(
+                "unknown timestamp style `{other}` (expected `absolute`, `relative`, or `both`)"
+            ),
+        }
+    }
+}
+
+impl TimestampStyle {
+    /// `timestamp` in this style; timestamps without a zone stay absolute.
+    pub fn format(self, timestamp: &str, now: SystemTime) -> String {
+        let relative = || relative_time(timestamp, now);
+        match self {
+            TimestampStyle::Absolute => None,
+            TimestampStyle::Relative => relative(),
+            TimestampStyle::Both => relative().map(|ago| format!("{timestamp} ({ago})")),
+        }
+        .unwrap_or_else(|| timestamp.to_string())
+    }
+}
+
+/// Whether payloads count the time since the session's last event while a
+/// turn is in progress.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ElapsedDisplay {
+    #[default]
+    Off,
+    /// Seconds in the payload's `elapsed` field.
+    Field,
+    /// The field, plus `· 1m 24s` after the label.
+    Text,
+}
+
+impl FromStr for ElapsedDisplay {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "off" => Ok(ElapsedDisplay::Off),
+            "field" => Ok(ElapsedDisplay::Field),
+            "text" => Ok(ElapsedDisplay::Text),
+            other => {
+                bail!("unknown elapsed display `{other}` (expected `off`, `field`, or `text`)")
+            }
+        }
+    }
+}
+
+/// What a payload's `percentage` field tracks.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PercentageSource {
+    /// Completed plan steps, or context usage in `--mode tokens`; each falls
+    /// back to the other.
+    #[default]
+    Auto,
+    /// Share of the context window in use.
+    Context,
+    /// Share of plan steps completed.
+    Plan,
+    /// Leave the field out.
+    Off,
+}
+
+impl FromStr for PercentageSource {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "auto" => Ok(PercentageSource::Auto),
+            "context" => Ok(PercentageSource::Context),
+            "plan" => Ok(PercentageSource::Plan),
+            "off" => Ok(PercentageSource::Off),
+            other => bail!(
+                "unknown percentage source `{other}` (expected `auto`, `context`, `plan`, or `off`)"
+            ),
+        }
+    }
+}
+
+/// Which built-in [`Renderer`] turns entries into payloads.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RendererKind {
+    /// The phase heading of reasoning, or its text when there is none.
+    #[default]
+    Compact,
+    /// The full reasoning text, truncated to fit.
+    Verbose,
+    /// Only reasoning that opens with a phase heading, plus errors.
+    PhaseOnly,
+}
+
+impl FromStr for RendererKind {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "compact" => Ok(RendererKind::Compact),
+            "verbose" => Ok(RendererKind::Verbose),
+            "phase-only" => Ok(RendererKind::PhaseOnly),
+            other => {
+                bail!("unknown renderer `{other}` (expected `compact`, `verbose`, or `phase-only`)")
+            }
+        }
+    }
+}
+
+impl RendererKind {
+    pub fn renderer(self) -> &'static dyn Renderer {
+        match self {
+            RendererKind::Compact => &Compact,
+            RendererKind::Verbose => &Verbose,
+            RendererKind::PhaseOnly => &PhaseOnly,
+        }
+    }
+}
+
+/// Turns an entry that stands on its own (reasoning, a reply, an error) into
+/// a payload. Session state such as running commands, approvals, and token
+/// usage is layered on top by the caller, so implementations only decide how
+/// a single entry reads.
+pub trait Renderer: Send + Sync {
+    fn render(
+        &self,
+        entry: &LogEntry,
+        truncation: &Truncation,
+        aliases: &PhaseAliases,
+    ) -> Option<RenderedEvent>;
+}
+
+/// See [`RendererKind::Compact`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Compact;
+
+/// See [`RendererKind::Verbose`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Verbose;
+
+/// See [`RendererKind::PhaseOnly`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PhaseOnly;
+
+impl Renderer for Compact {
+    fn render(
+        &self,
+        entry: &LogEntry,
+        truncation: &Truncation,
+        aliases: &PhaseAliases,
+    ) -> Option<RenderedEvent> {
+        render_entry(entry, truncation, aliases)
+    }
+}
+
+impl Renderer for Verbose {
+    fn render(
+        &self,
+        entry: &LogEntry,
+        truncation: &Truncation,
+        aliases: &PhaseAliases,
+    ) -> Option<RenderedEvent> {
+        let mut event = render_entry(entry, truncation, aliases)?;
+        if let EventPayload::AgentReasoning { text } = &entry.event {
+            event.payload.text = truncation.apply(&sanitize_text(text));
+        }
+        Some(event)
+    }
+}
+
+impl Renderer for PhaseOnly {
+    fn render(
+        &self,
+        entry: &LogEntry,
+        truncation: &Truncation,
+        aliases: &PhaseAliases,
+    ) -> Option<RenderedEvent> {
+        match &entry.event {
+            EventPayload::AgentReasoning { text } if extract_phase(text).is_some() => {
+                render_entry(entry, truncation, aliases)
+            }
+            EventPayload::Error { .. } | EventPayload::StreamError { .. } => {
+                render_entry(entry, truncation, aliases)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Nerd Font glyphs shown before phase headings, keyed by phase slug.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhaseIcons(BTreeMap<String, String>);
+
+impl Default for PhaseIcons {
+    fn default() -> Self {
+        let icons = [
+            ("analyzing", "\u{f080}"),
+            ("debugging", "\u{f188}"),
+            ("fixing", "\u{f0ad}"),
+            ("implementing", "\u{f121}"),
+            ("investigating", "\u{f002}"),
+            ("planning", "\u{f0cb}"),
+            ("refactoring", "\u{f0ad}"),
+            ("reviewing", "\u{f06e}"),
+            ("searching", "\u{f002}"),
+            ("summarizing", "\u{f0ca}"),
+            ("testing", "\u{f0c3}"),
+            ("thinking", "\u{f0eb}"),
+            ("writing", "\u{f040}"),
+        ];
+        PhaseIcons(
+            icons
+                .into_iter()
+                .map(|(slug, icon)| (slug.to_string(), icon.to_string()))
+                .collect(),
+        )
+    }
+}
+
+impl PhaseIcons {
+    /// The defaults with `overrides` applied; an empty icon removes a phase.
+    pub fn with_overrides(overrides: &BTreeMap<String, String>) -> Self {
+        let mut icons = PhaseIcons::default();
+        for (slug, icon) in overrides {
+            if icon.is_empty() {
+                icons.0.remove(slug);
+            } else {
+                icons.0.insert(slug.clone(), icon.clone());
+            }
+        }
+        icons
+    }
+
+    /// The icon of a phase heading, matched on its whole slug and then on its
+    /// first word, so `Testing the parser` uses the `testing` icon.
+    pub fn icon(&self, phase: &str) -> Option<&str> {
+        let slug = slugify(phase)?;
+        let first_word = slug.split('-').next().unwrap_or_default();
+        self.0
+            .get(&slug)
+            .or_else(|| self.0.get(first_word))
+            .map(String::as_str)
+    }
+}
+
+/// Settings that shape how events are rendered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderOptions {
+    pub truncation: Truncation,
+    pub mode: DisplayMode,
+    pub percentage: PercentageSource,
+    pub renderer: RendererKind,
+    pub markup: Markup,
+    pub timestamps: TimestampStyle,
+    pub elapsed: ElapsedDisplay,
+    /// Schema of the payloads.
+    pub payload: PayloadVersion,
+    /// Classes added when the label matches.
+    pub class_rules: ClassRules,
+    /// Masks secrets in labels and tooltips.
+    pub redactor: Redactor,
+    /// Log format of the lines being rendered.
+    pub agent: Agent,
+    /// Selectors used when `agent` is [`Agent::Generic`].
+    pub generic: GenericFormat,
+    /// Replaces the label when set.
+    pub format: Option<Template>,
+    pub phase_icons: PhaseIcons,
+    /// Renames phase headings before they are shown.
+    pub phase_aliases: PhaseAliases,
+    /// Replaces the tooltip when set.
+    pub tooltip_format: Option<Template>,
+}
+
+/// One rollout log line: its timestamp, record type, and typed payload.
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub timestamp: Option<String>,
+    /// The line's own `type`, e.g. `event_msg` or `session_meta`.
+    pub record: Option<String>,
+    /// The payload's `type`, e.g. `agent_reasoning`; empty when it has none.
+    pub kind: String,
+    pub event: EventPayload,
+}
+
+/// The fields of a rollout line read before its payload is looked at.
+#[derive(Debug, Deserialize)]
+struct Envelope {
+    timestamp: Option<String>,
+    #[serde(rename = "type")]
+    record: Option<String>,
+    payload: Option<Value>,
+}
+
+/// A payload, keyed by its `type`. Kinds without a variant read as
+/// [`EventPayload::Other`].
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventPayload {
+    /// The `session_meta` header record, which has no `type` of its own.
+    #[serde(skip)]
+    SessionMeta(SessionHeader),
+    /// The `turn_context` header record, likewise.
+    #[serde(skip)]
+    TurnContext(SessionHeader),
+    TaskStarted,
+    TaskComplete,
+    TurnAborted,
+    ShutdownComplete,
+    AgentReasoning {
+        #[serde(default, deserialize_with = "nullable")]
+        text: String,
+    },
+    AgentMessage {
+        #[serde(default, deserialize_with = "nullable")]
+        message: String,
+    },
+    /// A failed turn.
+    Error {
+        message: Option<String>,
+    },
+    /// A dropped model stream that Codex is retrying.
+    StreamError {
+        message: Option<String>,
+    },
+    PlanUpdate(PlanUpdate),
+    /// A tool call in the model's response; `update_plan` calls carry a plan.
+    FunctionCall {
+        name: Option<String>,
+        /// JSON-encoded arguments.
+        arguments: Option<String>,
+        #[serde(default, deserialize_with = "nullable")]
+        call_id: String,
+    },
+    TokenCount {
+        info: Option<TokenInfo>,
+    },
+    ExecApprovalRequest {
+        #[serde(default, deserialize_with = "nullable")]
+        call_id: String,
+        #[serde(default)]
+        command: CommandLine,
+        reason: Option<String>,
+    },
+    ApplyPatchApprovalRequest {
+        #[serde(default, deserialize_with = "nullable")]
+        call_id: String,
+        #[serde(default, deserialize_with = "nullable")]
+        changes: BTreeMap<String, FileChange>,
+        reason: Option<String>,
+    },
+    ExecCommandBegin {
+        #[serde(default, deserialize_with = "nullable")]
+        call_id: String,
+        #[serde(default)]
+        command: CommandLine,
+        cwd: Option<String>,
+    },
+    ExecCommandEnd {
+        #[serde(default, deserialize_with = "nullable")]
+        call_id: String,
+        exit_code: Option<i64>,
+    },
+    McpToolCallBegin {
+        #[serde(default, deserialize_with = "nullable")]
+        call_id: String,
+        invocation: Option<Invocation>,
+    },
+    McpToolCallEnd {
+        #[serde(default, deserialize_with = "nullable")]
+        call_id: String,
+        invocation: Option<Invocation>,
+        duration: Option<CallDuration>,
+        result: Option<ToolResult>,
+    },
+    TurnDiff {
+        #[serde(default, deserialize_with = "nullable")]
+        unified_diff: String,
+    },
+    PatchApplyBegin {
+        #[serde(default, deserialize_with = "nullable")]
+        call_id: String,
+        #[serde(default, deserialize_with = "nullable")]
+        changes: BTreeMap<String, FileChange>,
+    },
+    PatchApplyEnd {
+        #[serde(default, deserialize_with = "nullable")]
+        call_id: String,
+        success: Option<bool>,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// Reads `null` as the type's default, for fields some agents leave empty.
+fn nullable<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// Where and how a session runs, from its header records.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct SessionHeader {
+    pub cwd: Option<String>,
+    pub model: Option<String>,
+    pub originator: Option<String>,
+    pub cli_version: Option<String>,
+}
+
+/// The agent's step list, as sent with `plan_update` or `update_plan`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct PlanUpdate {
+    pub explanation: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub plan: Vec<PlanItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PlanItem {
+    /// Items without a step are skipped.
+    pub step: Option<String>,
+    pub status: Option<String>,
+}
+
+/// Token usage as reported by `token_count`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct TokenInfo {
+    pub total_token_usage: Option<TokenCounts>,
+    pub last_token_usage: Option<TokenCounts>,
+    pub model_context_window: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct TokenCounts {
+    pub input_tokens: u64,
+    pub cached_input_tokens: u64,
+    pub output_tokens: u64,
+    pub reasoning_output_tokens: u64,
+    pub total_tokens: u64,
+}
+
+/// A command as logged: one shell string, or an argv array.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum CommandLine {
+    Text(String),
+    Argv(Vec<String>),
+}
+
+impl Default for CommandLine {
+    fn default() -> Self {
+        CommandLine::Argv(Vec::new())
+    }
+}
+
+impl CommandLine {
+    /// The command as typed: the script of a `sh -c` argv, or the words
+    /// joined by spaces.
+    pub fn text(&self) -> String {
+        let argv = match self {
+            CommandLine::Text(command) => return command.clone(),
+            CommandLine::Argv(argv) => argv,
+        };
+        match argv.as_slice() {
+            [shell, flag, script] if shell.ends_with("sh") && flag.ends_with('c') => script.clone(),
+            _ => argv.join(" "),
+        }
+    }
+
+    fn redact(&mut self, redactor: &Redactor) {
+        match self {
+            CommandLine::Text(command) => *command = redactor.redact(command),
+            CommandLine::Argv(argv) => {
+                for word in argv {
+                    *word = redactor.redact(word);
+                }
+            }
+        }
+    }
+}
+
+/// Mask secrets in every string of a JSON value.
+fn redact_value(value: &mut Value, redactor: &Redactor) {
+    match value {
+        Value::String(text) => *text = redactor.redact(text),
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|item| redact_value(item, redactor)),
+        Value::Object(fields) => fields
+            .values_mut()
+            .for_each(|field| redact_value(field, redactor)),
+        _ => {}
+    }
+}
+
+/// One file of a patch, keyed by its path in `changes`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct FileChange {
+    pub add: Option<FileContent>,
+    pub delete: Option<FileContent>,
+    pub update: Option<FileUpdate>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct FileContent {
+    #[serde(default, deserialize_with = "nullable")]
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct FileUpdate {
+    #[serde(default, deserialize_with = "nullable")]
+    pub unified_diff: String,
+}
+
+/// The MCP tool a call went to.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct Invocation {
+    pub server: Option<String>,
+    pub tool: Option<String>,
+    /// Whatever the tool takes, passed through as is.
+    pub arguments: Option<Value>,
+}
+
+impl Invocation {
+    /// `server.tool`, or just the tool for built-in ones.
+    pub fn name(&self) -> String {
+        let tool = self.tool.as_deref().unwrap_or("?");
+        match &self.server {
+            Some(server) => format!("{server}.{tool}"),
+            None => tool.to_string(),
+        }
+    }
+}
+
+/// How long a tool call took: a serialized `Duration`, or preformatted text
+/// such as `"1.2s"`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum CallDuration {
+    Text(String),
+    Span {
+        secs: u64,
+        #[serde(default)]
+        nanos: u64,
+    },
+}
+
+/// The outcome of an MCP tool call.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum ToolResult {
+    Ok(ToolOutput),
+    Err(serde::de::IgnoredAny),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct ToolOutput {
+    #[serde(rename = "isError", default)]
+    pub is_error: bool,
+}
+
+impl ToolResult {
+    pub fn failed(&self) -> bool {
+        match self {
+            ToolResult::Ok(output) => output.is_error,
+            ToolResult::Err(_) => true,
+        }
+    }
+}
+
+impl EventPayload {
+    /// The payload of a line with record type `record`. A known kind whose
+    /// fields do not match is reported and read as [`EventPayload::Other`].
+    pub fn from_value(record: Option<&str>, payload: &Value) -> Self {
+        let header = || SessionHeader::deserialize(payload).unwrap_or_default();
+        match record {
+            Some("session_meta") => return EventPayload::SessionMeta(header()),
+            Some("turn_context") => return EventPayload::TurnContext(header()),
+            _ => {}
+        }
+        EventPayload::deserialize(payload).unwrap_or_else(|err| {
+            let parse_errors = METRICS.record_parse_error();
+            let kind = payload.get("type").and_then(Value::as_str);
+            warn!(
+                kind = kind.unwrap_or_default(),
+                error = %err,
+                parse_errors,
+                "Skipping log entry with unexpected fields"
+            );
+            EventPayload::Other
+        })
+    }
+
+    /// Mask secrets in the text that can reach the bar, before it is
+    /// rewritten or cut to fit, so a cut cannot leave part of one unmasked.
+    pub fn redact(&mut self, redactor: &Redactor) {
+        let mask = |text: &mut String| *text = redactor.redact(text);
+        match self {
+            EventPayload::AgentReasoning { text } => mask(text),
+            EventPayload::AgentMessage { message } => mask(message),
+            EventPayload::Error { message } | EventPayload::StreamError { message } => {
+                message.iter_mut().for_each(mask);
+            }
+            EventPayload::PlanUpdate(update) => {
+                update.explanation.iter_mut().for_each(mask);
+                for item in &mut update.plan {
+                    item.step.iter_mut().for_each(mask);
+                }
+            }
+            EventPayload::FunctionCall { arguments, .. } => arguments.iter_mut().for_each(mask),
+            EventPayload::ExecApprovalRequest {
+                command, reason, ..
+            } => {
+                command.redact(redactor);
+                reason.iter_mut().for_each(mask);
+            }
+            EventPayload::ApplyPatchApprovalRequest { reason, .. } => {
+                reason.iter_mut().for_each(mask);
+            }
+            EventPayload::ExecCommandBegin { command, .. } => command.redact(redactor),
+            EventPayload::McpToolCallBegin {
+                invocation:
+                    Some(Invocation {
+                        arguments: Some(arguments),
+                        ..
+                    }),
+                ..
+            } => redact_value(arguments, redactor),
+            _ => {}
+        }
+    }
+
+    /// The id tying a call's begin, end, and approval entries together;
+    /// empty for other entries.
+    pub fn call_id(&self) -> &str {
+        match self {
+            EventPayload::FunctionCall { call_id, .. }
+            | EventPayload::ExecApprovalRequest { call_id, .. }
+            | EventPayload::ApplyPatchApprovalRequest { call_id, .. }
+            | EventPayload::ExecCommandBegin { call_id, .. }
+            | EventPayload::ExecCommandEnd { call_id, .. }
+            | EventPayload::McpToolCallBegin { call_id, .. }
+            | EventPayload::McpToolCallEnd { call_id, .. }
+            | EventPayload::PatchApplyBegin { call_id, .. }
+            | EventPayload::PatchApplyEnd { call_id, .. } => call_id,
+            _ => "",
+        }
+    }
+}
+
+impl LogEntry {
+    pub fn new(timestamp: Option<String>, record: Option<String>, payload: Value) -> Self {
+        let kind = payload
+            .get("type")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let event = EventPayload::from_value(record.as_deref(), &payload);
+        LogEntry {
+            timestamp,
+            record,
+            kind,
+            event,
+        }
+    }
+
+    /// Parse a rollout line, returning `None` for blank, malformed, or
+    /// payload-less lines.
+    pub fn parse(line: &str) -> Option<LogEntry> {
+        if line.trim().is_empty() {
+            return None;
+        }
+
+        let envelope: Envelope = match serde_json::from_str(line) {
+            Ok(envelope) => envelope,
+            Err(err) => {
+                let parse_errors = METRICS.record_parse_error();
+                warn!(error = %err, parse_errors, "Skipping malformed log entry");
+                return None;
+            }
+        };
+        Some(LogEntry::new(
+            envelope.timestamp,
+            envelope.record,
+            envelope.payload?,
+        ))
+    }
+
+    /// The payload `type`, e.g. `agent_reasoning`.
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+}
+
+/// Render one rollout log line, returning `None` for entries that are not shown.
+pub fn process_log_line(line: &str, max_chars: usize) -> Result<Option<RenderedEvent>> {
+    Ok(LogEntry::parse(line).and_then(|entry| {
+        render_entry(
+            &entry,
+            &Truncation::new(max_chars),
+            &PhaseAliases::default(),
+        )
+    }))
+}
+
+/// Render the entry kinds that stand on their own, without session context.
+pub fn render_entry(
+    entry: &LogEntry,
+    truncation: &Truncation,
+    aliases: &PhaseAliases,
+) -> Option<RenderedEvent> {
+    let timestamp = entry.timestamp.clone();
+    match &entry.event {
+        EventPayload::AgentReasoning { text } => {
+            render_reasoning(text, timestamp, truncation, aliases)
+        }
+        EventPayload::AgentMessage { message } => render_message(message, timestamp, truncation),
+        EventPayload::Error { message } => {
+            render_error(false, message.as_deref(), timestamp, truncation)
+        }
+        EventPayload::StreamError { message } => {
+            render_error(true, message.as_deref(), timestamp, truncation)
+        }
+        _ => None,
+    }
+}
+
+fn render_reasoning(
+    raw_text: &str,
+    timestamp: Option<String>,
+    truncation: &Truncation,
+    aliases: &PhaseAliases,
+) -> Option<RenderedEvent> {
+    if raw_text.is_empty() {
+        return None;
+    }
+
+    let sanitized = sanitize_text(raw_text);
+    let truncated = truncation.apply(&sanitized);
+
+    let phase = extract_phase(raw_text).map(|phase| aliases.apply(&phase));
+
+    let mut classes = vec!["codex".to_string(), "agent-reasoning".to_string()];
+    if let Some(slug) = phase.as_deref().and_then(slugify) {
+        classes.push(format!("phase-{}", slug));
+    }
+
+    let tooltip = build_tooltip(timestamp.as_deref(), raw_text, &sanitized, &truncated);
+    let display_text = phase.clone().unwrap_or_else(|| truncated.clone());
+
+    Some(RenderedEvent {
+        payload: WaybarOutput {
+            text: display_text,
+            tooltip,
+            alt: phase,
+            class: classes,
+            ..Default::default()
+        },
+        timestamp,
+    })
+}
+
+/// The assistant's reply at the end of a turn. The label shows its first
+/// non-empty line as a summary; the tooltip keeps the whole message.
+fn render_message(
+    raw_text: &str,
+    timestamp: Option<String>,
+    truncation: &Truncation,
+) -> Option<RenderedEvent> {
+    let summary = raw_text
+        .lines()
+        .map(sanitize_text)
+        .find(|line| !line.is_empty())?;
+
+    let sanitized = sanitize_text(raw_text);
+    let truncated = truncation.apply(&summary);
+    let tooltip = build_tooltip(timestamp.as_deref(), raw_text, &sanitized, &truncated);
+
+    Some(RenderedEvent {
+        payload: WaybarOutput {
+            text: truncated,
+            tooltip,
+            alt: Some("message".to_string()),
+            class: vec!["codex".to_string(), "agent-message".to_string()],
+            ..Default::default()
+        },
+        timestamp,
+    })
+}
+
+/// A failed turn (`error`) or a dropped model stream that Codex is retrying
+/// (`stream_error`). The full message goes to the tooltip.
+fn render_error(
+    retrying: bool,
+    message: Option<&str>,
+    timestamp: Option<String>,
+    truncation: &Truncation,
+) -> Option<RenderedEvent> {
+    let message = message.unwrap_or("Unknown error").trim();
+    let mut class = vec!["codex".to_string(), "error".to_string()];
+    let label = if retrying {
+        class.push("stream-error".to_string());
+        "stream error"
+    } else {
+        "error"
+    };
+
+    let first_line = message.lines().next().unwrap_or_default();
+    let text = truncation.apply(&format!("{label}: {}", sanitize_text(first_line)));
+    let tooltip = match &timestamp {
+        Some(ts) => format!("{ts}\n{message}"),
+        None => message.to_string(),
+    };
+
+    Some(RenderedEvent {
+        payload: WaybarOutput {
+            text,
+            tooltip: Some(tooltip),
+            alt: Some("error".to_string()),
+            class,
+            ..Default::default()
+        },
+        timestamp,
+    })
+}
+
+/// Flatten Markdown prose into one plain line: fences, headings, quotes,
+/// list bullets, emphasis markers, and backticks go, links keep their text.
+pub(crate) fn sanitize_text(input: &str) -> String {
+    let input = strip_ansi(input);
+    let lines: Vec<&str> = input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with("```") && !line.starts_with("~~~"))
+        .map(strip_line_markers)
+        .collect();
+    let text = strip_links(&lines.join(" "))
+        .replace("**", "")
+        .replace('`', "");
+    collapse_whitespace(&text)
+}
+
+/// Flatten text that is not Markdown, such as a shell command, onto one line.
+pub(crate) fn flatten_text(input: &str) -> String {
+    collapse_whitespace(&strip_ansi(input).replace(['\n', '\r'], " "))
+}
+
+/// Remove terminal escape sequences (colors, cursor movement, OSC titles and
+/// hyperlinks) that tools print into their output.
+pub(crate) fn strip_ansi(input: &str) -> String {
+    if !input.contains(['\x1b', '\u{9b}']) {
+        return input.to_string();
+    }
+    let mut output = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+    let is_final = |ch: &char| ('\x40'..='\x7e').contains(ch);
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\x1b' => match chars.next() {
+                // CSI: parameters and intermediates, then one final byte.
+                Some('[') => {
+                    chars.by_ref().find(is_final);
+                }
+                // OSC, DCS, and similar strings run to BEL or `ESC \`.
+                Some(']' | 'P' | 'X' | '^' | '_') => {
+                    while let Some(ch) = chars.next() {
+                        if ch == '\x07' || (ch == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                }
+                // Two-character sequences such as `ESC 7`.
+                _ => {}
+            },
+            '\u{9b}' => {
+                chars.by_ref().find(is_final);
+            }
+            ch => output.push(ch),
+        }
+    }
+    output
+}
+
+/// Drop a heading, block quote, or list marker from the start of a line.
+fn strip_line_markers(line: &str) -> &str {
+    let mut line = line;
+    while let Some(rest) = line.strip_prefix('>') {
+        line = rest.trim_start();
+    }
+    let hashes = line.len() - line.trim_start_matches('#').len();
+    if (1..=6).contains(&hashes) && line[hashes..].starts_with(' ') {
+        return line[hashes..].trim_start();
+    }
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(rest) = line.strip_prefix(bullet) {
+            return rest.trim_start();
+        }
+    }
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits > 0
+        && let Some(rest) = line[digits..]
+            .strip_prefix(". ")
+            .or_else(|| line[digits..].strip_prefix(") "))
+    {
+        return rest.trim_start();
+    }
+    line
+}
+
+/// Replace `[text](url)` and `![alt](url)` with their text.
+fn strip_links(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(open) = rest.find('[') {
+        let link = rest[open + 1..].find("](").and_then(|close| {
+            let text_end = open + 1 + close;
+            let url_end = rest[text_end + 2..].find(')')?;
+            Some((text_end, text_end + 2 + url_end + 1))
+        });
+        let Some((text_end, end)) = link else {
+            out.push_str(&rest[..=open]);
+            rest = &rest[open + 1..];
+            continue;
+        };
+        out.push_str(rest[..open].strip_suffix('!').unwrap_or(&rest[..open]));
+        out.push_str(&rest[open + 1..text_end]);
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn collapse_whitespace(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut last_space = false;
+    for ch in input.chars() {
+        if ch.is_whitespace() {
+            if !last_space {
+                out.push(' ');
+                last_space = true;
+            }
+        } else {
+            out.push(ch);
+            last_space = false;
+        }
+    }
+    out.trim().to_string()
+}
+
+/// How labels are shortened to fit the bar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Truncation {
+    /// Display columns available, ellipsis included.
+    pub max_width: usize,
+    /// Appended to shortened text; empty to cut without a marker.
+    pub ellipsis: String,
+    /// Applied to the text before it is measured.
+    pub rewrites: Rewrites,
+}
+
+impl Truncation {
+    pub const DEFAULT_ELLIPSIS: &str = "…";
+
+    pub fn new(max_width: usize) -> Self {
+        Truncation {
+            max_width,
+            ellipsis: Self::DEFAULT_ELLIPSIS.to_string(),
+            rewrites: Rewrites::default(),
+        }
+    }
+
+    /// Shorten `text` to `max_width` display columns. Cuts fall between
+    /// grapheme clusters, so wide CJK characters count as two columns and
+    /// emoji sequences are never split, and back up to the previous word
+    /// boundary unless that would drop more than half the label.
+    pub fn apply(&self, text: &str) -> String {
+        let rewritten = self.rewrites.apply(text);
+        let text = rewritten.as_str();
+        if text.width() <= self.max_width {
+            return text.to_string();
+        }
+        let budget = self.max_width.saturating_sub(self.ellipsis.width());
+        let mut truncated = String::new();
+        let mut width = 0;
+        let mut word_break = None;
+        let mut mid_word = false;
+        for grapheme in text.graphemes(true) {
+            let is_space = grapheme.chars().all(char::is_whitespace);
+            if width + grapheme.width() > budget {
+                mid_word = !is_space;
+                break;
+            }
+            if is_space {
+                word_break = Some((truncated.len(), width));
+            }
+            width += grapheme.width();
+            truncated.push_str(grapheme);
+        }
+        if mid_word
+            && let Some((end, kept)) = word_break
+            && kept * 2 >= budget
+        {
+            truncated.truncate(end);
+        }
+        let mut truncated = truncated.trim_end().to_owned();
+        truncated.push_str(&self.ellipsis);
+        truncated
+    }
+}
+
+pub(crate) fn extract_phase(raw: &str) -> Option<String> {
+    let stripped = raw.strip_prefix("**")?;
+    let end = stripped.find("**")?;
+    Some(stripped[..end].trim().to_string())
+}
+
+pub(crate) fn slugify(input: &str) -> Option<String> {
+    let mut slug = String::new();
+    let mut last_dash = false;
+    for ch in input.chars() {
+        if ch.is_ascii_alphanumeric() {
+            slug.push(ch.to_ascii_lowercase());
+            last_dash = false;
+        } else if ch.is_whitespace() || ch == '-' || ch == '_' {
+            if !slug.is_empty() && !last_dash {
+                slug.push('-');
+                last_dash = true;
+            }
+        } else if !slug.is_empty() && !last_dash {
+            slug.push('-');
+            last_dash = true;
+        }
+    }
+    while slug.ends_with('-') {
+        slug.pop();
+    }
+    if slug.is_empty() { None } else { Some(slug) }
+}
+
+/// Parse an RFC 3339 timestamp such as `2025-10-29T12:00:00.123Z`, keeping
+/// its fraction of a second. Offsets written without a colon (`+0200`) are
+/// accepted too; times without a zone offset are rejected, since their
+/// instant is unknown.
+pub(crate) fn parse_timestamp(timestamp: &str) -> Option<SystemTime> {
+    let parsed = DateTime::parse_from_rfc3339(timestamp)
+        .or_else(|_| DateTime::parse_from_str(timestamp, "%Y-%m-%dT%H:%M:%S%.f%z"))
+        .ok()?;
+    Some(parsed.with_timezone(&Utc).into())
+}
+
+/// `time` as an RFC 3339 UTC timestamp with milliseconds, the way Codex logs
+/// them.
+pub(crate) fn format_timestamp(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Order two log timestamps by the instant they name, whatever their offsets
+/// and precision. Ones that do not parse are compared as text.
+pub fn compare_timestamps(a: &str, b: &str) -> Ordering {
+    match (parse_timestamp(a), parse_timestamp(b)) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        _ => a.cmp(b),
+    }
+}
+
+/// A duration such as `45s`, `1m 24s`, or `2h 5m`.
+pub(crate) fn duration_text(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3_600 => format!("{}m {}s", secs / 60, secs % 60),
+        _ => format!("{}h {}m", secs / 3_600, secs % 3_600 / 60),
+    }
+}
+
+/// How long before `now` the timestamp was, such as `2m ago`.
+pub(crate) fn relative_time(timestamp: &str, now: SystemTime) -> Option<String> {
+    let secs = now
+        .duration_since(parse_timestamp(timestamp)?)
+        .unwrap_or_default()
+        .as_secs();
+    Some(match secs {
+        0..10 => "just now".to_string(),
+        10..60 => format!("{secs}s ago"),
+        60..3_600 => format!("{}m ago", secs / 60),
+        3_600..86_400 => format!("{}h ago", secs / 3_600),
+        _ => format!("{}d ago", secs / 86_400),
+    })
+}
+
+fn build_tooltip(
+    timestamp: Option<&str>,
+    raw_text: &str,
+    sanitized: &str,
+    truncated: &str,
+) -> Option<String> {
+    let mut parts = Vec::new();
+    if let Some(ts) = timestamp {
+        parts.push(ts.to_string());
+    }
+    let raw_trimmed = raw_text.trim();
+    if !raw_trimmed.is_empty() && raw_trimmed != sanitized {
+        parts.push(raw_trimmed.to_string());
+    } else if sanitized != truncated {
+        parts.push(sanitized.to_string());
+    }
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn agent_message_shows_first_line_with_its_own_class() -> Result<()> {
+        let line = json!({
+            "timestamp": "2025-10-29T12:10:00Z",
+            "type": "event_msg",
+            "payload": {
+                "type": "agent_message",
+                "message": "\n**Fixed** the tailer offset bug.\n\nDetails follow here."
+            }
+        })
+        .to_string();
+
+        let event = process_log_line(&line, 120)?.expect("agent_message is rendered");
+        assert_eq!(event.payload.text, "Fixed the tailer offset bug.");
+        assert_eq!(event.payload.class, vec!["codex", "agent-message"]);
+        assert!(
+            event
+                .payload
+                .tooltip
+                .unwrap()
+                .contains("Details follow here.")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn error_events_get_error_class_and_message_tooltip() -> Result<()> {
+        let line = json!({
+            "timestamp": "2025-10-29T12:11:00Z",
+            "type": "event_msg",
+            "payload": {
+                "type": "stream_error",
+                "message": "stream disconnected before completion\nretrying 1/5"
+            }
+        })
+        .to_string();
+
+        let event = process_log_line(&line, 120)?.expect("stream_error is rendered");
+        assert_eq!(
+            event.payload.text,
+            "stream error: stream disconnected before completion"
+        );
+        assert_eq!(event.payload.class, vec!["codex", "error", "stream-error"]);
+        assert!(event.payload.tooltip.unwrap().ends_with("retrying 1/5"));
+        Ok(())
+    }
+
+    #[test]
+    fn payloads_read_as_typed_events_or_other() {
+        let event = |payload: Value| EventPayload::from_value(Some("event_msg"), &payload);
+        assert_eq!(
+            event(json!({ "type": "agent_reasoning", "text": "**Plan**" })),
+            EventPayload::AgentReasoning {
+                text: "**Plan**".to_string()
+            }
+        );
+        assert_eq!(
+            event(json!({ "type": "error" })),
+            EventPayload::Error { message: None }
+        );
+        assert_eq!(
+            event(json!({
+                "type": "exec_command_begin",
+                "call_id": "c1",
+                "command": ["bash", "-lc", "cargo test"]
+            })),
+            EventPayload::ExecCommandBegin {
+                call_id: "c1".to_string(),
+                command: CommandLine::Argv(vec![
+                    "bash".to_string(),
+                    "-lc".to_string(),
+                    "cargo test".to_string()
+                ]),
+                cwd: None,
+            }
+        );
+        let EventPayload::McpToolCallEnd {
+            invocation: Some(invocation),
+            duration: Some(CallDuration::Span { secs: 1, .. }),
+            result: Some(result),
+            ..
+        } = event(json!({
+            "type": "mcp_tool_call_end",
+            "call_id": "c2",
+            "invocation": { "server": "docs", "tool": "search" },
+            "duration": { "secs": 1, "nanos": 0 },
+            "result": { "Ok": { "isError": true } }
+        }))
+        else {
+            panic!("mcp_tool_call_end was not typed");
+        };
+        assert_eq!(invocation.name(), "docs.search");
+        assert!(result.failed());
+        assert_eq!(
+            EventPayload::from_value(Some("turn_context"), &json!({ "model": "gpt-5" })),
+            EventPayload::TurnContext(SessionHeader {
+                model: Some("gpt-5".to_string()),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            event(json!({ "type": "web_search_begin" })),
+            EventPayload::Other
+        );
+
+        // A known kind with the wrong fields is counted, not passed off as
+        // an unknown one.
+        let before = METRICS.record_parse_error();
+        assert_eq!(
+            event(json!({ "type": "agent_message", "message": 3 })),
+            EventPayload::Other
+        );
+        assert!(METRICS.record_parse_error() >= before + 2);
+    }
+
+    #[test]
+    fn renderers_choose_label_and_which_entries_show() {
+        let entry = |payload: Value| LogEntry::new(None, Some("event_msg".to_string()), payload);
+        let phased = entry(json!({ "type": "agent_reasoning", "text": "**Testing** the parser" }));
+        let plain = entry(json!({ "type": "agent_reasoning", "text": "Just thinking" }));
+        let reply = entry(json!({ "type": "agent_message", "message": "Done" }));
+
+        let label = |kind: RendererKind, entry: &LogEntry| {
+            kind.renderer()
+                .render(entry, &Truncation::new(120), &PhaseAliases::default())
+                .map(|event| event.payload.text)
+        };
+        assert_eq!(
+            label(RendererKind::Compact, &phased).as_deref(),
+            Some("Testing")
+        );
+        assert_eq!(
+            label(RendererKind::Verbose, &phased).as_deref(),
+            Some("Testing the parser")
+        );
+        assert_eq!(
+            label(RendererKind::PhaseOnly, &phased).as_deref(),
+            Some("Testing")
+        );
+        assert_eq!(label(RendererKind::PhaseOnly, &plain), None);
+        assert_eq!(label(RendererKind::PhaseOnly, &reply), None);
+        assert_eq!(
+            label(RendererKind::Verbose, &reply).as_deref(),
+            Some("Done")
+        );
+    }
+
+    #[test]
+    fn phase_icons_match_slug_or_first_word() {
+        let overrides = BTreeMap::from([
+            ("testing".to_string(), "T".to_string()),
+            ("planning".to_string(), String::new()),
+        ]);
+        let icons = PhaseIcons::with_overrides(&overrides);
+        assert_eq!(icons.icon("Testing the parser"), Some("T"));
+        assert_eq!(icons.icon("Investigating"), Some("\u{f002}"));
+        assert_eq!(icons.icon("Planning"), None);
+        assert_eq!(icons.icon("Pondering"), None);
+    }
+
+    #[test]
+    fn sanitize_strips_markdown_syntax() {
+        let text = "## **Plan**\n\n- Read [the docs](https://example.com) and ![logo](x.png)\n\
+                    1. Run `cargo test`\n> quoted\n```rust\nfn main() {}\n```\n[not a link]";
+        assert_eq!(
+            sanitize_text(text),
+            "Plan Read the docs and logo Run cargo test quoted fn main() {} [not a link]"
+        );
+        assert_eq!(
+            flatten_text("echo `date`\n  | tee out"),
+            "echo `date` | tee out"
+        );
+    }
+
+    #[test]
+    fn truncation_counts_display_columns() {
+        let truncate = |text: &str, width: usize| Truncation::new(width).apply(text);
+        assert_eq!(truncate("plain ascii text", 10), "plain…");
+        assert_eq!(truncate("测试解析器的输出", 7), "测试解…");
+        assert_eq!(truncate("👩‍💻👩‍💻👩‍💻", 5), "👩‍💻👩‍💻…");
+        assert_eq!(truncate("测试", 4), "测试");
+    }
+
+    #[test]
+    fn truncation_backs_up_to_word_boundary_with_custom_ellipsis() -> anyhow::Result<()> {
+        let dots = Truncation {
+            max_width: 16,
+            ellipsis: "...".to_string(),
+            rewrites: Rewrites::default(),
+        };
+        assert_eq!(dots.apply("Checking the parser output"), "Checking the...");
+        assert_eq!(dots.apply("a supercalifragilistic"), "a supercalifr...");
+        let bare = Truncation {
+            max_width: 12,
+            ellipsis: String::new(),
+            rewrites: Rewrites::default(),
+        };
+        assert_eq!(bare.apply("Checking the parser"), "Checking the");
+
+        let rewritten = Truncation {
+            rewrites: Rewrites::new(&[crate::rewrite::RewriteRule {
+                pattern: "^I'm going to ".to_string(),
+                replacement: String::new(),
+            }])?,
+            ..Truncation::new(16)
+        };
+        assert_eq!(
+            rewritten.apply("I'm going to run the tests"),
+            "run the tests"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn timestamps_parse_with_offsets_and_format_relative() {
+        let at = |ts: &str| {
+            parse_timestamp(ts)
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|since| since.as_secs())
+        };
+        assert_eq!(at("2025-10-29T12:00:00.123Z"), Some(1_761_739_200));
+        assert_eq!(at("2025-10-29T14:00:00+02:00"), Some(1_761_739_200));
+        assert_eq!(at("2025-10-29T07:00:00-05:00"), Some(1_761_739_200));
+        assert_eq!(at("2025-10-29T12:00:00"), None);
+        for invalid in [
+            "2025-13-29T12:00:00Z",
+            "2025-10-40T12:00:00Z",
+            "2025-10-29T99:00:00Z",
+            "2025-10-29T12:00:00+99:99",
+            "99999999999999999-10-29T12:00:00Z",
+        ] {
+            assert_eq!(parse_timestamp(invalid), None, "{invalid}");
+        }
+
+        let instant = UNIX_EPOCH + Duration::from_millis(1_761_739_200_123);
+        assert_eq!(format_timestamp(instant), "2025-10-29T12:00:00.123Z");
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_secs(951_782_400)),
+            "2000-02-29T00:00:00.000Z"
+        );
+        assert_eq!(parse_timestamp(&format_timestamp(instant)), Some(instant));
+
+        let now = UNIX_EPOCH + Duration::from_secs(1_761_739_200 + 150);
+        let ts = "2025-10-29T12:00:00Z";
+        assert_eq!(TimestampStyle::Relative.format(ts, now), "2m ago");
+        assert_eq!(
+            TimestampStyle::Both.format(ts, now),
+            format!("{ts} (2m ago)")
+        );
+        assert_eq!(TimestampStyle::Absolute.format(ts, now), ts);
+        assert_eq!(
+            TimestampStyle::Relative.format("2025-10-29T12:00:00", now),
+            "2025-10-29T12:00:00"
+        );
+    }
+
+    #[test]
+    fn durations_read_as_minutes_and_seconds() {
+        assert_eq!(duration_text(45), "45s");
+        assert_eq!(duration_text(84), "1m 24s");
+        assert_eq!(duration_text(7_500), "2h 5m");
+    }
+
+    #[test]
+    fn ansi_escapes_are_stripped() {
+        assert_eq!(
+            strip_ansi(
+                "\x1b[1;31merror\x1b[0m: \x1b]8;;https://x.test\x07link\x1b]8;;\x1b\\ done\x1b7"
+            ),
+            "error: link done"
+        );
+        assert_eq!(
+            sanitize_text("\x1b[32m**Testing**\x1b[m the parser"),
+            "Testing the parser"
+        );
+        assert_eq!(flatten_text("cargo \u{9b}1mtest"), "cargo test");
+    }
+
+    #[test]
+    fn timestamps_order_across_offsets_and_precision() {
+        let order = |a, b| compare_timestamps(a, b);
+        assert_eq!(
+            order("2025-10-29T13:00:00+02:00", "2025-10-29T12:00:00Z"),
+            Ordering::Less
+        );
+        assert_eq!(
+            order("2025-10-29T12:00:00.5Z", "2025-10-29T12:00:00.123456Z"),
+            Ordering::Greater
+        );
+        assert_eq!(
+            order("2025-10-29T12:00:00Z", "2025-10-29T12:00:00.000Z"),
+            Ordering::Equal
+        );
+        assert_eq!(
+            order("2025-10-29T14:00:00+0200", "2025-10-29T12:00:00Z"),
+            Ordering::Equal
+        );
+        assert_eq!(
+            order("2025-10-29T12:00:00-05:00", "2025-10-29T15:00:00Z"),
+            Ordering::Greater
+        );
+        assert_eq!(
+            order("2025-10-29T07:00:00-0500", "2025-10-29T12:00:00Z"),
+            Ordering::Equal
+        );
+        assert_eq!(
+            order("not a time", "2025-10-29T12:00:00Z"),
+            Ordering::Greater
+        );
+    }
+}
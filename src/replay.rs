@@ -0,0 +1,121 @@
+//! `codex-shimmer replay`: play a recorded session log back through the
+//! daemon. The lines are copied into a scratch log at the requested pace, and
+//! the daemon follows the copy like a live session, so a user's log shows the
+//! same payloads it showed them.
+
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use tracing::info;
+
+use crate::agent::Decoder;
+use crate::render::{RenderOptions, parse_timestamp};
+
+/// Longest pause between two lines when following the log's timestamps, so
+/// a session left open over lunch does not stall the replay.
+pub const MAX_PAUSE: Duration = Duration::from_secs(30);
+
+/// How fast lines are played back.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pacing {
+    /// The gaps between logged timestamps, divided by this factor.
+    Timed(f64),
+    /// The same pause before every line.
+    Fixed(Duration),
+}
+
+impl Pacing {
+    /// The pause between lines logged at `previous` and `next`. Lines without
+    /// a timestamp follow the one before without a pause when timed.
+    pub fn pause(self, previous: Option<&str>, next: Option<&str>) -> Duration {
+        match self {
+            Pacing::Fixed(interval) => interval,
+            Pacing::Timed(speed) => {
+                let (Some(previous), Some(next)) = (
+                    previous.and_then(parse_timestamp),
+                    next.and_then(parse_timestamp),
+                ) else {
+                    return Duration::ZERO;
+                };
+                next.duration_since(previous)
+                    .unwrap_or_default()
+                    .div_f64(speed)
+                    .min(MAX_PAUSE)
+            }
+        }
+    }
+}
+
+/// Create an empty copy of `source` in `dir`. It keeps the file name, which
+/// names the session for most agents.
+pub fn prepare(source: &Path, dir: &Path) -> Result<PathBuf> {
+    let name = source
+        .file_name()
+        .with_context(|| format!("{} is not a file", source.display()))?;
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let copy = dir.join(name);
+    std::fs::write(&copy, "").with_context(|| format!("Failed to write {}", copy.display()))?;
+    Ok(copy)
+}
+
+/// Append the lines of `source` to `copy`, pausing between them as `pacing`
+/// says. Timestamps are read the way `options.agent` logs them.
+pub async fn play(
+    source: PathBuf,
+    copy: PathBuf,
+    pacing: Pacing,
+    options: RenderOptions,
+) -> Result<()> {
+    let contents =
+        std::fs::read(&source).with_context(|| format!("Failed to read {}", source.display()))?;
+    let mut file = OpenOptions::new()
+        .append(true)
+        .open(&copy)
+        .with_context(|| format!("Failed to open {}", copy.display()))?;
+    let mut decoder = Decoder::default();
+    let mut previous: Option<String> = None;
+    for line in contents.split_inclusive(|byte| *byte == b'\n') {
+        let timestamp = decoder
+            .entries(&options, String::from_utf8_lossy(line).trim_end())
+            .into_iter()
+            .find_map(|entry| entry.timestamp);
+        tokio::time::sleep(pacing.pause(previous.as_deref(), timestamp.as_deref())).await;
+        file.write_all(line)?;
+        if !line.ends_with(b"\n") {
+            file.write_all(b"\n")?;
+        }
+        previous = timestamp.or(previous);
+    }
+    info!(path = %source.display(), "Replay finished");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pauses_follow_timestamps_or_a_fixed_interval() {
+        let (start, later) = ("2025-10-29T12:00:00Z", "2025-10-29T12:00:04.500Z");
+        let timed = |speed| Pacing::Timed(speed).pause(Some(start), Some(later));
+        assert_eq!(timed(1.0), Duration::from_millis(4_500));
+        assert_eq!(timed(3.0), Duration::from_millis(1_500));
+        assert_eq!(
+            Pacing::Timed(1.0).pause(Some(start), Some("2025-10-29T13:00:00Z")),
+            MAX_PAUSE
+        );
+        assert_eq!(Pacing::Timed(1.0).pause(None, Some(later)), Duration::ZERO);
+        assert_eq!(
+            Pacing::Timed(1.0).pause(Some(later), Some(start)),
+            Duration::ZERO
+        );
+
+        let fixed = Pacing::Fixed(Duration::from_millis(250));
+        assert_eq!(fixed.pause(None, None), Duration::from_millis(250));
+    }
+}
@@ -0,0 +1,207 @@
+//! User-defined `pattern -> replacement` rules that shorten label text after
+//! Markdown is stripped and before it is truncated, and aliases that rename
+//! verbose phase headings.
+
+use anyhow::{Context, Result, bail};
+use regex::Regex;
+use serde::Deserialize;
+
+/// One `[[rewrite]]` table of the config file.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RewriteRule {
+    pub pattern: String,
+    /// May refer to capture groups as `$1` or `${name}`; empty deletes the match.
+    pub replacement: String,
+}
+
+/// Compiled rules, applied in order so a later rule sees the output of the
+/// earlier ones.
+#[derive(Debug, Clone, Default)]
+pub struct Rewrites {
+    rules: Vec<(Regex, String)>,
+}
+
+impl PartialEq for Rewrites {
+    fn eq(&self, other: &Self) -> bool {
+        self.rules.len() == other.rules.len()
+            && self
+                .rules
+                .iter()
+                .zip(&other.rules)
+                .all(|((a, a_with), (b, b_with))| a.as_str() == b.as_str() && a_with == b_with)
+    }
+}
+
+impl Eq for Rewrites {}
+
+impl Rewrites {
+    pub fn new(rules: &[RewriteRule]) -> Result<Self> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                let pattern = Regex::new(&rule.pattern)
+                    .with_context(|| format!("Invalid rewrite pattern `{}`", rule.pattern))?;
+                Ok((pattern, rule.replacement.clone()))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Rewrites { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Run every rule over `text`, then collapse the whitespace a deletion
+    /// leaves behind.
+    pub fn apply(&self, text: &str) -> String {
+        if self.is_empty() {
+            return text.to_string();
+        }
+        let rewritten = self
+            .rules
+            .iter()
+            .fold(text.to_string(), |text, (pattern, replacement)| {
+                pattern
+                    .replace_all(&text, replacement.as_str())
+                    .into_owned()
+            });
+        rewritten.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+}
+
+/// One `[[phase-alias]]` table: the phase to rename, given exactly or as a
+/// regex, and its new name.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PhaseAliasRule {
+    pub phase: Option<String>,
+    pub pattern: Option<String>,
+    /// May refer to capture groups of `pattern` as `$1` or `${name}`.
+    pub alias: String,
+}
+
+#[derive(Debug, Clone)]
+enum PhaseMatch {
+    Exact(String),
+    Pattern(Regex),
+}
+
+/// Compiled phase aliases; the first rule that matches a phase renames it.
+#[derive(Debug, Clone, Default)]
+pub struct PhaseAliases {
+    rules: Vec<(PhaseMatch, String)>,
+}
+
+impl PartialEq for PhaseAliases {
+    fn eq(&self, other: &Self) -> bool {
+        let key = |matcher: &PhaseMatch| match matcher {
+            PhaseMatch::Exact(phase) => (false, phase.clone()),
+            PhaseMatch::Pattern(pattern) => (true, pattern.as_str().to_string()),
+        };
+        self.rules.len() == other.rules.len()
+            && self
+                .rules
+                .iter()
+                .zip(&other.rules)
+                .all(|((a, a_alias), (b, b_alias))| key(a) == key(b) && a_alias == b_alias)
+    }
+}
+
+impl Eq for PhaseAliases {}
+
+impl PhaseAliases {
+    pub fn new(rules: &[PhaseAliasRule]) -> Result<Self> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                let matcher = match (&rule.phase, &rule.pattern) {
+                    (Some(phase), None) => PhaseMatch::Exact(phase.clone()),
+                    (None, Some(pattern)) => PhaseMatch::Pattern(
+                        Regex::new(pattern)
+                            .with_context(|| format!("Invalid phase alias pattern `{pattern}`"))?,
+                    ),
+                    _ => bail!(
+                        "phase alias `{}` needs exactly one of `phase` or `pattern`",
+                        rule.alias
+                    ),
+                };
+                Ok((matcher, rule.alias.clone()))
+            })
+            .collect::<Result<_>>()?;
+        Ok(PhaseAliases { rules })
+    }
+
+    /// The alias of `phase`, or the phase itself when no rule matches.
+    pub fn apply(&self, phase: &str) -> String {
+        for (matcher, alias) in &self.rules {
+            match matcher {
+                PhaseMatch::Exact(exact) if exact == phase => return alias.clone(),
+                PhaseMatch::Pattern(pattern) => {
+                    if let Some(captures) = pattern.captures(phase) {
+                        let mut renamed = String::new();
+                        captures.expand(alias, &mut renamed);
+                        return renamed;
+                    }
+                }
+                PhaseMatch::Exact(_) => {}
+            }
+        }
+        phase.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_apply_in_order() -> Result<()> {
+        let rule = |pattern: &str, replacement: &str| RewriteRule {
+            pattern: pattern.to_string(),
+            replacement: replacement.to_string(),
+        };
+        let rewrites = Rewrites::new(&[
+            rule(r"(?i)\bI'm going to\b", ""),
+            rule(r"/home/\w+/src/(\w+)", "$1:"),
+            rule(r"crate:", "~"),
+        ])?;
+        assert_eq!(
+            rewrites.apply("I'm going to check /home/me/src/crate/main.rs now"),
+            "check ~/main.rs now"
+        );
+        assert_eq!(Rewrites::default().apply("  kept as is "), "  kept as is ");
+        assert!(Rewrites::new(&[rule("(", "")]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn phase_aliases_match_exactly_or_by_pattern() -> Result<()> {
+        let aliases = PhaseAliases::new(&[
+            PhaseAliasRule {
+                phase: Some("Investigating the failing test".to_string()),
+                pattern: None,
+                alias: "Investigating".to_string(),
+            },
+            PhaseAliasRule {
+                phase: None,
+                pattern: Some(r"^(\w+)ing the .*".to_string()),
+                alias: "$1".to_string(),
+            },
+        ])?;
+        assert_eq!(
+            aliases.apply("Investigating the failing test"),
+            "Investigating"
+        );
+        assert_eq!(aliases.apply("Fixing the parser bug"), "Fix");
+        assert_eq!(aliases.apply("Planning"), "Planning");
+        assert!(
+            PhaseAliases::new(&[PhaseAliasRule {
+                alias: "x".to_string(),
+                ..PhaseAliasRule::default()
+            }])
+            .is_err()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+//! `codex-shimmer search`: find reasoning and replies matching a pattern in
+//! the recent session logs, or in the event store.
+
+use std::fmt::Write;
+
+use anyhow::Result;
+use regex::Regex;
+
+use crate::agent::Decoder;
+use crate::config::Settings;
+use crate::discovery::session_logs;
+use crate::render::EventPayload;
+use crate::tailer::read_log;
+
+/// One reasoning entry or reply with matching lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hit {
+    pub session_id: String,
+    pub timestamp: Option<String>,
+    /// The matching lines and the context around them, each with whether it
+    /// matched; `None` marks lines left out between two excerpts.
+    pub lines: Vec<Option<(bool, String)>>,
+}
+
+/// The lines of `text` matching `pattern`, with up to `context` lines before
+/// and after each; `None` when no line matches.
+pub fn excerpt(text: &str, pattern: &Regex, context: usize) -> Option<Vec<Option<(bool, String)>>> {
+    let lines: Vec<&str> = text.lines().collect();
+    let matched: Vec<bool> = lines.iter().map(|line| pattern.is_match(line)).collect();
+    if !matched.contains(&true) {
+        return None;
+    }
+    let shown = |index: usize| {
+        let from = index.saturating_sub(context);
+        let to = (index + context).min(lines.len() - 1);
+        matched[from..=to].contains(&true)
+    };
+    let mut excerpt = Vec::new();
+    let mut skipped = false;
+    for (index, line) in lines.iter().enumerate() {
+        if !shown(index) {
+            skipped = true;
+            continue;
+        }
+        if skipped && !excerpt.is_empty() {
+            excerpt.push(None);
+        }
+        skipped = false;
+        excerpt.push(Some((matched[index], line.to_string())));
+    }
+    Some(excerpt)
+}
+
+/// Search the reasoning and replies of the `limit` most recent sessions, or
+/// of the one session asked for by id or file, oldest first.
+pub fn search_logs(
+    settings: &Settings,
+    pattern: &Regex,
+    context: usize,
+    limit: usize,
+) -> Result<Vec<Hit>> {
+    let options = settings.render_options();
+    let mut hits = Vec::new();
+    for (session_id, parts) in session_logs(settings, limit)? {
+        let mut decoder = Decoder::default();
+        for part in parts {
+            for line in read_log(&part, settings.max_line_bytes)? {
+                for entry in decoder.entries(&options, &line) {
+                    let text = match &entry.event {
+                        EventPayload::AgentReasoning { text } => text,
+                        EventPayload::AgentMessage { message } => message,
+                        _ => continue,
+                    };
+                    if let Some(lines) = excerpt(&settings.redactor.redact(text), pattern, context)
+                    {
+                        hits.push(Hit {
+                            session_id: session_id.clone(),
+                            timestamp: entry.timestamp,
+                            lines,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    Ok(hits)
+}
+
+/// Search the reasoning and replies kept in the event store, oldest first.
+#[cfg(feature = "store")]
+pub fn search_store(
+    connection: &rusqlite::Connection,
+    pattern: &Regex,
+    context: usize,
+) -> Result<Vec<Hit>> {
+    let mut query = connection.prepare(
+        "SELECT session_id, timestamp, text FROM events
+         WHERE kind IN ('agent_reasoning', 'agent_message') ORDER BY timestamp",
+    )?;
+    let rows = query.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+        ))
+    })?;
+    let mut hits = Vec::new();
+    for row in rows {
+        let (session_id, timestamp, text) = row?;
+        if let Some(lines) = excerpt(&text, pattern, context) {
+            hits.push(Hit {
+                session_id,
+                timestamp: (!timestamp.is_empty()).then_some(timestamp),
+                lines,
+            });
+        }
+    }
+    Ok(hits)
+}
+
+/// A hit as `search` prints it: the session and time, then the excerpt with
+/// matching lines marked by `>`.
+pub fn format_hit(hit: &Hit) -> String {
+    let mut out = hit.session_id.clone();
+    if let Some(timestamp) = &hit.timestamp {
+        let _ = write!(out, "  {timestamp}");
+    }
+    out.push('\n');
+    for line in &hit.lines {
+        match line {
+            Some((matched, text)) => {
+                let _ = writeln!(out, "{} {text}", if *matched { '>' } else { ' ' });
+            }
+            None => out.push_str("  …\n"),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn excerpts_keep_context_and_mark_gaps() -> Result<()> {
+        let text = "**Refactoring the parser**\n\nfirst\nsecond\nthird\nfourth\nparser done";
+        let pattern = Regex::new("(?i)parser")?;
+        let lines = excerpt(text, &pattern, 1).expect("matches");
+        let line = |matched, text: &str| Some((matched, text.to_string()));
+        assert_eq!(
+            lines,
+            [
+                line(true, "**Refactoring the parser**"),
+                line(false, ""),
+                None,
+                line(false, "fourth"),
+                line(true, "parser done"),
+            ]
+        );
+        assert_eq!(excerpt(text, &Regex::new("lexer")?, 1), None);
+
+        let hit = Hit {
+            session_id: "abc".to_string(),
+            timestamp: Some("2025-10-29T12:00:00Z".to_string()),
+            lines,
+        };
+        assert_eq!(
+            format_hit(&hit),
+            "abc  2025-10-29T12:00:00Z\n> **Refactoring the parser**\n  \n  …\n  fourth\n> parser done\n"
+        );
+        Ok(())
+    }
+}
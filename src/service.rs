@@ -0,0 +1,175 @@
+//! `codex-shimmer service`: writes the systemd user unit with the flags given
+//! on the command line and drives it through `systemctl --user`.
+
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::control::{ControlCommand, send_command};
+
+pub const UNIT: &str = "codex-shimmer.service";
+/// The unit shipped in `systemd/`; generated units only change `ExecStart`.
+const TEMPLATE: &str = include_str!("../systemd/codex-shimmer.service");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Enable,
+    Disable,
+    Status,
+    Restart,
+}
+
+/// The unit file running `exe` with `args`, or with the shipped unit's flags
+/// when `args` is empty.
+pub fn render_unit(exe: &Path, args: &[OsString]) -> String {
+    let mut unit = String::from(
+        "# Written by `codex-shimmer service enable`; run it again to change the flags.\n",
+    );
+    for line in TEMPLATE.lines() {
+        match line.strip_prefix("ExecStart=") {
+            Some(shipped) => {
+                let mut exec = format!("ExecStart={}", quote(&exe.to_string_lossy()));
+                if args.is_empty() {
+                    // Keep the shipped flags, which use `%h` on purpose.
+                    exec.extend(
+                        shipped
+                            .split_once(' ')
+                            .map(|(_, flags)| format!(" {flags}")),
+                    );
+                } else {
+                    for arg in args {
+                        exec.push(' ');
+                        exec.push_str(&quote(&arg.to_string_lossy()));
+                    }
+                }
+                unit.push_str(&exec);
+            }
+            None => unit.push_str(line),
+        }
+        unit.push('\n');
+    }
+    unit
+}
+
+/// Quote one word for `ExecStart`, escaping systemd's `%` specifiers and `$`
+/// variable expansion so the daemon sees it unchanged.
+fn quote(word: &str) -> String {
+    let escaped = word
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%")
+        .replace('$', "$$");
+    if !word.is_empty() && !word.contains(|c: char| c.is_whitespace() || c == '\'' || c == ';') {
+        escaped
+    } else {
+        format!("\"{escaped}\"")
+    }
+}
+
+/// Run `action`. `unit_dir` receives the unit on `enable`, and `socket` is
+/// asked for the daemon state on `status`. Returns whether the daemon is
+/// healthy, which only `status` reports on.
+pub async fn run(
+    action: Action,
+    unit_dir: &Path,
+    daemon_args: &[OsString],
+    socket: &Path,
+) -> Result<bool> {
+    match action {
+        Action::Enable => {
+            let exe = std::env::current_exe().context("Unable to locate the running binary")?;
+            let path = write_unit(unit_dir, &render_unit(&exe, daemon_args))?;
+            println!("Wrote {}", path.display());
+            systemctl(&["daemon-reload"])?;
+            systemctl(&["enable", UNIT])?;
+            // Picks up the new flags when the service was already running.
+            systemctl(&["restart", UNIT])?;
+            println!("{UNIT} is enabled and running");
+        }
+        Action::Disable => {
+            systemctl(&["disable", "--now", UNIT])?;
+            println!("{UNIT} is stopped and disabled");
+        }
+        Action::Restart => systemctl(&["restart", UNIT])?,
+        Action::Status => return status(socket).await,
+    }
+    Ok(true)
+}
+
+fn write_unit(unit_dir: &Path, unit: &str) -> Result<PathBuf> {
+    fs::create_dir_all(unit_dir)
+        .with_context(|| format!("Failed to create {}", unit_dir.display()))?;
+    let path = unit_dir.join(UNIT);
+    fs::write(&path, unit).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Healthy means systemd reports the unit active and the daemon answers on
+/// its control socket.
+async fn status(socket: &Path) -> Result<bool> {
+    let output = Command::new("systemctl")
+        .args(["--user", "is-active", UNIT])
+        .output()
+        .context("Failed to run systemctl")?;
+    let state = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    println!(
+        "{UNIT}: {}",
+        if state.is_empty() { "unknown" } else { &state }
+    );
+    match send_command(socket, &ControlCommand::Status).await {
+        Ok(daemon) => {
+            println!("{daemon}");
+            Ok(state == "active")
+        }
+        Err(err) => {
+            println!("Daemon is not answering: {err:#}");
+            Ok(false)
+        }
+    }
+}
+
+fn systemctl(args: &[&str]) -> Result<()> {
+    let status = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .status()
+        .context("Failed to run systemctl")?;
+    if !status.success() {
+        bail!("systemctl --user {} failed ({status})", args.join(" "));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unit_bakes_in_the_given_flags() {
+        let exe = Path::new("/home/me/.local/bin/codex-shimmer");
+        let args = [
+            "--cache-file",
+            "/tmp/my cache.json",
+            "--format",
+            "{phase} 50%",
+        ]
+        .map(OsString::from);
+        let unit = render_unit(exe, &args);
+        assert!(unit.contains(
+            "\nExecStart=/home/me/.local/bin/codex-shimmer --cache-file \"/tmp/my cache.json\" \
+             --format \"{phase} 50%%\"\n"
+        ));
+        assert!(unit.contains("\nRestart=on-failure\n"));
+
+        let shipped = render_unit(exe, &[]);
+        assert!(shipped.contains(
+            "ExecStart=/home/me/.local/bin/codex-shimmer --cache-file \
+             %h/.cache/codex-shimmer/latest.json"
+        ));
+    }
+}
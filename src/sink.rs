@@ -0,0 +1,130 @@
+//! Destinations for the payload on display. The emitter hands each payload to
+//! every sink the settings enable, in order.
+
+use std::{future::Future, path::PathBuf, pin::Pin};
+
+use anyhow::Result;
+use tokio::task::spawn_blocking;
+
+use crate::config::Settings;
+use crate::output::{WaybarOutput, emit_payload, write_payload_to_stdout};
+use crate::webhook::WebhookSink;
+
+pub type SinkFuture<'a> = Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>;
+
+pub trait Sink: Send {
+    /// Deliver one payload. An error stops the daemon, so a sink whose
+    /// destination can be briefly unreachable retries on its own instead.
+    fn deliver<'a>(&'a mut self, payload: &'a WaybarOutput) -> SinkFuture<'a>;
+}
+
+/// Writes the payload to a cache file, then signals Waybar.
+pub struct CacheSink {
+    path: PathBuf,
+    waybar_signal: Option<u8>,
+    waybar_process: Vec<String>,
+    fsync: bool,
+}
+
+impl CacheSink {
+    pub fn new(path: PathBuf, settings: &Settings) -> Self {
+        CacheSink {
+            path,
+            waybar_signal: settings.waybar_signal,
+            waybar_process: settings.waybar_process.clone(),
+            fsync: settings.fsync,
+        }
+    }
+}
+
+impl Sink for CacheSink {
+    fn deliver<'a>(&'a mut self, payload: &'a WaybarOutput) -> SinkFuture<'a> {
+        let (payload, path) = (payload.clone(), self.path.clone());
+        let (waybar_signal, waybar_process) = (self.waybar_signal, self.waybar_process.clone());
+        let fsync = self.fsync;
+        Box::pin(async move {
+            spawn_blocking(move || {
+                emit_payload(&payload, &path, waybar_signal, &waybar_process, fsync)
+            })
+            .await?
+        })
+    }
+}
+
+/// Prints one payload per line for Waybar's `exec` mode.
+pub struct StdoutSink;
+
+impl Sink for StdoutSink {
+    fn deliver<'a>(&'a mut self, payload: &'a WaybarOutput) -> SinkFuture<'a> {
+        Box::pin(async move { write_payload_to_stdout(payload) })
+    }
+}
+
+/// The settings the sinks are built from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SinkSettings {
+    cache_file: Option<PathBuf>,
+    waybar_signal: Option<u8>,
+    waybar_process: Vec<String>,
+    fsync: bool,
+    stdout: bool,
+    webhook_url: Option<String>,
+    webhook_class: Vec<String>,
+}
+
+impl SinkSettings {
+    fn of(settings: &Settings) -> Self {
+        SinkSettings {
+            cache_file: settings.cache_file.clone(),
+            waybar_signal: settings.waybar_signal,
+            waybar_process: settings.waybar_process.clone(),
+            fsync: settings.fsync,
+            stdout: settings.stdout,
+            webhook_url: settings.webhook_url.clone(),
+            webhook_class: settings.webhook_class.clone(),
+        }
+    }
+}
+
+/// The sinks for the payload on display, rebuilt when a settings reload
+/// changes them.
+pub struct Pipeline {
+    built_from: SinkSettings,
+    sinks: Vec<Box<dyn Sink>>,
+}
+
+impl Pipeline {
+    pub fn new(settings: &Settings) -> Self {
+        let mut sinks: Vec<Box<dyn Sink>> = Vec::new();
+        if settings.stdout {
+            sinks.push(Box::new(StdoutSink));
+        }
+        if let Some(cache_file) = &settings.cache_file {
+            sinks.push(Box::new(CacheSink::new(cache_file.clone(), settings)));
+        }
+        if let Some(url) = &settings.webhook_url {
+            sinks.push(Box::new(WebhookSink::new(
+                url.clone(),
+                settings.webhook_class.clone(),
+            )));
+        }
+        Pipeline {
+            built_from: SinkSettings::of(settings),
+            sinks,
+        }
+    }
+
+    /// Rebuild the sinks if `settings` configures them differently.
+    pub fn update(&mut self, settings: &Settings) {
+        if self.built_from != SinkSettings::of(settings) {
+            *self = Pipeline::new(settings);
+        }
+    }
+
+    pub async fn deliver(&mut self, payload: &WaybarOutput) -> Result<()> {
+        for sink in &mut self.sinks {
+            sink.deliver(payload).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,270 @@
+//! `codex-shimmer stats`: a usage summary over a period, read from the event
+//! store.
+
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use rusqlite::{Connection, params};
+use serde::Serialize;
+
+use crate::render::format_timestamp;
+use crate::session::compact;
+
+/// List prices in dollars per million input, cached input, and output
+/// tokens, by model name prefix; the first match wins.
+const PRICES: &[(&str, [f64; 3])] = &[
+    ("gpt-5-mini", [0.25, 0.025, 2.0]),
+    ("gpt-5-nano", [0.05, 0.005, 0.4]),
+    ("gpt-5", [1.25, 0.125, 10.0]),
+    ("gpt-4.1", [2.0, 0.5, 8.0]),
+    ("o4-mini", [1.1, 0.275, 4.4]),
+    ("o3", [2.0, 0.5, 8.0]),
+    ("claude-opus", [15.0, 1.5, 75.0]),
+    ("claude-sonnet", [3.0, 0.3, 15.0]),
+    ("claude-haiku", [0.8, 0.08, 4.0]),
+];
+
+/// Which stretch of history to sum up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    /// Since local midnight.
+    Today,
+    /// Today and the six days before.
+    Week,
+}
+
+impl Period {
+    fn label(self) -> &'static str {
+        match self {
+            Period::Today => "today",
+            Period::Week => "in the last 7 days",
+        }
+    }
+
+    /// The start of the period, from the C library's idea of the time zone.
+    pub fn start(self, now: SystemTime) -> SystemTime {
+        let days = match self {
+            Period::Today => 0,
+            Period::Week => 6,
+        };
+        let secs = now
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+        let since_midnight =
+            if unsafe { libc::localtime_r(&(secs as libc::time_t), &mut tm) }.is_null() {
+                0
+            } else {
+                (tm.tm_hour * 3600 + tm.tm_min * 60 + tm.tm_sec) as u64
+            };
+        SystemTime::UNIX_EPOCH
+            + Duration::from_secs(secs.saturating_sub(since_midnight + days * 86_400))
+    }
+}
+
+/// Totals over a period.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Stats {
+    /// Start of the period, as an RFC 3339 UTC timestamp.
+    pub since: String,
+    pub sessions: u64,
+    pub turns: u64,
+    pub commands: u64,
+    pub approvals: u64,
+    pub input_tokens: u64,
+    pub cached_input_tokens: u64,
+    pub output_tokens: u64,
+    /// Estimated from list prices, for the models that have one.
+    pub cost_usd: f64,
+    /// Models whose tokens are not in the cost.
+    pub unpriced_models: Vec<String>,
+}
+
+/// Sum up the events logged at or after `since`. Token totals are cumulative
+/// per session, so a session's usage is its newest total in the period less
+/// its newest total before it.
+pub fn collect(connection: &Connection, since: SystemTime) -> Result<Stats> {
+    let since = format_timestamp(since);
+    let mut stats = connection.query_row(
+        "SELECT COUNT(DISTINCT session_id),
+                COALESCE(SUM(kind = 'task_started'), 0),
+                COALESCE(SUM(kind = 'exec_command_begin'), 0),
+                COALESCE(SUM(kind IN ('exec_approval_request', 'apply_patch_approval_request')), 0)
+         FROM events WHERE timestamp >= ?1",
+        params![since],
+        |row| {
+            Ok(Stats {
+                sessions: row.get(0)?,
+                turns: row.get(1)?,
+                commands: row.get(2)?,
+                approvals: row.get(3)?,
+                ..Stats::default()
+            })
+        },
+    )?;
+
+    let mut usage = connection.prepare(
+        "SELECT
+            COALESCE(MAX(CASE WHEN timestamp >= ?1 THEN input_tokens END), 0),
+            COALESCE(MAX(CASE WHEN timestamp < ?1 THEN input_tokens END), 0),
+            COALESCE(MAX(CASE WHEN timestamp >= ?1 THEN cached_input_tokens END), 0),
+            COALESCE(MAX(CASE WHEN timestamp < ?1 THEN cached_input_tokens END), 0),
+            COALESCE(MAX(CASE WHEN timestamp >= ?1 THEN output_tokens END), 0),
+            COALESCE(MAX(CASE WHEN timestamp < ?1 THEN output_tokens END), 0),
+            (SELECT model FROM events AS latest
+             WHERE latest.session_id = events.session_id AND model IS NOT NULL
+             ORDER BY timestamp DESC LIMIT 1)
+         FROM events GROUP BY session_id HAVING MAX(timestamp) >= ?1",
+    )?;
+    let rows = usage.query_map(params![since], |row| {
+        let spent = |now: usize| -> rusqlite::Result<u64> {
+            let (now, before): (u64, u64) = (row.get(now)?, row.get(now + 1)?);
+            Ok(now.saturating_sub(before))
+        };
+        Ok((
+            [spent(0)?, spent(2)?, spent(4)?],
+            row.get::<_, Option<String>>(6)?,
+        ))
+    })?;
+    for row in rows {
+        let ([input, cached, output], model) = row?;
+        stats.input_tokens += input;
+        stats.cached_input_tokens += cached;
+        stats.output_tokens += output;
+        if input + output == 0 {
+            continue;
+        }
+        let model = model.unwrap_or_else(|| "unknown".to_string());
+        match price(&model) {
+            Some([input_rate, cached_rate, output_rate]) => {
+                let uncached = input.saturating_sub(cached);
+                stats.cost_usd += (uncached as f64 * input_rate
+                    + cached as f64 * cached_rate
+                    + output as f64 * output_rate)
+                    / 1_000_000.0;
+            }
+            None if !stats.unpriced_models.contains(&model) => stats.unpriced_models.push(model),
+            None => {}
+        }
+    }
+    stats.since = since;
+    Ok(stats)
+}
+
+fn price(model: &str) -> Option<[f64; 3]> {
+    PRICES
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map(|(_, rates)| *rates)
+}
+
+/// The summary as printed by `stats`.
+pub fn report(stats: &Stats, period: Period) -> String {
+    let mut cost = format!("~${:.2}", stats.cost_usd);
+    if !stats.unpriced_models.is_empty() {
+        cost.push_str(&format!(
+            " (not counting {})",
+            stats.unpriced_models.join(", ")
+        ));
+    }
+    format!(
+        "Codex usage {}\n\
+         Sessions   {}\n\
+         Turns      {}\n\
+         Commands   {}\n\
+         Approvals  {}\n\
+         Tokens     {} in ({} cached) · {} out\n\
+         Cost       {cost}\n",
+        period.label(),
+        stats.sessions,
+        stats.turns,
+        stats.commands,
+        stats.approvals,
+        compact(stats.input_tokens),
+        compact(stats.cached_input_tokens),
+        compact(stats.output_tokens),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{StoredEvent, insert, open};
+
+    #[test]
+    fn stats_count_the_period_and_price_token_growth() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let connection = open(&dir.path().join("events.sqlite"))?;
+        let model = |session: &str| {
+            if session == "a" {
+                "gpt-5-codex"
+            } else {
+                "mystery"
+            }
+        };
+        let event =
+            |session: &str, timestamp: &str, kind: &str, input: u64, output: u64| StoredEvent {
+                session_id: session.to_string(),
+                timestamp: timestamp.to_string(),
+                kind: kind.to_string(),
+                phase: None,
+                text: format!("{kind} at {timestamp}"),
+                model: Some(model(session).to_string()),
+                input_tokens: Some(input),
+                cached_input_tokens: Some(0),
+                output_tokens: Some(output),
+            };
+        for stored in [
+            // Yesterday's usage is not counted again.
+            event("a", "2025-10-28T23:00:00.000Z", "token_count", 1_000_000, 0),
+            event(
+                "a",
+                "2025-10-29T09:00:00.000Z",
+                "task_started",
+                1_000_000,
+                0,
+            ),
+            event(
+                "a",
+                "2025-10-29T09:00:01.000Z",
+                "exec_approval_request",
+                1_000_000,
+                0,
+            ),
+            event(
+                "a",
+                "2025-10-29T09:00:02.000Z",
+                "exec_command_begin",
+                1_000_000,
+                0,
+            ),
+            event(
+                "a",
+                "2025-10-29T09:00:03.000Z",
+                "token_count",
+                3_000_000,
+                100_000,
+            ),
+            event("b", "2025-10-29T10:00:00.000Z", "task_started", 0, 0),
+            event("b", "2025-10-29T10:00:01.000Z", "token_count", 500, 50),
+        ] {
+            insert(&connection, &stored)?;
+        }
+
+        let since = crate::render::parse_timestamp("2025-10-29T00:00:00Z").expect("timestamp");
+        let stats = collect(&connection, since)?;
+        assert_eq!(
+            (stats.sessions, stats.turns, stats.commands, stats.approvals),
+            (2, 2, 1, 1)
+        );
+        assert_eq!(
+            (stats.input_tokens, stats.output_tokens),
+            (2_000_500, 100_050)
+        );
+        // 2M uncached input at $1.25 and 100k output at $10 per million.
+        assert!((stats.cost_usd - 3.5).abs() < 1e-9);
+        assert_eq!(stats.unpriced_models, ["mystery"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+//! Sends the daemon's counters and timings to a StatsD or DogStatsD agent over
+//! UDP, for setups such as Telegraf that graph without scraping.
+
+use std::{net::UdpSocket, time::Duration};
+
+use anyhow::{Context, Result};
+
+pub const DEFAULT_PREFIX: &str = "codex_shimmer";
+
+#[derive(Debug)]
+pub struct Statsd {
+    socket: UdpSocket,
+    prefix: String,
+    /// Send tags DogStatsD-style (`|#type:x`) instead of in the metric name.
+    dogstatsd: bool,
+}
+
+impl Statsd {
+    pub fn connect(addr: &str, prefix: &str, dogstatsd: bool) -> Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")
+            .or_else(|_| UdpSocket::bind("[::]:0"))
+            .context("Failed to open a UDP socket for StatsD")?;
+        socket
+            .connect(addr)
+            .with_context(|| format!("Failed to resolve StatsD address {addr}"))?;
+        // Metrics are best effort; never wait on the agent.
+        socket.set_nonblocking(true)?;
+        Ok(Statsd {
+            socket,
+            prefix: prefix.to_string(),
+            dogstatsd,
+        })
+    }
+
+    pub fn count(&self, name: &str, tag: Option<(&str, &str)>) {
+        self.send(&self.line(name, "1", "c", tag));
+    }
+
+    pub fn gauge(&self, name: &str, value: u64) {
+        self.send(&self.line(name, &value.to_string(), "g", None));
+    }
+
+    pub fn timing(&self, name: &str, took: Duration) {
+        let millis = format!("{:.3}", took.as_secs_f64() * 1000.0);
+        self.send(&self.line(name, &millis, "ms", None));
+    }
+
+    fn line(&self, name: &str, value: &str, kind: &str, tag: Option<(&str, &str)>) -> String {
+        let prefix = match self.prefix.as_str() {
+            "" => String::new(),
+            prefix => format!("{prefix}."),
+        };
+        match tag {
+            Some((key, tag)) if self.dogstatsd => {
+                format!("{prefix}{name}:{value}|{kind}|#{key}:{}", sanitize(tag))
+            }
+            Some((_, tag)) => format!("{prefix}{name}.{}:{value}|{kind}", sanitize(tag)),
+            None => format!("{prefix}{name}:{value}|{kind}"),
+        }
+    }
+
+    fn send(&self, line: &str) {
+        // Nobody listening, or a full buffer, just loses the sample.
+        let _ = self.socket.send(line.as_bytes());
+    }
+}
+
+/// Keep a tag usable as a metric name segment or DogStatsD tag value.
+fn sanitize(tag: &str) -> String {
+    let tag = tag
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    if tag.is_empty() {
+        "unknown".to_string()
+    } else {
+        tag
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_use_plain_or_dogstatsd_tags() -> Result<()> {
+        let agent = UdpSocket::bind("127.0.0.1:0")?;
+        agent.set_read_timeout(Some(Duration::from_secs(2)))?;
+        let addr = agent.local_addr()?.to_string();
+
+        let plain = Statsd::connect(&addr, DEFAULT_PREFIX, false)?;
+        let tagged = Statsd::connect(&addr, "codex", true)?;
+        plain.count("events", Some(("type", "agent_reasoning")));
+        tagged.count("events", Some(("type", "say hi")));
+        plain.gauge("tracked_sessions", 3);
+        tagged.timing("cache_write", Duration::from_micros(1500));
+
+        let mut received = Vec::new();
+        let mut buffer = [0; 256];
+        for _ in 0..4 {
+            let len = agent.recv(&mut buffer)?;
+            received.push(String::from_utf8_lossy(&buffer[..len]).into_owned());
+        }
+        assert_eq!(
+            received,
+            [
+                "codex_shimmer.events.agent_reasoning:1|c",
+                "codex.events:1|c|#type:say_hi",
+                "codex_shimmer.tracked_sessions:3|g",
+                "codex.cache_write:1.500|ms",
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+//! The event store: every rendered event kept in a SQLite database, with the
+//! `store` feature, so history outlives the daemon and the logs it came from.
+//!
+//! Sessions are re-read from the start whenever the daemon starts, so rows
+//! are unique by session, timestamp, kind, and text and repeats are dropped.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use rusqlite::{Connection, params};
+use tokio::sync::mpsc::UnboundedReceiver;
+use tracing::warn;
+
+use crate::render::RenderedEvent;
+use crate::session::SessionRenderer;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS events (
+    id INTEGER PRIMARY KEY,
+    session_id TEXT NOT NULL,
+    timestamp TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    phase TEXT,
+    text TEXT NOT NULL,
+    model TEXT,
+    input_tokens INTEGER,
+    cached_input_tokens INTEGER,
+    output_tokens INTEGER,
+    UNIQUE (session_id, timestamp, kind, text)
+);
+CREATE INDEX IF NOT EXISTS events_by_time ON events (timestamp);
+";
+
+/// One rendered event as stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredEvent {
+    pub session_id: String,
+    /// As logged; empty when the line had none.
+    pub timestamp: String,
+    /// Kind of the log entry, such as `agent_reasoning` or `exec_command_begin`.
+    pub kind: String,
+    pub phase: Option<String>,
+    /// The full reasoning or message, redacted, or the label for other events.
+    pub text: String,
+    pub model: Option<String>,
+    /// The session's token totals as of this event.
+    pub input_tokens: Option<u64>,
+    pub cached_input_tokens: Option<u64>,
+    pub output_tokens: Option<u64>,
+}
+
+impl StoredEvent {
+    /// The row for `event`, just rendered by `renderer`.
+    pub fn new(session_id: &str, renderer: &SessionRenderer, event: &RenderedEvent) -> Self {
+        let tokens = renderer.tokens();
+        StoredEvent {
+            session_id: session_id.to_string(),
+            timestamp: event.timestamp.clone().unwrap_or_default(),
+            kind: renderer.event_type().unwrap_or_default().to_string(),
+            phase: renderer.phase().map(str::to_string),
+            text: renderer
+                .text()
+                .map_or_else(|| event.payload.text.clone(), str::to_string),
+            model: renderer.model().map(str::to_string),
+            input_tokens: tokens.map(|tokens| tokens.input),
+            cached_input_tokens: tokens.map(|tokens| tokens.cached_input),
+            output_tokens: tokens.map(|tokens| tokens.output),
+        }
+    }
+}
+
+/// Open the database at `path`, creating it and its directory as needed.
+pub fn open(path: &Path) -> Result<Connection> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let connection = Connection::open(path)
+        .with_context(|| format!("Failed to open event store {}", path.display()))?;
+    // Readers such as `stats` may query while the daemon writes.
+    connection.pragma_update(None, "journal_mode", "WAL")?;
+    connection.execute_batch(SCHEMA)?;
+    Ok(connection)
+}
+
+/// Add `event` unless the same one is already stored.
+pub fn insert(connection: &Connection, event: &StoredEvent) -> Result<()> {
+    connection.execute(
+        "INSERT OR IGNORE INTO events (session_id, timestamp, kind, phase, text, model, \
+         input_tokens, cached_input_tokens, output_tokens) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            event.session_id,
+            event.timestamp,
+            event.kind,
+            event.phase,
+            event.text,
+            event.model,
+            event.input_tokens,
+            event.cached_input_tokens,
+            event.output_tokens,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Store events from `events` until the daemon drops the sender, writing
+/// whatever has queued up in one transaction. Runs on a blocking thread.
+pub fn write(path: PathBuf, mut events: UnboundedReceiver<StoredEvent>) {
+    let mut connection = match open(&path) {
+        Ok(connection) => connection,
+        Err(err) => {
+            warn!(error = %format!("{err:#}"), "Event store unavailable");
+            return;
+        }
+    };
+    while let Some(first) = events.blocking_recv() {
+        let mut batch = vec![first];
+        while let Ok(event) = events.try_recv() {
+            batch.push(event);
+        }
+        let stored = connection
+            .transaction()
+            .map_err(anyhow::Error::from)
+            .and_then(|transaction| {
+                for event in &batch {
+                    insert(&transaction, event)?;
+                }
+                Ok(transaction.commit()?)
+            });
+        if let Err(err) = stored {
+            warn!(error = %format!("{err:#}"), count = batch.len(), "Failed to store events");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_stored_once() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("state/events.sqlite");
+        let event = StoredEvent {
+            session_id: "abc".to_string(),
+            timestamp: "2025-10-29T12:00:00Z".to_string(),
+            kind: "agent_reasoning".to_string(),
+            phase: Some("Planning".to_string()),
+            text: "**Planning**\n\nRead the parser first.".to_string(),
+            model: Some("gpt-5-codex".to_string()),
+            input_tokens: Some(1_200),
+            cached_input_tokens: None,
+            output_tokens: Some(300),
+        };
+
+        let connection = open(&path)?;
+        insert(&connection, &event)?;
+        insert(&connection, &event)?;
+        drop(connection);
+
+        let connection = open(&path)?;
+        let (count, phase, input): (u64, String, u64) = connection.query_row(
+            "SELECT COUNT(*), phase, input_tokens FROM events",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        assert_eq!((count, phase.as_str(), input), (1, "Planning", 1_200));
+        Ok(())
+    }
+}
@@ -0,0 +1,261 @@
+//! Sway's IPC on the socket named by `$SWAYSOCK`: following the focused
+//! window for `--follow-focus sway`, and switching the bar's mode while an
+//! approval is pending.
+
+use std::{env, path::Path, str::FromStr};
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UnixStream,
+    sync::{mpsc, watch},
+};
+use tracing::warn;
+
+use crate::alert::AlertKind;
+use crate::output::WaybarOutput;
+
+const MAGIC: &[u8; 6] = b"i3-ipc";
+const HEADER_LEN: usize = 14;
+const RUN_COMMAND: u32 = 0;
+const SUBSCRIBE: u32 = 2;
+const GET_TREE: u32 = 4;
+const GET_BAR_CONFIG: u32 = 6;
+const WORKSPACE_EVENT: u32 = 0x8000_0000;
+const WINDOW_EVENT: u32 = 0x8000_0003;
+
+/// A swaybar `mode`.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum BarMode {
+    Dock,
+    Hide,
+    Invisible,
+    Overlay,
+}
+
+impl FromStr for BarMode {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "dock" => Ok(BarMode::Dock),
+            "hide" => Ok(BarMode::Hide),
+            "invisible" => Ok(BarMode::Invisible),
+            "overlay" => Ok(BarMode::Overlay),
+            other => bail!(
+                "unknown bar mode `{other}` (expected `dock`, `hide`, `invisible`, or `overlay`)"
+            ),
+        }
+    }
+}
+
+impl BarMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            BarMode::Dock => "dock",
+            BarMode::Hide => "hide",
+            BarMode::Invisible => "invisible",
+            BarMode::Overlay => "overlay",
+        }
+    }
+}
+
+/// Send the pid of the focused window, or `None` on an empty workspace, now
+/// and on every focus change.
+pub async fn watch_focus(pids: mpsc::Sender<Option<u32>>) -> Result<()> {
+    let mut ipc = Ipc::connect().await?;
+    let tree = ipc.request(GET_TREE, "").await?;
+    if pids.send(focused_pid(&tree)).await.is_err() {
+        return Ok(());
+    }
+    let reply = ipc.request(SUBSCRIBE, r#"["window", "workspace"]"#).await?;
+    if reply["success"] != true {
+        bail!("sway refused the event subscription");
+    }
+    loop {
+        let (kind, event) = ipc.receive().await?;
+        let pid = match (kind, event["change"].as_str()) {
+            (WINDOW_EVENT, Some("focus")) => window_pid(&event["container"]),
+            // Workspaces with windows are followed by a window event.
+            (WORKSPACE_EVENT, Some("focus")) if is_empty(&event["current"]) => None,
+            _ => continue,
+        };
+        if pids.send(pid).await.is_err() {
+            return Ok(());
+        }
+    }
+}
+
+/// Switch every bar to `mode` while the payload on display asks for an
+/// approval, and back to the mode it had once the approval is answered.
+pub async fn show_bar_on_approval(
+    mode: BarMode,
+    mut published: watch::Receiver<Option<WaybarOutput>>,
+) -> Result<()> {
+    let mut ipc = Ipc::connect().await?;
+    let mut restore: Vec<(String, String)> = Vec::new();
+    let mut pending = false;
+    while published.changed().await.is_ok() {
+        let approval = published
+            .borrow_and_update()
+            .as_ref()
+            .and_then(AlertKind::of)
+            == Some(AlertKind::Approval);
+        if approval == pending {
+            continue;
+        }
+        pending = approval;
+        if pending {
+            restore.clear();
+            let ids = ipc.request(GET_BAR_CONFIG, "").await?;
+            for id in ids
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+            {
+                let bar = ipc.request(GET_BAR_CONFIG, id).await?;
+                if let Some(previous) = bar["mode"].as_str() {
+                    restore.push((id.to_string(), previous.to_string()));
+                }
+                ipc.run(&format!("bar {id} mode {}", mode.as_str())).await?;
+            }
+        } else {
+            for (id, previous) in restore.drain(..) {
+                ipc.run(&format!("bar {id} mode {previous}")).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// One connection to sway, speaking the i3 IPC framing: the magic string,
+/// then the payload length and message type in native byte order.
+struct Ipc(UnixStream);
+
+impl Ipc {
+    async fn connect() -> Result<Self> {
+        let path = env::var_os("SWAYSOCK").context("SWAYSOCK is not set; is sway running?")?;
+        let stream = UnixStream::connect(&path).await.with_context(|| {
+            format!(
+                "Failed to connect to sway at {}",
+                Path::new(&path).display()
+            )
+        })?;
+        Ok(Ipc(stream))
+    }
+
+    /// Send a message and wait for its reply, skipping events.
+    async fn request(&mut self, kind: u32, payload: &str) -> Result<Value> {
+        self.0.write_all(&encode(kind, payload)).await?;
+        loop {
+            let (reply_kind, reply) = self.receive().await?;
+            if reply_kind == kind {
+                return Ok(reply);
+            }
+        }
+    }
+
+    async fn receive(&mut self) -> Result<(u32, Value)> {
+        let mut header = [0; HEADER_LEN];
+        self.0
+            .read_exact(&mut header)
+            .await
+            .context("sway closed its IPC socket")?;
+        let (len, kind) = decode_header(&header)?;
+        let mut body = vec![0; len];
+        self.0.read_exact(&mut body).await?;
+        Ok((kind, serde_json::from_slice(&body)?))
+    }
+
+    /// Run a sway command, warning when it is rejected.
+    async fn run(&mut self, command: &str) -> Result<()> {
+        let replies = self.request(RUN_COMMAND, command).await?;
+        for reply in replies.as_array().into_iter().flatten() {
+            if reply["success"] != true {
+                warn!(command, error = %reply["error"], "sway rejected a command");
+            }
+        }
+        Ok(())
+    }
+}
+
+fn encode(kind: u32, payload: &str) -> Vec<u8> {
+    let mut message = Vec::with_capacity(HEADER_LEN + payload.len());
+    message.extend_from_slice(MAGIC);
+    message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+    message.extend_from_slice(&kind.to_ne_bytes());
+    message.extend_from_slice(payload.as_bytes());
+    message
+}
+
+/// The payload length and message type of a reply or event.
+fn decode_header(header: &[u8; HEADER_LEN]) -> Result<(usize, u32)> {
+    let (magic, rest) = header.split_at(MAGIC.len());
+    if magic != MAGIC {
+        bail!("Unexpected message from sway");
+    }
+    let (len, kind) = rest.split_at(4);
+    Ok((
+        u32::from_ne_bytes(len.try_into()?) as usize,
+        u32::from_ne_bytes(kind.try_into()?),
+    ))
+}
+
+/// The pid of the focused window in a tree of nodes.
+fn focused_pid(node: &Value) -> Option<u32> {
+    if node["focused"] == true
+        && let Some(pid) = window_pid(node)
+    {
+        return Some(pid);
+    }
+    children(node).find_map(focused_pid)
+}
+
+fn window_pid(node: &Value) -> Option<u32> {
+    node["pid"]
+        .as_u64()
+        .and_then(|pid| u32::try_from(pid).ok())
+        .filter(|pid| *pid > 0)
+}
+
+fn is_empty(workspace: &Value) -> bool {
+    children(workspace).next().is_none()
+}
+
+fn children(node: &Value) -> impl Iterator<Item = &Value> {
+    ["nodes", "floating_nodes"]
+        .into_iter()
+        .flat_map(|key| node[key].as_array().into_iter().flatten())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn messages_are_framed_and_trees_searched() -> Result<()> {
+        let message = encode(GET_BAR_CONFIG, "bar-0");
+        let header: [u8; HEADER_LEN] = message[..HEADER_LEN].try_into()?;
+        assert_eq!(decode_header(&header)?, (5, GET_BAR_CONFIG));
+        assert_eq!(&message[HEADER_LEN..], b"bar-0");
+        assert!(decode_header(&[0; HEADER_LEN]).is_err());
+
+        let tree = json!({
+            "type": "root",
+            "nodes": [{
+                "type": "workspace",
+                "nodes": [{"type": "con", "pid": 11, "focused": false}],
+                "floating_nodes": [{"type": "floating_con", "pid": 12, "focused": true}],
+            }],
+        });
+        assert_eq!(focused_pid(&tree), Some(12));
+        assert!(!is_empty(&tree["nodes"][0]));
+        assert!(is_empty(&json!({"type": "workspace", "nodes": []})));
+        Ok(())
+    }
+}
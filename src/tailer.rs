@@ -0,0 +1,1017 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, ErrorKind, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
+};
+
+use anyhow::Result;
+use flate2::read::MultiGzDecoder;
+use ruzstd::decoding::StreamingDecoder;
+use tokio::{
+    sync::{Notify, mpsc, watch},
+    task::spawn_blocking,
+    time::sleep,
+};
+use tracing::{debug, info, warn};
+
+use crate::config::Settings;
+use crate::daemon::DaemonMessage;
+use crate::discovery::SessionSource;
+use crate::render::{RenderOptions, RenderedEvent};
+use crate::session::SessionRenderer;
+
+/// Tail position within a single session rollout file.
+#[derive(Debug)]
+pub struct SessionState {
+    pub path: PathBuf,
+    pub offset: u64,
+}
+
+pub fn initialize_session_state(
+    session_id: &str,
+    explicit_path: Option<&PathBuf>,
+    source: &SessionSource,
+    options: &RenderOptions,
+    start_at_beginning: bool,
+    max_line_bytes: usize,
+) -> Result<Option<(SessionState, Option<RenderedEvent>)>> {
+    let parts = match explicit_path {
+        Some(path) => vec![path.clone()],
+        None => source.find_parts(session_id)?,
+    };
+    let Some((path, earlier)) = parts.split_last() else {
+        return Ok(None);
+    };
+
+    let mut offset = 0;
+    let event = prime_resumed_session(
+        session_id,
+        earlier,
+        path,
+        &mut offset,
+        options,
+        start_at_beginning,
+        max_line_bytes,
+    )?;
+    Ok(Some((
+        SessionState {
+            path: path.clone(),
+            offset,
+        },
+        event,
+    )))
+}
+
+/// Tail one session: locate and prime its file, then forward appended lines to
+/// the coordinator whenever `wake` fires or the fallback poll elapses. A
+/// session resumed into a new rollout file is primed from all of its files,
+/// and the tailer moves on to a new one when the session is resumed later.
+pub async fn tail_session(
+    session_id: String,
+    explicit_path: Option<PathBuf>,
+    source: SessionSource,
+    wake: Arc<Notify>,
+    settings: watch::Receiver<Settings>,
+    watching: bool,
+    tx: mpsc::Sender<DaemonMessage>,
+) {
+    let follow_resumes = explicit_path.is_none();
+    let max_line_bytes = settings.borrow().max_line_bytes;
+    let lookup = source.clone();
+    let lookup_id = session_id.clone();
+    let primed = spawn_blocking(move || -> Result<Option<(PathBuf, Vec<String>, u64)>> {
+        let parts = match explicit_path {
+            Some(path) => vec![path],
+            None => lookup.find_parts(&lookup_id)?,
+        };
+        let Some((path, earlier)) = parts.split_last() else {
+            return Ok(None);
+        };
+        let mut offset = 0;
+        let lines = read_resumed_session(earlier, path, &mut offset, max_line_bytes)?;
+        Ok(lines.map(|lines| (path.clone(), lines, offset)))
+    })
+    .await;
+
+    let (mut path, mut offset) = match primed {
+        Ok(Ok(Some((path, lines, offset)))) => {
+            let message = DaemonMessage::Primed {
+                session_id: session_id.clone(),
+                path: path.clone(),
+                lines,
+            };
+            if tx.send(message).await.is_err() {
+                return;
+            }
+            (path, offset)
+        }
+        Ok(Ok(None)) => {
+            let _ = tx.send(DaemonMessage::Missing { session_id }).await;
+            return;
+        }
+        Ok(Err(err)) => {
+            warn!(session_id = %session_id, error = %format!("{err:#}"), "Failed to open session");
+            let _ = tx.send(DaemonMessage::Missing { session_id }).await;
+            return;
+        }
+        Err(_) => return,
+    };
+
+    let mut resume_checked = Instant::now();
+    let mut last_data = Instant::now();
+    // Reads in a row that failed.
+    let mut failures = 0;
+    loop {
+        let (fallback, refresh, max_line_bytes) = {
+            let settings = settings.borrow();
+            let refresh = settings.session_refresh_interval();
+            // A quiet session is polled less often until it writes again,
+            // and a failing one less often the longer it keeps failing.
+            let poll = if failures > 0 {
+                settings.retry_interval(failures)
+            } else {
+                settings.backoff_interval(last_data.elapsed())
+            };
+            let fallback = if watching { refresh.max(poll) } else { poll };
+            (fallback, refresh, settings.max_line_bytes)
+        };
+        tokio::select! {
+            _ = wake.notified() => {}
+            _ = sleep(fallback) => {}
+        }
+
+        let check_resume = follow_resumes && resume_checked.elapsed() >= refresh;
+        if check_resume {
+            resume_checked = Instant::now();
+        }
+        let read_path = path.clone();
+        let lookup = source.clone();
+        let lookup_id = session_id.clone();
+        let read = spawn_blocking(move || {
+            let mut next = offset;
+            let mut lines = read_new_lines(&read_path, &mut next, max_line_bytes)?;
+            if check_resume
+                && let Ok(Some(newer)) = lookup.find(&lookup_id)
+                && newer != read_path
+            {
+                next = 0;
+                lines.extend(read_new_lines(&newer, &mut next, max_line_bytes)?);
+                return Ok((lines, next, Some(newer)));
+            }
+            Ok::<_, io::Error>((lines, next, None))
+        })
+        .await;
+
+        match read {
+            Ok(Ok((lines, next, resumed))) => {
+                if failures >= DEGRADED_AFTER {
+                    info!(session_id = %session_id, "Session log is readable again");
+                    let message = DaemonMessage::Degraded {
+                        session_id: session_id.clone(),
+                        error: None,
+                    };
+                    if tx.send(message).await.is_err() {
+                        return;
+                    }
+                }
+                failures = 0;
+                offset = next;
+                if let Some(newer) = resumed {
+                    info!(
+                        session_id = %session_id,
+                        path = %newer.display(),
+                        "Session was resumed in a new log"
+                    );
+                    path = newer;
+                    let message = DaemonMessage::Resumed {
+                        session_id: session_id.clone(),
+                        path: path.clone(),
+                    };
+                    if tx.send(message).await.is_err() {
+                        return;
+                    }
+                }
+                if lines.is_empty() {
+                    continue;
+                }
+                last_data = Instant::now();
+                let message = DaemonMessage::Lines {
+                    session_id: session_id.clone(),
+                    lines,
+                };
+                if tx.send(message).await.is_err() {
+                    return;
+                }
+            }
+            Ok(Err(err)) if err.kind() == ErrorKind::NotFound => {
+                let _ = tx.send(DaemonMessage::Missing { session_id }).await;
+                return;
+            }
+            Ok(Err(err)) => {
+                failures += 1;
+                if failures == 1 || failures == DEGRADED_AFTER {
+                    warn!(session_id = %session_id, path = %path.display(), error = %err, failures, "Failed to read session log");
+                } else {
+                    debug!(session_id = %session_id, error = %err, failures, "Failed to read session log");
+                }
+                if failures == DEGRADED_AFTER {
+                    let message = DaemonMessage::Degraded {
+                        session_id: session_id.clone(),
+                        error: Some(err.to_string()),
+                    };
+                    if tx.send(message).await.is_err() {
+                        return;
+                    }
+                }
+            }
+            Err(_) => return,
+        }
+    }
+}
+
+/// Failed reads in a row after which the display is marked `degraded`.
+const DEGRADED_AFTER: u32 = 3;
+
+/// An earlier log of a session, decompressing rotated `.gz` and `.zst` copies.
+fn open_log(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    let file = File::open(path)?;
+    Ok(match path.extension().and_then(|ext| ext.to_str()) {
+        Some("gz") => Box::new(BufReader::new(MultiGzDecoder::new(file))),
+        Some("zst") => Box::new(BufReader::new(
+            StreamingDecoder::new(file).map_err(io::Error::other)?,
+        )),
+        _ => Box::new(BufReader::new(file)),
+    })
+}
+
+/// Every line of a session log, including rotated `.gz` and `.zst` copies.
+pub fn read_log(path: &Path, max_line_bytes: usize) -> io::Result<Vec<String>> {
+    let mut read = 0;
+    read_lines(&mut open_log(path)?, path, &mut read, max_line_bytes)
+}
+
+/// Complete lines appended to `path` since `offset`, which moves past them.
+/// An unterminated last line is left for the next read while Codex is still
+/// writing it, unless it already holds a whole JSON entry.
+pub fn read_new_lines(
+    path: &Path,
+    offset: &mut u64,
+    max_line_bytes: usize,
+) -> io::Result<Vec<String>> {
+    let file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+    if *offset > file_len {
+        info!(path = %path.display(), "Session log was truncated; reading it from the start");
+        *offset = 0;
+    }
+
+    let mut reader = BufReader::new(file);
+    reader.seek(SeekFrom::Start(*offset))?;
+    read_lines(&mut reader, path, offset, max_line_bytes)
+}
+
+/// The lines of `reader`, counting the bytes taken in `offset`. Only the
+/// first `max_line_bytes` of a line are kept in memory; the entry is closed
+/// off after them by [`cut_entry`], or dropped when that fails.
+fn read_lines(
+    reader: &mut impl BufRead,
+    path: &Path,
+    offset: &mut u64,
+    max_line_bytes: usize,
+) -> io::Result<Vec<String>> {
+    let mut lines = Vec::new();
+    let mut buffer = Vec::new();
+    loop {
+        buffer.clear();
+        let (bytes, terminated) = read_capped_line(reader, &mut buffer, max_line_bytes)?;
+        if bytes == 0 {
+            break;
+        }
+        let cut = bytes - usize::from(terminated) > buffer.len();
+        // Captured command output can hold bytes that are not UTF-8; they
+        // become U+FFFD rather than stall the session.
+        let text = String::from_utf8_lossy(&buffer);
+        if !terminated && (cut || serde_json::from_str::<serde::de::IgnoredAny>(&text).is_err()) {
+            break;
+        }
+        *offset += bytes as u64;
+        if cut {
+            match cut_entry(&buffer) {
+                Some(line) => {
+                    debug!(path = %path.display(), bytes, "Cut a long log line short");
+                    lines.push(line);
+                }
+                None => {
+                    warn!(path = %path.display(), bytes, "Skipped a log line over --max-line-bytes")
+                }
+            }
+        } else if !text.is_empty() {
+            // Empty after a whole entry taken unterminated.
+            lines.push(text.into_owned());
+        }
+    }
+    Ok(lines)
+}
+
+/// Read up to and past the next newline, keeping at most `max` bytes of the
+/// line in `line`. Returns the bytes consumed and whether a newline ended
+/// them.
+fn read_capped_line(
+    reader: &mut impl BufRead,
+    line: &mut Vec<u8>,
+    max: usize,
+) -> io::Result<(usize, bool)> {
+    let mut consumed = 0;
+    loop {
+        let available = match reader.fill_buf() {
+            Ok(available) => available,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        if available.is_empty() {
+            return Ok((consumed, false));
+        }
+        let (chunk, terminated) = match available.iter().position(|byte| *byte == b'\n') {
+            Some(end) => (&available[..end], true),
+            None => (available, false),
+        };
+        let room = max.saturating_sub(line.len());
+        line.extend_from_slice(&chunk[..chunk.len().min(room)]);
+        let taken = chunk.len() + usize::from(terminated);
+        reader.consume(taken);
+        consumed += taken;
+        if terminated {
+            return Ok((consumed, true));
+        }
+    }
+}
+
+/// Close off a JSON entry cut short inside a string, such as the text of a
+/// long reasoning summary, ending that string with `…`. `None` when the cut
+/// falls anywhere else.
+fn cut_entry(prefix: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(trim_split_char(prefix));
+    let mut open = Vec::new();
+    let mut string_start = None;
+    let mut escape_start = None;
+    let mut chars = text.char_indices();
+    while let Some((index, c)) = chars.next() {
+        if string_start.is_some() {
+            match c {
+                '\\' => {
+                    let complete = match chars.next() {
+                        Some((_, 'u')) => chars.by_ref().take(4).count() == 4,
+                        Some(_) => true,
+                        None => false,
+                    };
+                    if !complete {
+                        escape_start = Some(index);
+                        break;
+                    }
+                }
+                '"' => string_start = None,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => string_start = Some(index),
+            '{' => open.push('}'),
+            '[' => open.push(']'),
+            '}' | ']' => {
+                open.pop();
+            }
+            _ => {}
+        }
+    }
+    string_start?;
+    let mut entry = text[..escape_start.unwrap_or(text.len())].to_string();
+    entry.push_str("…\"");
+    entry.extend(open.iter().rev());
+    serde_json::from_str::<serde::de::IgnoredAny>(&entry)
+        .is_ok()
+        .then_some(entry)
+}
+
+/// `bytes` without the character the cut split at their end, if any.
+fn trim_split_char(bytes: &[u8]) -> &[u8] {
+    let Some(lead) = bytes.iter().rposition(|byte| byte & 0xC0 != 0x80) else {
+        return bytes;
+    };
+    let width = match bytes[lead] {
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF7 => 4,
+        _ => 1,
+    };
+    if bytes.len() - lead < width {
+        &bytes[..lead]
+    } else {
+        bytes
+    }
+}
+
+pub fn prime_session(
+    session_id: &str,
+    path: &Path,
+    offset: &mut u64,
+    options: &RenderOptions,
+    start_at_beginning: bool,
+    max_line_bytes: usize,
+) -> Result<Option<RenderedEvent>> {
+    prime_resumed_session(
+        session_id,
+        &[],
+        path,
+        offset,
+        options,
+        start_at_beginning,
+        max_line_bytes,
+    )
+}
+
+/// [`prime_session`] for a session resumed into `path`, rendering the files it
+/// was written to before, `earlier`, first so the latest one carries on
+/// their state.
+pub fn prime_resumed_session(
+    session_id: &str,
+    earlier: &[PathBuf],
+    path: &Path,
+    offset: &mut u64,
+    options: &RenderOptions,
+    start_at_beginning: bool,
+    max_line_bytes: usize,
+) -> Result<Option<RenderedEvent>> {
+    let metadata = match fs::metadata(path) {
+        Ok(meta) => meta,
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            *offset = 0;
+            return Ok(None);
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    if start_at_beginning {
+        *offset = 0;
+    } else {
+        *offset = metadata.len();
+    }
+
+    let mut read_to = 0;
+    let Some(lines) = read_resumed_session(earlier, path, &mut read_to, max_line_bytes)? else {
+        *offset = 0;
+        return Ok(None);
+    };
+    let mut renderer = SessionRenderer::new(session_id);
+    let mut last_event: Option<RenderedEvent> = None;
+    for line in lines {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(event) = renderer.render_line(&line, options)? {
+            last_event = Some(event);
+        }
+    }
+
+    // Before a line Codex is still writing, so it is read once complete.
+    *offset = read_to;
+
+    Ok(last_event)
+}
+
+/// Every line of a session written to `earlier` logs before it was resumed
+/// into `path`, followed by the complete lines of `path` from `offset`, which
+/// moves past them. Earlier logs that are gone are skipped; `None` when `path`
+/// is.
+fn read_resumed_session(
+    earlier: &[PathBuf],
+    path: &Path,
+    offset: &mut u64,
+    max_line_bytes: usize,
+) -> io::Result<Option<Vec<String>>> {
+    let mut lines = Vec::new();
+    for part in earlier {
+        match read_log(part, max_line_bytes) {
+            Ok(part_lines) => lines.extend(part_lines),
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+    }
+    match read_new_lines(path, offset, max_line_bytes) {
+        Ok(latest) => {
+            lines.extend(latest);
+            Ok(Some(lines))
+        }
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::tests::options;
+    use serde_json::json;
+    use std::fs;
+    use std::io::Write;
+    use tempfile::{NamedTempFile, tempdir};
+
+    const MAX_LINE: usize = 1 << 20;
+
+    #[test]
+    fn prime_session_returns_none_when_file_missing() -> Result<()> {
+        let dir = tempdir()?;
+        let session_path = dir.path().join("missing-session.jsonl");
+        let mut offset = 42;
+
+        let result = prime_session(
+            "session",
+            &session_path,
+            &mut offset,
+            &options(),
+            false,
+            MAX_LINE,
+        )?;
+
+        assert!(result.is_none());
+        assert_eq!(offset, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn prime_session_reads_last_payload() -> Result<()> {
+        let dir = tempdir()?;
+        let session_path = dir.path().join("session.jsonl");
+        let mut file = File::create(&session_path)?;
+        let payload_one = json!({
+            "timestamp": "2025-10-29T12:00:00Z",
+            "type": "event_msg",
+            "payload": { "type": "agent_reasoning", "text": "**First step** details" }
+        });
+        let payload_two = json!({
+            "timestamp": "2025-10-29T12:01:00Z",
+            "type": "event_msg",
+            "payload": { "type": "agent_reasoning", "text": "Second step" }
+        });
+        writeln!(file, "{payload_one}")?;
+        writeln!(file, "{payload_two}")?;
+
+        let mut offset = 0;
+        let result = prime_session(
+            "session",
+            &session_path,
+            &mut offset,
+            &options(),
+            false,
+            MAX_LINE,
+        )?;
+
+        assert!(result.is_some());
+        let event = result.unwrap();
+        assert_eq!(event.payload.text, "Second step");
+        assert_eq!(event.timestamp.as_deref(), Some("2025-10-29T12:01:00Z"));
+        Ok(())
+    }
+
+    #[test]
+    fn read_new_lines_resets_offset_when_file_shrinks() -> Result<()> {
+        let temp = NamedTempFile::new()?;
+        fs::write(temp.path(), "line1\nline2\n")?;
+        let mut offset = fs::metadata(temp.path())?.len();
+
+        fs::write(temp.path(), "line3\n")?;
+
+        let lines = read_new_lines(temp.path(), &mut offset, MAX_LINE)?;
+        assert_eq!(lines, vec!["line3".to_string()]);
+        assert_eq!(offset, fs::metadata(temp.path())?.len());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn tail_session_forwards_primed_and_appended_lines() -> Result<()> {
+        let dir = tempdir()?;
+        let session_path = dir.path().join("rollout-2025-10-29T12-00-00-alpha.jsonl");
+        fs::write(&session_path, "first\n")?;
+
+        let config = crate::config::Config {
+            cache_file: Some(dir.path().join("cache.json")),
+            sessions_root: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        let settings = Settings::resolve(&config)?;
+        let source = settings.session_source();
+        let (_settings_tx, settings_rx) = watch::channel(settings);
+        let (tx, mut rx) = mpsc::channel(8);
+        let wake = Arc::new(Notify::new());
+        let task = tokio::spawn(tail_session(
+            "alpha".to_string(),
+            Some(session_path.clone()),
+            source,
+            wake.clone(),
+            settings_rx,
+            true,
+            tx,
+        ));
+
+        match rx.recv().await {
+            Some(DaemonMessage::Primed { lines, .. }) => assert_eq!(lines, vec!["first"]),
+            other => panic!("expected primed message, got {other:?}"),
+        }
+
+        let mut file = fs::OpenOptions::new().append(true).open(&session_path)?;
+        writeln!(file, "second")?;
+        wake.notify_one();
+
+        match rx.recv().await {
+            Some(DaemonMessage::Lines { session_id, lines }) => {
+                assert_eq!(session_id, "alpha");
+                assert_eq!(lines, vec!["second"]);
+            }
+            other => panic!("expected new lines, got {other:?}"),
+        }
+        task.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn resumed_sessions_are_primed_from_every_log_and_followed() -> Result<()> {
+        let dir = tempdir()?;
+        let log = |day: &str, started: &str, line: &str| -> Result<PathBuf> {
+            let path = dir
+                .path()
+                .join(day)
+                .join(format!("rollout-{started}-beta.jsonl"));
+            fs::create_dir_all(path.parent().expect("parent"))?;
+            fs::write(&path, format!("{line}\n"))?;
+            Ok(path)
+        };
+        log("2025/10/29", "2025-10-29T12-00-00", "two")?;
+        log("2025/10/28", "2025-10-28T09-00-00", "one")?;
+
+        let config = crate::config::Config {
+            cache_file: Some(dir.path().join("cache.json")),
+            sessions_root: Some(dir.path().to_path_buf()),
+            session_refresh_secs: Some(0),
+            ..Default::default()
+        };
+        let settings = Settings::resolve(&config)?;
+        let source = settings.session_source();
+        let (_settings_tx, settings_rx) = watch::channel(settings);
+        let (tx, mut rx) = mpsc::channel(8);
+        let wake = Arc::new(Notify::new());
+        let task = tokio::spawn(tail_session(
+            "beta".to_string(),
+            None,
+            source,
+            wake.clone(),
+            settings_rx,
+            true,
+            tx,
+        ));
+
+        match rx.recv().await {
+            Some(DaemonMessage::Primed { lines, .. }) => assert_eq!(lines, ["one", "two"]),
+            other => panic!("expected primed message, got {other:?}"),
+        }
+
+        let resumed = log("2025/10/30", "2025-10-30T08-00-00", "three")?;
+        wake.notify_one();
+        match rx.recv().await {
+            Some(DaemonMessage::Resumed { path, .. }) => assert_eq!(path, resumed),
+            other => panic!("expected the new log, got {other:?}"),
+        }
+        match rx.recv().await {
+            Some(DaemonMessage::Lines { lines, .. }) => assert_eq!(lines, ["three"]),
+            other => panic!("expected new lines, got {other:?}"),
+        }
+        task.abort();
+        Ok(())
+    }
+
+    /// A rollout line of `kind`, `extra` merged into its payload.
+    fn event(timestamp: &str, kind: &str, extra: serde_json::Value) -> String {
+        let mut payload = json!({ "type": kind });
+        if let (Some(payload), Some(extra)) = (payload.as_object_mut(), extra.as_object()) {
+            payload.extend(extra.clone());
+        }
+        json!({ "timestamp": timestamp, "type": "event_msg", "payload": payload }).to_string()
+    }
+
+    #[test]
+    fn split_sessions_prime_in_order_without_replaying_earlier_logs() -> Result<()> {
+        let dir = tempdir()?;
+        let write = |day: &str, started: &str, lines: &[String]| -> Result<PathBuf> {
+            let path = dir
+                .path()
+                .join(day)
+                .join(format!("rollout-{started}-delta.jsonl"));
+            fs::create_dir_all(path.parent().expect("parent"))?;
+            fs::write(&path, lines.join("\n") + "\n")?;
+            Ok(path)
+        };
+        // The resumed log is written first and the original touched last,
+        // so neither creation order nor mtime gives the right order.
+        let resumed = write(
+            "2025/10/29",
+            "2025-10-29T12-00-00",
+            &[
+                event(
+                    "2025-10-29T12:00:00Z",
+                    "exec_command_end",
+                    json!({ "call_id": "c1" }),
+                ),
+                event(
+                    "2025-10-29T12:00:05Z",
+                    "agent_reasoning",
+                    json!({ "text": "**Writing tests**" }),
+                ),
+            ],
+        )?;
+        let original = write(
+            "2025/10/28",
+            "2025-10-28T09-00-00",
+            &[
+                event("2025-10-28T09:00:00Z", "task_started", json!({})),
+                event(
+                    "2025-10-28T09:00:01Z",
+                    "agent_reasoning",
+                    json!({ "text": "**Reading code**" }),
+                ),
+                event(
+                    "2025-10-28T09:00:02Z",
+                    "exec_approval_request",
+                    json!({ "call_id": "c1", "command": ["cargo", "test"] }),
+                ),
+            ],
+        )?;
+        File::options()
+            .write(true)
+            .open(&resumed)?
+            .set_modified(std::time::SystemTime::now() - std::time::Duration::from_secs(60))?;
+
+        let config = crate::config::Config {
+            cache_file: Some(dir.path().join("cache.json")),
+            sessions_root: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        let source = Settings::resolve(&config)?.session_source();
+        let parts = [original.clone(), resumed.clone()];
+        assert_eq!(source.find_parts("delta")?, parts);
+        // Once known, the parts are only checked against recent days.
+        assert_eq!(source.find_parts("delta")?, parts);
+
+        let (state, event) =
+            initialize_session_state("delta", None, &source, &options(), false, MAX_LINE)?
+                .expect("primed session");
+        assert_eq!(state.path, resumed);
+        // The approval asked in the first log was answered in the second.
+        let event = event.expect("primed event");
+        assert!(event.payload.text.ends_with("Writing tests"));
+        assert_eq!(event.timestamp.as_deref(), Some("2025-10-29T12:00:05Z"));
+        // Tailing goes on from the end of the live log; nothing is read again.
+        let mut offset = state.offset;
+        assert!(read_new_lines(&resumed, &mut offset, MAX_LINE)?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn tailing_moves_onto_a_log_resumed_mid_tail() -> Result<()> {
+        let dir = tempdir()?;
+        let day = dir.path().join("2025/10/29");
+        fs::create_dir_all(&day)?;
+        let first = day.join("rollout-2025-10-29T12-00-00-epsilon.jsonl");
+        fs::write(
+            &first,
+            event(
+                "2025-10-29T12:00:00Z",
+                "agent_reasoning",
+                json!({ "text": "**Reading code**" }),
+            ) + "\n",
+        )?;
+
+        let config = crate::config::Config {
+            cache_file: Some(dir.path().join("cache.json")),
+            sessions_root: Some(dir.path().to_path_buf()),
+            session_refresh_secs: Some(0),
+            // Read only when woken, so both changes below land in one read.
+            poll_ms: Some(60_000),
+            max_poll_ms: Some(60_000),
+            ..Default::default()
+        };
+        let settings = Settings::resolve(&config)?;
+        let source = settings.session_source();
+        let (_settings_tx, settings_rx) = watch::channel(settings);
+        let (tx, mut rx) = mpsc::channel(8);
+        let wake = Arc::new(Notify::new());
+        let task = tokio::spawn(tail_session(
+            "epsilon".to_string(),
+            None,
+            source.clone(),
+            wake.clone(),
+            settings_rx,
+            true,
+            tx,
+        ));
+        let mut renderer = SessionRenderer::new("epsilon");
+        match rx.recv().await {
+            Some(DaemonMessage::Primed { path, lines, .. }) => {
+                assert_eq!(path, first);
+                for line in &lines {
+                    renderer.render_line(line, &options())?;
+                }
+            }
+            other => panic!("expected primed message, got {other:?}"),
+        }
+
+        // The old log gets a last line as Codex exits, then the session is
+        // resumed into a new one, all before the tailer looks again.
+        writeln!(
+            fs::OpenOptions::new().append(true).open(&first)?,
+            "{}",
+            event(
+                "2025-10-29T12:00:01Z",
+                "exec_approval_request",
+                json!({ "call_id": "c1", "command": "cargo test" }),
+            )
+        )?;
+        let next_day = dir.path().join("2025/10/30");
+        fs::create_dir_all(&next_day)?;
+        let second = next_day.join("rollout-2025-10-30T08-00-00-epsilon.jsonl");
+        fs::write(
+            &second,
+            event(
+                "2025-10-30T08:00:00Z",
+                "exec_command_end",
+                json!({ "call_id": "c1" }),
+            ) + "\n",
+        )?;
+        wake.notify_one();
+
+        match rx.recv().await {
+            Some(DaemonMessage::Resumed { path, .. }) => assert_eq!(path, second),
+            other => panic!("expected the new log, got {other:?}"),
+        }
+        let lines = match rx.recv().await {
+            Some(DaemonMessage::Lines { lines, .. }) => lines,
+            other => panic!("expected new lines, got {other:?}"),
+        };
+        // The rest of the old log, then the new one, each line once.
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("exec_approval_request"));
+        assert!(lines[1].contains("exec_command_end"));
+        let mut shown = None;
+        for line in &lines {
+            shown = renderer.render_line(line, &options())?.or(shown);
+        }
+        assert!(!renderer.awaiting_approval());
+        let shown = shown.expect("the approval came and went");
+        assert!(shown.payload.text.ends_with("Reading code"));
+        assert_eq!(source.find_parts("epsilon")?, [first, second.clone()]);
+
+        // Later lines come from the new log only.
+        writeln!(
+            fs::OpenOptions::new().append(true).open(&second)?,
+            "{}",
+            event(
+                "2025-10-30T08:00:01Z",
+                "agent_reasoning",
+                json!({ "text": "**Writing tests**" }),
+            )
+        )?;
+        wake.notify_one();
+        match rx.recv().await {
+            Some(DaemonMessage::Lines { lines, .. }) => {
+                assert_eq!(lines.len(), 1);
+                assert!(lines[0].contains("Writing tests"));
+            }
+            other => panic!("expected new lines, got {other:?}"),
+        }
+        task.abort();
+        Ok(())
+    }
+
+    #[test]
+    fn compressed_logs_prime_before_the_live_one() -> Result<()> {
+        use flate2::{Compression, write::GzEncoder};
+        use ruzstd::encoding::{CompressionLevel, compress_to_vec};
+
+        let dir = tempdir()?;
+        let day = dir.path().join("2025/10/29");
+        fs::create_dir_all(&day)?;
+        let mut gz = GzEncoder::new(Vec::new(), Compression::fast());
+        gz.write_all(b"one\n")?;
+        fs::write(
+            day.join("rollout-2025-10-28T09-00-00-gamma.jsonl.gz"),
+            gz.finish()?,
+        )?;
+        let live = day.join("rollout-2025-10-29T12-00-00-gamma.jsonl");
+        fs::write(
+            live.with_extension("jsonl.zst"),
+            compress_to_vec(&b"two\n"[..], CompressionLevel::Fastest),
+        )?;
+
+        let config = crate::config::Config {
+            cache_file: Some(dir.path().join("cache.json")),
+            sessions_root: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        let source = Settings::resolve(&config)?.session_source();
+        assert_eq!(source.find("gamma")?, None, "only a live log is tailed");
+
+        fs::write(&live, "three\n")?;
+        let parts = source.find_parts("gamma")?;
+        assert_eq!(parts.last(), Some(&live));
+        let mut lines = Vec::new();
+        for part in &parts {
+            lines.extend(read_log(part, MAX_LINE)?);
+        }
+        assert_eq!(lines, ["one", "two", "three"]);
+        Ok(())
+    }
+
+    #[test]
+    fn read_new_lines_holds_back_a_line_being_written() -> Result<()> {
+        let temp = NamedTempFile::new()?;
+        fs::write(temp.path(), "{\"a\":1}\n{\"b\":")?;
+        let mut offset = 0;
+        assert_eq!(
+            read_new_lines(temp.path(), &mut offset, MAX_LINE)?,
+            ["{\"a\":1}"]
+        );
+        assert_eq!(offset, 8);
+
+        let mut file = fs::OpenOptions::new().append(true).open(temp.path())?;
+        write!(file, "2}}")?;
+        assert_eq!(
+            read_new_lines(temp.path(), &mut offset, MAX_LINE)?,
+            ["{\"b\":2}"]
+        );
+        assert_eq!(offset, fs::metadata(temp.path())?.len());
+
+        writeln!(file)?;
+        assert!(read_new_lines(temp.path(), &mut offset, MAX_LINE)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_utf8_is_read_lossily() -> Result<()> {
+        let temp = NamedTempFile::new()?;
+        let mut bytes = b"{\"text\":\"out \xff\xfe put\"}\n".to_vec();
+        bytes.extend_from_slice(b"{\"text\":\"\xc3\"}");
+        fs::write(temp.path(), &bytes)?;
+
+        let mut offset = 0;
+        let lines = read_new_lines(temp.path(), &mut offset, MAX_LINE)?;
+        assert_eq!(
+            lines,
+            [
+                "{\"text\":\"out \u{FFFD}\u{FFFD} put\"}",
+                "{\"text\":\"\u{FFFD}\"}"
+            ]
+        );
+        assert_eq!(offset, bytes.len() as u64);
+
+        assert_eq!(
+            cut_entry(b"{\"text\":\"\xff caf\xc3").as_deref(),
+            Some("{\"text\":\"\u{FFFD} caf…\"}")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn long_lines_are_cut_short_inside_a_string() -> Result<()> {
+        let temp = NamedTempFile::new()?;
+        let text = "é".repeat(200);
+        let long = format!(
+            r#"{{"type":"event_msg","payload":{{"type":"agent_reasoning","text":"{text}"}}}}"#
+        );
+        let closed = "{\"a\":[1,2,3,4,5,6,7,8,9,10,11,12,13,14,15,16,17,18,19,20,21,22,23,24,25,26,27,28,29,30]}";
+        fs::write(temp.path(), format!("{long}\n{closed}\n{{\"b\":1}}\n"))?;
+
+        let mut offset = 0;
+        let lines = read_new_lines(temp.path(), &mut offset, 80)?;
+        assert_eq!(offset, fs::metadata(temp.path())?.len());
+        assert_eq!(lines.len(), 2, "the entry cut between tokens is skipped");
+        let cut: serde_json::Value = serde_json::from_str(&lines[0])?;
+        assert_eq!(cut["payload"]["type"], "agent_reasoning");
+        assert!(
+            cut["payload"]["text"]
+                .as_str()
+                .is_some_and(|text| text.ends_with("é…"))
+        );
+        assert_eq!(lines[1], "{\"b\":1}");
+
+        assert_eq!(
+            cut_entry(br#"{"text":"quote \"#).as_deref(),
+            Some(r#"{"text":"quote …"}"#)
+        );
+        assert_eq!(
+            cut_entry(br#"{"text":"\u00"#).as_deref(),
+            Some(r#"{"text":"…"}"#)
+        );
+        assert_eq!(cut_entry(br#"{"te"#), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+//! User-defined label and tooltip formats such as `{phase}: {text}`.
+
+use std::{fmt, str::FromStr};
+
+use anyhow::{Result, bail};
+use serde::Deserialize;
+
+/// Names a format may refer to; see the README for what each expands to.
+pub const VARIABLES: &[&str] = &[
+    "text",
+    "tooltip",
+    "phase",
+    "icon",
+    "state",
+    "timestamp",
+    "model",
+    "cwd",
+    "project",
+    "session",
+    "session_short",
+    "tokens",
+    "elapsed",
+    "context",
+];
+
+/// A parsed format string. `{name}` expands to a variable, `{{` and `}}` to
+/// literal braces; variables without a value expand to nothing.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Template {
+    source: String,
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Variable(&'static str),
+}
+
+impl FromStr for Template {
+    type Err = anyhow::Error;
+
+    fn from_str(source: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = source.chars();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let Some((name, rest)) = chars.as_str().split_once('}') else {
+                        bail!("unclosed `{{` in format `{source}`");
+                    };
+                    let Some(variable) = VARIABLES.iter().find(|known| **known == name) else {
+                        bail!(
+                            "unknown variable `{{{name}}}` in format `{source}` (expected one of {})",
+                            VARIABLES.join(", ")
+                        );
+                    };
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Variable(variable));
+                    chars = rest.chars();
+                }
+                '}' => bail!("unmatched `}}` in format `{source}`; write `}}}}` for a brace"),
+                ch => literal.push(ch),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Template {
+            source: source.to_string(),
+            parts,
+        })
+    }
+}
+
+impl TryFrom<String> for Template {
+    type Error = anyhow::Error;
+
+    fn try_from(source: String) -> Result<Self> {
+        source.parse()
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl Template {
+    /// Expand the format, looking each variable up with `value`.
+    pub fn render(&self, value: impl Fn(&str) -> Option<String>) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Literal(text) => text.clone(),
+                Part::Variable(name) => value(name).unwrap_or_default(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variables_expand_and_braces_escape() -> Result<()> {
+        let template: Template = "{phase}: {text} ({session_short}) {{x}}".parse()?;
+        let rendered = template.render(|name| match name {
+            "phase" => Some("Testing".to_string()),
+            "text" => Some("cargo test".to_string()),
+            _ => None,
+        });
+        assert_eq!(rendered, "Testing: cargo test () {x}");
+        assert_eq!(
+            template.to_string(),
+            "{phase}: {text} ({session_short}) {{x}}"
+        );
+
+        assert!("{nope}".parse::<Template>().is_err());
+        assert!("{text".parse::<Template>().is_err());
+        assert!("text}".parse::<Template>().is_err());
+        Ok(())
+    }
+}
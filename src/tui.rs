@@ -0,0 +1,336 @@
+//! `codex-shimmer tui`: the tracked sessions in a terminal, with the reasoning
+//! and commands of the selected one, following the logs as they grow. A
+//! companion to the bar for when the tooltip is too small.
+
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::Result;
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, List, ListItem, ListState, Paragraph, Wrap},
+};
+
+use crate::config::Settings;
+use crate::discovery::{is_compressed, session_logs};
+use crate::last::Recent;
+use crate::session::SessionRenderer;
+use crate::tailer::{read_log, read_new_lines};
+
+/// How long to wait for a key before reading the logs again.
+const POLL: Duration = Duration::from_millis(250);
+/// How often to look for new sessions.
+const RESCAN: Duration = Duration::from_secs(5);
+/// Events kept per session.
+const HISTORY: usize = 200;
+
+/// One session followed by the viewer.
+struct Tracked {
+    id: String,
+    renderer: SessionRenderer,
+    /// The log written to now, and how far it has been read.
+    path: Option<PathBuf>,
+    offset: u64,
+    /// Oldest first.
+    events: VecDeque<Recent>,
+}
+
+impl Tracked {
+    fn push(&mut self, lines: Vec<String>, settings: &Settings) -> Result<()> {
+        let options = settings.render_options();
+        for line in lines {
+            if let Some(event) = self.renderer.render_line(&line, &options)? {
+                if self.events.len() == HISTORY {
+                    self.events.pop_front();
+                }
+                self.events
+                    .push_back(Recent::new(&self.id, &self.renderer, event));
+            }
+        }
+        Ok(())
+    }
+
+    /// Read what was appended to the followed log.
+    fn tail(&mut self, settings: &Settings) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let lines = read_new_lines(path, &mut self.offset, settings.max_line_bytes)?;
+        self.push(lines, settings)
+    }
+}
+
+/// The viewer's state, apart from the terminal.
+pub struct App {
+    settings: Settings,
+    sessions: Vec<Tracked>,
+    selected: usize,
+    /// The last error reading the logs, shown instead of the key help.
+    error: Option<String>,
+}
+
+impl App {
+    pub fn new(settings: Settings) -> Self {
+        App {
+            settings,
+            sessions: Vec::new(),
+            selected: 0,
+            error: None,
+        }
+    }
+
+    /// Start following sessions that appeared, or were resumed into a new
+    /// log, since the last scan.
+    pub fn rescan(&mut self) -> Result<()> {
+        for (id, parts) in session_logs(&self.settings, self.settings.session_window)? {
+            if self.settings.session_id.is_none() && self.settings.ignore.session(&id) {
+                continue;
+            }
+            let index = match self.sessions.iter().position(|tracked| tracked.id == id) {
+                Some(index) => index,
+                None => {
+                    self.sessions.push(Tracked {
+                        renderer: SessionRenderer::new(&id),
+                        id,
+                        path: None,
+                        offset: 0,
+                        events: VecDeque::new(),
+                    });
+                    self.sessions.len() - 1
+                }
+            };
+            let tracked = &mut self.sessions[index];
+            // Parts up to the one already followed have been read.
+            let unread = match &tracked.path {
+                Some(path) => match parts.iter().position(|part| part == path) {
+                    Some(position) => &parts[position + 1..],
+                    None => &parts[..],
+                },
+                None => &parts[..],
+            };
+            for part in unread {
+                if is_compressed(part) {
+                    let lines = read_log(part, self.settings.max_line_bytes)?;
+                    tracked.push(lines, &self.settings)?;
+                } else {
+                    tracked.path = Some(part.clone());
+                    tracked.offset = 0;
+                    tracked.tail(&self.settings)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Read what was appended to every followed log.
+    pub fn tail(&mut self) -> Result<()> {
+        for tracked in &mut self.sessions {
+            tracked.tail(&self.settings)?;
+        }
+        Ok(())
+    }
+
+    fn select(&mut self, step: isize) {
+        let last = self.sessions.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(step).min(last);
+    }
+
+    /// Draw the session list, the selected session's events, newest first,
+    /// and a line of key help.
+    pub fn draw(&self, frame: &mut Frame) {
+        let [main, footer] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+        let [list_area, detail_area] =
+            Layout::horizontal([Constraint::Percentage(30), Constraint::Min(1)]).areas(main);
+
+        let items: Vec<ListItem> = self
+            .sessions
+            .iter()
+            .map(|tracked| {
+                let latest = tracked.events.back();
+                let style = match latest {
+                    Some(event)
+                        if event
+                            .payload
+                            .class
+                            .iter()
+                            .any(|class| class == "approval-pending") =>
+                    {
+                        Style::new().fg(Color::Yellow)
+                    }
+                    _ => Style::new(),
+                };
+                ListItem::new(vec![
+                    Line::from(Span::styled(
+                        tracked.id.clone(),
+                        Style::new().add_modifier(Modifier::BOLD),
+                    )),
+                    Line::from(Span::styled(
+                        latest
+                            .map_or("", |event| event.payload.text.as_str())
+                            .to_string(),
+                        style,
+                    )),
+                ])
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered().title(" Sessions "))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        frame.render_stateful_widget(list, list_area, &mut state);
+
+        let selected = self.sessions.get(self.selected);
+        let mut title = selected.map_or_else(String::new, |tracked| tracked.id.clone());
+        if let Some(cwd) = selected
+            .and_then(|tracked| tracked.events.back())
+            .and_then(|event| event.payload.session.as_ref())
+            .and_then(|info| info.cwd.as_deref())
+        {
+            title = format!("{title} · {cwd}");
+        }
+        let now = SystemTime::now();
+        let mut lines = Vec::new();
+        for event in selected
+            .into_iter()
+            .flat_map(|tracked| tracked.events.iter().rev())
+        {
+            let time = event
+                .timestamp
+                .as_deref()
+                .map(|timestamp| self.settings.timestamps.format(timestamp, now))
+                .unwrap_or_default();
+            lines.push(Line::from(vec![
+                Span::styled(time, Style::new().add_modifier(Modifier::BOLD)),
+                Span::raw("  "),
+                Span::styled(event.kind.clone(), Style::new().fg(Color::DarkGray)),
+            ]));
+            lines.extend(event.text.lines().map(|line| Line::raw(line.to_string())));
+            lines.push(Line::default());
+        }
+        let detail = Paragraph::new(lines)
+            .block(Block::bordered().title(format!(" {title} ")))
+            .wrap(Wrap { trim: false });
+        frame.render_widget(detail, detail_area);
+
+        let help = match &self.error {
+            Some(error) => Line::styled(error.clone(), Style::new().fg(Color::Red)),
+            None => Line::styled("↑/↓ select · q quit", Style::new().fg(Color::DarkGray)),
+        };
+        frame.render_widget(help, footer);
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        let mut scanned = Instant::now();
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            if event::poll(POLL)?
+                && let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        return Ok(());
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => self.select(-1),
+                    KeyCode::Down | KeyCode::Char('j') => self.select(1),
+                    _ => {}
+                }
+            }
+            // A log that went away is reported, not fatal.
+            let read = if scanned.elapsed() >= RESCAN {
+                scanned = Instant::now();
+                self.rescan()
+            } else {
+                self.tail()
+            };
+            self.error = read.err().map(|err| format!("{err:#}"));
+        }
+    }
+}
+
+/// Show the viewer until the user quits.
+pub fn run(settings: Settings) -> Result<()> {
+    let mut app = App::new(settings);
+    app.rescan()?;
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use ratatui::{Terminal, backend::TestBackend};
+    use std::io::Write;
+
+    #[test]
+    fn follows_appended_lines_and_draws_the_selected_session() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let history_path = dir.path().join("history.jsonl");
+        let sessions_root = dir.path().join("sessions");
+        std::fs::create_dir_all(&sessions_root)?;
+        std::fs::write(&history_path, "{\"session_id\":\"alpha\"}\n")?;
+        let log = sessions_root.join("rollout-alpha.jsonl");
+        let line = |time: &str, text: &str| {
+            let line = serde_json::json!({
+                "timestamp": format!("2025-10-29T{time}Z"),
+                "type": "event_msg",
+                "payload": { "type": "agent_reasoning", "text": text }
+            });
+            format!("{line}\n")
+        };
+        std::fs::write(
+            &log,
+            line("12:00:00", "**Reading code**\n\nThe parser first."),
+        )?;
+
+        let settings = Settings::resolve(&Config {
+            history_path: Some(history_path),
+            sessions_root: Some(sessions_root),
+            stdout: Some(true),
+            ..Default::default()
+        })?;
+        let mut app = App::new(settings);
+        app.rescan()?;
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&log)?
+            .write_all(line("12:00:05", "**Writing tests**").as_bytes())?;
+        app.tail()?;
+        let texts: Vec<&str> = app.sessions[0]
+            .events
+            .iter()
+            .map(|event| event.text.as_str())
+            .collect();
+        assert_eq!(
+            texts,
+            ["**Reading code**\n\nThe parser first.", "**Writing tests**"]
+        );
+
+        let mut terminal = Terminal::new(TestBackend::new(80, 12))?;
+        terminal.draw(|frame| app.draw(frame))?;
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("alpha"));
+        assert!(screen.contains("The parser first."));
+        // Newest first.
+        assert!(screen.find("**Writing tests**") < screen.find("**Reading code**"));
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+//! `codex-shimmer uninstall`: the same cleanup as `scripts/uninstall.sh`, for
+//! installs that no longer have the script around.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Result};
+use dirs::{config_dir, home_dir};
+
+use crate::service::UNIT;
+
+const LEGACY_SERVICE: &str = "codex-waybar.service";
+const EXAMPLE_FILES: [&str; 3] = [
+    "codex-shimmer.service",
+    "waybar-config-snippet.jsonc",
+    "waybar-style.css",
+];
+
+/// Where `install.sh` put things, honouring the same environment overrides.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    pub prefix: PathBuf,
+    pub bin_dir: PathBuf,
+    pub share_dir: PathBuf,
+    pub lib_paths: Vec<PathBuf>,
+    pub systemd_user_dir: PathBuf,
+    /// `~/.config`, holding `codex-shimmer/config.toml`.
+    pub config_dir: PathBuf,
+}
+
+impl Layout {
+    /// The install layout under `prefix`, or under `$PREFIX` or `~/.local`.
+    pub fn from_env(prefix: Option<PathBuf>) -> Result<Self> {
+        let home = home_dir().context("Home directory not found")?;
+        let var = |name: &str| std::env::var_os(name).map(PathBuf::from);
+        let prefix = prefix
+            .or_else(|| var("PREFIX"))
+            .unwrap_or_else(|| home.join(".local"));
+        Ok(Layout {
+            bin_dir: var("BIN_DIR").unwrap_or_else(|| prefix.join("bin")),
+            share_dir: var("SHARE_DIR").unwrap_or_else(|| prefix.join("share/codex-shimmer")),
+            lib_paths: vec![
+                var("LIB_WAYBAR_DIR")
+                    .unwrap_or_else(|| prefix.join("lib/waybar/wb_codex_shimmer.so")),
+                var("LIB64_WAYBAR_DIR")
+                    .unwrap_or_else(|| prefix.join("lib64/waybar/wb_codex_shimmer.so")),
+            ],
+            systemd_user_dir: var("SYSTEMD_USER_DIR")
+                .unwrap_or_else(|| home.join(".config/systemd/user")),
+            config_dir: config_dir().unwrap_or_else(|| home.join(".config")),
+            prefix,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    /// `systemctl --user disable --now`, ignoring failures.
+    StopService(&'static str),
+    RemoveFile(PathBuf),
+    RemoveDirIfEmpty(PathBuf),
+    RemoveTree(PathBuf),
+    DaemonReload,
+}
+
+/// Everything to undo, in order. Units are stopped before their files go.
+pub fn plan(layout: &Layout, keep_config: bool) -> Vec<Step> {
+    let mut steps = vec![Step::StopService(UNIT)];
+    let legacy_unit = layout.systemd_user_dir.join(LEGACY_SERVICE);
+    if legacy_unit.exists() {
+        steps.push(Step::StopService(LEGACY_SERVICE));
+    }
+    steps.push(Step::RemoveFile(layout.systemd_user_dir.join(UNIT)));
+    steps.push(Step::RemoveFile(legacy_unit));
+    steps.push(Step::DaemonReload);
+
+    steps.push(Step::RemoveFile(layout.bin_dir.join("codex-shimmer")));
+    steps.push(Step::RemoveFile(layout.bin_dir.join("codex-waybar")));
+
+    let examples = layout.share_dir.join("examples");
+    steps.push(Step::RemoveFile(layout.share_dir.join("README.md")));
+    for example in EXAMPLE_FILES {
+        steps.push(Step::RemoveFile(examples.join(example)));
+    }
+    steps.push(Step::RemoveDirIfEmpty(examples));
+    steps.push(Step::RemoveDirIfEmpty(layout.share_dir.clone()));
+    steps.push(Step::RemoveDirIfEmpty(
+        layout.prefix.join("share/codex-waybar"),
+    ));
+
+    for lib in &layout.lib_paths {
+        steps.push(Step::RemoveFile(lib.clone()));
+        if let Some(parent) = lib.parent() {
+            steps.push(Step::RemoveDirIfEmpty(parent.to_path_buf()));
+        }
+    }
+
+    if !keep_config {
+        steps.push(Step::RemoveTree(layout.config_dir.join("codex-shimmer")));
+        steps.push(Step::RemoveTree(layout.config_dir.join("codex-waybar")));
+    }
+    steps
+}
+
+/// Carry out (or with `dry_run`, describe) each step. Anything already gone
+/// is skipped, so running this twice is harmless.
+pub fn execute(steps: &[Step], dry_run: bool) -> Result<()> {
+    let verb = if dry_run { "Would remove" } else { "Removing" };
+    for step in steps {
+        match step {
+            Step::StopService(unit) => {
+                if dry_run {
+                    println!("Would stop and disable {unit}");
+                } else {
+                    systemctl(&["disable", "--now", unit]);
+                }
+            }
+            Step::DaemonReload => {
+                if !dry_run {
+                    systemctl(&["daemon-reload"]);
+                }
+            }
+            Step::RemoveFile(path) => {
+                if !path.is_file() {
+                    continue;
+                }
+                println!("{verb} {}", path.display());
+                if !dry_run {
+                    ignore_missing(fs::remove_file(path))
+                        .with_context(|| format!("Failed to remove {}", path.display()))?;
+                }
+            }
+            Step::RemoveDirIfEmpty(dir) => {
+                if !is_empty_dir(dir) {
+                    continue;
+                }
+                println!("{verb} empty directory {}", dir.display());
+                if !dry_run {
+                    ignore_missing(fs::remove_dir(dir))
+                        .with_context(|| format!("Failed to remove {}", dir.display()))?;
+                }
+            }
+            Step::RemoveTree(dir) => {
+                if !dir.is_dir() {
+                    continue;
+                }
+                println!("{verb} {}", dir.display());
+                if !dry_run {
+                    ignore_missing(fs::remove_dir_all(dir))
+                        .with_context(|| format!("Failed to remove {}", dir.display()))?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// In a dry run, directories emptied by earlier steps still hold their files,
+/// so only the ones already empty are listed.
+fn is_empty_dir(dir: &Path) -> bool {
+    fs::read_dir(dir).is_ok_and(|mut entries| entries.next().is_none())
+}
+
+fn ignore_missing(result: io::Result<()>) -> io::Result<()> {
+    match result {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Best effort: without systemd there is no unit to stop.
+fn systemctl(args: &[&str]) {
+    let _ = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn layout(root: &Path) -> Layout {
+        let prefix = root.join("local");
+        Layout {
+            bin_dir: prefix.join("bin"),
+            share_dir: prefix.join("share/codex-shimmer"),
+            lib_paths: vec![prefix.join("lib/waybar/wb_codex_shimmer.so")],
+            systemd_user_dir: root.join("systemd"),
+            config_dir: root.join("config"),
+            prefix,
+        }
+    }
+
+    /// Leave out the steps that would reach the real user manager.
+    fn local(steps: Vec<Step>) -> Vec<Step> {
+        steps
+            .into_iter()
+            .filter(|step| !matches!(step, Step::StopService(_) | Step::DaemonReload))
+            .collect()
+    }
+
+    fn touch(path: &Path) -> Result<()> {
+        fs::create_dir_all(path.parent().expect("parent"))?;
+        fs::write(path, "")?;
+        Ok(())
+    }
+
+    #[test]
+    fn removes_installed_files_and_is_idempotent() -> Result<()> {
+        let dir = tempdir()?;
+        let layout = layout(dir.path());
+        let bin = layout.bin_dir.join("codex-shimmer");
+        let other_bin = layout.bin_dir.join("other-tool");
+        let example = layout.share_dir.join("examples/waybar-style.css");
+        let lib = layout.lib_paths[0].clone();
+        let config = layout.config_dir.join("codex-shimmer/config.toml");
+        for path in [&bin, &other_bin, &example, &lib, &config] {
+            touch(path)?;
+        }
+        let steps = local(plan(&layout, true));
+
+        execute(&steps, true)?;
+        assert!(bin.exists() && example.exists());
+
+        execute(&steps, false)?;
+        assert!(!bin.exists() && !lib.exists());
+        assert!(!layout.share_dir.exists());
+        assert!(!lib.parent().expect("parent").exists());
+        assert!(other_bin.exists(), "unrelated files stay");
+        assert!(config.exists(), "--keep-config keeps the config");
+
+        execute(&steps, false)?;
+        execute(&local(plan(&layout, false)), false)?;
+        assert!(!config.exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher, event::ModifyKind};
+
+/// Paths touched by a single filesystem event.
+#[derive(Debug, Clone)]
+pub struct FsChange {
+    pub paths: Vec<PathBuf>,
+    /// The file was removed or renamed, so any watch on it is stale.
+    pub replaced: bool,
+}
+
+/// Thin wrapper over an inotify-backed watcher that forwards changed paths to
+/// a callback.
+pub struct FsWatcher {
+    watcher: RecommendedWatcher,
+    files: HashSet<PathBuf>,
+    dirs: HashSet<PathBuf>,
+}
+
+impl FsWatcher {
+    pub fn new<F>(on_change: F) -> notify::Result<Self>
+    where
+        F: Fn(FsChange) + Send + 'static,
+    {
+        let watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            if let Ok(event) = event {
+                let replaced = matches!(
+                    event.kind,
+                    EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_))
+                );
+                on_change(FsChange {
+                    paths: event.paths,
+                    replaced,
+                });
+            }
+        })?;
+        Ok(Self {
+            watcher,
+            files: HashSet::new(),
+            dirs: HashSet::new(),
+        })
+    }
+
+    /// Watch a single file. Missing files are not remembered, so the call can
+    /// simply be repeated once the file exists.
+    pub fn watch_file(&mut self, path: &Path) {
+        if self.files.contains(path) {
+            return;
+        }
+        if self
+            .watcher
+            .watch(path, RecursiveMode::NonRecursive)
+            .is_ok()
+        {
+            self.files.insert(path.to_path_buf());
+        }
+    }
+
+    /// Watch the entries of a directory (non-recursively), e.g. the folder
+    /// holding `history.jsonl` so the file can be created or replaced.
+    /// Returns whether the directory was not watched before.
+    pub fn watch_dir(&mut self, path: &Path) -> bool {
+        self.watch(path, RecursiveMode::NonRecursive)
+    }
+
+    /// Watch a directory and everything below it, e.g. the date-sharded tree
+    /// of session logs so new logs are noticed as they appear.
+    pub fn watch_tree(&mut self, path: &Path) -> bool {
+        self.watch(path, RecursiveMode::Recursive)
+    }
+
+    fn watch(&mut self, path: &Path, mode: RecursiveMode) -> bool {
+        if self.dirs.contains(path) {
+            return false;
+        }
+        let watched = self.watcher.watch(path, mode).is_ok();
+        if watched {
+            self.dirs.insert(path.to_path_buf());
+        }
+        watched
+    }
+
+    /// Drop the watch on a file whose inode went away so it can be re-armed.
+    pub fn forget_file(&mut self, path: &Path) {
+        if self.files.remove(path) {
+            let _ = self.watcher.unwatch(path);
+        }
+    }
+
+    /// Stop watching files that are no longer in `keep`.
+    pub fn retain_files(&mut self, keep: &HashSet<&PathBuf>) {
+        let stale: Vec<PathBuf> = self
+            .files
+            .iter()
+            .filter(|path| !keep.contains(path))
+            .cloned()
+            .collect();
+        for path in stale {
+            self.forget_file(&path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    #[test]
+    fn watcher_reports_modified_file() -> anyhow::Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("session.jsonl");
+        fs::write(&path, "")?;
+
+        let (tx, rx) = channel();
+        let mut watcher = FsWatcher::new(move |change| {
+            let _ = tx.send(change);
+        })?;
+        watcher.watch_file(&path);
+        fs::write(&path, "{}\n")?;
+
+        let change = rx.recv_timeout(Duration::from_secs(5))?;
+        assert!(change.paths.contains(&path));
+        Ok(())
+    }
+}
@@ -0,0 +1,438 @@
+//! `codex-shimmer setup-waybar`: adds the `custom/codex` module and its CSS
+//! to the user's Waybar config, or takes them out again.
+//!
+//! The config is edited as text so comments and formatting survive; every
+//! insertion sits between `codex-shimmer` marker comments, which is how
+//! `--remove` finds it again.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use regex::Regex;
+
+/// Files Waybar reads its config from, in the order it tries them.
+pub const CONFIG_NAMES: [&str; 3] = ["config.jsonc", "config", "config.json"];
+const MODULE: &str = "custom/codex";
+const LIST_ENTRY: &str = "\"custom/codex\" /* codex-shimmer */";
+const BEGIN: &str =
+    "/* codex-shimmer: begin (remove with `codex-shimmer setup-waybar --remove`) */";
+const END: &str = "/* codex-shimmer: end */";
+const MODULE_BLOCK: &str = r#""custom/codex": {
+    "exec": "codex-shimmer --stdout --max-chars 80 --markup escape",
+    "return-type": "json",
+    "restart-interval": 5
+  }"#;
+const CSS_BEGIN: &str = "/* codex-shimmer: begin */";
+const CSS_END: &str = "/* codex-shimmer: end */";
+const CSS: &str = include_str!("../examples/waybar-style.css");
+
+/// The config file Waybar would load from `dir`.
+pub fn find_config(dir: &Path) -> Option<PathBuf> {
+    CONFIG_NAMES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// Add the module to the config and style sheet in `dir`, after copying the
+/// directory below `backup_root`. Returns whether anything changed.
+pub fn install(dir: &Path, backup_root: &Path) -> Result<bool> {
+    let config =
+        find_config(dir).with_context(|| format!("No Waybar config found in {}", dir.display()))?;
+    let text = read(&config)?;
+    if has_module(&text)? {
+        println!("{} already has {MODULE}", config.display());
+        return Ok(false);
+    }
+    let edited = add_module(&text)?;
+    let style = dir.join("style.css");
+    // Creating a style sheet would hide the system-wide one Waybar falls back to.
+    let css = if style.exists() {
+        Some(read(&style)?)
+    } else {
+        None
+    };
+
+    let backup = backup(dir, backup_root)?;
+    println!("Backed up {} to {}", dir.display(), backup.display());
+    write(&config, &edited)?;
+    println!("Added {MODULE} to {}", config.display());
+    match css {
+        Some(css) if !css.contains(CSS_BEGIN) => {
+            write(&style, &add_css(&css))?;
+            println!("Added its style to {}", style.display());
+        }
+        Some(_) => {}
+        None => println!("No {} to add the module's style to", style.display()),
+    }
+    Ok(true)
+}
+
+/// Take out everything `install` added. Returns whether anything changed.
+pub fn remove(dir: &Path, backup_root: &Path) -> Result<bool> {
+    let config =
+        find_config(dir).with_context(|| format!("No Waybar config found in {}", dir.display()))?;
+    let text = read(&config)?;
+    let edited = remove_module(&text)?;
+    let style = dir.join("style.css");
+    let css = if style.exists() {
+        read(&style)?
+    } else {
+        String::new()
+    };
+    let stripped = remove_css(&css);
+    if edited == text && stripped == css {
+        println!("{} has no module added by codex-shimmer", config.display());
+        return Ok(false);
+    }
+
+    let backup = backup(dir, backup_root)?;
+    println!("Backed up {} to {}", dir.display(), backup.display());
+    if edited != text {
+        write(&config, &edited)?;
+        println!("Removed {MODULE} from {}", config.display());
+    }
+    if stripped != css {
+        write(&style, &stripped)?;
+        println!("Removed its style from {}", style.display());
+    }
+    Ok(true)
+}
+
+fn add_module(text: &str) -> Result<String> {
+    let tokens = tokenize(text)?;
+    parse(&tokens, text)?;
+    let bar = bar_object(&tokens)?;
+    let keys = top_level_keys(&tokens, bar);
+    let mut insertions = Vec::new();
+
+    let mut block = format!("\n  {BEGIN}\n");
+    match keys.iter().find(|(key, _)| key == "modules-right") {
+        Some(&(_, value)) if tokens[value].text == "[" => {
+            let empty = tokens.get(value + 1).is_some_and(|next| next.text == "]");
+            let entry = if empty {
+                LIST_ENTRY.to_string()
+            } else {
+                format!("{LIST_ENTRY}, ")
+            };
+            insertions.push((tokens[value].end, entry));
+        }
+        Some(_) => bail!("`modules-right` in the Waybar config is not a list"),
+        None => block.push_str(&format!("  \"modules-right\": [{LIST_ENTRY}],\n")),
+    }
+    let empty = tokens.get(bar + 1).is_some_and(|next| next.text == "}");
+    block.push_str(&format!(
+        "  {MODULE_BLOCK}{}\n  {END}",
+        if empty { "" } else { "," }
+    ));
+    insertions.push((tokens[bar].end, block));
+
+    let mut edited = text.to_string();
+    insertions.sort_by_key(|&(at, _)| std::cmp::Reverse(at));
+    for (at, insertion) in insertions {
+        edited.insert_str(at, &insertion);
+    }
+    parse(&tokenize(&edited)?, &edited).context("Adding the module broke the config")?;
+    Ok(edited)
+}
+
+fn remove_module(text: &str) -> Result<String> {
+    let block = Regex::new(&format!(
+        r"(?s)\n[ \t]*{}.*?{}",
+        regex::escape("/* codex-shimmer: begin"),
+        regex::escape(END)
+    ))?;
+    let edited = block
+        .replace_all(text, "")
+        .replace(&format!("{LIST_ENTRY}, "), "")
+        .replace(LIST_ENTRY, "");
+    parse(&tokenize(&edited)?, &edited).context("Removing the module broke the config")?;
+    Ok(edited)
+}
+
+fn has_module(text: &str) -> Result<bool> {
+    let tokens = tokenize(text)?;
+    parse(&tokens, text)?;
+    let bar = bar_object(&tokens)?;
+    Ok(top_level_keys(&tokens, bar)
+        .iter()
+        .any(|(key, _)| key == MODULE))
+}
+
+fn add_css(css: &str) -> String {
+    let mut css = css.to_string();
+    if !css.is_empty() && !css.ends_with('\n') {
+        css.push('\n');
+    }
+    format!("{css}\n{CSS_BEGIN}\n{CSS}{CSS_END}\n")
+}
+
+fn remove_css(css: &str) -> String {
+    let block = Regex::new(&format!(
+        r"(?s)\n?{}.*?{}\n?",
+        regex::escape(CSS_BEGIN),
+        regex::escape(CSS_END)
+    ))
+    .expect("valid CSS marker pattern");
+    block.replace_all(css, "").into_owned()
+}
+
+#[derive(Debug)]
+struct Token<'a> {
+    text: &'a str,
+    end: usize,
+}
+
+/// Split JSONC into tokens, dropping whitespace and comments. Strings keep
+/// their quotes; anything else that is not punctuation (numbers, `true`, …)
+/// is one token.
+fn tokenize(text: &str) -> Result<Vec<Token<'_>>> {
+    let bytes = text.as_bytes();
+    let mut tokens = Vec::new();
+    let mut at = 0;
+    while at < bytes.len() {
+        let start = at;
+        match bytes[at] {
+            b' ' | b'\t' | b'\r' | b'\n' => at += 1,
+            b'/' if bytes.get(at + 1) == Some(&b'/') => {
+                at = text[at..].find('\n').map_or(bytes.len(), |end| at + end);
+            }
+            b'/' if bytes.get(at + 1) == Some(&b'*') => {
+                at = text[at + 2..]
+                    .find("*/")
+                    .map(|end| at + 2 + end + 2)
+                    .context("Unterminated comment in the Waybar config")?;
+            }
+            b'"' => {
+                at += 1;
+                while at < bytes.len() && bytes[at] != b'"' {
+                    at += if bytes[at] == b'\\' { 2 } else { 1 };
+                }
+                if at >= bytes.len() {
+                    bail!("Unterminated string in the Waybar config");
+                }
+                at += 1;
+            }
+            b'{' | b'}' | b'[' | b']' | b':' | b',' => at += 1,
+            _ => {
+                at += 1;
+                while at < bytes.len() && !b" \t\r\n{}[]:,\"/".contains(&bytes[at]) {
+                    at += 1;
+                }
+            }
+        }
+        let token = &text[start..at];
+        if !token.trim().is_empty() && !token.starts_with("//") && !token.starts_with("/*") {
+            tokens.push(Token {
+                text: token,
+                end: at,
+            });
+        }
+    }
+    Ok(tokens)
+}
+
+/// Check the tokens form valid JSON once trailing commas are dropped, the
+/// way Waybar reads it.
+fn parse(tokens: &[Token], text: &str) -> Result<serde_json::Value> {
+    let mut json = String::with_capacity(text.len());
+    for (index, token) in tokens.iter().enumerate() {
+        let next = tokens.get(index + 1).map(|next| next.text);
+        if token.text == "," && matches!(next, Some("]" | "}")) {
+            continue;
+        }
+        json.push_str(token.text);
+        json.push(' ');
+    }
+    serde_json::from_str(&json).context("The Waybar config is not valid JSONC")
+}
+
+/// The `{` of the bar to edit: the config itself, or the first bar when it
+/// holds a list of bars.
+fn bar_object(tokens: &[Token]) -> Result<usize> {
+    match tokens.first().map(|token| token.text) {
+        Some("{") => Ok(0),
+        Some("[") if tokens.get(1).is_some_and(|token| token.text == "{") => Ok(1),
+        _ => bail!("The Waybar config holds no bar object"),
+    }
+}
+
+/// Each key directly inside the object opened at `open`, with the index of
+/// its value's first token.
+fn top_level_keys(tokens: &[Token], open: usize) -> Vec<(String, usize)> {
+    let mut keys = Vec::new();
+    let mut depth = 0;
+    for (index, token) in tokens.iter().enumerate().skip(open) {
+        match token.text {
+            "{" | "[" => depth += 1,
+            "}" | "]" => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            text if depth == 1
+                && text.starts_with('"')
+                && tokens.get(index + 1).is_some_and(|next| next.text == ":") =>
+            {
+                if let Ok(key) = serde_json::from_str::<String>(text) {
+                    keys.push((key, index + 2));
+                }
+            }
+            _ => {}
+        }
+    }
+    keys
+}
+
+/// Copy `dir` to `<backup_root>/waybar-<timestamp>`, as `install.sh` does. A
+/// backup is never written over: another one within the same second gets a
+/// `-1`, `-2`, … suffix.
+fn backup(dir: &Path, backup_root: &Path) -> Result<PathBuf> {
+    fs::create_dir_all(backup_root)
+        .with_context(|| format!("Failed to create {}", backup_root.display()))?;
+    let stamp = backup_stamp();
+    let mut target = backup_root.join(format!("waybar-{stamp}"));
+    let mut taken = 0;
+    loop {
+        match fs::create_dir(&target) {
+            Ok(()) => break,
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+                taken += 1;
+                target = backup_root.join(format!("waybar-{stamp}-{taken}"));
+            }
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to create {}", target.display()));
+            }
+        }
+    }
+    copy_dir(dir, &target).with_context(|| {
+        format!(
+            "Failed to back up {} to {}",
+            dir.display(),
+            target.display()
+        )
+    })?;
+    Ok(target)
+}
+
+/// Copy the contents of `from` into the empty directory `to`. Symlinks are
+/// copied as links, so one to a directory neither fails nor pulls in what it
+/// points at.
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            std::os::unix::fs::symlink(fs::read_link(entry.path())?, target)?;
+        } else if file_type.is_dir() {
+            fs::create_dir(&target)?;
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Local time as `%Y%m%d%H%M%S`, matching the installer's backups.
+fn backup_stamp() -> String {
+    let now = unsafe { libc::time(std::ptr::null_mut()) };
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&now, &mut tm) }.is_null() {
+        return now.to_string();
+    }
+    format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
+
+fn read(path: &Path) -> Result<String> {
+    fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+}
+
+fn write(path: &Path, text: &str) -> Result<()> {
+    fs::write(path, text).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const CONFIG: &str = r#"// My bar
+{
+  "layer": "top", /* keep on top */
+  "modules-right": ["clock", "tray",],
+  "clock": { "format": "{:%H:%M}" }
+}
+"#;
+
+    #[test]
+    fn module_is_added_and_removed_keeping_comments() -> Result<()> {
+        let added = add_module(CONFIG)?;
+        assert!(added.contains("/* keep on top */"));
+        assert!(added.contains(r#"["custom/codex" /* codex-shimmer */, "clock""#));
+        assert!(has_module(&added)?);
+        let config = parse(&tokenize(&added)?, &added)?;
+        assert_eq!(config["custom/codex"]["return-type"], "json");
+
+        assert_eq!(remove_module(&added)?, CONFIG);
+        Ok(())
+    }
+
+    #[test]
+    fn empty_bars_and_bar_lists_get_a_module_list() -> Result<()> {
+        for config in ["{}", "[{\"layer\": \"top\"}, {}]"] {
+            let added = add_module(config)?;
+            let value = parse(&tokenize(&added)?, &added)?;
+            let bar = if value.is_array() { &value[0] } else { &value };
+            assert_eq!(bar["modules-right"][0], MODULE, "{added}");
+            assert_eq!(remove_module(&added)?, config);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn install_backs_up_and_is_idempotent() -> Result<()> {
+        let dir = tempdir()?;
+        let waybar = dir.path().join("waybar");
+        let backups = dir.path().join("backups");
+        fs::create_dir_all(&waybar)?;
+        fs::write(waybar.join("config.jsonc"), CONFIG)?;
+        fs::write(waybar.join("style.css"), "window { color: red; }")?;
+        let themes = dir.path().join("themes");
+        fs::create_dir_all(&themes)?;
+        std::os::unix::fs::symlink(&themes, waybar.join("themes"))?;
+
+        assert!(install(&waybar, &backups)?);
+        assert!(!install(&waybar, &backups)?);
+        let style = fs::read_to_string(waybar.join("style.css"))?;
+        assert!(style.starts_with("window { color: red; }\n"));
+        assert!(style.contains("#custom-codex {"));
+        let first = fs::read_dir(&backups)?.next().expect("a backup")?.path();
+        assert_eq!(fs::read_link(first.join("themes"))?, themes);
+
+        // A second backup in the same second must not overwrite the first.
+        assert!(remove(&waybar, &backups)?);
+        assert_eq!(fs::read_dir(&backups)?.count(), 2);
+        assert_eq!(fs::read_to_string(first.join("config.jsonc"))?, CONFIG);
+        assert_eq!(fs::read_to_string(waybar.join("config.jsonc"))?, CONFIG);
+        assert_eq!(
+            fs::read_to_string(waybar.join("style.css"))?,
+            "window { color: red; }\n"
+        );
+        assert!(!remove(&waybar, &backups)?);
+        Ok(())
+    }
+}
@@ -0,0 +1,177 @@
+//! Posts payloads as JSON to a URL, so a relay can forward Codex activity to
+//! chat tools such as Slack or Discord.
+
+use std::time::Duration;
+
+use tokio::{sync::mpsc, task::spawn_blocking, time::sleep};
+use tracing::warn;
+
+use crate::output::WaybarOutput;
+use crate::sink::{Sink, SinkFuture};
+
+/// Payloads waiting to be posted before new ones are dropped.
+const QUEUE_LEN: usize = 32;
+const ATTEMPTS: u32 = 5;
+/// Wait before the first retry, doubled for each later one.
+const FIRST_RETRY: Duration = Duration::from_secs(1);
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Queues payloads for a background task that posts them in order, so a slow
+/// or unreachable endpoint never holds up the cache file.
+pub struct WebhookSink {
+    classes: Vec<String>,
+    queue: mpsc::Sender<WaybarOutput>,
+}
+
+impl WebhookSink {
+    /// Post to `url` every payload carrying one of `classes`, or every
+    /// payload when `classes` is empty. The posting task ends once the sink
+    /// is dropped and its queue is drained.
+    pub fn new(url: String, classes: Vec<String>) -> Self {
+        let (queue, mut payloads) = mpsc::channel(QUEUE_LEN);
+        let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+        tokio::spawn(async move {
+            while let Some(payload) = payloads.recv().await {
+                post_with_retries(&agent, &url, &payload, FIRST_RETRY).await;
+            }
+        });
+        WebhookSink { classes, queue }
+    }
+
+    fn wants(&self, payload: &WaybarOutput) -> bool {
+        self.classes.is_empty()
+            || payload
+                .class
+                .iter()
+                .any(|class| self.classes.contains(class))
+    }
+}
+
+impl Sink for WebhookSink {
+    fn deliver<'a>(&'a mut self, payload: &'a WaybarOutput) -> SinkFuture<'a> {
+        if self.wants(payload) && self.queue.try_send(payload.clone()).is_err() {
+            warn!("Webhook is not keeping up; dropping a payload");
+        }
+        Box::pin(async { Ok(()) })
+    }
+}
+
+enum Failure {
+    /// A network error, a timeout, or a 429 or 5xx response.
+    Transient(String),
+    Permanent(String),
+}
+
+/// Post `payload`, retrying transient failures with exponential backoff.
+/// Failures are reported on stderr; the payload is dropped after the last
+/// attempt.
+async fn post_with_retries(
+    agent: &ureq::Agent,
+    url: &str,
+    payload: &WaybarOutput,
+    first_retry: Duration,
+) {
+    let body = match serde_json::to_string(payload) {
+        Ok(body) => body,
+        Err(err) => return warn!(error = %err, "Webhook payload could not be encoded"),
+    };
+    let mut delay = first_retry;
+    for attempt in 1..=ATTEMPTS {
+        let (agent, url, body) = (agent.clone(), url.to_string(), body.clone());
+        let failure = match spawn_blocking(move || post(&agent, &url, &body)).await {
+            Ok(Ok(())) => return,
+            Ok(Err(failure)) => failure,
+            Err(err) => Failure::Permanent(err.to_string()),
+        };
+        match failure {
+            Failure::Transient(err) if attempt < ATTEMPTS => {
+                warn!(error = %err, retry_in = ?delay, "Webhook failed; retrying");
+                sleep(delay).await;
+                delay *= 2;
+            }
+            Failure::Transient(err) | Failure::Permanent(err) => {
+                return warn!(error = %err, "Webhook failed; dropping the payload");
+            }
+        }
+    }
+}
+
+fn post(agent: &ureq::Agent, url: &str, body: &str) -> Result<(), Failure> {
+    match agent
+        .post(url)
+        .set("Content-Type", "application/json")
+        .send_string(body)
+    {
+        Ok(_) => Ok(()),
+        Err(ureq::Error::Status(status, _)) if status == 429 || status >= 500 => {
+            Err(Failure::Transient(format!("HTTP {status}")))
+        }
+        Err(ureq::Error::Status(status, _)) => Err(Failure::Permanent(format!("HTTP {status}"))),
+        Err(err) => Err(Failure::Transient(err.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+    use crate::output::idle_payload;
+
+    /// Answer each request with the next status, returning the bodies posted.
+    fn serve(statuses: &'static [u16]) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind test server");
+        let url = format!("http://{}/hook", listener.local_addr().expect("address"));
+        let server = thread::spawn(move || {
+            let mut bodies = Vec::new();
+            for status in statuses {
+                let (stream, _) = listener.accept().expect("accept");
+                let mut reader = BufReader::new(stream);
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).expect("read header");
+                    if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().expect("content length");
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).expect("read body");
+                bodies.push(String::from_utf8(body).expect("utf-8 body"));
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 {status} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                )
+                .expect("respond");
+            }
+            bodies
+        });
+        (url, server)
+    }
+
+    #[tokio::test]
+    async fn server_errors_are_retried() {
+        let (url, server) = serve(&[503, 200]);
+        let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+        post_with_retries(&agent, &url, &idle_payload(), Duration::from_millis(10)).await;
+
+        let bodies = server.join().expect("server thread");
+        assert_eq!(bodies.len(), 2);
+        assert!(bodies[1].contains("\"text\":\"Codex idle\""));
+    }
+
+    #[tokio::test]
+    async fn classes_filter_what_is_posted() {
+        let sink = WebhookSink::new("http://127.0.0.1:9/".to_string(), vec!["idle".to_string()]);
+        assert!(sink.wants(&idle_payload()));
+        assert!(!sink.wants(&crate::output::placeholder_payload()));
+        assert!(WebhookSink::new(String::new(), Vec::new()).wants(&idle_payload()));
+    }
+}
@@ -45,7 +45,7 @@ fn install_creates_waybar_backup() -> TestResult {
     }
     let release_systemd = release_staging.join("systemd");
     fs::create_dir_all(&release_systemd)?;
-    if let Ok(_) = fs::metadata(repo_root.join("systemd/codex-shimmer.service")) {
+    if fs::metadata(repo_root.join("systemd/codex-shimmer.service")).is_ok() {
         fs::copy(
             repo_root.join("systemd/codex-shimmer.service"),
             release_systemd.join("codex-shimmer.service"),